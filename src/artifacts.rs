@@ -0,0 +1,102 @@
+use crate::error::SlashError;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+/// Layout of the artifacts a run writes under `--output-dir`, in a directory named after the
+/// run ID:
+///
+/// ```text
+/// <output-dir>/<run-id>/
+///     manifest.json   payout manifest
+///     payouts.csv     payouts as CSV
+///     receipts/       receipt of every batch included in a block
+///     logs/           logs of every command run under the run ID
+///     snapshot/       chain state the payouts were calculated from
+///     result.json     summary of the last command run
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunArtifacts {
+    dir: PathBuf,
+}
+
+impl RunArtifacts {
+    pub fn new(output_dir: &Path, run_id: &str) -> Self {
+        RunArtifacts {
+            dir: output_dir.join(run_id),
+        }
+    }
+
+    /// Creates the directory of the run along with its subdirectories.
+    pub fn create(&self) -> Result<(), SlashError> {
+        for dir in [self.receipts_dir(), self.logs_dir(), self.snapshot_dir()] {
+            std::fs::create_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn manifest(&self) -> PathBuf {
+        self.dir.join("manifest.json")
+    }
+
+    pub fn payouts_csv(&self) -> PathBuf {
+        self.dir.join("payouts.csv")
+    }
+
+    pub fn receipts_dir(&self) -> PathBuf {
+        self.dir.join("receipts")
+    }
+
+    pub fn logs_dir(&self) -> PathBuf {
+        self.dir.join("logs")
+    }
+
+    pub fn snapshot_dir(&self) -> PathBuf {
+        self.dir.join("snapshot")
+    }
+
+    pub fn result(&self) -> PathBuf {
+        self.dir.join("result.json")
+    }
+
+    /// Opens the log of the command for appending, so that the logs of a resumed command
+    /// are kept along with the ones of the previous attempts.
+    pub fn open_log(&self, command: &str) -> Result<File, SlashError> {
+        Ok(OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.logs_dir().join(format!("{command}.log")))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn artifacts_are_laid_out_per_run() {
+        let artifacts = RunArtifacts::new(Path::new("out"), "1700000000");
+        assert_eq!(artifacts.dir(), Path::new("out/1700000000"));
+        assert_eq!(
+            artifacts.manifest(),
+            Path::new("out/1700000000/manifest.json")
+        );
+        assert_eq!(
+            artifacts.payouts_csv(),
+            Path::new("out/1700000000/payouts.csv")
+        );
+        assert_eq!(
+            artifacts.receipts_dir(),
+            Path::new("out/1700000000/receipts")
+        );
+        assert_eq!(artifacts.logs_dir(), Path::new("out/1700000000/logs"));
+        assert_eq!(
+            artifacts.snapshot_dir(),
+            Path::new("out/1700000000/snapshot")
+        );
+        assert_eq!(artifacts.result(), Path::new("out/1700000000/result.json"));
+    }
+}
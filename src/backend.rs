@@ -0,0 +1,696 @@
+use crate::error::SlashError;
+use crate::rpc;
+use crate::{Api, Hash, Number};
+use codec::{Decode, Encode, Input, Output};
+use futures::future::join_all;
+use sp_core::storage::{StorageChangeSet, StorageData, StorageKey};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Mutex;
+use substrate_api_client::ac_primitives::RpcParams;
+use substrate_api_client::rpc::Request;
+use substrate_api_client::runtime_api::RuntimeApi;
+use substrate_api_client::{GetChainInfo, GetStorage};
+use tracing::debug;
+
+/// Maximum number of storage keys queried in a single `state_queryStorageAt` request.
+const STORAGE_QUERY_CHUNK_SIZE: usize = 256;
+
+/// Number of storage keys requested per page, which is the limit of the nodes.
+pub(crate) const STORAGE_KEYS_PAGE_SIZE: u32 = 1000;
+
+/// Source of the chain state used for the refund calculation.
+///
+/// All the queries are made at a given block hash.
+// Futures are awaited on the current task through `join_all`, so they are not required
+// to be `Send`.
+#[allow(async_fn_in_trait)]
+pub trait ChainBackend {
+    /// Returns the hash of the block with the given number.
+    async fn block_hash(&self, number: Number) -> Result<Option<Hash>, SlashError>;
+
+    /// Returns the value of the storage map at `key`.
+    async fn storage_map<K: Encode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key: K,
+        at: Hash,
+    ) -> Result<Option<V>, SlashError>;
+
+    /// Returns the value of the storage double map at `(key1, key2)`.
+    async fn storage_double_map<K1: Encode, K2: Encode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key1: K1,
+        key2: K2,
+        at: Hash,
+    ) -> Result<Option<V>, SlashError>;
+
+    /// Returns all the entries of the storage double map under `key1`.
+    ///
+    /// Second key is expected to be stored with `Identity` hasher so that it can be decoded
+    /// from the storage key.
+    async fn storage_double_map_entries<K1: Encode, K2: Decode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key1: K1,
+        at: Hash,
+    ) -> Result<Vec<(K2, V)>, SlashError>;
+
+    /// Calls the runtime api `method` with the encoded `params`.
+    async fn runtime_call<V: Decode>(
+        &self,
+        method: &'static str,
+        params: Vec<Vec<u8>>,
+        at: Hash,
+    ) -> Result<V, SlashError>;
+}
+
+impl ChainBackend for Api {
+    async fn block_hash(&self, number: Number) -> Result<Option<Hash>, SlashError> {
+        Ok(self.get_block_hash(Some(number)).await?)
+    }
+
+    async fn storage_map<K: Encode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key: K,
+        at: Hash,
+    ) -> Result<Option<V>, SlashError> {
+        Ok(self.get_storage_map(pallet, storage, key, Some(at)).await?)
+    }
+
+    async fn storage_double_map<K1: Encode, K2: Encode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key1: K1,
+        key2: K2,
+        at: Hash,
+    ) -> Result<Option<V>, SlashError> {
+        Ok(self
+            .get_storage_double_map(pallet, storage, key1, key2, Some(at))
+            .await?)
+    }
+
+    async fn storage_double_map_entries<K1: Encode, K2: Decode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key1: K1,
+        at: Hash,
+    ) -> Result<Vec<(K2, V)>, SlashError> {
+        let storage_prefix = self
+            .get_storage_double_map_key_prefix(pallet, storage, key1)
+            .await?;
+        // pages start after the last key of the previous page
+        let mut storage_keys: Vec<StorageKey> = vec![];
+        loop {
+            let page = self
+                .get_storage_keys_paged(
+                    Some(storage_prefix.clone()),
+                    STORAGE_KEYS_PAGE_SIZE,
+                    storage_keys.last().cloned(),
+                    Some(at),
+                )
+                .await?;
+            let last_page = page.len() < STORAGE_KEYS_PAGE_SIZE as usize;
+            storage_keys.extend(page);
+            if last_page {
+                break;
+            }
+        }
+
+        // values are fetched in chunks of keys instead of one request per key
+        let storage_futures: Vec<_> = storage_keys
+            .chunks(STORAGE_QUERY_CHUNK_SIZE)
+            .map(|storage_keys| query_storage_at(self, storage_keys.to_vec(), at))
+            .collect();
+
+        let mut entries = vec![];
+        for changes in join_all(storage_futures).await {
+            for (storage_key, data) in changes? {
+                let data = data.ok_or(SlashError::StorageNotFound {
+                    pallet,
+                    storage,
+                    at,
+                })?;
+                let mut key2 = &storage_key.0[storage_prefix.0.len()..];
+                entries.push((K2::decode(&mut key2)?, V::decode(&mut data.0.as_slice())?));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    async fn runtime_call<V: Decode>(
+        &self,
+        method: &'static str,
+        params: Vec<Vec<u8>>,
+        at: Hash,
+    ) -> Result<V, SlashError> {
+        Ok(self
+            .runtime_api()
+            .runtime_call::<V>(method, params, Some(at))
+            .await?)
+    }
+}
+
+/// Returns the values of the storage keys at the given block with a single
+/// `state_queryStorageAt` request.
+///
+/// Nodes not serving `state_queryStorageAt` are sent JSON-RPC batches of `state_getStorage`
+/// requests instead.
+async fn query_storage_at(
+    api: &Api,
+    storage_keys: Vec<StorageKey>,
+    at: Hash,
+) -> Result<Vec<(StorageKey, Option<StorageData>)>, SlashError> {
+    let mut params = RpcParams::new();
+    params.insert(storage_keys.clone())?;
+    params.insert(Some(at))?;
+    let change_sets: Vec<StorageChangeSet<Hash>> =
+        match api.client().request("state_queryStorageAt", params).await {
+            Ok(change_sets) => change_sets,
+            Err(err) if rpc::is_call_error(&err) => {
+                debug!("state_queryStorageAt failed, batching state_getStorage instead: {err:?}");
+                return get_storage_batched(api, storage_keys, at).await;
+            }
+            Err(err) => return Err(substrate_api_client::Error::RpcClient(err).into()),
+        };
+    Ok(change_sets
+        .into_iter()
+        .flat_map(|change_set| change_set.changes)
+        .collect())
+}
+
+/// Returns the values of the storage keys at the given block with JSON-RPC batches of
+/// `state_getStorage` requests.
+async fn get_storage_batched(
+    api: &Api,
+    storage_keys: Vec<StorageKey>,
+    at: Hash,
+) -> Result<Vec<(StorageKey, Option<StorageData>)>, SlashError> {
+    let params = storage_keys
+        .iter()
+        .map(|storage_key| {
+            let mut params = RpcParams::new();
+            params.insert(storage_key)?;
+            params.insert(Some(at))?;
+            Ok(params)
+        })
+        .collect::<Result<Vec<_>, SlashError>>()?;
+    let values: Vec<Option<StorageData>> = api
+        .client()
+        .batch_request("state_getStorage", params)
+        .await
+        .map_err(substrate_api_client::Error::RpcClient)?;
+    Ok(storage_keys.into_iter().zip(values).collect())
+}
+
+/// Chain backend serving the state from memory.
+///
+/// It can either be populated manually, such as in tests, or loaded from a SCALE encoded
+/// state dump so that the calculation can run without access to a node.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct MemoryBackend {
+    block_hashes: BTreeMap<Number, Hash>,
+    storage: BTreeMap<(Hash, Vec<u8>), Vec<u8>>,
+}
+
+impl MemoryBackend {
+    /// Reads the state dump from the given path.
+    pub fn read(path: &Path) -> Result<Self, SlashError> {
+        Ok(MemoryBackend::decode(&mut std::fs::read(path)?.as_slice())?)
+    }
+
+    /// Writes the state dump to the given path.
+    pub fn write(&self, path: &Path) -> Result<(), SlashError> {
+        Ok(std::fs::write(path, self.encode())?)
+    }
+
+    pub fn insert_block_hash(&mut self, number: Number, hash: Hash) {
+        self.block_hashes.insert(number, hash);
+    }
+
+    pub fn insert_storage_map<K: Encode, V: Encode>(
+        &mut self,
+        pallet: &'static str,
+        storage: &'static str,
+        key: K,
+        at: Hash,
+        value: V,
+    ) {
+        self.storage.insert(
+            (at, storage_key(pallet, storage, &[key.encode()])),
+            value.encode(),
+        );
+    }
+
+    pub fn insert_storage_double_map<K1: Encode, K2: Encode, V: Encode>(
+        &mut self,
+        pallet: &'static str,
+        storage: &'static str,
+        key1: K1,
+        key2: K2,
+        at: Hash,
+        value: V,
+    ) {
+        self.storage.insert(
+            (
+                at,
+                storage_key(pallet, storage, &[key1.encode(), key2.encode()]),
+            ),
+            value.encode(),
+        );
+    }
+
+    pub fn insert_runtime_call<V: Encode>(
+        &mut self,
+        method: &'static str,
+        params: Vec<Vec<u8>>,
+        at: Hash,
+        value: V,
+    ) {
+        self.storage.insert(
+            (at, storage_key(RUNTIME_CALL_PREFIX, method, &params)),
+            value.encode(),
+        );
+    }
+
+    fn get<V: Decode>(&self, key: Vec<u8>, at: Hash) -> Result<Option<V>, SlashError> {
+        self.storage
+            .get(&(at, key))
+            .map(|value| V::decode(&mut value.as_slice()))
+            .transpose()
+            .map_err(Into::into)
+    }
+}
+
+/// Prefix of the keys under which runtime call results are stored.
+const RUNTIME_CALL_PREFIX: &str = "RuntimeApi";
+
+/// Returns the key of the storage item in `MemoryBackend`.
+///
+/// Names are SCALE encoded with their length so that a key is never a prefix of another
+/// storage item's key.
+fn storage_key(pallet: &str, storage: &str, keys: &[Vec<u8>]) -> Vec<u8> {
+    let mut storage_key = (pallet, storage).encode();
+    keys.iter().for_each(|key| storage_key.extend(key));
+    storage_key
+}
+
+impl ChainBackend for MemoryBackend {
+    async fn block_hash(&self, number: Number) -> Result<Option<Hash>, SlashError> {
+        Ok(self.block_hashes.get(&number).cloned())
+    }
+
+    async fn storage_map<K: Encode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key: K,
+        at: Hash,
+    ) -> Result<Option<V>, SlashError> {
+        self.get(storage_key(pallet, storage, &[key.encode()]), at)
+    }
+
+    async fn storage_double_map<K1: Encode, K2: Encode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key1: K1,
+        key2: K2,
+        at: Hash,
+    ) -> Result<Option<V>, SlashError> {
+        self.get(
+            storage_key(pallet, storage, &[key1.encode(), key2.encode()]),
+            at,
+        )
+    }
+
+    async fn storage_double_map_entries<K1: Encode, K2: Decode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key1: K1,
+        at: Hash,
+    ) -> Result<Vec<(K2, V)>, SlashError> {
+        let prefix = storage_key(pallet, storage, &[key1.encode()]);
+        self.storage
+            .range((at, prefix.clone())..)
+            .take_while(|((hash, key), _)| *hash == at && key.starts_with(&prefix))
+            .map(|((_, key), value)| {
+                let mut key2 = &key[prefix.len()..];
+                Ok::<_, SlashError>((K2::decode(&mut key2)?, V::decode(&mut value.as_slice())?))
+            })
+            .collect()
+    }
+
+    async fn runtime_call<V: Decode>(
+        &self,
+        method: &'static str,
+        params: Vec<Vec<u8>>,
+        at: Hash,
+    ) -> Result<V, SlashError> {
+        self.get(storage_key(RUNTIME_CALL_PREFIX, method, &params), at)?
+            .ok_or(SlashError::RuntimeCallNotFound { method, at })
+    }
+}
+
+/// Storage value kept as its raw SCALE encoding.
+///
+/// Decoding consumes the rest of the input, so that values can be cached, or quarantined
+/// when they fail to be decoded, without knowing their type.
+pub struct RawValue(pub Vec<u8>);
+
+impl Decode for RawValue {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+        let len = input
+            .remaining_len()?
+            .ok_or("Remaining length of the raw value is unknown")?;
+        let mut value = vec![0; len];
+        input.read(&mut value)?;
+        Ok(RawValue(value))
+    }
+}
+
+impl Encode for RawValue {
+    fn size_hint(&self) -> usize {
+        self.0.len()
+    }
+
+    fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+        // already encoded, so written as is without a length prefix
+        dest.write(&self.0)
+    }
+}
+
+/// Chain backend caching the state read from another backend on disk.
+///
+/// All the queries are made at fixed block hashes so their results never change and can be
+/// reused across runs. Only block hashes that were found are cached, since blocks that are
+/// not yet produced may be found later.
+pub struct CachedBackend<'a, B> {
+    inner: &'a B,
+    block_hashes: sled::Tree,
+    storage: sled::Tree,
+    entries: sled::Tree,
+    runtime_calls: sled::Tree,
+}
+
+impl<'a, B: ChainBackend> CachedBackend<'a, B> {
+    /// Opens the cache at the given path, creating it if it does not exist.
+    pub fn open(inner: &'a B, path: &Path) -> Result<Self, SlashError> {
+        let db = sled::open(path)?;
+        Ok(CachedBackend {
+            inner,
+            block_hashes: db.open_tree("block_hashes")?,
+            storage: db.open_tree("storage")?,
+            entries: db.open_tree("entries")?,
+            runtime_calls: db.open_tree("runtime_calls")?,
+        })
+    }
+
+    /// Returns the cached value of `key` at `at`, fetching and caching it if it is missing.
+    async fn get_or_fetch<T: Encode + Decode>(
+        tree: &sled::Tree,
+        key: Vec<u8>,
+        at: Hash,
+        fetch: impl Future<Output = Result<T, SlashError>>,
+    ) -> Result<T, SlashError> {
+        let key = (at, key).encode();
+        if let Some(value) = tree.get(&key)? {
+            return Ok(T::decode(&mut value.as_ref())?);
+        }
+
+        let value = fetch.await?;
+        tree.insert(key, value.encode())?;
+        Ok(value)
+    }
+}
+
+impl<'a, B: ChainBackend> ChainBackend for CachedBackend<'a, B> {
+    async fn block_hash(&self, number: Number) -> Result<Option<Hash>, SlashError> {
+        let key = number.encode();
+        if let Some(hash) = self.block_hashes.get(&key)? {
+            return Ok(Some(Hash::decode(&mut hash.as_ref())?));
+        }
+
+        let hash = self.inner.block_hash(number).await?;
+        if let Some(hash) = hash {
+            self.block_hashes.insert(key, hash.encode())?;
+        }
+        Ok(hash)
+    }
+
+    async fn storage_map<K: Encode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key: K,
+        at: Hash,
+    ) -> Result<Option<V>, SlashError> {
+        let value = Self::get_or_fetch(
+            &self.storage,
+            storage_key(pallet, storage, &[key.encode()]),
+            at,
+            async {
+                let value = self
+                    .inner
+                    .storage_map::<_, RawValue>(pallet, storage, key, at)
+                    .await?;
+                Ok::<_, SlashError>(value.map(|value| value.0))
+            },
+        )
+        .await?;
+        value
+            .map(|value| V::decode(&mut value.as_slice()))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    async fn storage_double_map<K1: Encode, K2: Encode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key1: K1,
+        key2: K2,
+        at: Hash,
+    ) -> Result<Option<V>, SlashError> {
+        let value = Self::get_or_fetch(
+            &self.storage,
+            storage_key(pallet, storage, &[key1.encode(), key2.encode()]),
+            at,
+            async {
+                let value = self
+                    .inner
+                    .storage_double_map::<_, _, RawValue>(pallet, storage, key1, key2, at)
+                    .await?;
+                Ok::<_, SlashError>(value.map(|value| value.0))
+            },
+        )
+        .await?;
+        value
+            .map(|value| V::decode(&mut value.as_slice()))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    async fn storage_double_map_entries<K1: Encode, K2: Decode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key1: K1,
+        at: Hash,
+    ) -> Result<Vec<(K2, V)>, SlashError> {
+        let entries = Self::get_or_fetch(
+            &self.entries,
+            storage_key(pallet, storage, &[key1.encode()]),
+            at,
+            async {
+                let entries = self
+                    .inner
+                    .storage_double_map_entries::<_, RawValue, RawValue>(pallet, storage, key1, at)
+                    .await?;
+                Ok::<_, SlashError>(
+                    entries
+                        .into_iter()
+                        .map(|(key2, value)| (key2.0, value.0))
+                        .collect::<Vec<_>>(),
+                )
+            },
+        )
+        .await?;
+        entries
+            .into_iter()
+            .map(|(key2, value)| {
+                Ok::<_, SlashError>((
+                    K2::decode(&mut key2.as_slice())?,
+                    V::decode(&mut value.as_slice())?,
+                ))
+            })
+            .collect()
+    }
+
+    async fn runtime_call<V: Decode>(
+        &self,
+        method: &'static str,
+        params: Vec<Vec<u8>>,
+        at: Hash,
+    ) -> Result<V, SlashError> {
+        let value = Self::get_or_fetch(
+            &self.runtime_calls,
+            storage_key(RUNTIME_CALL_PREFIX, method, &params),
+            at,
+            async {
+                let value = self
+                    .inner
+                    .runtime_call::<RawValue>(method, params, at)
+                    .await?;
+                Ok::<_, SlashError>(value.0)
+            },
+        )
+        .await?;
+        Ok(V::decode(&mut value.as_slice())?)
+    }
+}
+
+/// Chain backend recording the state read from another backend into a `MemoryBackend`.
+///
+/// The recorded state can be written as a state dump so that the same calculation can be
+/// replayed without access to a node, such as in the regression tests.
+pub struct RecordingBackend<'a, B> {
+    inner: &'a B,
+    state: Mutex<MemoryBackend>,
+}
+
+impl<'a, B: ChainBackend> RecordingBackend<'a, B> {
+    pub fn new(inner: &'a B) -> Self {
+        RecordingBackend {
+            inner,
+            state: Mutex::new(MemoryBackend::default()),
+        }
+    }
+
+    /// Returns the state recorded so far.
+    pub fn into_state(self) -> MemoryBackend {
+        self.state.into_inner().unwrap()
+    }
+}
+
+impl<'a, B: ChainBackend> ChainBackend for RecordingBackend<'a, B> {
+    async fn block_hash(&self, number: Number) -> Result<Option<Hash>, SlashError> {
+        let hash = self.inner.block_hash(number).await?;
+        if let Some(hash) = hash {
+            self.state.lock().unwrap().insert_block_hash(number, hash);
+        }
+        Ok(hash)
+    }
+
+    async fn storage_map<K: Encode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key: K,
+        at: Hash,
+    ) -> Result<Option<V>, SlashError> {
+        let encoded_key = RawValue(key.encode());
+        let value = self
+            .inner
+            .storage_map::<_, RawValue>(pallet, storage, key, at)
+            .await?;
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let decoded = V::decode(&mut value.0.as_slice())?;
+        self.state
+            .lock()
+            .unwrap()
+            .insert_storage_map(pallet, storage, encoded_key, at, value);
+        Ok(Some(decoded))
+    }
+
+    async fn storage_double_map<K1: Encode, K2: Encode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key1: K1,
+        key2: K2,
+        at: Hash,
+    ) -> Result<Option<V>, SlashError> {
+        let encoded_keys = (RawValue(key1.encode()), RawValue(key2.encode()));
+        let value = self
+            .inner
+            .storage_double_map::<_, _, RawValue>(pallet, storage, key1, key2, at)
+            .await?;
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let decoded = V::decode(&mut value.0.as_slice())?;
+        let (key1, key2) = encoded_keys;
+        self.state
+            .lock()
+            .unwrap()
+            .insert_storage_double_map(pallet, storage, key1, key2, at, value);
+        Ok(Some(decoded))
+    }
+
+    async fn storage_double_map_entries<K1: Encode, K2: Decode, V: Decode>(
+        &self,
+        pallet: &'static str,
+        storage: &'static str,
+        key1: K1,
+        at: Hash,
+    ) -> Result<Vec<(K2, V)>, SlashError> {
+        let encoded_key1 = key1.encode();
+        let entries = self
+            .inner
+            .storage_double_map_entries::<_, RawValue, RawValue>(pallet, storage, key1, at)
+            .await?;
+        let decoded = entries
+            .iter()
+            .map(|(key2, value)| {
+                Ok::<_, SlashError>((
+                    K2::decode(&mut key2.0.as_slice())?,
+                    V::decode(&mut value.0.as_slice())?,
+                ))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut state = self.state.lock().unwrap();
+        for (key2, value) in entries {
+            state.insert_storage_double_map(
+                pallet,
+                storage,
+                RawValue(encoded_key1.clone()),
+                key2,
+                at,
+                value,
+            );
+        }
+        Ok(decoded)
+    }
+
+    async fn runtime_call<V: Decode>(
+        &self,
+        method: &'static str,
+        params: Vec<Vec<u8>>,
+        at: Hash,
+    ) -> Result<V, SlashError> {
+        let value = self
+            .inner
+            .runtime_call::<RawValue>(method, params.clone(), at)
+            .await?;
+        let decoded = V::decode(&mut value.0.as_slice())?;
+        self.state
+            .lock()
+            .unwrap()
+            .insert_runtime_call(method, params, at, value);
+        Ok(decoded)
+    }
+}
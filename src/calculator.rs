@@ -0,0 +1,2265 @@
+use crate::backend::{ChainBackend, RawValue};
+use crate::compat::DynamicDecoder;
+use crate::error::SlashError;
+use crate::manifest::{
+    DepositComponent, NominatorPayout, OperatorPayout, PayoutComponents, PayoutManifest,
+    PendingDepositHandling, PendingKind, UnconvertedPending, UnlockableWithdrawal,
+    WithdrawalComponent,
+};
+use crate::progress::Progress;
+use crate::quarantine::{Quarantine, QuarantinedEntry};
+use crate::types::versioned::{
+    Layout, OperatorV0, PerbillSharePrice, PerquintillSharePrice, RuntimeUpgrades,
+    SharePricePrecision, WithdrawalV0,
+};
+use crate::types::{
+    ConfirmedDomainBlock, Deposit, DomainEpoch, NominatorStorage, Operator, OperatorStatus,
+    PendingDeposit, RuntimeVersion, SharePrice, StorageFundRedeemPrice, Withdrawal,
+    WithdrawalInBalance, WithdrawalInShares,
+};
+use crate::{AccountId, Balance, Hash, Number};
+use codec::{Decode, Encode};
+use futures::future::join_all;
+use futures::try_join;
+use scale_info::TypeInfo;
+use sp_domains::{DomainId, OperatorId};
+use sp_runtime::traits::Zero;
+use std::collections::BTreeMap;
+use tracing::{debug_span, info, instrument, warn, Instrument, Span};
+
+/// Calculates the balances slashed from the nominators of the slashed operators so that
+/// they can be refunded from the treasury.
+pub struct SlashRefundCalculator<'a, B> {
+    backend: &'a B,
+    fallback_raw_amount: bool,
+    exclude_unlockable_withdrawals: bool,
+    upgrades: RuntimeUpgrades,
+    at_block: Option<Hash>,
+    domain_id: Option<DomainId>,
+    quarantine: Option<&'a Quarantine>,
+    dynamic_decoder: Option<&'a DynamicDecoder>,
+}
+
+impl<'a, B: ChainBackend> SlashRefundCalculator<'a, B> {
+    pub fn new(backend: &'a B) -> Self {
+        SlashRefundCalculator {
+            backend,
+            fallback_raw_amount: false,
+            exclude_unlockable_withdrawals: false,
+            upgrades: RuntimeUpgrades::default(),
+            at_block: None,
+            domain_id: None,
+            quarantine: None,
+            dynamic_decoder: None,
+        }
+    }
+
+    /// Refunds the pending deposits and withdrawals whose epoch share price is not found
+    /// instead of leaving them out of the payouts.
+    ///
+    /// Deposits are refunded as their raw amount and withdrawals are converted at the share
+    /// price of the operator at the slash.
+    pub fn fallback_raw_amount(mut self, fallback_raw_amount: bool) -> Self {
+        self.fallback_raw_amount = fallback_raw_amount;
+        self
+    }
+
+    /// Leaves the withdrawals that were unlockable before the slash out of the payouts, since
+    /// the nominators could have claimed them.
+    ///
+    /// A withdrawal is unlockable once the domain block it unlocks at is confirmed.
+    pub fn exclude_unlockable_withdrawals(mut self, exclude_unlockable_withdrawals: bool) -> Self {
+        self.exclude_unlockable_withdrawals = exclude_unlockable_withdrawals;
+        self
+    }
+
+    /// Decodes the storage with the layouts of the runtime upgrades of the network it is read
+    /// from, instead of the ones of Gemini 3h.
+    pub fn runtime_upgrades(mut self, upgrades: RuntimeUpgrades) -> Self {
+        self.upgrades = upgrades;
+        self
+    }
+
+    /// Calculates the balances from the state of the given block instead of the block before
+    /// the slash of each operator, such as to see what the refunds would have been at another
+    /// point.
+    pub fn at_block(mut self, at_block: Option<Hash>) -> Self {
+        self.at_block = at_block;
+        self
+    }
+
+    /// Only calculates the operators of the given domain, skipping the others, such as when
+    /// the refunds are approved domain by domain.
+    pub fn domain_id(mut self, domain_id: Option<DomainId>) -> Self {
+        self.domain_id = domain_id;
+        self
+    }
+
+    /// Writes the storage entries of the nominators that fail to be decoded to the
+    /// quarantine before failing their operator, so that their raw keys and values are kept
+    /// to be decoded again.
+    pub fn quarantine(mut self, quarantine: Option<&'a Quarantine>) -> Self {
+        self.quarantine = quarantine;
+        self
+    }
+
+    /// Decodes the operators and the storage of the nominators that fail to be decoded into
+    /// the types in `types.rs` with the types of the runtime metadata instead, warning about
+    /// them, so that a minor change of their layout does not fail the operators.
+    pub fn dynamic_decoder(mut self, dynamic_decoder: Option<&'a DynamicDecoder>) -> Self {
+        self.dynamic_decoder = dynamic_decoder;
+        self
+    }
+
+    /// Calculates the slashed balances of the nominators of each given operator.
+    ///
+    /// Each operator is given along with the block number at which it was slashed and the
+    /// balances are calculated from the state of the block before. Operators are calculated
+    /// independently so that a failure of one operator does not affect the others.
+    pub async fn calculate(&self, slashed_operators: Vec<(OperatorId, Number)>) -> Calculation {
+        calculate_payouts(
+            self.backend,
+            slashed_operators,
+            self.fallback_raw_amount,
+            self.exclude_unlockable_withdrawals,
+            self.upgrades,
+            self.at_block,
+            self.domain_id,
+            self.quarantine,
+            self.dynamic_decoder,
+        )
+        .await
+    }
+
+    /// Calculates the slashed balances of the nominators of each given operator along with
+    /// the state of the operator they are derived from, to be audited.
+    pub async fn audit(&self, slashed_operators: Vec<(OperatorId, Number)>) -> Audit {
+        calculate_audits(
+            self.backend,
+            slashed_operators,
+            self.fallback_raw_amount,
+            self.exclude_unlockable_withdrawals,
+            self.upgrades,
+            self.at_block,
+            self.domain_id,
+            self.quarantine,
+            self.dynamic_decoder,
+        )
+        .await
+    }
+
+    /// Returns the deposits and withdrawals of the nominators of the operator that its
+    /// slashed balances are calculated from.
+    pub async fn nominator_storage(
+        &self,
+        operator_id: OperatorId,
+        slashed_at: Number,
+    ) -> Result<BTreeMap<AccountId, NominatorStorage>, SlashError> {
+        let block_hash = get_state_block_hash(self.backend, slashed_at, self.at_block).await?;
+        let spec_version = get_spec_version(self.backend, block_hash).await?;
+        get_nominator_deposits_and_withdrawal(
+            self.backend,
+            operator_id,
+            block_hash,
+            spec_version,
+            Layout::at(spec_version, self.upgrades),
+            self.quarantine,
+            self.dynamic_decoder,
+        )
+        .await
+    }
+
+    /// Calculates the breakdown of the slashed balance of the nominator for each of the given
+    /// operators it nominated.
+    ///
+    /// Operators the nominator did not nominate before the slash are skipped without
+    /// calculating them.
+    pub async fn breakdown(
+        &self,
+        slashed_operators: Vec<(OperatorId, Number)>,
+        nominator_id: &AccountId,
+    ) -> Breakdown {
+        let futs: Vec<_> = slashed_operators
+            .into_iter()
+            .map(|(operator_id, slashed_at)| async move {
+                calculate_nominator_breakdown(
+                    self.backend,
+                    operator_id,
+                    slashed_at,
+                    nominator_id,
+                    self.fallback_raw_amount,
+                    self.exclude_unlockable_withdrawals,
+                    self.upgrades,
+                    self.at_block,
+                    self.domain_id,
+                    self.quarantine,
+                    self.dynamic_decoder,
+                )
+                .await
+                .map_err(|err| SlashError::operator(operator_id, err))
+            })
+            .collect();
+
+        let mut breakdowns = vec![];
+        let mut failures = vec![];
+        for result in join_all(futs).await {
+            match result {
+                Ok(Some(breakdown)) => breakdowns.push(breakdown),
+                Ok(None) => {}
+                Err(err) => failures.push(err),
+            }
+        }
+
+        Breakdown {
+            breakdowns,
+            failures,
+        }
+    }
+}
+
+/// Outcome of the calculation of the slashed operators.
+pub struct Calculation {
+    /// Payouts of the operators that were calculated successfully.
+    pub payouts: PayoutManifest,
+    /// Errors of the operators that failed to be calculated.
+    pub failures: Vec<SlashError>,
+}
+
+/// Outcome of the audit of the slashed operators.
+pub struct Audit {
+    /// Audits of the operators that were calculated successfully.
+    pub audits: Vec<OperatorAudit>,
+    /// Errors of the operators that failed to be calculated.
+    pub failures: Vec<SlashError>,
+}
+
+/// State of a slashed operator and the breakdown of the slashed balance of each of its
+/// nominators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperatorAudit {
+    pub operator_id: OperatorId,
+    /// Domain the operator was staking on when it was slashed.
+    pub domain_id: DomainId,
+    pub slashed_at: Number,
+    /// Hash of the block whose state is used for the calculation, the block before the slash
+    /// unless calculated at another block.
+    pub block_hash: Hash,
+    pub spec_version: u32,
+    /// Total active stake of the nominators of the operator.
+    pub current_total_stake: Balance,
+    /// Rewards of the operator in the epoch it was slashed in.
+    pub current_epoch_rewards: Balance,
+    pub nominators: BTreeMap<AccountId, NominatorBreakdown>,
+    /// Balance left over after rounding down the payouts of the nominators.
+    pub dust: Balance,
+    /// Balance left over beyond the rounding of the payouts of the nominators.
+    pub unexplained: Balance,
+}
+
+impl OperatorAudit {
+    fn into_payout(self) -> OperatorPayout {
+        let mut nominators = BTreeMap::new();
+        let mut unconverted = BTreeMap::new();
+        let mut unlockable = BTreeMap::new();
+        let mut components = BTreeMap::new();
+        for (nominator_id, breakdown) in self.nominators {
+            components.insert(nominator_id.clone(), breakdown.components());
+            if !breakdown.unconverted.is_empty() {
+                unconverted.insert(nominator_id.clone(), breakdown.unconverted);
+            }
+            if let Some(unlockable_withdrawal) = breakdown.unlockable {
+                unlockable.insert(nominator_id.clone(), unlockable_withdrawal);
+            }
+            nominators.insert(nominator_id, breakdown.payout);
+        }
+
+        OperatorPayout {
+            operator_id: self.operator_id,
+            domain_id: Some(self.domain_id),
+            slashed_at: self.slashed_at,
+            block_hash: self.block_hash,
+            spec_version: self.spec_version,
+            nominators,
+            unconverted,
+            unlockable,
+            components,
+            dust: self.dust,
+            unexplained: self.unexplained,
+        }
+    }
+}
+
+/// Outcome of the breakdown of the slashed balances of a nominator.
+pub struct Breakdown {
+    /// Breakdown for each operator nominated by the nominator.
+    pub breakdowns: Vec<NominatorBreakdown>,
+    /// Errors of the operators that failed to be calculated.
+    pub failures: Vec<SlashError>,
+}
+
+/// Inputs of the calculation of the balance slashed from a nominator of an operator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NominatorBreakdown {
+    pub operator_id: OperatorId,
+    pub slashed_at: Number,
+    /// Hash of the block whose state is used for the calculation, the block before the slash
+    /// unless calculated at another block.
+    pub block_hash: Hash,
+    /// Shares of the nominator, including the deposits of previous epochs converted at the
+    /// share price of their epoch and the shares withdrawn in the current epoch.
+    pub shares: Balance,
+    /// Share price of the operator used to convert the shares to the staked balance.
+    pub share_price: SharePrice,
+    /// Storage fee deposited by the nominator into the storage fund.
+    pub storage_fee_deposit: Balance,
+    /// Balance of the storage fund of the operator.
+    pub storage_fund_balance: Balance,
+    /// Storage fee deposits of all the nominators, excluding the pending deposits.
+    pub total_storage_fee_deposit: Balance,
+    /// Pending deposits and withdrawals whose epoch share price was not found.
+    pub unconverted: Vec<UnconvertedPending>,
+    /// Withdrawals that were unlockable before the slash, if any.
+    pub unlockable: Option<UnlockableWithdrawal>,
+    /// Withdrawals unlocking, before the withdrawal in shares is converted.
+    pub ready_to_withdraw: Balance,
+    /// Withdrawal in shares and the balance it was converted to, if any.
+    pub withdrawal_in_shares: Option<WithdrawalComponent>,
+    /// Storage fee refunds of the withdrawals, including the withdrawal in shares converted.
+    pub storage_fee_refund: Balance,
+    /// Pending deposit and how it was refunded, if any.
+    pub pending_deposit: Option<DepositComponent>,
+    pub payout: NominatorPayout,
+}
+
+impl NominatorBreakdown {
+    /// Returns the components the payout is derived from, as recorded in the manifest.
+    pub fn components(&self) -> PayoutComponents {
+        PayoutComponents {
+            shares: self.shares,
+            share_price: self.share_price.parts(),
+            ready_to_withdraw: self.ready_to_withdraw,
+            withdrawal_in_shares: self.withdrawal_in_shares.clone(),
+            storage_fee_refund: self.storage_fee_refund,
+            pending_deposit: self.pending_deposit.clone(),
+            storage_fee_deposit: self.storage_fee_deposit,
+            storage_fund_balance: self.storage_fund_balance,
+            total_storage_fee_deposit: self.total_storage_fee_deposit,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn calculate_payouts<B: ChainBackend>(
+    backend: &B,
+    slashed_operators: Vec<(OperatorId, Number)>,
+    fallback_raw_amount: bool,
+    exclude_unlockable_withdrawals: bool,
+    upgrades: RuntimeUpgrades,
+    at_block: Option<Hash>,
+    domain_id: Option<DomainId>,
+    quarantine: Option<&Quarantine>,
+    dynamic_decoder: Option<&DynamicDecoder>,
+) -> Calculation {
+    let Audit {
+        audits,
+        mut failures,
+    } = calculate_audits(
+        backend,
+        slashed_operators,
+        fallback_raw_amount,
+        exclude_unlockable_withdrawals,
+        upgrades,
+        at_block,
+        domain_id,
+        quarantine,
+        dynamic_decoder,
+    )
+    .await;
+    let payouts = PayoutManifest::new(audits.into_iter().map(OperatorAudit::into_payout).collect())
+        .unwrap_or_else(|err| {
+            // the payouts are dropped altogether since their total cannot be recorded
+            failures.push(err);
+            PayoutManifest::empty()
+        });
+    Calculation { payouts, failures }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn calculate_audits<B: ChainBackend>(
+    backend: &B,
+    slashed_operators: Vec<(OperatorId, Number)>,
+    fallback_raw_amount: bool,
+    exclude_unlockable_withdrawals: bool,
+    upgrades: RuntimeUpgrades,
+    at_block: Option<Hash>,
+    domain_id: Option<DomainId>,
+    quarantine: Option<&Quarantine>,
+    dynamic_decoder: Option<&DynamicDecoder>,
+) -> Audit {
+    let progress = Progress::new("Calculated operators", slashed_operators.len());
+    let progress = &progress;
+    let futs: Vec<_> = slashed_operators
+        .into_iter()
+        .map(|(operator_id, slashed_at)| async move {
+            let result = calculate_operator_audit(
+                backend,
+                operator_id,
+                slashed_at,
+                fallback_raw_amount,
+                exclude_unlockable_withdrawals,
+                upgrades,
+                at_block,
+                domain_id,
+                quarantine,
+                dynamic_decoder,
+            )
+            .await
+            .map_err(|err| SlashError::operator(operator_id, err));
+            progress.advance(1);
+            result
+        })
+        .collect();
+
+    let mut audits = vec![];
+    let mut failures = vec![];
+    for result in join_all(futs).await {
+        match result {
+            Ok(Some(audit)) => audits.push(audit),
+            Ok(None) => {}
+            Err(err) => failures.push(err),
+        }
+    }
+
+    Audit { audits, failures }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[instrument(
+    skip(backend, quarantine, dynamic_decoder),
+    fields(block_hash = tracing::field::Empty)
+)]
+async fn calculate_operator_audit<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    slashed_at: Number,
+    fallback_raw_amount: bool,
+    exclude_unlockable_withdrawals: bool,
+    upgrades: RuntimeUpgrades,
+    at_block: Option<Hash>,
+    domain_id: Option<DomainId>,
+    quarantine: Option<&Quarantine>,
+    dynamic_decoder: Option<&DynamicDecoder>,
+) -> Result<Option<OperatorAudit>, SlashError> {
+    let block_hash = get_state_block_hash(backend, slashed_at, at_block).await?;
+    Span::current().record("block_hash", tracing::field::debug(block_hash));
+
+    // storage is decoded with the layout of the runtime at the block
+    let spec_version = get_spec_version(backend, block_hash).await?;
+    let layout = Layout::at(spec_version, upgrades);
+    // the operator is loaded first so that the nominators of other domains are not loaded
+    let operator = get_operator(backend, operator_id, block_hash, layout, dynamic_decoder).await?;
+    if !in_domain(&operator, domain_id) {
+        info!(
+            "Skipping Operator[{operator_id:?}] of Domain[{:?}]",
+            operator.current_domain_id
+        );
+        return Ok(None);
+    }
+    let nominator_storage = get_nominator_deposits_and_withdrawal(
+        backend,
+        operator_id,
+        block_hash,
+        spec_version,
+        layout,
+        quarantine,
+        dynamic_decoder,
+    )
+    .await?;
+    info!(
+        "Loaded {} nominators of Operator[{operator_id:?}]",
+        nominator_storage.len()
+    );
+
+    let (domain_id, current_total_stake, current_epoch_rewards) = (
+        operator.current_domain_id,
+        operator.current_total_stake,
+        operator.current_epoch_rewards,
+    );
+    let (nominators, Leftover { dust, unexplained }) = calculate_nominators_slashed_amount(
+        backend,
+        operator_id,
+        operator,
+        nominator_storage,
+        slashed_at,
+        block_hash,
+        // share prices are computed in the precision of the runtime at the block
+        SharePricePrecision::at(spec_version, upgrades),
+        fallback_raw_amount,
+        exclude_unlockable_withdrawals,
+    )
+    .await?;
+
+    Ok(Some(OperatorAudit {
+        operator_id,
+        domain_id,
+        slashed_at,
+        block_hash,
+        spec_version,
+        current_total_stake,
+        current_epoch_rewards,
+        nominators,
+        dust,
+        unexplained,
+    }))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn calculate_nominator_breakdown<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    slashed_at: Number,
+    nominator_id: &AccountId,
+    fallback_raw_amount: bool,
+    exclude_unlockable_withdrawals: bool,
+    upgrades: RuntimeUpgrades,
+    at_block: Option<Hash>,
+    domain_id: Option<DomainId>,
+    quarantine: Option<&Quarantine>,
+    dynamic_decoder: Option<&DynamicDecoder>,
+) -> Result<Option<NominatorBreakdown>, SlashError> {
+    let block_hash = get_state_block_hash(backend, slashed_at, at_block).await?;
+    let deposit = backend
+        .storage_double_map::<_, _, Deposit>(
+            "Domains",
+            "Deposits",
+            operator_id,
+            nominator_id,
+            block_hash,
+        )
+        .await?;
+    if deposit.is_none() {
+        return Ok(None);
+    }
+
+    // the storage fund is shared by all the nominators, so all of them are calculated
+    let spec_version = get_spec_version(backend, block_hash).await?;
+    let layout = Layout::at(spec_version, upgrades);
+    let (nominator_storage, operator) = try_join!(
+        get_nominator_deposits_and_withdrawal(
+            backend,
+            operator_id,
+            block_hash,
+            spec_version,
+            layout,
+            quarantine,
+            dynamic_decoder,
+        ),
+        get_operator(backend, operator_id, block_hash, layout, dynamic_decoder),
+    )?;
+    if !in_domain(&operator, domain_id) {
+        return Ok(None);
+    }
+    let (mut breakdowns, _) = calculate_nominators_slashed_amount(
+        backend,
+        operator_id,
+        operator,
+        nominator_storage,
+        slashed_at,
+        block_hash,
+        // share prices are computed in the precision of the runtime at the block
+        SharePricePrecision::at(spec_version, upgrades),
+        fallback_raw_amount,
+        exclude_unlockable_withdrawals,
+    )
+    .await?;
+
+    Ok(breakdowns.remove(nominator_id))
+}
+
+/// Whether the operator is staking on the domain, or any domain if none is given.
+fn in_domain(operator: &Operator, domain_id: Option<DomainId>) -> bool {
+    domain_id.map_or(true, |domain_id| operator.current_domain_id == domain_id)
+}
+
+/// Returns the hash of the block whose state the calculation reads, which is the block before
+/// the slash unless another block is given.
+async fn get_state_block_hash<B: ChainBackend>(
+    backend: &B,
+    slashed_at: Number,
+    at_block: Option<Hash>,
+) -> Result<Hash, SlashError> {
+    if let Some(at_block) = at_block {
+        return Ok(at_block);
+    }
+    let number = slashed_at - 1;
+    backend
+        .block_hash(number)
+        .await?
+        .ok_or(SlashError::BlockNotFound(number))
+}
+
+async fn get_nominator_deposits_and_withdrawal<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    block_hash: Hash,
+    spec_version: u32,
+    layout: Layout,
+    quarantine: Option<&Quarantine>,
+    dynamic_decoder: Option<&DynamicDecoder>,
+) -> Result<BTreeMap<AccountId, NominatorStorage>, SlashError> {
+    let deposits = get_nominator_storage::<Deposit, _>(
+        backend,
+        operator_id,
+        block_hash,
+        spec_version,
+        "Deposits",
+        quarantine,
+        dynamic_decoder,
+    )
+    .await?;
+    // every nominator has a deposit, along with the operator owner, so fewer deposits means
+    // the storage keys were truncated
+    let nominator_count = backend
+        .storage_map::<_, u32>("Domains", "NominatorCount", operator_id, block_hash)
+        .await?;
+    if let Some(nominator_count) = nominator_count {
+        if deposits.len() < nominator_count as usize {
+            return Err(SlashError::NominatorCountMismatch {
+                found: deposits.len(),
+                expected: nominator_count,
+            });
+        }
+    }
+    let withdrawals: Vec<(AccountId, Withdrawal)> = match layout {
+        Layout::V0 => get_nominator_storage::<WithdrawalV0, _>(
+            backend,
+            operator_id,
+            block_hash,
+            spec_version,
+            "Withdrawals",
+            quarantine,
+            dynamic_decoder,
+        )
+        .await?
+        .into_iter()
+        .map(|(nominator_id, withdrawal)| (nominator_id, withdrawal.into()))
+        .collect(),
+        Layout::Current => {
+            get_nominator_storage(
+                backend,
+                operator_id,
+                block_hash,
+                spec_version,
+                "Withdrawals",
+                quarantine,
+                dynamic_decoder,
+            )
+            .await?
+        }
+    };
+    let mut storage = BTreeMap::new();
+    deposits.into_iter().for_each(|(nominator_id, deposit)| {
+        storage.insert(
+            nominator_id,
+            NominatorStorage {
+                deposit,
+                withdrawal: None,
+            },
+        );
+    });
+    for (nominator_id, withdrawal) in withdrawals {
+        // there will always be a deposit for this nominator even with zero shares
+        match storage.get_mut(&nominator_id) {
+            None => {
+                return Err(SlashError::nominator(
+                    nominator_id,
+                    SlashError::MissingDeposit,
+                ))
+            }
+            Some(nominator_storage) => nominator_storage.withdrawal = Some(withdrawal),
+        }
+    }
+
+    Ok(storage)
+}
+
+/// Returns the entries of the storage of the nominators of the operator.
+///
+/// With a quarantine, every entry that fails to be decoded is written to it before failing
+/// the operator, instead of failing on the first of them.
+async fn get_nominator_storage<V: Decode + TypeInfo + 'static, B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    block_hash: Hash,
+    spec_version: u32,
+    storage: &'static str,
+    quarantine: Option<&Quarantine>,
+    dynamic_decoder: Option<&DynamicDecoder>,
+) -> Result<Vec<(AccountId, V)>, SlashError> {
+    if quarantine.is_none() && dynamic_decoder.is_none() {
+        return backend
+            .storage_double_map_entries("Domains", storage, operator_id, block_hash)
+            .await;
+    }
+    let entries = backend
+        .storage_double_map_entries::<_, RawValue, RawValue>(
+            "Domains",
+            storage,
+            operator_id,
+            block_hash,
+        )
+        .await?;
+    let mut decoded = Vec::with_capacity(entries.len());
+    let mut quarantined = 0;
+    for (RawValue(key), RawValue(value)) in entries {
+        let result = AccountId::decode(&mut key.as_slice())
+            .map_err(SlashError::from)
+            .and_then(|nominator_id| {
+                let value = decode_storage(dynamic_decoder, storage, &value)?;
+                Ok((nominator_id, value))
+            });
+        match result {
+            Ok(entry) => decoded.push(entry),
+            Err(err) => {
+                let Some(quarantine) = quarantine else {
+                    return Err(err);
+                };
+                warn!("Quarantining {storage} entry of Operator[{operator_id:?}]: {err}");
+                quarantine.add(&QuarantinedEntry {
+                    pallet: "Domains".to_string(),
+                    storage: storage.to_string(),
+                    operator_id,
+                    block_hash,
+                    spec_version,
+                    key: key.into(),
+                    value: value.into(),
+                    error: err.to_string(),
+                })?;
+                quarantined += 1;
+            }
+        }
+    }
+    // refunds calculated without some of the nominators would be silently wrong
+    if quarantined > 0 {
+        return Err(SlashError::Quarantined {
+            storage,
+            count: quarantined,
+        });
+    }
+    Ok(decoded)
+}
+
+/// Decodes the storage value into `V`, falling back to the types of the runtime metadata
+/// if a dynamic decoder is given.
+fn decode_storage<V: Decode + TypeInfo + 'static>(
+    dynamic_decoder: Option<&DynamicDecoder>,
+    storage: &'static str,
+    value: &[u8],
+) -> Result<V, SlashError> {
+    let error = match V::decode(&mut &value[..]) {
+        Ok(value) => return Ok(value),
+        Err(error) => error,
+    };
+    let Some(dynamic_decoder) = dynamic_decoder else {
+        return Err(error.into());
+    };
+    match dynamic_decoder.decode(storage, value) {
+        Ok(value) => {
+            warn!("Decoded {storage} entry with the runtime metadata after it failed to be decoded: {error}");
+            Ok(value)
+        }
+        Err(dynamic_error) => Err(SlashError::Undecodable {
+            storage,
+            error,
+            dynamic_error,
+        }),
+    }
+}
+
+async fn get_operator<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    block_hash: Hash,
+    layout: Layout,
+    dynamic_decoder: Option<&DynamicDecoder>,
+) -> Result<Operator, SlashError> {
+    let operator = match dynamic_decoder {
+        None => find_operator(backend, operator_id, block_hash, layout).await?,
+        Some(dynamic_decoder) => backend
+            .storage_map::<_, RawValue>("Domains", "Operators", operator_id, block_hash)
+            .await?
+            .map(|RawValue(value)| match layout {
+                Layout::V0 => {
+                    decode_storage::<OperatorV0>(Some(dynamic_decoder), "Operators", &value)
+                        .map(Operator::from)
+                }
+                Layout::Current => decode_storage(Some(dynamic_decoder), "Operators", &value),
+            })
+            .transpose()?,
+    };
+    operator.ok_or(SlashError::StorageNotFound {
+        pallet: "Domains",
+        storage: "Operators",
+        at: block_hash,
+    })
+}
+
+/// Returns the operator decoded with the layout of the runtime at the block, if it is
+/// registered at the block.
+pub(crate) async fn find_operator<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    block_hash: Hash,
+    layout: Layout,
+) -> Result<Option<Operator>, SlashError> {
+    Ok(match layout {
+        Layout::V0 => backend
+            .storage_map::<_, OperatorV0>("Domains", "Operators", operator_id, block_hash)
+            .await?
+            .map(Operator::from),
+        Layout::Current => {
+            backend
+                .storage_map::<_, Operator>("Domains", "Operators", operator_id, block_hash)
+                .await?
+        }
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn calculate_nominators_slashed_amount<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    mut operator: Operator,
+    operator_nominators: BTreeMap<AccountId, NominatorStorage>,
+    slashed_at: Number,
+    block_hash: Hash,
+    precision: SharePricePrecision,
+    fallback_raw_amount: bool,
+    exclude_unlockable_withdrawals: bool,
+) -> Result<(BTreeMap<AccountId, NominatorBreakdown>, Leftover), SlashError> {
+    let total_stake = operator
+        .current_total_stake
+        .checked_add(operator.current_epoch_rewards)
+        .ok_or(SlashError::Overflow)?;
+
+    operator.current_epoch_rewards = Zero::zero();
+    let total_shares = operator.current_total_shares;
+    // the stake of a deregistered operator is unlocked at the share price of the epoch it was
+    // deregistered in
+    let deregistered_at = match operator.stored_status() {
+        OperatorStatus::Deregistered(deregistered_info) => Some(deregistered_info.domain_epoch),
+        _ => None,
+    };
+    let share_price = match deregistered_at {
+        None => SharePrice::new(total_shares, total_stake, precision),
+        Some(domain_epoch) => {
+            info!("Operator[{operator_id:?}] was deregistered at {domain_epoch:?}");
+            // the current share price would understate or overstate the refunds
+            get_operator_epoch_share_price(
+                backend,
+                operator_id,
+                domain_epoch,
+                block_hash,
+                precision,
+            )
+            .await?
+            .ok_or(SlashError::DeregistrationSharePriceNotFound(
+                domain_epoch.deconstruct().1,
+            ))?
+        }
+    };
+
+    let operator_storage_fund_balance =
+        get_operator_storage_fund_balance(backend, operator_id, block_hash).await?;
+    // withdrawals unlock once the domain block they unlock at is confirmed, so none of them
+    // is unlockable if that block is not known
+    let confirmed_domain_block =
+        get_confirmed_domain_block_number(backend, operator.current_domain_id, block_hash).await?;
+    if confirmed_domain_block.is_none() {
+        warn!(
+            "Confirmed block of Domain[{:?}] not found, the unlockable withdrawals of Operator[{operator_id:?}] are not told apart",
+            operator.current_domain_id
+        );
+    }
+    let mut total_storage_fee_deposit = operator.total_storage_fee_deposit;
+
+    let mut nominators_slashed_balances = BTreeMap::new();
+    let mut nominator_storage_fund_deposited_balances = vec![];
+    let mut nominators_shares = Balance::zero();
+    let mut nominators_stake = Balance::zero();
+    let mut has_unconverted = false;
+    for (nominator_id, mut nominator_storage) in operator_nominators {
+        let nominator_err = |err: SlashError| SlashError::nominator(nominator_id.clone(), err);
+        let span = debug_span!("nominator", nominator = %nominator_id);
+        let (known_shares, pending) = (
+            nominator_storage.deposit.known.shares,
+            nominator_storage.deposit.pending,
+        );
+        // deposits pending since the deregistration are never converted to shares and are
+        // unlocked as is, along with their storage fee deposit
+        let pending_unlock = match (deregistered_at, nominator_storage.deposit.pending) {
+            (Some(deregistered_at), Some(pending_deposit))
+                if pending_deposit.effective_domain_epoch.deconstruct().1
+                    >= deregistered_at.deconstruct().1 =>
+            {
+                nominator_storage.deposit.pending = None;
+                nominator_storage.deposit.known.storage_fee_deposit = nominator_storage
+                    .deposit
+                    .known
+                    .storage_fee_deposit
+                    .checked_add(pending_deposit.storage_fee_deposit)
+                    .ok_or_else(|| nominator_err(SlashError::Overflow))?;
+                pending_deposit.amount
+            }
+            _ => Zero::zero(),
+        };
+        let mut unconverted = vec![];
+        let mut fallback_stake = Balance::zero();
+        let unconverted_deposit = do_convert_previous_epoch_deposits(
+            backend,
+            operator_id,
+            &mut nominator_storage.deposit,
+            block_hash,
+            precision,
+        )
+        .instrument(span.clone())
+        .await
+        .map_err(nominator_err)?;
+        if let Some(pending_deposit) = unconverted_deposit {
+            let (_, epoch) = pending_deposit.effective_domain_epoch.deconstruct();
+            warn!(
+                "Share price of epoch {epoch} not found for the pending deposit of {} of Nominator[{nominator_id}] of Operator[{operator_id:?}]",
+                pending_deposit.amount
+            );
+            if fallback_raw_amount {
+                fallback_stake = pending_deposit.amount;
+                nominator_storage.deposit.known.storage_fee_deposit = nominator_storage
+                    .deposit
+                    .known
+                    .storage_fee_deposit
+                    .checked_add(pending_deposit.storage_fee_deposit)
+                    .ok_or_else(|| nominator_err(SlashError::Overflow))?;
+            } else {
+                // the storage fee of the deposit is not redeemed by the nominator, so it is
+                // left out of the storage fee deposits the storage fund is redeemed against
+                total_storage_fee_deposit =
+                    total_storage_fee_deposit.saturating_sub(pending_deposit.storage_fee_deposit);
+            }
+            unconverted.push(UnconvertedPending {
+                kind: PendingKind::Deposit,
+                epoch,
+                amount: pending_deposit.amount,
+                refunded: fallback_raw_amount,
+            });
+        }
+        let pending_deposit = pending.map(|pending| {
+            let handling = if !pending_unlock.is_zero() {
+                PendingDepositHandling::Unlocked
+            } else if unconverted_deposit.is_none() {
+                PendingDepositHandling::Converted {
+                    shares: nominator_storage
+                        .deposit
+                        .known
+                        .shares
+                        .saturating_sub(known_shares),
+                }
+            } else if fallback_raw_amount {
+                PendingDepositHandling::RawAmount
+            } else {
+                PendingDepositHandling::NotRefunded
+            };
+            DepositComponent {
+                epoch: pending.effective_domain_epoch.deconstruct().1,
+                amount: pending.amount,
+                storage_fee_deposit: pending.storage_fee_deposit,
+                handling,
+            }
+        });
+
+        let mut unlockable = None;
+        let mut ready_to_withdraw = Balance::zero();
+        let mut withdrawal_in_shares = None;
+        let mut storage_fee_refund = Balance::zero();
+        let (amount_ready_to_withdraw, shares_withdrew_in_current_epoch, storage_fund_withdrew) =
+            match nominator_storage.withdrawal {
+                None => (Zero::zero(), Zero::zero(), Zero::zero()),
+                Some(mut withdrawal) => {
+                    ready_to_withdraw = withdrawal.total_withdrawal_amount;
+                    let pending_withdrawal = withdrawal.withdrawal_in_shares.clone();
+                    let unconverted_withdrawal = do_convert_previous_epoch_withdrawal(
+                        backend,
+                        operator_id,
+                        &mut withdrawal,
+                        block_hash,
+                        precision,
+                    )
+                    .instrument(span)
+                    .await
+                    .map_err(nominator_err)?;
+                    if let Some(withdrawal_in_shares) = unconverted_withdrawal {
+                        let (_, epoch) = withdrawal_in_shares.domain_epoch.deconstruct();
+                        warn!(
+                            "Share price of epoch {epoch} not found for the pending withdrawal of {} shares of Nominator[{nominator_id}] of Operator[{operator_id:?}]",
+                            withdrawal_in_shares.shares
+                        );
+                        unconverted.push(UnconvertedPending {
+                            kind: PendingKind::Withdrawal,
+                            epoch,
+                            amount: withdrawal_in_shares.shares,
+                            refunded: fallback_raw_amount,
+                        });
+                        // shares are converted at the share price of the operator instead
+                        if fallback_raw_amount {
+                            unlock_withdrawal_in_shares(
+                                &mut withdrawal,
+                                withdrawal_in_shares,
+                                &share_price,
+                            )
+                            .map_err(nominator_err)?;
+                        }
+                    }
+                    withdrawal_in_shares =
+                        pending_withdrawal.map(|pending_withdrawal| WithdrawalComponent {
+                            epoch: pending_withdrawal.domain_epoch.deconstruct().1,
+                            shares: pending_withdrawal.shares,
+                            amount: withdrawal
+                                .total_withdrawal_amount
+                                .saturating_sub(ready_to_withdraw),
+                        });
+                    let (unlockable_amount, unlockable_storage_fee_refund) =
+                        match confirmed_domain_block {
+                            Some(confirmed_domain_block) => {
+                                unlockable_withdrawals(&withdrawal, confirmed_domain_block)
+                                    .map_err(nominator_err)?
+                            }
+                            None => (Zero::zero(), Zero::zero()),
+                        };
+                    let mut total_withdrawal_amount = withdrawal.total_withdrawal_amount;
+                    storage_fee_refund = withdrawal
+                        .withdrawals
+                        .iter()
+                        .try_fold(Balance::zero(), |acc, withdrawal_in_balance| {
+                            acc.checked_add(withdrawal_in_balance.storage_fee_refund)
+                        })
+                        .ok_or_else(|| nominator_err(SlashError::Overflow))?;
+                    let mut refunded_storage_fee_refund = storage_fee_refund;
+                    if let Some(confirmed_domain_block) = confirmed_domain_block {
+                        let amount = unlockable_amount
+                            .checked_add(unlockable_storage_fee_refund)
+                            .ok_or_else(|| nominator_err(SlashError::Overflow))?;
+                        if !amount.is_zero() {
+                            unlockable = Some(UnlockableWithdrawal {
+                                amount,
+                                confirmed_domain_block,
+                                refunded: !exclude_unlockable_withdrawals,
+                            });
+                        }
+                    }
+                    if exclude_unlockable_withdrawals {
+                        total_withdrawal_amount =
+                            total_withdrawal_amount.saturating_sub(unlockable_amount);
+                        refunded_storage_fee_refund = refunded_storage_fee_refund
+                            .saturating_sub(unlockable_storage_fee_refund);
+                    }
+                    (
+                        total_withdrawal_amount,
+                        withdrawal
+                            .withdrawal_in_shares
+                            .map(|WithdrawalInShares { shares, .. }| shares)
+                            .unwrap_or_default(),
+                        refunded_storage_fee_refund,
+                    )
+                }
+            };
+
+        let nominator_shares = nominator_storage
+            .deposit
+            .known
+            .shares
+            .checked_add(shares_withdrew_in_current_epoch)
+            .ok_or_else(|| nominator_err(SlashError::Overflow))?;
+
+        let nominator_staked_amount = share_price.shares_to_stake(nominator_shares);
+        let refunded_stake = nominator_staked_amount
+            .checked_add(fallback_stake)
+            .ok_or_else(|| nominator_err(SlashError::Overflow))?;
+        nominators_stake = nominators_stake
+            .checked_add(nominator_staked_amount)
+            .ok_or(SlashError::Overflow)?;
+        nominators_shares = nominators_shares
+            .checked_add(nominator_shares)
+            .ok_or(SlashError::Overflow)?;
+        has_unconverted |= !unconverted.is_empty();
+
+        // current staked amount + amount ready to withdraw + withdrawn storage fund
+        nominators_slashed_balances.insert(
+            nominator_id.clone(),
+            NominatorBreakdown {
+                operator_id,
+                slashed_at,
+                block_hash,
+                shares: nominator_shares,
+                share_price,
+                storage_fee_deposit: nominator_storage.deposit.known.storage_fee_deposit,
+                storage_fund_balance: operator_storage_fund_balance,
+                total_storage_fee_deposit: Zero::zero(),
+                unconverted,
+                unlockable,
+                ready_to_withdraw,
+                withdrawal_in_shares,
+                storage_fee_refund,
+                pending_deposit,
+                payout: NominatorPayout {
+                    staked: refunded_stake,
+                    withdrawable: amount_ready_to_withdraw
+                        .checked_add(storage_fund_withdrew)
+                        .and_then(|withdrawable| withdrawable.checked_add(pending_unlock))
+                        .ok_or_else(|| nominator_err(SlashError::Overflow))?,
+                    storage_fund: Zero::zero(),
+                    scaled: None,
+                    overridden: None,
+                    dust: 0,
+                },
+            },
+        );
+
+        // add remaining storage fund balance that is still in the pool for each nominator
+        nominator_storage_fund_deposited_balances.push((
+            nominator_id,
+            nominator_storage.deposit.known.storage_fee_deposit,
+        ))
+    }
+
+    // the shares and stake of the nominators must add up to the ones of the operator, up to the
+    // rounding down of the stake of each nominator. The shares of the pending deposits and
+    // withdrawals that could not be converted are unknown, and the stake of a deregistered
+    // operator is unlocked at another share price, so these are not checked.
+    let tolerance = nominators_slashed_balances.len() as Balance;
+    if !has_unconverted {
+        check_nominators_total("shares", nominators_shares, total_shares, tolerance)?;
+        if deregistered_at.is_none() {
+            check_nominators_total("stake", nominators_stake, total_stake, tolerance)?;
+        }
+    }
+
+    // iterate through each nominator storage fund and calculate the actual storage fund based on
+    // total storage fund balance.
+    nominator_storage_fund_deposited_balances
+        .into_iter()
+        .for_each(|(nominator_id, deposited_balance)| {
+            let storage_fund_share_price = StorageFundRedeemPrice::new(
+                operator_storage_fund_balance,
+                total_storage_fee_deposit,
+            );
+            let storage_fund_slashed = storage_fund_share_price.redeem(deposited_balance);
+            if let Some(breakdown) = nominators_slashed_balances.get_mut(&nominator_id) {
+                breakdown.total_storage_fee_deposit = total_storage_fee_deposit;
+                breakdown.payout.storage_fund = storage_fund_slashed;
+            }
+        });
+
+    // rounding down the stake and the storage fund of each nominator leaves dust that is not
+    // refunded to any of them
+    let refunded_storage_fund = nominators_slashed_balances
+        .values()
+        .fold(Balance::zero(), |acc, breakdown| {
+            acc.saturating_add(breakdown.payout.storage_fund)
+        });
+    let leftover = Leftover::new(
+        total_stake.saturating_sub(nominators_stake),
+        operator_storage_fund_balance.saturating_sub(refunded_storage_fund),
+        // the leftover of the conversions that were skipped or made at another share price
+        // is not only rounding
+        (!has_unconverted && deregistered_at.is_none()).then_some(tolerance),
+    );
+
+    Ok((nominators_slashed_balances, leftover))
+}
+
+/// Stake and storage fund of an operator left over after the payouts of its nominators.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Leftover {
+    /// Leftover of the rounding down of the payouts, which can be assigned to a nominator.
+    dust: Balance,
+    /// Leftover beyond the rounding, which is only reported.
+    unexplained: Balance,
+}
+
+impl Leftover {
+    /// Splits the leftover stake and storage fund into the dust within `tolerance` of each,
+    /// and the unexplained rest. All of it is unexplained without a tolerance.
+    fn new(stake: Balance, storage_fund: Balance, tolerance: Option<Balance>) -> Self {
+        let tolerance = tolerance.unwrap_or_default();
+        let dust = stake
+            .min(tolerance)
+            .saturating_add(storage_fund.min(tolerance));
+        Leftover {
+            dust,
+            unexplained: stake.saturating_add(storage_fund).saturating_sub(dust),
+        }
+    }
+}
+
+/// Checks that the total of the nominators is within `tolerance` of the operator's total.
+fn check_nominators_total(
+    kind: &'static str,
+    nominators_total: Balance,
+    operator_total: Balance,
+    tolerance: Balance,
+) -> Result<(), SlashError> {
+    if nominators_total.abs_diff(operator_total) > tolerance {
+        return Err(SlashError::NominatorsTotalMismatch {
+            kind,
+            nominators_total,
+            operator_total,
+        });
+    }
+    Ok(())
+}
+
+async fn do_convert_previous_epoch_deposits<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    deposit: &mut Deposit,
+    block_hash: Hash,
+    precision: SharePricePrecision,
+) -> Result<Option<PendingDeposit>, SlashError> {
+    // if it is one of the previous domain epoch, then calculate shares for the epoch and update known deposit
+    let pending_deposit = match deposit.pending.take() {
+        None => return Ok(None),
+        Some(pd) => pd,
+    };
+
+    let PendingDeposit {
+        effective_domain_epoch,
+        amount,
+        storage_fee_deposit,
+    } = pending_deposit;
+
+    if let Some(epoch_share_price) = get_operator_epoch_share_price(
+        backend,
+        operator_id,
+        effective_domain_epoch,
+        block_hash,
+        precision,
+    )
+    .await?
+    {
+        let new_shares = epoch_share_price.stake_to_shares(amount);
+        deposit.known.shares = deposit
+            .known
+            .shares
+            .checked_add(new_shares)
+            .ok_or(SlashError::Overflow)?;
+        deposit.known.storage_fee_deposit = deposit
+            .known
+            .storage_fee_deposit
+            .checked_add(storage_fee_deposit)
+            .ok_or(SlashError::Overflow)?;
+        Ok(None)
+    } else {
+        Ok(Some(pending_deposit))
+    }
+}
+
+async fn do_convert_previous_epoch_withdrawal<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    withdrawal: &mut Withdrawal,
+    block_hash: Hash,
+    precision: SharePricePrecision,
+) -> Result<Option<WithdrawalInShares>, SlashError> {
+    let pending_withdrawal = match withdrawal.withdrawal_in_shares.take() {
+        None => return Ok(None),
+        Some(pw) => pw,
+    };
+
+    match get_operator_epoch_share_price(
+        backend,
+        operator_id,
+        pending_withdrawal.domain_epoch,
+        block_hash,
+        precision,
+    )
+    .await?
+    {
+        Some(epoch_share_price) => {
+            unlock_withdrawal_in_shares(withdrawal, pending_withdrawal, &epoch_share_price)?;
+            Ok(None)
+        }
+        None => Ok(Some(pending_withdrawal)),
+    }
+}
+
+/// Converts the withdrawal in shares to balance at the given share price and adds it to the
+/// unlocking withdrawals.
+fn unlock_withdrawal_in_shares(
+    withdrawal: &mut Withdrawal,
+    withdrawal_in_shares: WithdrawalInShares,
+    share_price: &SharePrice,
+) -> Result<(), SlashError> {
+    let WithdrawalInShares {
+        domain_epoch,
+        unlock_at_confirmed_domain_block_number,
+        shares,
+        storage_fee_refund,
+    } = withdrawal_in_shares;
+    let withdrawal_amount = share_price.shares_to_stake(shares);
+
+    withdrawal.total_withdrawal_amount = withdrawal
+        .total_withdrawal_amount
+        .checked_add(withdrawal_amount)
+        .ok_or(SlashError::Overflow)?;
+
+    let (domain_id, _) = domain_epoch.deconstruct();
+
+    let withdraw_in_balance = WithdrawalInBalance {
+        domain_id,
+        unlock_at_confirmed_domain_block_number,
+        amount_to_unlock: withdrawal_amount,
+        storage_fee_refund,
+    };
+    withdrawal.withdrawals.push_back(withdraw_in_balance);
+
+    Ok(())
+}
+
+/// Returns the balance and the storage fee refund of the withdrawals that are unlockable at
+/// the confirmed domain block.
+///
+/// Withdrawals decoded with an earlier layout have no unlock block, recorded as 0, so they are
+/// never unlockable.
+fn unlockable_withdrawals(
+    withdrawal: &Withdrawal,
+    confirmed_domain_block: Number,
+) -> Result<(Balance, Balance), SlashError> {
+    withdrawal
+        .withdrawals
+        .iter()
+        .filter(|withdrawal_in_balance| {
+            let unlock_at = withdrawal_in_balance.unlock_at_confirmed_domain_block_number;
+            unlock_at != 0 && unlock_at <= confirmed_domain_block
+        })
+        .try_fold(
+            (Balance::zero(), Balance::zero()),
+            |(amount, storage_fee_refund), withdrawal_in_balance| {
+                Some((
+                    amount.checked_add(withdrawal_in_balance.amount_to_unlock)?,
+                    storage_fee_refund.checked_add(withdrawal_in_balance.storage_fee_refund)?,
+                ))
+            },
+        )
+        .ok_or(SlashError::Overflow)
+}
+
+/// Returns the latest confirmed block of the domain at the block, which the withdrawals are
+/// unlocked against.
+///
+/// `Domains::HeadDomainNumber` is not used since the head is not confirmed yet. The earlier
+/// runtimes only store the number of the confirmed block, in
+/// `Domains::LatestConfirmedDomainBlockNumber`.
+async fn get_confirmed_domain_block_number<B: ChainBackend>(
+    backend: &B,
+    domain_id: DomainId,
+    block_hash: Hash,
+) -> Result<Option<Number>, SlashError> {
+    if let Some(confirmed_domain_block) = backend
+        .storage_map::<_, ConfirmedDomainBlock>(
+            "Domains",
+            "LatestConfirmedDomainBlock",
+            domain_id,
+            block_hash,
+        )
+        .await?
+    {
+        return Ok(Some(confirmed_domain_block.block_number));
+    }
+    backend
+        .storage_map::<_, Number>(
+            "Domains",
+            "LatestConfirmedDomainBlockNumber",
+            domain_id,
+            block_hash,
+        )
+        .await
+}
+
+async fn get_operator_epoch_share_price<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    domain_epoch: DomainEpoch,
+    block_hash: Hash,
+    precision: SharePricePrecision,
+) -> Result<Option<SharePrice>, SlashError> {
+    Ok(match precision {
+        SharePricePrecision::Perbill => backend
+            .storage_double_map::<_, _, PerbillSharePrice>(
+                "Domains",
+                "OperatorEpochSharePrice",
+                operator_id,
+                domain_epoch,
+                block_hash,
+            )
+            .await?
+            .map(SharePrice::from),
+        SharePricePrecision::Perquintill => backend
+            .storage_double_map::<_, _, PerquintillSharePrice>(
+                "Domains",
+                "OperatorEpochSharePrice",
+                operator_id,
+                domain_epoch,
+                block_hash,
+            )
+            .await?
+            .map(SharePrice::from),
+    })
+}
+
+pub(crate) async fn get_operator_storage_fund_balance<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    block_hash: Hash,
+) -> Result<Balance, SlashError> {
+    backend
+        .runtime_call::<Balance>(
+            "DomainsApi_storage_fund_account_balance",
+            vec![operator_id.encode()],
+            block_hash,
+        )
+        .await
+}
+
+pub(crate) async fn get_spec_version<B: ChainBackend>(
+    backend: &B,
+    block_hash: Hash,
+) -> Result<u32, SlashError> {
+    Ok(backend
+        .runtime_call::<RuntimeVersion>("Core_version", vec![], block_hash)
+        .await?
+        .spec_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+    use crate::types::{KnownDeposit, OperatorDeregisteredInfo};
+    use sp_domains::{DomainId, EpochIndex};
+    use sp_runtime::{Perbill, Percent};
+    use std::collections::VecDeque;
+
+    const OPERATOR_ID: OperatorId = 1;
+    const SLASHED_AT: Number = 10;
+    const EPOCH: EpochIndex = 5;
+    /// Total stake and shares of the operator.
+    const TOTAL_STAKE: Balance = 1000;
+    /// Storage fund balance and total storage fee deposits of the operator.
+    const STORAGE_FUND: Balance = 100;
+
+    fn block_hash() -> Hash {
+        Hash::repeat_byte(1)
+    }
+
+    fn account(byte: u8) -> AccountId {
+        AccountId::new([byte; 32])
+    }
+
+    fn domain_epoch(epoch_index: EpochIndex) -> DomainEpoch {
+        DomainEpoch::decode(&mut (DomainId::new(0), epoch_index).encode().as_slice()).unwrap()
+    }
+
+    fn deposit(shares: Balance, storage_fee_deposit: Balance) -> Deposit {
+        Deposit {
+            known: KnownDeposit {
+                shares,
+                storage_fee_deposit,
+            },
+            pending: None,
+        }
+    }
+
+    /// Backend with a registered operator of 1000 shares worth 1000 stake and a storage fund
+    /// of 100 matching its storage fee deposits, at the block before the slash.
+    fn backend() -> MemoryBackend {
+        backend_with_status(OperatorStatus::Registered)
+    }
+
+    fn backend_with_status(status: OperatorStatus) -> MemoryBackend {
+        let mut backend = MemoryBackend::default();
+        backend.insert_block_hash(SLASHED_AT - 1, block_hash());
+        // `Operator` is inserted as a tuple of its fields since its status is private
+        backend.insert_storage_map(
+            "Domains",
+            "Operators",
+            OPERATOR_ID,
+            block_hash(),
+            (
+                [0u8; 32],
+                DomainId::new(0),
+                DomainId::new(0),
+                Balance::zero(),
+                Percent::zero(),
+                TOTAL_STAKE,
+                Balance::zero(),
+                TOTAL_STAKE,
+                status,
+                Balance::zero(),
+                Balance::zero(),
+                STORAGE_FUND,
+            ),
+        );
+        backend.insert_runtime_call(
+            "DomainsApi_storage_fund_account_balance",
+            vec![OPERATOR_ID.encode()],
+            block_hash(),
+            STORAGE_FUND,
+        );
+        backend.insert_runtime_call(
+            "Core_version",
+            vec![],
+            block_hash(),
+            RuntimeVersion {
+                spec_name: "subspace".to_string(),
+                impl_name: "subspace".to_string(),
+                authoring_version: 0,
+                spec_version: 7,
+            },
+        );
+        backend
+    }
+
+    fn insert_deposit(backend: &mut MemoryBackend, nominator_id: AccountId, deposit: Deposit) {
+        backend.insert_storage_double_map(
+            "Domains",
+            "Deposits",
+            OPERATOR_ID,
+            nominator_id,
+            block_hash(),
+            deposit,
+        );
+    }
+
+    fn insert_withdrawal(
+        backend: &mut MemoryBackend,
+        nominator_id: AccountId,
+        withdrawal: Withdrawal,
+    ) {
+        backend.insert_storage_double_map(
+            "Domains",
+            "Withdrawals",
+            OPERATOR_ID,
+            nominator_id,
+            block_hash(),
+            withdrawal,
+        );
+    }
+
+    /// Inserts the share price of `EPOCH` worth 2 stake per share.
+    fn insert_epoch_share_price(backend: &mut MemoryBackend) {
+        backend.insert_storage_double_map(
+            "Domains",
+            "OperatorEpochSharePrice",
+            OPERATOR_ID,
+            domain_epoch(EPOCH),
+            block_hash(),
+            PerbillSharePrice(Perbill::from_percent(50)),
+        );
+    }
+
+    async fn calculate(backend: &MemoryBackend) -> BTreeMap<AccountId, NominatorPayout> {
+        let Calculation { payouts, failures } = SlashRefundCalculator::new(backend)
+            .calculate(vec![(OPERATOR_ID, SLASHED_AT)])
+            .await;
+        assert!(failures.is_empty(), "{failures:?}");
+        let operator_payout = payouts.operators.into_iter().next().unwrap();
+        assert_eq!(operator_payout.block_hash, block_hash());
+        assert_eq!(operator_payout.spec_version, 7);
+        operator_payout.nominators
+    }
+
+    #[tokio::test]
+    async fn known_deposits() {
+        let mut backend = backend();
+        insert_deposit(&mut backend, account(1), deposit(600, 60));
+        insert_deposit(&mut backend, account(2), deposit(400, 40));
+
+        let nominators = calculate(&backend).await;
+        assert_eq!(
+            nominators,
+            BTreeMap::from([
+                (
+                    account(1),
+                    NominatorPayout {
+                        staked: 600,
+                        withdrawable: 0,
+                        storage_fund: 60,
+                        scaled: None,
+                        overridden: None,
+                        dust: 0,
+                    }
+                ),
+                (
+                    account(2),
+                    NominatorPayout {
+                        staked: 400,
+                        withdrawable: 0,
+                        storage_fund: 40,
+                        scaled: None,
+                        overridden: None,
+                        dust: 0,
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn rounding_dust_is_reported() {
+        let mut backend = backend();
+        for byte in 1..=3 {
+            insert_deposit(&mut backend, account(byte), deposit(333, 33));
+        }
+
+        let Calculation { payouts, failures } = SlashRefundCalculator::new(&backend)
+            .calculate(vec![(OPERATOR_ID, SLASHED_AT)])
+            .await;
+        assert!(failures.is_empty(), "{failures:?}");
+        // 1 stake and 1 storage fund left over
+        assert_eq!(payouts.operators[0].dust, 2);
+        assert_eq!(payouts.operators[0].unexplained, 0);
+        assert_eq!(payouts.total, 3 * (333 + 33));
+
+        let payouts = payouts.assign_dust_to_largest_nominator().unwrap();
+        assert_eq!(payouts.operators[0].nominators[&account(1)].dust, 2);
+        assert_eq!(payouts.total, 3 * (333 + 33) + 2);
+    }
+
+    #[test]
+    fn leftover_beyond_rounding_is_unexplained() {
+        assert_eq!(
+            Leftover::new(5, 1, Some(3)),
+            Leftover {
+                dust: 4,
+                unexplained: 2,
+            }
+        );
+        assert_eq!(
+            Leftover::new(5, 1, None),
+            Leftover {
+                dust: 0,
+                unexplained: 6,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn leftover_of_unconverted_pending_is_not_assigned() {
+        let mut backend = backend();
+        insert_deposit(&mut backend, account(1), deposit(600, 60));
+        insert_deposit(&mut backend, account(2), deposit(300, 40));
+        insert_withdrawal(
+            &mut backend,
+            account(2),
+            Withdrawal {
+                total_withdrawal_amount: 0,
+                withdrawals: VecDeque::new(),
+                withdrawal_in_shares: Some(WithdrawalInShares {
+                    domain_epoch: domain_epoch(EPOCH),
+                    unlock_at_confirmed_domain_block_number: 0,
+                    shares: 100,
+                    storage_fee_refund: 0,
+                }),
+            },
+        );
+
+        let Calculation { payouts, failures } = SlashRefundCalculator::new(&backend)
+            .calculate(vec![(OPERATOR_ID, SLASHED_AT)])
+            .await;
+        assert!(failures.is_empty(), "{failures:?}");
+        // the stake of the withdrawal not converted is left over
+        assert_eq!(payouts.operators[0].dust, 0);
+        assert_eq!(payouts.operators[0].unexplained, 100);
+
+        let total = payouts.total;
+        let payouts = payouts.assign_dust_to_largest_nominator().unwrap();
+        assert!(!payouts.dust_assigned());
+        assert_eq!(payouts.total, total);
+    }
+
+    #[tokio::test]
+    async fn operators_of_other_domains_are_skipped() {
+        let mut backend = backend();
+        insert_deposit(&mut backend, account(1), deposit(600, 60));
+
+        let Calculation { payouts, failures } = SlashRefundCalculator::new(&backend)
+            .domain_id(Some(DomainId::new(1)))
+            .calculate(vec![(OPERATOR_ID, SLASHED_AT)])
+            .await;
+        assert!(failures.is_empty(), "{failures:?}");
+        assert!(payouts.operators.is_empty());
+
+        let Calculation { payouts, .. } = SlashRefundCalculator::new(&backend)
+            .domain_id(Some(DomainId::new(0)))
+            .calculate(vec![(OPERATOR_ID, SLASHED_AT)])
+            .await;
+        assert_eq!(payouts.operators[0].domain_id, Some(DomainId::new(0)));
+    }
+
+    #[tokio::test]
+    async fn truncated_deposits_are_rejected() {
+        let mut backend = backend();
+        insert_deposit(&mut backend, account(1), deposit(600, 60));
+        insert_deposit(&mut backend, account(2), deposit(400, 40));
+        backend.insert_storage_map("Domains", "NominatorCount", OPERATOR_ID, block_hash(), 3u32);
+
+        let Calculation { payouts, failures } = SlashRefundCalculator::new(&backend)
+            .calculate(vec![(OPERATOR_ID, SLASHED_AT)])
+            .await;
+        assert!(payouts.operators.is_empty());
+        assert_eq!(failures.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn pending_deposit_is_converted_at_epoch_share_price() {
+        let mut backend = backend();
+        insert_epoch_share_price(&mut backend);
+        insert_deposit(
+            &mut backend,
+            account(1),
+            Deposit {
+                pending: Some(PendingDeposit {
+                    effective_domain_epoch: domain_epoch(EPOCH),
+                    amount: 200,
+                    storage_fee_deposit: 20,
+                }),
+                ..deposit(600, 60)
+            },
+        );
+        insert_deposit(&mut backend, account(2), deposit(300, 20));
+
+        let nominators = calculate(&backend).await;
+        assert_eq!(
+            nominators[&account(1)],
+            NominatorPayout {
+                staked: 700,
+                withdrawable: 0,
+                storage_fund: 80,
+                scaled: None,
+                overridden: None,
+                dust: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn pending_withdrawal_is_converted_at_epoch_share_price() {
+        let mut backend = backend();
+        insert_epoch_share_price(&mut backend);
+        insert_deposit(&mut backend, account(1), deposit(400, 40));
+        insert_deposit(&mut backend, account(2), deposit(600, 60));
+        insert_withdrawal(
+            &mut backend,
+            account(1),
+            Withdrawal {
+                total_withdrawal_amount: 50,
+                withdrawals: VecDeque::from([WithdrawalInBalance {
+                    domain_id: DomainId::new(0),
+                    unlock_at_confirmed_domain_block_number: 0,
+                    amount_to_unlock: 50,
+                    storage_fee_refund: 5,
+                }]),
+                withdrawal_in_shares: Some(WithdrawalInShares {
+                    domain_epoch: domain_epoch(EPOCH),
+                    unlock_at_confirmed_domain_block_number: 0,
+                    shares: 100,
+                    storage_fee_refund: 10,
+                }),
+            },
+        );
+
+        let nominators = calculate(&backend).await;
+        assert_eq!(
+            nominators[&account(1)],
+            NominatorPayout {
+                staked: 400,
+                // 50 unlocking + 100 shares at 2 stake per share + 5 and 10 storage fee refunds
+                withdrawable: 265,
+                storage_fund: 40,
+                scaled: None,
+                overridden: None,
+                dust: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn payout_components_are_recorded() {
+        let mut backend = backend();
+        insert_epoch_share_price(&mut backend);
+        insert_deposit(
+            &mut backend,
+            account(1),
+            Deposit {
+                pending: Some(PendingDeposit {
+                    effective_domain_epoch: domain_epoch(EPOCH),
+                    amount: 200,
+                    storage_fee_deposit: 20,
+                }),
+                ..deposit(500, 50)
+            },
+        );
+        insert_deposit(&mut backend, account(2), deposit(400, 30));
+        insert_withdrawal(
+            &mut backend,
+            account(1),
+            Withdrawal {
+                total_withdrawal_amount: 50,
+                withdrawals: VecDeque::from([WithdrawalInBalance {
+                    domain_id: DomainId::new(0),
+                    unlock_at_confirmed_domain_block_number: 0,
+                    amount_to_unlock: 50,
+                    storage_fee_refund: 5,
+                }]),
+                withdrawal_in_shares: Some(WithdrawalInShares {
+                    domain_epoch: domain_epoch(EPOCH),
+                    unlock_at_confirmed_domain_block_number: 0,
+                    shares: 100,
+                    storage_fee_refund: 10,
+                }),
+            },
+        );
+
+        let Calculation { payouts, failures } = SlashRefundCalculator::new(&backend)
+            .calculate(vec![(OPERATOR_ID, SLASHED_AT)])
+            .await;
+        assert!(failures.is_empty(), "{failures:?}");
+        let operator_payout = &payouts.operators[0];
+        let components = &operator_payout.components[&account(1)];
+        assert_eq!(
+            *components,
+            PayoutComponents {
+                // 500 known shares and 100 shares of the deposit at 2 stake per share
+                shares: 600,
+                share_price: SharePrice::new(
+                    TOTAL_STAKE,
+                    TOTAL_STAKE,
+                    SharePricePrecision::Perbill
+                )
+                .parts(),
+                ready_to_withdraw: 50,
+                withdrawal_in_shares: Some(WithdrawalComponent {
+                    epoch: EPOCH,
+                    shares: 100,
+                    amount: 200,
+                }),
+                storage_fee_refund: 15,
+                pending_deposit: Some(DepositComponent {
+                    epoch: EPOCH,
+                    amount: 200,
+                    storage_fee_deposit: 20,
+                    handling: PendingDepositHandling::Converted { shares: 100 },
+                }),
+                storage_fee_deposit: 70,
+                storage_fund_balance: STORAGE_FUND,
+                total_storage_fee_deposit: STORAGE_FUND,
+            }
+        );
+        // every component adds up to the payout
+        let nominator_payout = operator_payout.nominators[&account(1)];
+        assert_eq!(nominator_payout.staked, components.shares);
+        assert_eq!(
+            nominator_payout.withdrawable,
+            components.ready_to_withdraw + 200 + components.storage_fee_refund
+        );
+        assert_eq!(
+            nominator_payout.storage_fund,
+            components.storage_fee_deposit
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_epoch_share_price_skips_pending_conversions() {
+        let mut backend = backend();
+        insert_deposit(
+            &mut backend,
+            account(1),
+            Deposit {
+                pending: Some(PendingDeposit {
+                    effective_domain_epoch: domain_epoch(EPOCH),
+                    amount: 200,
+                    storage_fee_deposit: 20,
+                }),
+                ..deposit(600, 40)
+            },
+        );
+        insert_deposit(&mut backend, account(2), deposit(400, 40));
+        insert_withdrawal(
+            &mut backend,
+            account(2),
+            Withdrawal {
+                total_withdrawal_amount: 50,
+                withdrawals: VecDeque::from([WithdrawalInBalance {
+                    domain_id: DomainId::new(0),
+                    unlock_at_confirmed_domain_block_number: 0,
+                    amount_to_unlock: 50,
+                    storage_fee_refund: 5,
+                }]),
+                withdrawal_in_shares: Some(WithdrawalInShares {
+                    domain_epoch: domain_epoch(EPOCH),
+                    unlock_at_confirmed_domain_block_number: 0,
+                    shares: 100,
+                    storage_fee_refund: 10,
+                }),
+            },
+        );
+
+        let nominators = calculate(&backend).await;
+        // the storage fee of the pending deposit is left out of the 100 storage fee deposits,
+        // so the storage fund of 100 is redeemed against the 80 of the nominators
+        assert_eq!(
+            nominators,
+            BTreeMap::from([
+                (
+                    account(1),
+                    NominatorPayout {
+                        staked: 600,
+                        withdrawable: 0,
+                        storage_fund: 50,
+                        scaled: None,
+                        overridden: None,
+                        dust: 0,
+                    }
+                ),
+                (
+                    account(2),
+                    NominatorPayout {
+                        staked: 400,
+                        withdrawable: 55,
+                        storage_fund: 50,
+                        scaled: None,
+                        overridden: None,
+                        dust: 0,
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn unconverted_deposit_storage_fee_is_not_redeemed() {
+        let mut backend = backend();
+        // the storage fee deposits of the operator include the one of the pending deposit
+        insert_deposit(
+            &mut backend,
+            account(1),
+            Deposit {
+                pending: Some(PendingDeposit {
+                    effective_domain_epoch: domain_epoch(EPOCH),
+                    amount: 200,
+                    storage_fee_deposit: 20,
+                }),
+                ..deposit(600, 40)
+            },
+        );
+        insert_deposit(&mut backend, account(2), deposit(400, 40));
+
+        let Breakdown { breakdowns, .. } = SlashRefundCalculator::new(&backend)
+            .breakdown(vec![(OPERATOR_ID, SLASHED_AT)], &account(2))
+            .await;
+        assert_eq!(breakdowns[0].total_storage_fee_deposit, 80);
+
+        // the storage fund is redeemed against the storage fee deposits of the nominators only,
+        // instead of being diluted by the one of the deposit that is not refunded
+        let nominators = calculate(&backend).await;
+        assert_eq!(nominators[&account(1)].storage_fund, 50);
+        assert_eq!(nominators[&account(2)].storage_fund, 50);
+    }
+
+    #[tokio::test]
+    async fn zero_share_nominator_is_refunded_nothing() {
+        let mut backend = backend();
+        insert_deposit(&mut backend, account(1), deposit(1000, 100));
+        insert_deposit(&mut backend, account(2), deposit(0, 0));
+
+        let nominators = calculate(&backend).await;
+        assert_eq!(nominators[&account(2)], NominatorPayout::default());
+        assert_eq!(nominators[&account(2)].total().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn withdrawal_without_deposit_fails_operator() {
+        let mut backend = backend();
+        insert_deposit(&mut backend, account(1), deposit(1000, 100));
+        insert_withdrawal(&mut backend, account(2), Withdrawal::default());
+
+        let Calculation { payouts, failures } = SlashRefundCalculator::new(&backend)
+            .calculate(vec![(OPERATOR_ID, SLASHED_AT)])
+            .await;
+        assert!(payouts.operators.is_empty());
+        assert!(matches!(
+            failures.as_slice(),
+            [SlashError::Operator {
+                operator_id: OPERATOR_ID,
+                ..
+            }]
+        ));
+    }
+
+    #[tokio::test]
+    async fn nominator_shares_must_add_up_to_operator_shares() {
+        let mut backend = backend();
+        insert_deposit(&mut backend, account(1), deposit(600, 60));
+        insert_deposit(&mut backend, account(2), deposit(300, 40));
+
+        let Calculation { payouts, failures } = SlashRefundCalculator::new(&backend)
+            .calculate(vec![(OPERATOR_ID, SLASHED_AT)])
+            .await;
+        assert!(payouts.operators.is_empty());
+        assert!(matches!(
+            failures.as_slice(),
+            [SlashError::Operator {
+                operator_id: OPERATOR_ID,
+                ..
+            }]
+        ));
+    }
+
+    #[tokio::test]
+    async fn breakdown_of_nominator() {
+        let mut backend = backend();
+        insert_deposit(&mut backend, account(1), deposit(600, 60));
+        insert_deposit(&mut backend, account(2), deposit(400, 40));
+
+        let Breakdown {
+            breakdowns,
+            failures,
+        } = SlashRefundCalculator::new(&backend)
+            .breakdown(vec![(OPERATOR_ID, SLASHED_AT)], &account(1))
+            .await;
+        assert!(failures.is_empty(), "{failures:?}");
+        assert_eq!(
+            breakdowns,
+            vec![NominatorBreakdown {
+                operator_id: OPERATOR_ID,
+                slashed_at: SLASHED_AT,
+                block_hash: block_hash(),
+                shares: 600,
+                share_price: SharePrice::new(
+                    TOTAL_STAKE,
+                    TOTAL_STAKE,
+                    SharePricePrecision::Perbill
+                ),
+                storage_fee_deposit: 60,
+                storage_fund_balance: STORAGE_FUND,
+                total_storage_fee_deposit: STORAGE_FUND,
+                unconverted: vec![],
+                unlockable: None,
+                ready_to_withdraw: 0,
+                withdrawal_in_shares: None,
+                storage_fee_refund: 0,
+                pending_deposit: None,
+                payout: NominatorPayout {
+                    staked: 600,
+                    withdrawable: 0,
+                    storage_fund: 60,
+                    scaled: None,
+                    overridden: None,
+                    dust: 0,
+                },
+            }]
+        );
+
+        // operators not nominated by the account are skipped
+        let Breakdown { breakdowns, .. } = SlashRefundCalculator::new(&backend)
+            .breakdown(vec![(OPERATOR_ID, SLASHED_AT)], &account(3))
+            .await;
+        assert!(breakdowns.is_empty());
+    }
+
+    #[tokio::test]
+    async fn deregistered_operator_unlocks_at_deregistration_share_price() {
+        let mut backend =
+            backend_with_status(OperatorStatus::Deregistered(OperatorDeregisteredInfo {
+                domain_epoch: domain_epoch(EPOCH),
+                unlock_at_confirmed_domain_block_number: 0,
+            }));
+        insert_epoch_share_price(&mut backend);
+        insert_deposit(
+            &mut backend,
+            account(1),
+            Deposit {
+                pending: Some(PendingDeposit {
+                    effective_domain_epoch: domain_epoch(EPOCH),
+                    amount: 200,
+                    storage_fee_deposit: 20,
+                }),
+                ..deposit(600, 60)
+            },
+        );
+        insert_deposit(&mut backend, account(2), deposit(400, 20));
+
+        let nominators = calculate(&backend).await;
+        assert_eq!(
+            nominators[&account(1)],
+            NominatorPayout {
+                // 600 shares at 2 stake per share
+                staked: 1200,
+                // deposit pending since the deregistration is unlocked as is
+                withdrawable: 200,
+                storage_fund: 80,
+                scaled: None,
+                overridden: None,
+                dust: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_deregistration_share_price_fails_the_operator() {
+        let mut backend =
+            backend_with_status(OperatorStatus::Deregistered(OperatorDeregisteredInfo {
+                domain_epoch: domain_epoch(EPOCH),
+                unlock_at_confirmed_domain_block_number: 0,
+            }));
+        insert_deposit(&mut backend, account(1), deposit(600, 60));
+        insert_deposit(&mut backend, account(2), deposit(400, 40));
+
+        let Calculation { payouts, failures } = SlashRefundCalculator::new(&backend)
+            .calculate(vec![(OPERATOR_ID, SLASHED_AT)])
+            .await;
+        assert!(payouts.operators.is_empty());
+        assert_eq!(failures.len(), 1);
+        assert!(
+            failures[0]
+                .to_string()
+                .contains("Share price of epoch 5 the operator was deregistered in not found"),
+            "{failures:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn unlockable_withdrawals_are_told_apart() {
+        let mut backend = backend();
+        insert_deposit(&mut backend, account(1), deposit(400, 40));
+        insert_deposit(&mut backend, account(2), deposit(600, 60));
+        insert_withdrawal(
+            &mut backend,
+            account(1),
+            Withdrawal {
+                total_withdrawal_amount: 80,
+                withdrawals: VecDeque::from([
+                    WithdrawalInBalance {
+                        domain_id: DomainId::new(0),
+                        unlock_at_confirmed_domain_block_number: 20,
+                        amount_to_unlock: 50,
+                        storage_fee_refund: 5,
+                    },
+                    WithdrawalInBalance {
+                        domain_id: DomainId::new(0),
+                        unlock_at_confirmed_domain_block_number: 40,
+                        amount_to_unlock: 30,
+                        storage_fee_refund: 3,
+                    },
+                ]),
+                withdrawal_in_shares: None,
+            },
+        );
+        backend.insert_storage_map(
+            "Domains",
+            "LatestConfirmedDomainBlock",
+            DomainId::new(0),
+            block_hash(),
+            ConfirmedDomainBlock { block_number: 30 },
+        );
+
+        let Calculation { payouts, failures } = SlashRefundCalculator::new(&backend)
+            .calculate(vec![(OPERATOR_ID, SLASHED_AT)])
+            .await;
+        assert!(failures.is_empty(), "{failures:?}");
+        let operator_payout = &payouts.operators[0];
+        assert_eq!(operator_payout.nominators[&account(1)].withdrawable, 88);
+        assert_eq!(
+            operator_payout.unlockable[&account(1)],
+            UnlockableWithdrawal {
+                amount: 55,
+                confirmed_domain_block: 30,
+                refunded: true,
+            }
+        );
+        assert!(!payouts.exclude_unlockable_withdrawals());
+
+        let Calculation { payouts, failures } = SlashRefundCalculator::new(&backend)
+            .exclude_unlockable_withdrawals(true)
+            .calculate(vec![(OPERATOR_ID, SLASHED_AT)])
+            .await;
+        assert!(failures.is_empty(), "{failures:?}");
+        // only the withdrawal unlocking after the confirmed block is refunded
+        assert_eq!(
+            payouts.operators[0].nominators[&account(1)].withdrawable,
+            33
+        );
+        assert!(!payouts.operators[0].unlockable[&account(1)].refunded);
+        assert!(payouts.exclude_unlockable_withdrawals());
+    }
+
+    #[tokio::test]
+    async fn missing_epoch_share_price_falls_back_to_raw_amount() {
+        let mut backend = backend();
+        insert_deposit(
+            &mut backend,
+            account(1),
+            Deposit {
+                pending: Some(PendingDeposit {
+                    effective_domain_epoch: domain_epoch(EPOCH),
+                    amount: 200,
+                    storage_fee_deposit: 20,
+                }),
+                ..deposit(600, 60)
+            },
+        );
+        insert_deposit(&mut backend, account(2), deposit(400, 40));
+        insert_withdrawal(
+            &mut backend,
+            account(2),
+            Withdrawal {
+                withdrawal_in_shares: Some(WithdrawalInShares {
+                    domain_epoch: domain_epoch(EPOCH),
+                    unlock_at_confirmed_domain_block_number: 0,
+                    shares: 100,
+                    storage_fee_refund: 10,
+                }),
+                ..Default::default()
+            },
+        );
+
+        let Calculation { payouts, failures } = SlashRefundCalculator::new(&backend)
+            .fallback_raw_amount(true)
+            .calculate(vec![(OPERATOR_ID, SLASHED_AT)])
+            .await;
+        assert!(failures.is_empty(), "{failures:?}");
+        let operator_payout = payouts.operators.into_iter().next().unwrap();
+        // raw amount of the deposit along with its storage fee deposit
+        assert_eq!(operator_payout.nominators[&account(1)].staked, 800);
+        assert_eq!(operator_payout.nominators[&account(1)].storage_fund, 80);
+        // shares of the withdrawal at the share price of the operator
+        assert_eq!(operator_payout.nominators[&account(2)].withdrawable, 110);
+        assert_eq!(
+            operator_payout.unconverted[&account(1)],
+            vec![UnconvertedPending {
+                kind: PendingKind::Deposit,
+                epoch: EPOCH,
+                amount: 200,
+                refunded: true,
+            }]
+        );
+        assert_eq!(
+            operator_payout.unconverted[&account(2)],
+            vec![UnconvertedPending {
+                kind: PendingKind::Withdrawal,
+                epoch: EPOCH,
+                amount: 100,
+                refunded: true,
+            }]
+        );
+    }
+}
@@ -0,0 +1,463 @@
+use crate::error::SlashError;
+use crate::types::VestingSchedule;
+use crate::{AccountId, Balance, Number};
+use codec::{Compact, Encode, Output};
+use scale_info::TypeDef;
+use scale_value::{Composite, Primitive, Value, ValueDef};
+use sp_core::hashing::blake2_256;
+use sp_runtime::MultiAddress;
+use substrate_api_client::ac_compose_macros::compose_call;
+use substrate_api_client::ac_node_api::Metadata;
+
+/// Call encoded along with its pallet and call index.
+///
+/// Used to nest a composed call in another call, such as `Sudo::sudo` or
+/// `Multisig::as_multi`, regardless of the type of the composed call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpaqueCall(pub Vec<u8>);
+
+impl OpaqueCall {
+    pub fn new<C: Encode>(call: C) -> Self {
+        OpaqueCall(call.encode())
+    }
+
+    /// Blake2-256 hash of the call, which identifies the call in `Multisig`.
+    pub fn hash(&self) -> [u8; 32] {
+        blake2_256(&self.0)
+    }
+}
+
+impl Encode for OpaqueCall {
+    fn size_hint(&self) -> usize {
+        self.0.len()
+    }
+
+    fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+        // already encoded, so written as is without a length prefix
+        dest.write(&self.0)
+    }
+}
+
+/// Multisig account approving the calls on behalf of a set of signatories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigAccount {
+    threshold: u16,
+    /// Signatories sorted as required by `Multisig`.
+    signatories: Vec<AccountId>,
+}
+
+impl MultisigAccount {
+    pub fn new(threshold: u16, mut signatories: Vec<AccountId>) -> Result<Self, SlashError> {
+        signatories.sort();
+        signatories.dedup();
+        if threshold < 2 {
+            return Err(SlashError::InvalidMultisig("threshold must be at least 2"));
+        }
+        if usize::from(threshold) > signatories.len() {
+            return Err(SlashError::InvalidMultisig(
+                "threshold must not exceed the number of signatories",
+            ));
+        }
+
+        Ok(MultisigAccount {
+            threshold,
+            signatories,
+        })
+    }
+
+    pub fn threshold(&self) -> u16 {
+        self.threshold
+    }
+
+    /// Account id of the multisig, derived the same way as `Multisig::multi_account_id`.
+    pub fn account_id(&self) -> AccountId {
+        AccountId::from(blake2_256(
+            &(b"modlpy/utilisuba", &self.signatories, self.threshold).encode(),
+        ))
+    }
+
+    /// Returns the signatories other than `signatory`, failing if `signatory` is not one of
+    /// the signatories.
+    pub fn other_signatories(&self, signatory: &AccountId) -> Result<Vec<AccountId>, SlashError> {
+        if !self.signatories.contains(signatory) {
+            return Err(SlashError::InvalidMultisig(
+                "signer is not one of the signatories",
+            ));
+        }
+
+        Ok(self
+            .signatories
+            .iter()
+            .filter(|other| *other != signatory)
+            .cloned()
+            .collect())
+    }
+}
+
+/// Account on behalf of which the signer dispatches the calls as a proxy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyAccount {
+    pub real: AccountId,
+    /// Index of the variant of the runtime's `ProxyType`.
+    pub proxy_type: Option<u8>,
+}
+
+impl ProxyAccount {
+    /// Creates the proxy account, resolving the name of the proxy type from the metadata.
+    pub fn new(
+        metadata: &Metadata,
+        real: AccountId,
+        proxy_type: Option<&str>,
+    ) -> Result<Self, SlashError> {
+        let proxy_type = proxy_type
+            .map(|name| proxy_type_index(metadata, name))
+            .transpose()?;
+        Ok(ProxyAccount { real, proxy_type })
+    }
+
+    /// Wraps the call in `Proxy::proxy` so that it is dispatched from the real account.
+    pub fn wrap(&self, metadata: &Metadata, call: OpaqueCall) -> Result<OpaqueCall, SlashError> {
+        let proxy_call = compose_call!(
+            metadata,
+            "Proxy",
+            "proxy",
+            MultiAddress::<AccountId, ()>::Id(self.real.clone()),
+            self.proxy_type,
+            call
+        )
+        .ok_or(SlashError::CallNotFound {
+            pallet: "Proxy",
+            call: "proxy",
+        })?;
+        Ok(OpaqueCall::new(proxy_call))
+    }
+}
+
+/// Vesting of the refunds, which are released gradually from the treasury with
+/// `Vesting::vested_transfer` instead of transferred at once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vesting {
+    treasury: AccountId,
+    start: Number,
+    period: Number,
+    per_block: Balance,
+    keep_alive: bool,
+}
+
+impl Vesting {
+    pub fn new(
+        treasury: AccountId,
+        start: Number,
+        period: Number,
+        per_block: Balance,
+    ) -> Result<Self, SlashError> {
+        if period == 0 {
+            return Err(SlashError::InvalidVesting("period must not be zero"));
+        }
+        if per_block == 0 {
+            return Err(SlashError::InvalidVesting("per block must not be zero"));
+        }
+
+        Ok(Vesting {
+            treasury,
+            start,
+            period,
+            per_block,
+            keep_alive: true,
+        })
+    }
+
+    /// Whether the remainder of the refunds is transferred with
+    /// `Balances::transfer_keep_alive`, which is the default, or with
+    /// `Balances::transfer_allow_death`.
+    pub fn keep_alive(mut self, keep_alive: bool) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Composes the calls refunding `amount` to the nominator.
+    ///
+    /// The vesting schedule can only release a multiple of the amount released per period,
+    /// so the remainder is transferred at once.
+    pub fn compose_refund_calls(
+        &self,
+        metadata: &Metadata,
+        nominator_id: AccountId,
+        amount: Balance,
+    ) -> Result<Vec<OpaqueCall>, SlashError> {
+        let per_period = self
+            .per_block
+            .checked_mul(Balance::from(self.period))
+            .ok_or(SlashError::Overflow)?;
+        let period_count = u32::try_from(amount / per_period)
+            .map_err(|_| SlashError::InvalidVesting("too many periods"))?;
+        let remainder = amount % per_period;
+
+        let dest = MultiAddress::<AccountId, ()>::Id(nominator_id);
+        let mut calls = vec![];
+        if period_count > 0 {
+            let schedule = VestingSchedule {
+                start: self.start,
+                period: self.period,
+                period_count,
+                per_period,
+            };
+            let vested_transfer_call = compose_call!(
+                metadata,
+                "Vesting",
+                "vested_transfer",
+                dest.clone(),
+                schedule
+            )
+            .ok_or(SlashError::CallNotFound {
+                pallet: "Vesting",
+                call: "vested_transfer",
+            })?;
+            calls.push(OpaqueCall::new(vested_transfer_call));
+        }
+        if remainder > 0 {
+            calls.push(compose_balances_transfer_call(
+                metadata,
+                dest,
+                remainder,
+                self.keep_alive,
+            )?);
+        }
+        Ok(calls)
+    }
+
+    /// Wraps the call in `Sudo::sudo_as` so that it is dispatched from the treasury, which
+    /// funds the vested transfers.
+    pub fn wrap(&self, metadata: &Metadata, call: OpaqueCall) -> Result<OpaqueCall, SlashError> {
+        compose_sudo_as_call(metadata, &self.treasury, call)
+    }
+}
+
+/// Transfers of the refunds with `Balances` from the treasury instead of
+/// `Domains::transfer_treasury_funds`, so that whether the treasury may be reaped is explicit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalancesTransfer {
+    treasury: AccountId,
+    keep_alive: bool,
+}
+
+impl BalancesTransfer {
+    /// Transfers with `Balances::transfer_keep_alive` if `keep_alive`, and otherwise with
+    /// `Balances::transfer_allow_death`.
+    pub fn new(treasury: AccountId, keep_alive: bool) -> Self {
+        BalancesTransfer {
+            treasury,
+            keep_alive,
+        }
+    }
+
+    /// Composes the call transferring `amount` to the nominator.
+    pub fn compose_transfer_call(
+        &self,
+        metadata: &Metadata,
+        nominator_id: AccountId,
+        amount: Balance,
+    ) -> Result<OpaqueCall, SlashError> {
+        compose_balances_transfer_call(
+            metadata,
+            MultiAddress::Id(nominator_id),
+            amount,
+            self.keep_alive,
+        )
+    }
+
+    /// Wraps the call in `Sudo::sudo_as` so that it is dispatched from the treasury.
+    pub fn wrap(&self, metadata: &Metadata, call: OpaqueCall) -> Result<OpaqueCall, SlashError> {
+        compose_sudo_as_call(metadata, &self.treasury, call)
+    }
+}
+
+fn compose_balances_transfer_call(
+    metadata: &Metadata,
+    dest: MultiAddress<AccountId, ()>,
+    amount: Balance,
+    keep_alive: bool,
+) -> Result<OpaqueCall, SlashError> {
+    let call = if keep_alive {
+        "transfer_keep_alive"
+    } else {
+        "transfer_allow_death"
+    };
+    let transfer_call = compose_call!(metadata, "Balances", call, dest, Compact(amount)).ok_or(
+        SlashError::CallNotFound {
+            pallet: "Balances",
+            call,
+        },
+    )?;
+    Ok(OpaqueCall::new(transfer_call))
+}
+
+fn compose_sudo_as_call(
+    metadata: &Metadata,
+    who: &AccountId,
+    call: OpaqueCall,
+) -> Result<OpaqueCall, SlashError> {
+    let sudo_as_call = compose_call!(
+        metadata,
+        "Sudo",
+        "sudo_as",
+        MultiAddress::<AccountId, ()>::Id(who.clone()),
+        call
+    )
+    .ok_or(SlashError::CallNotFound {
+        pallet: "Sudo",
+        call: "sudo_as",
+    })?;
+    Ok(OpaqueCall::new(sudo_as_call))
+}
+
+/// Returns the index of the variant `name` of the runtime's `ProxyType`.
+///
+/// Variants without fields are encoded as their index, so the index can be used in place of
+/// the runtime type.
+fn proxy_type_index(metadata: &Metadata, name: &str) -> Result<u8, SlashError> {
+    metadata
+        .types()
+        .types
+        .iter()
+        .filter(|ty| ty.ty.path.segments.last().map(String::as_str) == Some("ProxyType"))
+        .find_map(|ty| match &ty.ty.type_def {
+            TypeDef::Variant(def) => def
+                .variants
+                .iter()
+                .find(|variant| variant.name == name)
+                .map(|variant| variant.index),
+            _ => None,
+        })
+        .ok_or_else(|| SlashError::UnknownProxyType(name.to_string()))
+}
+
+/// Decodes the transfers of a composed batch call, such as one signed offline, into the
+/// accounts they are paid to along with the transferred balances.
+///
+/// Vested transfers count as the balance released over all their periods. Calls other than
+/// the ones composed to transfer the refunds fail to be decoded, so that nothing else is
+/// dispatched along with the transfers.
+pub fn decode_transfers(
+    metadata: &Metadata,
+    call: &[u8],
+) -> Result<Vec<(AccountId, Balance)>, SlashError> {
+    let types = metadata.types();
+    let call_type = types
+        .types
+        .iter()
+        .find(|ty| ty.ty.path.segments.last().map(String::as_str) == Some("RuntimeCall"))
+        .ok_or_else(|| {
+            SlashError::UndecodableCall("`RuntimeCall` not found in the runtime metadata".into())
+        })?;
+    let mut input = call;
+    let call = scale_value::scale::decode_as_type(&mut input, call_type.id, types)
+        .map_err(|err| SlashError::UndecodableCall(err.to_string()))?;
+    if !input.is_empty() {
+        return Err(SlashError::UndecodableCall(
+            "trailing bytes after the call".into(),
+        ));
+    }
+    let mut transfers = vec![];
+    collect_transfers(&call, &mut transfers).map_err(SlashError::UndecodableCall)?;
+    Ok(transfers)
+}
+
+fn collect_transfers<T>(
+    call: &Value<T>,
+    transfers: &mut Vec<(AccountId, Balance)>,
+) -> Result<(), String> {
+    // `RuntimeCall` variant of the pallet wrapping the variant of the call
+    let (pallet, call) = match &call.value {
+        ValueDef::Variant(pallet) => match pallet.values.values().next().map(|call| &call.value) {
+            Some(ValueDef::Variant(call)) => (pallet.name.as_str(), call),
+            _ => return Err(format!("call of {} not found", pallet.name)),
+        },
+        _ => return Err("not a call".into()),
+    };
+    let fields: Vec<_> = call.values.values().collect();
+    match (pallet, call.name.as_str(), fields.as_slice()) {
+        ("Sudo", "sudo", [call])
+        | ("Sudo", "sudo_as", [_, call])
+        | ("Proxy", "proxy", [_, _, call])
+        | ("Multisig", "as_multi", [_, _, _, call, _]) => collect_transfers(call, transfers),
+        ("Utility", "batch_all", [calls]) => match &calls.value {
+            ValueDef::Composite(calls) => calls
+                .values()
+                .try_for_each(|call| collect_transfers(call, transfers)),
+            _ => Err("calls of Utility::batch_all not found".into()),
+        },
+        ("System", "remark", [_]) => Ok(()),
+        ("Domains", "transfer_treasury_funds", [account_id, balance])
+        | ("Balances", "transfer_keep_alive" | "transfer_allow_death", [account_id, balance]) => {
+            transfers.push((decode_account_id(account_id)?, decode_balance(balance)?));
+            Ok(())
+        }
+        ("Vesting", "vested_transfer", [account_id, schedule]) => {
+            let period_count = decode_balance(field(schedule, "period_count")?)?;
+            let per_period = decode_balance(field(schedule, "per_period")?)?;
+            let balance = period_count
+                .checked_mul(per_period)
+                .ok_or("vested balance overflows")?;
+            transfers.push((decode_account_id(account_id)?, balance));
+            Ok(())
+        }
+        (pallet, call, _) => Err(format!("unexpected call {pallet}::{call}")),
+    }
+}
+
+fn field<'a, T>(value: &'a Value<T>, name: &str) -> Result<&'a Value<T>, String> {
+    match &value.value {
+        ValueDef::Composite(Composite::Named(fields)) => fields
+            .iter()
+            .find_map(|(field_name, value)| (field_name == name).then_some(value))
+            .ok_or_else(|| format!("field {name} not found")),
+        _ => Err(format!("field {name} not found")),
+    }
+}
+
+/// Decodes an account id, or a `MultiAddress::Id`.
+fn decode_account_id<T>(value: &Value<T>) -> Result<AccountId, String> {
+    let value = match &value.value {
+        ValueDef::Variant(address) if address.name == "Id" => address
+            .values
+            .values()
+            .next()
+            .ok_or("account id of the address not found")?,
+        ValueDef::Variant(address) => return Err(format!("unexpected address {}", address.name)),
+        _ => value,
+    };
+    let mut bytes = vec![];
+    collect_bytes(value, &mut bytes)?;
+    <[u8; 32]>::try_from(bytes.as_slice())
+        .map(AccountId::from)
+        .map_err(|_| "account id is not 32 bytes".into())
+}
+
+fn collect_bytes<T>(value: &Value<T>, bytes: &mut Vec<u8>) -> Result<(), String> {
+    match &value.value {
+        ValueDef::Composite(composite) => composite
+            .values()
+            .try_for_each(|value| collect_bytes(value, bytes)),
+        ValueDef::Primitive(Primitive::U128(byte)) => {
+            bytes.push(u8::try_from(*byte).map_err(|_| "account id is not bytes")?);
+            Ok(())
+        }
+        _ => Err("account id is not bytes".into()),
+    }
+}
+
+/// Decodes a balance, possibly wrapped in a single field composite such as `Compact`.
+fn decode_balance<T>(value: &Value<T>) -> Result<Balance, String> {
+    match &value.value {
+        ValueDef::Primitive(Primitive::U128(balance)) => Ok(*balance),
+        ValueDef::Composite(composite) => {
+            let mut values = composite.values();
+            match (values.next(), values.next()) {
+                (Some(value), None) => decode_balance(value),
+                _ => Err("balance is not a number".into()),
+            }
+        }
+        _ => Err("balance is not a number".into()),
+    }
+}
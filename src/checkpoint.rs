@@ -0,0 +1,276 @@
+use crate::error::SlashError;
+use crate::ledger::Ledger;
+use crate::network::Network;
+use crate::{AccountId, Hash, Number};
+use serde::{Deserialize, Serialize};
+use sp_domains::OperatorId;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Status of a batch submitted for an operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubmissionStatus {
+    /// Batch was submitted but the outcome is not known yet.
+    Submitted,
+    /// Batch was included in a block.
+    InBlock,
+    /// Multisig approval of the batch was included in a block but the batch is yet to be
+    /// approved by the other signatories.
+    Approved,
+    /// Batch failed to be submitted or included.
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Submission {
+    /// Block the operator was slashed at, not recorded by the earlier versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slashed_at: Option<Number>,
+    pub status: SubmissionStatus,
+    pub extrinsic_hash: Hash,
+    /// Block the batch was included in.
+    pub block_hash: Option<Hash>,
+    /// Nominators transferred to in the batch.
+    pub nominators: Vec<AccountId>,
+}
+
+/// Submission state of the batches of each operator in a payout manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Payload hash of the payout manifest being submitted.
+    pub payload_hash: Hash,
+    /// Whether the payouts of each nominator were consolidated into a single transfer.
+    #[serde(default)]
+    pub consolidated: bool,
+    /// Transfers of an operator may be split into multiple batches, each of which is
+    /// identified by its extrinsic hash.
+    pub operators: BTreeMap<OperatorId, Vec<Submission>>,
+}
+
+/// Checkpoint persisted to a file on every update so that an interrupted submission can
+/// be resumed without sending the same batch twice.
+pub struct CheckpointFile {
+    path: PathBuf,
+    checkpoint: Mutex<Checkpoint>,
+    /// Ledger every update is recorded in as well.
+    ledger: Option<Ledger>,
+    /// Directory the receipt of every batch included in a block is written to.
+    receipts: Option<PathBuf>,
+    /// Network whose explorers the receipts link to.
+    network: Network,
+}
+
+/// Position of a batch in the block it was included in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Inclusion {
+    pub block_number: Number,
+    /// Index of the extrinsic in the block, identifying it as `<block_number>-<index>`.
+    pub extrinsic_index: u32,
+    /// Blake2 256 hash of the `System::Events` of the block, to compare with the events
+    /// shown by the explorers.
+    pub events_digest: Hash,
+    /// Links to the extrinsic in the block explorers.
+    pub explorer_urls: Vec<String>,
+}
+
+/// Receipt of a batch included in a block.
+#[derive(Debug, Serialize)]
+struct Receipt<'a> {
+    operator_id: OperatorId,
+    #[serde(flatten)]
+    submission: &'a Submission,
+    #[serde(flatten)]
+    inclusion: &'a Inclusion,
+}
+
+impl Receipt<'_> {
+    /// Describes the receipt on a single line.
+    fn line(&self) -> String {
+        let Receipt {
+            operator_id,
+            submission,
+            inclusion,
+        } = self;
+        let mut line = format!(
+            "Operator[{operator_id:?}] slashed at #{}: {:?} batch of {} nominators in extrinsic {}-{} of block {:?}",
+            submission.slashed_at.unwrap_or_default(),
+            submission.status,
+            submission.nominators.len(),
+            inclusion.block_number,
+            inclusion.extrinsic_index,
+            submission.block_hash.unwrap_or_default(),
+        );
+        for explorer_url in &inclusion.explorer_urls {
+            line.push(' ');
+            line.push_str(explorer_url);
+        }
+        line
+    }
+}
+
+impl CheckpointFile {
+    /// Creates a new checkpoint file for the payout manifest with `payload_hash`.
+    ///
+    /// Fails if the file already exists since the previous run must be resumed instead.
+    pub fn create(path: &Path, payload_hash: Hash, consolidated: bool) -> Result<Self, SlashError> {
+        if path.exists() {
+            return Err(SlashError::InvalidCheckpoint {
+                path: path.to_path_buf(),
+                reason: "already exists, use `--resume` to continue the previous run",
+            });
+        }
+        let checkpoint_file = CheckpointFile {
+            path: path.to_path_buf(),
+            checkpoint: Mutex::new(Checkpoint {
+                payload_hash,
+                consolidated,
+                operators: BTreeMap::new(),
+            }),
+            ledger: None,
+            receipts: None,
+            network: Network::default(),
+        };
+        checkpoint_file.flush(&checkpoint_file.checkpoint.lock().unwrap())?;
+        Ok(checkpoint_file)
+    }
+
+    /// Opens the checkpoint file of the previous run of the payout manifest with `payload_hash`.
+    pub fn open(path: &Path, payload_hash: Hash) -> Result<Self, SlashError> {
+        let checkpoint: Checkpoint = serde_json::from_slice(&std::fs::read(path)?)?;
+        if checkpoint.payload_hash != payload_hash {
+            return Err(SlashError::InvalidCheckpoint {
+                path: path.to_path_buf(),
+                reason: "belongs to a different payout manifest",
+            });
+        }
+        Ok(CheckpointFile {
+            path: path.to_path_buf(),
+            checkpoint: Mutex::new(checkpoint),
+            ledger: None,
+            receipts: None,
+            network: Network::default(),
+        })
+    }
+
+    /// Records every update of the checkpoint in the ledger as well.
+    pub fn with_ledger(mut self, ledger: Option<Ledger>) -> Self {
+        self.ledger = ledger;
+        self
+    }
+
+    /// Writes a receipt of every batch included in a block to the directory as well, linking
+    /// to the explorers of the network.
+    pub fn with_receipts(mut self, receipts: Option<PathBuf>, network: Network) -> Self {
+        self.receipts = receipts;
+        self.network = network;
+        self
+    }
+
+    /// Returns the network whose explorers the receipts link to.
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Whether the payouts of each nominator were consolidated into a single transfer.
+    pub fn consolidated(&self) -> bool {
+        self.checkpoint.lock().unwrap().consolidated
+    }
+
+    /// Returns the submissions of the batches of the operator slashed at `slashed_at` in the
+    /// previous runs.
+    ///
+    /// Submissions recorded without the block the operator was slashed at belong to any
+    /// slash of the operator.
+    pub fn submissions(&self, operator_id: OperatorId, slashed_at: Number) -> Vec<Submission> {
+        self.checkpoint
+            .lock()
+            .unwrap()
+            .operators
+            .get(&operator_id)
+            .into_iter()
+            .flatten()
+            .filter(|submission| {
+                submission.slashed_at.map_or(true, |submitted_slashed_at| {
+                    submitted_slashed_at == slashed_at
+                })
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Updates the submission of the operator's batch with the same extrinsic hash, or adds
+    /// it if the batch is new, and writes the checkpoint to the file.
+    pub fn update(
+        &self,
+        operator_id: OperatorId,
+        submission: Submission,
+    ) -> Result<(), SlashError> {
+        if let Some(ledger) = &self.ledger {
+            ledger.record_submission(operator_id, &submission)?;
+        }
+        let mut checkpoint = self.checkpoint.lock().unwrap();
+        let submissions = checkpoint.operators.entry(operator_id).or_default();
+        match submissions
+            .iter_mut()
+            .find(|existing| existing.extrinsic_hash == submission.extrinsic_hash)
+        {
+            Some(existing) => *existing = submission,
+            None => submissions.push(submission),
+        }
+        self.flush(&checkpoint)
+    }
+
+    /// Writes the receipt of the operator's batch included in a block as JSON, and appends
+    /// it as a line to `receipts.txt`, if the receipts are written.
+    ///
+    /// Returns the line describing the receipt.
+    pub fn receipt(
+        &self,
+        operator_id: OperatorId,
+        submission: &Submission,
+        inclusion: &Inclusion,
+    ) -> Result<String, SlashError> {
+        let receipt = Receipt {
+            operator_id,
+            submission,
+            inclusion,
+        };
+        let line = receipt.line();
+        if let Some(receipts) = &self.receipts {
+            std::fs::write(
+                receipts.join(format!(
+                    "operator-{operator_id}-{}-{:?}.json",
+                    submission.slashed_at.unwrap_or_default(),
+                    submission.extrinsic_hash
+                )),
+                serde_json::to_vec_pretty(&receipt)?,
+            )?;
+            let mut lines = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(receipts.join("receipts.txt"))?;
+            writeln!(lines, "{line}")?;
+        }
+        Ok(line)
+    }
+
+    /// Returns the path of the checkpoint file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the checkpoint to the file again, such as before the run exits.
+    pub fn persist(&self) -> Result<(), SlashError> {
+        self.flush(&self.checkpoint.lock().unwrap())
+    }
+
+    fn flush(&self, checkpoint: &Checkpoint) -> Result<(), SlashError> {
+        // write to a temporary file first so that the checkpoint is never left half written
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec_pretty(checkpoint)?)?;
+        Ok(std::fs::rename(tmp_path, &self.path)?)
+    }
+}
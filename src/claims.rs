@@ -0,0 +1,130 @@
+use crate::error::SlashError;
+use crate::manifest::PayoutManifest;
+use crate::merkle::{PayoutProof, PayoutTree};
+use crate::{Balance, Hash};
+use serde::{Deserialize, Serialize};
+use sp_runtime::traits::Zero;
+use std::path::Path;
+
+/// Payouts to be claimed by the nominators from a claim pallet or an airdrop contract instead
+/// of being transferred from the treasury.
+///
+/// The claim pallet is funded with `total` and initialised with `root`. Each account then
+/// claims its payout with its proof, so that no funds are pushed to accounts that are no
+/// longer in use.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClaimsManifest {
+    /// Version of the tool that generated the claims.
+    pub tool_version: String,
+    /// Payload hash of the payout manifest the claims were generated from.
+    pub payload_hash: Hash,
+    /// Merkle root of the payouts of all the accounts.
+    pub root: Hash,
+    /// Total balance to be claimed.
+    pub total: Balance,
+    pub claims: Vec<PayoutProof>,
+}
+
+impl ClaimsManifest {
+    pub fn new(payouts: &PayoutManifest) -> Result<Self, SlashError> {
+        let tree = PayoutTree::new(payouts.account_totals()?);
+        Ok(ClaimsManifest {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            payload_hash: payouts.payload_hash,
+            root: tree.root(),
+            total: payouts.total,
+            claims: tree.proofs(),
+        })
+    }
+
+    /// Reads the claims manifest from the given path.
+    ///
+    /// Fails if any of the proofs does not lead to the root or if the total does not match
+    /// the claims.
+    pub fn read(path: &Path) -> Result<Self, SlashError> {
+        let claims: ClaimsManifest = serde_json::from_slice(&std::fs::read(path)?)?;
+        claims.validate()?;
+        Ok(claims)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SlashError> {
+        Ok(std::fs::write(path, serde_json::to_vec_pretty(self)?)?)
+    }
+
+    fn validate(&self) -> Result<(), SlashError> {
+        if !self.claims.iter().all(|claim| claim.verify(&self.root)) {
+            return Err(SlashError::InvalidClaims("proof does not match the root"));
+        }
+        let total = self
+            .claims
+            .iter()
+            .try_fold(Balance::zero(), |acc, claim| acc.checked_add(claim.amount));
+        if total != Some(self.total) {
+            return Err(SlashError::InvalidClaims(
+                "total does not match the sum of the claims",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{NominatorPayout, OperatorPayout};
+    use crate::AccountId;
+    use std::collections::BTreeMap;
+
+    fn payouts() -> PayoutManifest {
+        let operator_payout = |operator_id, nominators: &[(u8, Balance)]| OperatorPayout {
+            operator_id,
+            domain_id: None,
+            slashed_at: 100,
+            block_hash: Hash::zero(),
+            spec_version: 1,
+            nominators: nominators
+                .iter()
+                .map(|(byte, staked)| {
+                    let nominator_payout = NominatorPayout {
+                        staked: *staked,
+                        ..Default::default()
+                    };
+                    (AccountId::new([*byte; 32]), nominator_payout)
+                })
+                .collect(),
+            unconverted: BTreeMap::new(),
+            unlockable: BTreeMap::new(),
+            components: BTreeMap::new(),
+            dust: 0,
+            unexplained: 0,
+        };
+        PayoutManifest::new(vec![
+            operator_payout(1, &[(1, 10), (2, 20)]),
+            operator_payout(2, &[(2, 5), (3, 30)]),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn claims_match_payouts() {
+        let payouts = payouts();
+        let claims = ClaimsManifest::new(&payouts).unwrap();
+        assert!(claims.validate().is_ok());
+        assert_eq!(claims.total, 65);
+        // accounts nominating multiple operators claim once
+        assert_eq!(claims.claims.len(), 3);
+        assert_eq!(claims.claims[1].amount, 25);
+    }
+
+    #[test]
+    fn tampered_claims_are_rejected() {
+        let mut claims = ClaimsManifest::new(&payouts()).unwrap();
+        claims.claims[0].amount += 1;
+        claims.total += 1;
+        assert!(claims.validate().is_err());
+
+        let mut claims = ClaimsManifest::new(&payouts()).unwrap();
+        claims.total += 1;
+        assert!(claims.validate().is_err());
+    }
+}
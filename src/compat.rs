@@ -0,0 +1,263 @@
+use crate::error::SlashError;
+use crate::types::versioned::{
+    PerbillSharePrice, PerquintillSharePrice, RuntimeUpgrades, SharePricePrecision,
+};
+use crate::types::{Deposit, Operator, Withdrawal};
+use codec::DecodeAll;
+use scale_info::form::PortableForm;
+use scale_info::{meta_type, Field, PortableRegistry, Registry, TypeDef, TypeInfo};
+use std::collections::BTreeMap;
+use substrate_api_client::ac_node_api::Metadata;
+
+/// Checks that the types of the `Domains` pallet in the runtime metadata are laid out as the
+/// ones in `types.rs` they are decoded into.
+///
+/// Types are compared by their encoding only, so renamed fields and variants are accepted.
+pub fn check_domains_types(
+    metadata: &Metadata,
+    spec_version: u32,
+    upgrades: RuntimeUpgrades,
+) -> Result<(), SlashError> {
+    check_type::<Operator>(metadata, spec_version, "Operator")?;
+    check_type::<Deposit>(metadata, spec_version, "Deposit")?;
+    check_type::<Withdrawal>(metadata, spec_version, "Withdrawal")?;
+    match SharePricePrecision::at(spec_version, upgrades) {
+        SharePricePrecision::Perbill => {
+            check_type::<PerbillSharePrice>(metadata, spec_version, "SharePrice")
+        }
+        SharePricePrecision::Perquintill => {
+            check_type::<PerquintillSharePrice>(metadata, spec_version, "SharePrice")
+        }
+    }
+}
+
+fn check_type<T: TypeInfo + 'static>(
+    metadata: &Metadata,
+    spec_version: u32,
+    name: &'static str,
+) -> Result<(), SlashError> {
+    let incompatible = |reason: &str| SlashError::IncompatibleRuntime {
+        spec_version,
+        reason: format!("`{name}` {reason}"),
+    };
+
+    let (expected, expected_id) = registry::<T>();
+
+    let runtime = metadata.types();
+    let mut candidates = runtime
+        .types
+        .iter()
+        .filter(|ty| {
+            ty.ty.path.segments.first().map(String::as_str) == Some("pallet_domains")
+                && ty.ty.path.segments.last().map(String::as_str) == Some(name)
+        })
+        .peekable();
+    if candidates.peek().is_none() {
+        return Err(incompatible("not found in the runtime metadata"));
+    }
+    // generic types are registered once per instance
+    if candidates.any(|ty| same_layout(&expected, expected_id, runtime, ty.id)) {
+        Ok(())
+    } else {
+        Err(incompatible("layout changed in the runtime"))
+    }
+}
+
+/// Decodes the storage of the `Domains` pallet with the types of the runtime metadata, for
+/// when the types in `types.rs` fail to decode it after a minor change of their layout.
+///
+/// The value is decoded as the type of the runtime and encoded again as the static type,
+/// matching the fields by name, so that added fields are left out and reordered or widened
+/// fields are converted, while removed or renamed fields still fail.
+pub struct DynamicDecoder {
+    types: PortableRegistry,
+    /// Type of the value of each storage of the pallet.
+    value_types: BTreeMap<String, u32>,
+}
+
+impl DynamicDecoder {
+    pub fn new(metadata: &Metadata) -> Self {
+        let value_types = metadata
+            .pallet_by_name("Domains")
+            .and_then(|pallet| pallet.storage())
+            .map(|storage| {
+                storage
+                    .entries()
+                    .iter()
+                    .map(|entry| (entry.name().to_string(), entry.entry_type().value_ty()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        DynamicDecoder {
+            types: metadata.types().clone(),
+            value_types,
+        }
+    }
+
+    /// Decodes the value of the storage as the type of the runtime metadata into `T`.
+    pub fn decode<T: DecodeAll + TypeInfo + 'static>(
+        &self,
+        storage: &str,
+        value: &[u8],
+    ) -> Result<T, String> {
+        let value_type = self
+            .value_types
+            .get(storage)
+            .ok_or_else(|| format!("`{storage}` not found in the runtime metadata"))?;
+        let value = scale_value::scale::decode_as_type(&mut &value[..], *value_type, &self.types)
+            .map_err(|err| err.to_string())?;
+        let (expected, expected_id) = registry::<T>();
+        let mut encoded = vec![];
+        scale_value::scale::encode_as_type(&value, expected_id, &expected, &mut encoded)
+            .map_err(|err| err.to_string())?;
+        T::decode_all(&mut encoded.as_slice()).map_err(|err| err.to_string())
+    }
+}
+
+/// Returns a registry with the type `T` along with its ID.
+fn registry<T: TypeInfo + 'static>() -> (PortableRegistry, u32) {
+    let mut registry = Registry::new();
+    let id = registry.register_type(&meta_type::<T>()).id;
+    (PortableRegistry::from(registry), id)
+}
+
+/// Whether the type `a` of registry `a_types` is encoded as the type `b` of `b_types`.
+fn same_layout(a_types: &PortableRegistry, a: u32, b_types: &PortableRegistry, b: u32) -> bool {
+    let (Some(a_ty), Some(b_ty)) = (a_types.resolve(a), b_types.resolve(b)) else {
+        return false;
+    };
+    let same_fields = |a_fields: &[Field<PortableForm>], b_fields: &[Field<PortableForm>]| {
+        a_fields.len() == b_fields.len()
+            && a_fields.iter().zip(b_fields).all(|(a_field, b_field)| {
+                same_layout(a_types, a_field.ty.id, b_types, b_field.ty.id)
+            })
+    };
+
+    match (&a_ty.type_def, &b_ty.type_def) {
+        (TypeDef::Composite(a_def), TypeDef::Composite(b_def)) => {
+            same_fields(&a_def.fields, &b_def.fields)
+        }
+        (TypeDef::Variant(a_def), TypeDef::Variant(b_def)) => {
+            a_def.variants.len() == b_def.variants.len()
+                && a_def.variants.iter().all(|a_variant| {
+                    b_def.variants.iter().any(|b_variant| {
+                        a_variant.index == b_variant.index
+                            && same_fields(&a_variant.fields, &b_variant.fields)
+                    })
+                })
+        }
+        (TypeDef::Sequence(a_def), TypeDef::Sequence(b_def)) => {
+            same_layout(a_types, a_def.type_param.id, b_types, b_def.type_param.id)
+        }
+        (TypeDef::Array(a_def), TypeDef::Array(b_def)) => {
+            a_def.len == b_def.len
+                && same_layout(a_types, a_def.type_param.id, b_types, b_def.type_param.id)
+        }
+        (TypeDef::Tuple(a_def), TypeDef::Tuple(b_def)) => {
+            a_def.fields.len() == b_def.fields.len()
+                && a_def
+                    .fields
+                    .iter()
+                    .zip(&b_def.fields)
+                    .all(|(a_field, b_field)| same_layout(a_types, a_field.id, b_types, b_field.id))
+        }
+        (TypeDef::Primitive(a_def), TypeDef::Primitive(b_def)) => a_def == b_def,
+        (TypeDef::Compact(a_def), TypeDef::Compact(b_def)) => {
+            same_layout(a_types, a_def.type_param.id, b_types, b_def.type_param.id)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::KnownDeposit;
+    use codec::Encode;
+
+    #[test]
+    fn same_types_have_same_layout() {
+        let (a_types, a) = registry::<Operator>();
+        let (b_types, b) = registry::<Operator>();
+        assert!(same_layout(&a_types, a, &b_types, b));
+    }
+
+    #[test]
+    fn changed_types_have_different_layout() {
+        let (a_types, a) = registry::<Deposit>();
+        let (b_types, b) = registry::<Withdrawal>();
+        assert!(!same_layout(&a_types, a, &b_types, b));
+
+        // known deposit is nested in the deposit
+        let (a_types, a) = registry::<Deposit>();
+        let (b_types, b) = registry::<KnownDeposit>();
+        assert!(!same_layout(&a_types, a, &b_types, b));
+    }
+
+    #[test]
+    fn renamed_fields_have_same_layout() {
+        #[derive(TypeInfo)]
+        #[allow(dead_code)]
+        struct RenamedKnownDeposit {
+            staked_shares: u128,
+            storage_fee: u128,
+        }
+
+        let (a_types, a) = registry::<KnownDeposit>();
+        let (b_types, b) = registry::<RenamedKnownDeposit>();
+        assert!(same_layout(&a_types, a, &b_types, b));
+    }
+
+    fn decoder<T: TypeInfo + 'static>(storage: &str) -> DynamicDecoder {
+        let (types, id) = registry::<T>();
+        DynamicDecoder {
+            types,
+            value_types: BTreeMap::from([(storage.to_string(), id)]),
+        }
+    }
+
+    #[test]
+    fn extended_types_are_decoded_dynamically() {
+        #[derive(TypeInfo, Encode)]
+        struct ExtendedKnownDeposit {
+            storage_fee_deposit: u128,
+            shares: u128,
+            unlocked_at: u32,
+        }
+
+        let value = ExtendedKnownDeposit {
+            storage_fee_deposit: 2,
+            shares: 1,
+            unlocked_at: 3,
+        }
+        .encode();
+        let decoder = decoder::<ExtendedKnownDeposit>("Deposits");
+        assert_eq!(
+            decoder.decode::<KnownDeposit>("Deposits", &value),
+            Ok(KnownDeposit {
+                shares: 1,
+                storage_fee_deposit: 2,
+            })
+        );
+        assert!(decoder
+            .decode::<KnownDeposit>("Withdrawals", &value)
+            .is_err());
+    }
+
+    #[test]
+    fn renamed_fields_are_not_decoded_dynamically() {
+        #[derive(TypeInfo, Encode)]
+        struct RenamedKnownDeposit {
+            staked_shares: u128,
+            storage_fee: u128,
+        }
+
+        let value = RenamedKnownDeposit {
+            staked_shares: 1,
+            storage_fee: 2,
+        }
+        .encode();
+        let decoder = decoder::<RenamedKnownDeposit>("Deposits");
+        assert!(decoder.decode::<KnownDeposit>("Deposits", &value).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use crate::error::SlashError;
+use crate::network::Network;
+use crate::{Balance, Number};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use sp_domains::OperatorId;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::{Path, PathBuf};
+
+/// Settings of a refund run read from a TOML file, so that they do not have to be repeated
+/// on the command line of every command.
+///
+/// Every setting is optional, and the flags given on the command line take precedence:
+///
+/// ```toml
+/// network = "gemini-3h"
+/// endpoints = ["wss://rpc-0.gemini-3h.subspace.network/ws", "wss://rpc-1.gemini-3h.subspace.network/ws"]
+/// max_rps = 20
+/// rpc_timeout = 60
+/// rpc_connections = 4
+/// retry_budget = 100
+/// circuit_breaker = 20
+///
+/// [operators]
+/// file = "slashed.csv"
+/// ids = [12]
+/// slashed = [{ operator_id = 3, slashed_at = 1234 }]
+///
+/// [submit]
+/// max_batch_size = 128
+/// sequential = true
+/// max_total = "1000000000000000000000"
+///
+/// [output]
+/// manifest = "payouts.json"
+/// checkpoint = "checkpoint.json"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RefundConfig {
+    #[serde(default)]
+    pub network: Option<Network>,
+    /// RPC endpoints of the node, tried in order until one of them connects.
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+    #[serde(default)]
+    pub max_rps: Option<NonZeroU32>,
+    /// Seconds after which an RPC request times out.
+    #[serde(default)]
+    pub rpc_timeout: Option<u64>,
+    /// Number of connections the reads are spread across.
+    #[serde(default)]
+    pub rpc_connections: Option<NonZeroUsize>,
+    /// Number of replays of the failed requests allowed for the whole run.
+    #[serde(default)]
+    pub retry_budget: Option<usize>,
+    /// Number of failed requests in a row after which the run is stopped.
+    #[serde(default)]
+    pub circuit_breaker: Option<NonZeroUsize>,
+    #[serde(default)]
+    pub operators: OperatorsConfig,
+    #[serde(default)]
+    pub submit: SubmitConfig,
+    #[serde(default)]
+    pub output: OutputConfig,
+}
+
+/// Slashed operators of the run, combined like the `--operators-file`, `--operator` and
+/// `--operator-id` flags.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperatorsConfig {
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+    #[serde(default)]
+    pub slashed: Vec<SlashedOperator>,
+    /// IDs of the operators whose slash block is to be found.
+    #[serde(default)]
+    pub ids: Vec<OperatorId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SlashedOperator {
+    pub operator_id: OperatorId,
+    pub slashed_at: Number,
+}
+
+/// Batching, concurrency and safety thresholds of the submission.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubmitConfig {
+    #[serde(default)]
+    pub max_batch_size: Option<NonZeroUsize>,
+    /// Submit one batch at a time instead of all the batches at once.
+    #[serde(default)]
+    pub sequential: Option<bool>,
+    /// Maximum total balance to transfer from the treasury, as a decimal string since TOML
+    /// integers do not fit balances.
+    #[serde(default, deserialize_with = "deserialize_balance")]
+    pub max_total: Option<Balance>,
+}
+
+/// Paths the commands read and write.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Directory of the on-disk cache of the chain state.
+    #[serde(default)]
+    pub cache: Option<PathBuf>,
+    /// SQLite ledger the run is recorded in.
+    #[serde(default)]
+    pub ledger: Option<PathBuf>,
+    /// Payout manifest written by `calculate` and read by the other commands.
+    #[serde(default)]
+    pub manifest: Option<PathBuf>,
+    /// CSV of the payouts additionally written by `calculate`.
+    #[serde(default)]
+    pub csv: Option<PathBuf>,
+    /// Checkpoint of `submit` and `submit-signed`.
+    #[serde(default)]
+    pub checkpoint: Option<PathBuf>,
+    /// Directory the reports are written to.
+    #[serde(default)]
+    pub reports: Option<PathBuf>,
+}
+
+impl RefundConfig {
+    pub fn read(path: &Path) -> Result<Self, SlashError> {
+        let invalid = |reason: String| SlashError::InvalidConfig {
+            path: path.to_path_buf(),
+            reason,
+        };
+        Self::parse(&std::fs::read_to_string(path)?).map_err(invalid)
+    }
+
+    fn parse(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|err| err.to_string())
+    }
+}
+
+fn deserialize_balance<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Balance>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|amount| {
+            amount
+                .parse()
+                .map_err(|err| D::Error::custom(format!("invalid balance `{amount}`: {err}")))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_is_parsed() {
+        let content = r#"
+endpoints = ["wss://a", "wss://b"]
+max_rps = 20
+
+[operators]
+ids = [12]
+slashed = [{ operator_id = 3, slashed_at = 1234 }]
+
+[submit]
+max_batch_size = 128
+max_total = "1000000000000000000000"
+
+[output]
+manifest = "run/payouts.json"
+"#;
+        let config = RefundConfig::parse(content).unwrap();
+        assert_eq!(config.endpoints, vec!["wss://a", "wss://b"]);
+        assert_eq!(config.max_rps, NonZeroU32::new(20));
+        assert_eq!(config.rpc_timeout, None);
+        assert_eq!(config.operators.ids, vec![12]);
+        assert_eq!(
+            config.operators.slashed,
+            vec![SlashedOperator {
+                operator_id: 3,
+                slashed_at: 1234
+            }]
+        );
+        assert_eq!(config.submit.max_batch_size, NonZeroUsize::new(128));
+        assert_eq!(config.submit.sequential, None);
+        assert_eq!(config.submit.max_total, Some(1_000_000_000_000_000_000_000));
+        assert_eq!(
+            config.output.manifest,
+            Some(PathBuf::from("run/payouts.json"))
+        );
+
+        assert_eq!(RefundConfig::parse("").unwrap(), RefundConfig::default());
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        assert!(RefundConfig::parse("max_rps = 0").is_err());
+        assert!(RefundConfig::parse("[submit]\nmax_total = \"ten\"").is_err());
+        // misspelled settings are not silently ignored
+        assert!(RefundConfig::parse("endpoint = [\"wss://a\"]").is_err());
+    }
+}
@@ -0,0 +1,378 @@
+use crate::error::SlashError;
+use crate::types::NominatorStorage;
+use crate::{AccountId, Balance, Number};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use sp_domains::OperatorId;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Query of the nominations of an operator at a block, sent with the `operatorId` and
+/// `blockHeight` variables.
+///
+/// Indexers with another schema are queried with a query of their own, aliasing their fields
+/// to the ones of this query.
+pub const DEFAULT_QUERY: &str = r#"query Nominations($operatorId: String!, $blockHeight: String!) {
+  nominations(operatorId: $operatorId, blockHeight: $blockHeight) {
+    accountId
+    knownShares
+    storageFeeDeposit
+    pendingDeposit
+    withdrawalInShares
+    totalWithdrawalAmount
+  }
+}"#;
+
+/// Time to wait for the indexer to answer a query.
+const INDEXER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Balances of a nominator of an operator compared between the node and the indexer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Nomination {
+    #[serde(default, deserialize_with = "deserialize_balance")]
+    pub known_shares: Balance,
+    #[serde(default, deserialize_with = "deserialize_balance")]
+    pub storage_fee_deposit: Balance,
+    /// Amount of the deposit pending until the end of the epoch.
+    #[serde(default, deserialize_with = "deserialize_balance")]
+    pub pending_deposit: Balance,
+    /// Shares withdrawn in an epoch that has not ended yet.
+    #[serde(default, deserialize_with = "deserialize_balance")]
+    pub withdrawal_in_shares: Balance,
+    /// Amount of the withdrawals that are unlocking.
+    #[serde(default, deserialize_with = "deserialize_balance")]
+    pub total_withdrawal_amount: Balance,
+}
+
+impl Nomination {
+    fn fields(&self) -> [(&'static str, Balance); 5] {
+        [
+            ("known shares", self.known_shares),
+            ("storage fee deposit", self.storage_fee_deposit),
+            ("pending deposit", self.pending_deposit),
+            ("withdrawal in shares", self.withdrawal_in_shares),
+            ("total withdrawal amount", self.total_withdrawal_amount),
+        ]
+    }
+}
+
+impl From<&NominatorStorage> for Nomination {
+    fn from(storage: &NominatorStorage) -> Self {
+        let withdrawal = storage.withdrawal.as_ref();
+        Nomination {
+            known_shares: storage.deposit.known.shares,
+            storage_fee_deposit: storage.deposit.known.storage_fee_deposit,
+            pending_deposit: storage
+                .deposit
+                .pending
+                .map_or(0, |pending_deposit| pending_deposit.amount),
+            withdrawal_in_shares: withdrawal
+                .and_then(|withdrawal| withdrawal.withdrawal_in_shares.as_ref())
+                .map_or(0, |withdrawal_in_shares| withdrawal_in_shares.shares),
+            total_withdrawal_amount: withdrawal
+                .map_or(0, |withdrawal| withdrawal.total_withdrawal_amount),
+        }
+    }
+}
+
+/// Nomination of an account as indexed.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedNomination {
+    #[serde(deserialize_with = "deserialize_account_id")]
+    pub account_id: AccountId,
+    #[serde(flatten)]
+    pub nomination: Nomination,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryResponse {
+    data: Option<NominationsData>,
+    #[serde(default)]
+    errors: Vec<QueryError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NominationsData {
+    nominations: Vec<IndexedNomination>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryError {
+    message: String,
+}
+
+/// GraphQL endpoint of a SubQuery or Subsquid indexer of the staking of the domains.
+pub struct Indexer {
+    url: String,
+    query: String,
+    client: reqwest::Client,
+}
+
+impl Indexer {
+    pub fn new(url: String, query: Option<String>) -> Self {
+        Indexer {
+            url,
+            query: query.unwrap_or_else(|| DEFAULT_QUERY.to_string()),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Returns the nominations of the operator as indexed at the block.
+    pub async fn nominations(
+        &self,
+        operator_id: OperatorId,
+        block_number: Number,
+    ) -> Result<Vec<IndexedNomination>, SlashError> {
+        let body = serde_json::json!({
+            "query": self.query,
+            "variables": {
+                "operatorId": operator_id.to_string(),
+                "blockHeight": block_number.to_string(),
+            },
+        });
+        let response = self
+            .client
+            .post(&self.url)
+            .timeout(INDEXER_TIMEOUT)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| SlashError::Indexer(err.to_string()))?
+            .text()
+            .await
+            .map_err(|err| SlashError::Indexer(err.to_string()))?;
+        Self::parse(&response)
+    }
+
+    fn parse(response: &str) -> Result<Vec<IndexedNomination>, SlashError> {
+        let QueryResponse { data, errors } =
+            serde_json::from_str(response).map_err(|err| SlashError::Indexer(err.to_string()))?;
+        if !errors.is_empty() {
+            let messages: Vec<_> = errors.into_iter().map(|error| error.message).collect();
+            return Err(SlashError::Indexer(messages.join(", ")));
+        }
+        data.map(|data| data.nominations)
+            .ok_or_else(|| SlashError::Indexer("no data in the response".to_string()))
+    }
+}
+
+/// Difference between the nominations read from the node and the ones of the indexer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// Nominator found on the node but not by the indexer.
+    NotIndexed {
+        operator_id: OperatorId,
+        nominator_id: AccountId,
+    },
+    /// Nominator found by the indexer but not on the node.
+    NotOnChain {
+        operator_id: OperatorId,
+        nominator_id: AccountId,
+    },
+    /// Balance of the nominator that differs between the node and the indexer.
+    Balance {
+        operator_id: OperatorId,
+        nominator_id: AccountId,
+        field: &'static str,
+        node: Balance,
+        indexer: Balance,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::NotIndexed {
+                operator_id,
+                nominator_id,
+            } => write!(
+                f,
+                "Operator[{operator_id:?}] Nominator[{nominator_id}]: not found by the indexer"
+            ),
+            Divergence::NotOnChain {
+                operator_id,
+                nominator_id,
+            } => write!(
+                f,
+                "Operator[{operator_id:?}] Nominator[{nominator_id}]: indexed but not found on the node"
+            ),
+            Divergence::Balance {
+                operator_id,
+                nominator_id,
+                field,
+                node,
+                indexer,
+            } => write!(
+                f,
+                "Operator[{operator_id:?}] Nominator[{nominator_id}]: {field} is {node} on the node but {indexer} in the indexer"
+            ),
+        }
+    }
+}
+
+/// Compares the nominations of the operator read from the node with the indexed ones.
+pub fn crosscheck(
+    operator_id: OperatorId,
+    node: &BTreeMap<AccountId, NominatorStorage>,
+    indexed: Vec<IndexedNomination>,
+) -> Vec<Divergence> {
+    let mut indexed: BTreeMap<_, _> = indexed
+        .into_iter()
+        .map(|indexed| (indexed.account_id, indexed.nomination))
+        .collect();
+    let mut divergences = vec![];
+    for (nominator_id, storage) in node {
+        let Some(indexed) = indexed.remove(nominator_id) else {
+            divergences.push(Divergence::NotIndexed {
+                operator_id,
+                nominator_id: nominator_id.clone(),
+            });
+            continue;
+        };
+        let nomination = Nomination::from(storage);
+        for ((field, node), (_, indexer)) in nomination.fields().into_iter().zip(indexed.fields()) {
+            if node != indexer {
+                divergences.push(Divergence::Balance {
+                    operator_id,
+                    nominator_id: nominator_id.clone(),
+                    field,
+                    node,
+                    indexer,
+                });
+            }
+        }
+    }
+    divergences.extend(
+        indexed
+            .into_keys()
+            .map(|nominator_id| Divergence::NotOnChain {
+                operator_id,
+                nominator_id,
+            }),
+    );
+    divergences
+}
+
+fn deserialize_account_id<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<AccountId, D::Error> {
+    let account_id = String::deserialize(deserializer)?;
+    AccountId::from_str(&account_id)
+        .map_err(|err| D::Error::custom(format!("invalid account ID `{account_id}`: {err}")))
+}
+
+/// Amount as indexed, either as a number or as a decimal string since GraphQL integers do
+/// not fit balances.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Amount {
+    Number(u64),
+    Text(String),
+}
+
+fn deserialize_balance<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Balance, D::Error> {
+    match Option::<Amount>::deserialize(deserializer)? {
+        None => Ok(0),
+        Some(Amount::Number(amount)) => Ok(amount.into()),
+        Some(Amount::Text(amount)) => amount
+            .parse()
+            .map_err(|err| D::Error::custom(format!("invalid balance `{amount}`: {err}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Deposit, KnownDeposit, Withdrawal};
+
+    #[test]
+    fn indexed_nominations_are_parsed() {
+        let nominator_id = AccountId::new([1; 32]);
+        let response = format!(
+            r#"{{"data": {{"nominations": [{{
+                "accountId": "{nominator_id}",
+                "knownShares": "1000000000000000000000",
+                "storageFeeDeposit": 20,
+                "pendingDeposit": null
+            }}]}}}}"#
+        );
+        assert_eq!(
+            Indexer::parse(&response).unwrap(),
+            vec![IndexedNomination {
+                account_id: nominator_id,
+                nomination: Nomination {
+                    known_shares: 1_000_000_000_000_000_000_000,
+                    storage_fee_deposit: 20,
+                    ..Nomination::default()
+                },
+            }]
+        );
+
+        let response = r#"{"data": null, "errors": [{"message": "unknown field"}]}"#;
+        assert!(matches!(
+            Indexer::parse(response),
+            Err(SlashError::Indexer(message)) if message == "unknown field"
+        ));
+    }
+
+    #[test]
+    fn divergences_are_found() {
+        let (a, b, c) = (
+            AccountId::new([1; 32]),
+            AccountId::new([2; 32]),
+            AccountId::new([3; 32]),
+        );
+        let storage = |shares| NominatorStorage {
+            deposit: Deposit {
+                known: KnownDeposit {
+                    shares,
+                    storage_fee_deposit: 10,
+                },
+                pending: None,
+            },
+            withdrawal: Some(Withdrawal {
+                total_withdrawal_amount: 5,
+                ..Withdrawal::default()
+            }),
+        };
+        let node = BTreeMap::from([(a.clone(), storage(100)), (b.clone(), storage(200))]);
+        let indexed = |account_id: &AccountId, known_shares| IndexedNomination {
+            account_id: account_id.clone(),
+            nomination: Nomination {
+                known_shares,
+                storage_fee_deposit: 10,
+                total_withdrawal_amount: 5,
+                ..Nomination::default()
+            },
+        };
+
+        assert_eq!(
+            crosscheck(3, &node, vec![indexed(&a, 100), indexed(&c, 300)]),
+            vec![
+                Divergence::NotIndexed {
+                    operator_id: 3,
+                    nominator_id: b.clone(),
+                },
+                Divergence::NotOnChain {
+                    operator_id: 3,
+                    nominator_id: c,
+                },
+            ]
+        );
+        assert_eq!(
+            crosscheck(3, &node, vec![indexed(&a, 100), indexed(&b, 150)]),
+            vec![Divergence::Balance {
+                operator_id: 3,
+                nominator_id: b,
+                field: "known shares",
+                node: 200,
+                indexer: 150,
+            }]
+        );
+    }
+}
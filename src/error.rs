@@ -0,0 +1,247 @@
+use crate::types::{OperatorStatus, Weight};
+use crate::{AccountId, Balance, Hash, Number};
+use sp_domains::{EpochIndex, OperatorId};
+use std::path::PathBuf;
+
+/// Error of the slash refund pipeline.
+#[derive(Debug, thiserror::Error)]
+pub enum SlashError {
+    /// Failed to connect to the node.
+    #[error("Failed to connect to the node: {0}")]
+    Connection(String),
+    /// RPC request to the node failed.
+    #[error("RPC request failed: {0:?}")]
+    Rpc(substrate_api_client::Error),
+    /// Extrinsic was dropped from the transaction pool without being included.
+    #[error("Extrinsic dropped: {0:?}")]
+    ExtrinsicDropped(substrate_api_client::Error),
+    #[error("Block #{0} not found")]
+    BlockNotFound(Number),
+    #[error("Block {0:?} not found")]
+    BlockHashNotFound(Hash),
+    #[error("Operator is not slashed at block #{0}")]
+    NotSlashed(Number),
+    #[error("Operator is {status:?} instead of pending slash at block #{at}")]
+    NotPendingSlash { status: OperatorStatus, at: Number },
+    #[error("{pallet}::{storage} not found at block {at:?}")]
+    StorageNotFound {
+        pallet: &'static str,
+        storage: &'static str,
+        at: Hash,
+    },
+    #[error("Runtime call {method} returned no value at block {at:?}")]
+    RuntimeCallNotFound { method: &'static str, at: Hash },
+    #[error("Failed to decode: {0}")]
+    Decode(#[from] codec::Error),
+    #[error("Arithmetic overflow")]
+    Overflow,
+    #[error("Share price of epoch {0} the operator was deregistered in not found")]
+    DeregistrationSharePriceNotFound(EpochIndex),
+    #[error("Withdrawal found without a deposit")]
+    MissingDeposit,
+    #[error("Found {found} deposits but {expected} nominators")]
+    NominatorCountMismatch { found: usize, expected: u32 },
+    /// Storage entries of the nominators failed to be decoded and were quarantined.
+    #[error("{count} {storage} entries failed to be decoded and were quarantined")]
+    Quarantined { storage: &'static str, count: usize },
+    /// Storage value failed to be decoded both as the static type and as the type of the
+    /// runtime metadata.
+    #[error(
+        "Failed to decode {storage} entry: {error}, and with the runtime metadata: {dynamic_error}"
+    )]
+    Undecodable {
+        storage: &'static str,
+        error: codec::Error,
+        dynamic_error: String,
+    },
+    /// Shares or stake of the nominators do not add up to the ones of the operator.
+    #[error("Total {kind} of the nominators is {nominators_total} but {operator_total} for the operator")]
+    NominatorsTotalMismatch {
+        kind: &'static str,
+        nominators_total: Balance,
+        operator_total: Balance,
+    },
+    /// Call is not present in the runtime metadata.
+    #[error("{pallet}::{call} not found in metadata")]
+    CallNotFound {
+        pallet: &'static str,
+        call: &'static str,
+    },
+    /// Types of the runtime are not laid out as the ones they are decoded into.
+    #[error("Runtime with spec version {spec_version} is not supported: {reason}")]
+    IncompatibleRuntime { spec_version: u32, reason: String },
+    #[error("Invalid multisig: {0}")]
+    InvalidMultisig(&'static str),
+    #[error("Invalid proposal: {0}")]
+    InvalidProposal(&'static str),
+    #[error("Invalid vesting schedule: {0}")]
+    InvalidVesting(&'static str),
+    #[error("Proxy type {0} not found in metadata")]
+    UnknownProxyType(String),
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(&'static str),
+    /// Batch call is not one composed to transfer the refunds.
+    #[error("Failed to decode the transfers of the batch call: {0}")]
+    UndecodableCall(String),
+    /// Transfers of a batch are not the refunds of the payout manifest.
+    #[error("Batch does not match the payout manifest: {0}")]
+    BatchMismatch(String),
+    /// Extrinsics cannot be signed without a signer set on the api.
+    #[error("Signer not set")]
+    SignerNotSet,
+    #[error("Invalid keystore: {0}")]
+    InvalidKeystore(&'static str),
+    #[error("Invalid keystore SURI: {0:?}")]
+    InvalidSuri(sp_core::crypto::SecretStringError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Cache error: {0}")]
+    Cache(#[from] sled::Error),
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Ledger error: {0}")]
+    Ledger(#[from] rusqlite::Error),
+    #[error("Invalid ledger: {0}")]
+    InvalidLedger(&'static str),
+    /// Command requires the ledger but `--ledger` was not given.
+    #[error("Ledger not set, pass `--ledger`")]
+    LedgerNotSet,
+    #[error("Run {0} not found in the ledger")]
+    RunNotFound(String),
+    #[error("Invalid slashed operators file {}:{line}: {reason}", path.display())]
+    InvalidOperatorsFile {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+    #[error("Invalid payouts CSV {}:{line}: {reason}", path.display())]
+    InvalidPayoutsCsv {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+    #[error("Invalid overrides file {}: {reason}", path.display())]
+    InvalidOverrides { path: PathBuf, reason: String },
+    #[error("Invalid redirections file {}: {reason}", path.display())]
+    InvalidRedirections { path: PathBuf, reason: String },
+    #[error("Invalid config file {}: {reason}", path.display())]
+    InvalidConfig { path: PathBuf, reason: String },
+    #[error("No slashed operators given, pass `--operators-file`, `--operator`, `--operator-id` or `--scan-from`, or set `[operators]` in the config file")]
+    NoSlashedOperators,
+    #[error("Invalid exclude file {}:{line}: {reason}", path.display())]
+    InvalidExcludeFile {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+    #[error("Account {0} not found in the payout manifest")]
+    AccountNotFound(AccountId),
+    #[error("Invalid payout manifest: {0}")]
+    InvalidManifest(&'static str),
+    #[error("Invalid claims manifest: {0}")]
+    InvalidClaims(&'static str),
+    #[error("Invalid checkpoint {}: {reason}", path.display())]
+    InvalidCheckpoint { path: PathBuf, reason: &'static str },
+    #[error("Treasury balance {balance} not sufficient to transfer {required}")]
+    InsufficientTreasuryBalance { balance: Balance, required: Balance },
+    #[error("Total {total} exceeds the maximum total {max_total} and was not confirmed")]
+    ExceedsMaximumTotal { total: Balance, max_total: Balance },
+    #[error("Signer balance {balance} not sufficient to pay the estimated fees {required}")]
+    InsufficientFeeBalance { balance: Balance, required: Balance },
+    #[error("Inclusion of extrinsic {0:?} could not be confirmed")]
+    ExtrinsicNotFound(Hash),
+    /// Batch was included but failed to dispatch, so none of its transfers were made.
+    #[error("Batch included in block {block_hash:?} failed: {reason}")]
+    BatchFailed {
+        block_hash: Hash,
+        reason: &'static str,
+    },
+    #[error("Block {0:?} was reverted before being finalized")]
+    BlockReverted(Hash),
+    #[error("Batch exceeds the block limits with weight {weight:?} and length {length}")]
+    ExceedsBlockLimits { weight: Weight, length: u32 },
+    /// Some of the operators failed, the details of which are already reported.
+    #[error("{failed} of {total} operators failed")]
+    OperatorsFailed { failed: usize, total: usize },
+    /// Some of the batches failed, the details of which are already reported.
+    #[error("{failed} of {total} batches failed")]
+    BatchesFailed { failed: usize, total: usize },
+    /// Batch was left unsubmitted since the run was asked to stop.
+    #[error("Batch with Nonce[{nonce}] not submitted since the run was stopped")]
+    NotSubmitted { nonce: u32 },
+    /// Run was stopped with Ctrl-C or by the circuit breaker, the batches left are already
+    /// reported.
+    #[error("Stopped with {not_submitted} of {total} batches not submitted and {failed} failed")]
+    Interrupted {
+        not_submitted: usize,
+        failed: usize,
+        total: usize,
+    },
+    /// Some of the batches failed the dry run, the details of which are already reported.
+    #[error("{failed} of {total} batches failed the dry run")]
+    DryRunFailed { failed: usize, total: usize },
+    #[error("Found {payouts} mismatches in payout manifest and {transfers} mismatches in treasury transfers")]
+    Mismatches { payouts: usize, transfers: usize },
+    #[error("Slashed balances of {0} blocks do not match the treasury inflows")]
+    SlashInflowMismatches(usize),
+    #[error("Found {0} divergences between the node and the indexer")]
+    Divergences(usize),
+    #[error("Indexer query failed: {0}")]
+    Indexer(String),
+    /// Node belongs to another network than the one the refunds are for.
+    #[error("Node is on the network with genesis hash {found:?} instead of {expected:?}")]
+    GenesisHashMismatch { expected: Hash, found: Hash },
+    #[error("Genesis hash of the network is not known, pass `--genesis-hash`")]
+    GenesisHashNotSet,
+    /// Calls would fail with `BadOrigin` since they are not dispatched from the sudo key.
+    #[error(
+        "Calls are dispatched from {account} but the sudo key is {}",
+        sudo_key.as_ref().map_or_else(|| "not set".to_string(), ToString::to_string)
+    )]
+    NotSudoKey {
+        account: AccountId,
+        sudo_key: Option<AccountId>,
+    },
+    /// Signer has extrinsics in the pool or in blocks that are not finalized yet, which the
+    /// batches would collide with.
+    #[error("Signer {account} has Nonce[{finalized}] at the finalized head but Nonce[{next}] with its pending extrinsics, wait for them to be finalized")]
+    SignerNoncePending {
+        account: AccountId,
+        finalized: u32,
+        next: u32,
+    },
+    #[error("Operator[{operator_id:?}]: {source}")]
+    Operator {
+        operator_id: OperatorId,
+        source: Box<SlashError>,
+    },
+    #[error("Nominator[{nominator_id}]: {source}")]
+    Nominator {
+        nominator_id: AccountId,
+        source: Box<SlashError>,
+    },
+}
+
+impl SlashError {
+    /// Attributes the error to the given operator.
+    pub fn operator(operator_id: OperatorId, err: SlashError) -> Self {
+        SlashError::Operator {
+            operator_id,
+            source: Box::new(err),
+        }
+    }
+
+    /// Attributes the error to the given nominator.
+    pub fn nominator(nominator_id: AccountId, err: SlashError) -> Self {
+        SlashError::Nominator {
+            nominator_id,
+            source: Box::new(err),
+        }
+    }
+}
+
+impl From<substrate_api_client::Error> for SlashError {
+    fn from(err: substrate_api_client::Error) -> Self {
+        SlashError::Rpc(err)
+    }
+}
@@ -0,0 +1,139 @@
+use crate::error::SlashError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use crypto_secretbox::aead::{Aead, KeyInit};
+use crypto_secretbox::{Nonce, XSalsa20Poly1305};
+use serde::Deserialize;
+use sp_core::sr25519::Pair;
+use sp_core::Pair as PairT;
+use std::path::Path;
+
+/// Length of the scrypt salt and parameters prepended to the encrypted key.
+const SCRYPT_LENGTH: usize = 32 + 3 * 4;
+const NONCE_LENGTH: usize = 24;
+const PKCS8_HEADER: [u8; 16] = [48, 83, 2, 1, 1, 48, 5, 6, 3, 43, 101, 112, 4, 34, 4, 32];
+const PKCS8_DIVIDER: [u8; 5] = [161, 35, 3, 33, 0];
+const SECRET_KEY_LENGTH: usize = 64;
+const PUBLIC_KEY_LENGTH: usize = 32;
+
+/// Encrypted JSON keystore, as exported by polkadot-js.
+#[derive(Debug, Deserialize)]
+struct KeystoreJson {
+    encoded: String,
+    encoding: KeystoreEncoding,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeystoreEncoding {
+    content: Vec<String>,
+    #[serde(rename = "type")]
+    kind: Vec<String>,
+    version: String,
+}
+
+/// Reads the sr25519 key pair from the encrypted JSON keystore at the given path.
+pub fn read_pair(path: &Path, password: &str) -> Result<Pair, SlashError> {
+    let keystore: KeystoreJson = serde_json::from_slice(&std::fs::read(path)?)?;
+    let KeystoreEncoding {
+        content,
+        kind,
+        version,
+    } = &keystore.encoding;
+    if version != "3"
+        || kind
+            .iter()
+            .map(String::as_str)
+            .ne(["scrypt", "xsalsa20-poly1305"])
+    {
+        return Err(SlashError::InvalidKeystore("unsupported encryption"));
+    }
+    if !content.iter().any(|content| content == "sr25519") {
+        return Err(SlashError::InvalidKeystore("not an sr25519 key"));
+    }
+
+    let encoded = STANDARD
+        .decode(&keystore.encoded)
+        .map_err(|_| SlashError::InvalidKeystore("invalid base64"))?;
+    if encoded.len() < SCRYPT_LENGTH + NONCE_LENGTH {
+        return Err(SlashError::InvalidKeystore("encoded key too short"));
+    }
+    let (scrypt_params, encrypted) = encoded.split_at(SCRYPT_LENGTH);
+    let key = derive_key(password, scrypt_params)?;
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+    let decrypted = XSalsa20Poly1305::new(&key.into())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| SlashError::InvalidKeystore("wrong password"))?;
+
+    decode_pkcs8(&decrypted)
+}
+
+/// Derives the encryption key from the password with the scrypt salt and parameters.
+fn derive_key(password: &str, scrypt_params: &[u8]) -> Result<[u8; 32], SlashError> {
+    let (salt, params) = scrypt_params.split_at(32);
+    let param = |idx: usize| {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&params[idx * 4..(idx + 1) * 4]);
+        u32::from_le_bytes(bytes)
+    };
+    let (n, p, r) = (param(0), param(1), param(2));
+    if !n.is_power_of_two() {
+        return Err(SlashError::InvalidKeystore("invalid scrypt parameters"));
+    }
+    let params = scrypt::Params::new(n.trailing_zeros() as u8, r, p, 32)
+        .map_err(|_| SlashError::InvalidKeystore("invalid scrypt parameters"))?;
+    let mut key = [0u8; 32];
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut key)
+        .map_err(|_| SlashError::InvalidKeystore("invalid scrypt parameters"))?;
+    Ok(key)
+}
+
+/// Decodes the key pair from the PKCS#8 encoding of polkadot-js, which holds the secret key
+/// in its ed25519 form followed by the public key.
+fn decode_pkcs8(decrypted: &[u8]) -> Result<Pair, SlashError> {
+    let expected_length =
+        PKCS8_HEADER.len() + SECRET_KEY_LENGTH + PKCS8_DIVIDER.len() + PUBLIC_KEY_LENGTH;
+    if decrypted.len() != expected_length || !decrypted.starts_with(&PKCS8_HEADER) {
+        return Err(SlashError::InvalidKeystore("invalid PKCS#8 encoding"));
+    }
+    let (secret_key, rest) = decrypted[PKCS8_HEADER.len()..].split_at(SECRET_KEY_LENGTH);
+    let (divider, public_key) = rest.split_at(PKCS8_DIVIDER.len());
+    if divider != PKCS8_DIVIDER {
+        return Err(SlashError::InvalidKeystore("invalid PKCS#8 encoding"));
+    }
+
+    let secret_key = schnorrkel::SecretKey::from_ed25519_bytes(secret_key)
+        .map_err(|_| SlashError::InvalidKeystore("invalid secret key"))?;
+    let pair = Pair::from(secret_key);
+    if pair.public().as_ref() != public_key {
+        return Err(SlashError::InvalidKeystore(
+            "public key does not match the secret key",
+        ));
+    }
+    Ok(pair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pkcs8(pair: &Pair, secret_key: &[u8]) -> Vec<u8> {
+        let mut encoded = PKCS8_HEADER.to_vec();
+        encoded.extend(secret_key);
+        encoded.extend(PKCS8_DIVIDER);
+        encoded.extend(pair.public().as_ref());
+        encoded
+    }
+
+    #[test]
+    fn pkcs8_key_pair_is_decoded() {
+        let secret_key = schnorrkel::MiniSecretKey::from_bytes(&[1; 32])
+            .unwrap()
+            .expand(schnorrkel::ExpansionMode::Ed25519);
+        let pair = Pair::from(secret_key.clone());
+        let decoded = decode_pkcs8(&pkcs8(&pair, &secret_key.to_ed25519_bytes())).unwrap();
+        assert_eq!(decoded.public(), pair.public());
+
+        let other = Pair::from_seed(&[2; 32]);
+        assert!(decode_pkcs8(&pkcs8(&other, &secret_key.to_ed25519_bytes())).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+use crate::checkpoint::{Submission, SubmissionStatus};
+use crate::error::SlashError;
+use crate::manifest::PayoutManifest;
+use crate::{AccountId, Balance, Number};
+use rusqlite::{params, Connection, OptionalExtension};
+use sp_domains::OperatorId;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// balances are stored as decimal text since they do not fit in an SQLite integer
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    run_id TEXT PRIMARY KEY,
+    command TEXT NOT NULL,
+    started_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS payouts (
+    run_id TEXT NOT NULL,
+    payload_hash TEXT NOT NULL,
+    operator_id INTEGER NOT NULL,
+    slashed_at INTEGER NOT NULL,
+    nominator TEXT NOT NULL,
+    staked TEXT NOT NULL,
+    withdrawable TEXT NOT NULL,
+    storage_fund TEXT NOT NULL,
+    refund TEXT NOT NULL,
+    PRIMARY KEY (run_id, operator_id, slashed_at, nominator)
+);
+CREATE TABLE IF NOT EXISTS submissions (
+    run_id TEXT NOT NULL,
+    operator_id INTEGER NOT NULL,
+    slashed_at INTEGER,
+    extrinsic_hash TEXT NOT NULL,
+    status TEXT NOT NULL,
+    block_hash TEXT,
+    nominators TEXT NOT NULL,
+    updated_at INTEGER NOT NULL,
+    PRIMARY KEY (run_id, extrinsic_hash)
+);
+CREATE TABLE IF NOT EXISTS verifications (
+    run_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    mismatch TEXT,
+    verified_at INTEGER NOT NULL
+);
+";
+
+/// Local SQLite database recording the payouts, submissions and verifications of each run,
+/// so that runs can be audited and compared later without scraping their logs.
+///
+/// Runs are keyed by their run ID, and a run given an existing run ID is recorded along with
+/// the previous one, such as when a submission is resumed.
+pub struct Ledger {
+    run_id: String,
+    connection: Mutex<Connection>,
+}
+
+impl Ledger {
+    /// Opens the ledger at `path`, creating it if needed, and records the start of the run.
+    pub fn open(path: &Path, run_id: &str, command: &str) -> Result<Self, SlashError> {
+        let connection = Connection::open(path)?;
+        migrate(&connection)?;
+        connection.execute_batch(SCHEMA)?;
+        connection.execute(
+            "INSERT OR IGNORE INTO runs (run_id, command, started_at) VALUES (?1, ?2, ?3)",
+            params![run_id, command, now()],
+        )?;
+        Ok(Ledger {
+            run_id: run_id.to_string(),
+            connection: Mutex::new(connection),
+        })
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Records the payout of each nominator in the payout manifest.
+    pub fn record_payouts(&self, payouts: &PayoutManifest) -> Result<(), SlashError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        {
+            let mut statement = transaction.prepare(
+                "INSERT OR REPLACE INTO payouts (run_id, payload_hash, operator_id, slashed_at, nominator, staked, withdrawable, storage_fund, refund) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+            for operator_payout in &payouts.operators {
+                for (nominator_id, nominator_payout) in &operator_payout.nominators {
+                    statement.execute(params![
+                        self.run_id,
+                        format!("{:?}", payouts.payload_hash),
+                        operator_payout.operator_id,
+                        operator_payout.slashed_at,
+                        nominator_id.to_string(),
+                        nominator_payout.staked.to_string(),
+                        nominator_payout.withdrawable.to_string(),
+                        nominator_payout.storage_fund.to_string(),
+                        nominator_payout.refund()?.to_string(),
+                    ])?;
+                }
+            }
+        }
+        Ok(transaction.commit()?)
+    }
+
+    /// Records the submission of the operator's batch, replacing its previous status.
+    pub fn record_submission(
+        &self,
+        operator_id: OperatorId,
+        submission: &Submission,
+    ) -> Result<(), SlashError> {
+        let nominators = submission
+            .nominators
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        self.connection.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO submissions (run_id, operator_id, slashed_at, extrinsic_hash, status, block_hash, nominators, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                self.run_id,
+                operator_id,
+                submission.slashed_at,
+                format!("{:?}", submission.extrinsic_hash),
+                status_name(submission.status),
+                submission.block_hash.map(|block_hash| format!("{block_hash:?}")),
+                nominators,
+                now(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Records the result of a verification of `kind`, with a row per mismatch found or a
+    /// single row without a mismatch if the verification passed.
+    pub fn record_verification<T: fmt::Display>(
+        &self,
+        kind: &str,
+        mismatches: &[T],
+    ) -> Result<(), SlashError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        {
+            let mut statement = transaction.prepare(
+                "INSERT INTO verifications (run_id, kind, mismatch, verified_at) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            let verified_at = now();
+            if mismatches.is_empty() {
+                statement.execute(params![self.run_id, kind, None::<String>, verified_at])?;
+            }
+            for mismatch in mismatches {
+                statement.execute(params![
+                    self.run_id,
+                    kind,
+                    mismatch.to_string(),
+                    verified_at
+                ])?;
+            }
+        }
+        Ok(transaction.commit()?)
+    }
+
+    /// Returns the refund of each nominator of each slashed operator recorded in the run.
+    pub fn payouts(
+        &self,
+        run_id: &str,
+    ) -> Result<BTreeMap<(OperatorId, Number, AccountId), Balance>, SlashError> {
+        let connection = self.connection.lock().unwrap();
+        let found = connection
+            .query_row(
+                "SELECT 1 FROM runs WHERE run_id = ?1",
+                params![run_id],
+                |_| Ok(()),
+            )
+            .optional()?;
+        if found.is_none() {
+            return Err(SlashError::RunNotFound(run_id.to_string()));
+        }
+
+        let mut statement = connection.prepare(
+            "SELECT operator_id, slashed_at, nominator, refund FROM payouts WHERE run_id = ?1",
+        )?;
+        let rows = statement.query_map(params![run_id], |row| {
+            Ok((
+                row.get::<_, OperatorId>(0)?,
+                row.get::<_, Number>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+        let mut payouts = BTreeMap::new();
+        for row in rows {
+            let (operator_id, slashed_at, nominator_id, refund) = row?;
+            let nominator_id = AccountId::from_str(&nominator_id)
+                .map_err(|_| SlashError::InvalidLedger("invalid nominator account"))?;
+            let refund = refund
+                .parse()
+                .map_err(|_| SlashError::InvalidLedger("invalid refund"))?;
+            payouts.insert((operator_id, slashed_at, nominator_id), refund);
+        }
+        Ok(payouts)
+    }
+}
+
+/// Difference in the refund of a nominator between two runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutDiff {
+    pub operator_id: OperatorId,
+    pub slashed_at: Number,
+    pub nominator_id: AccountId,
+    pub from: Option<Balance>,
+    pub to: Option<Balance>,
+}
+
+impl fmt::Display for PayoutDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let display = |refund: Option<Balance>| match refund {
+            None => "none".to_string(),
+            Some(refund) => refund.to_string(),
+        };
+        write!(
+            f,
+            "Operator[{:?}] slashed at #{} Nominator[{}]: {} -> {}",
+            self.operator_id,
+            self.slashed_at,
+            self.nominator_id,
+            display(self.from),
+            display(self.to)
+        )
+    }
+}
+
+/// Compares the refunds of the nominators recorded in two runs.
+pub fn diff_payouts(
+    from: &BTreeMap<(OperatorId, Number, AccountId), Balance>,
+    to: &BTreeMap<(OperatorId, Number, AccountId), Balance>,
+) -> Vec<PayoutDiff> {
+    let keys: BTreeSet<_> = from.keys().chain(to.keys()).collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let (from, to) = (from.get(key).copied(), to.get(key).copied());
+            (from != to).then(|| PayoutDiff {
+                operator_id: key.0,
+                slashed_at: key.1,
+                nominator_id: key.2.clone(),
+                from,
+                to,
+            })
+        })
+        .collect()
+}
+
+/// Migrates a ledger written before the payouts and submissions were keyed by the block the
+/// operator was slashed at, recording the existing payouts as slashed at block 0.
+fn migrate(connection: &Connection) -> Result<(), SlashError> {
+    let has_column = |table: &str, column: &str| -> Result<Option<bool>, SlashError> {
+        let mut statement = connection.prepare(&format!("PRAGMA table_info({table})"))?;
+        let columns = statement
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((!columns.is_empty()).then(|| columns.iter().any(|name| name == column)))
+    };
+    if has_column("payouts", "slashed_at")? == Some(false) {
+        // the primary key changes, so the table is rebuilt
+        connection.execute_batch(
+            "
+ALTER TABLE payouts RENAME TO payouts_v0;
+CREATE TABLE payouts (
+    run_id TEXT NOT NULL,
+    payload_hash TEXT NOT NULL,
+    operator_id INTEGER NOT NULL,
+    slashed_at INTEGER NOT NULL,
+    nominator TEXT NOT NULL,
+    staked TEXT NOT NULL,
+    withdrawable TEXT NOT NULL,
+    storage_fund TEXT NOT NULL,
+    refund TEXT NOT NULL,
+    PRIMARY KEY (run_id, operator_id, slashed_at, nominator)
+);
+INSERT INTO payouts SELECT run_id, payload_hash, operator_id, 0, nominator, staked, withdrawable, storage_fund, refund FROM payouts_v0;
+DROP TABLE payouts_v0;
+",
+        )?;
+    }
+    if has_column("submissions", "slashed_at")? == Some(false) {
+        connection.execute_batch("ALTER TABLE submissions ADD COLUMN slashed_at INTEGER;")?;
+    }
+    Ok(())
+}
+
+fn status_name(status: SubmissionStatus) -> &'static str {
+    match status {
+        SubmissionStatus::Submitted => "submitted",
+        SubmissionStatus::InBlock => "in_block",
+        SubmissionStatus::Approved => "approved",
+        SubmissionStatus::Failed => "failed",
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_payouts_reports_changed_and_missing_refunds() {
+        let nominator = |byte| AccountId::new([byte; 32]);
+        let from = BTreeMap::from([
+            ((1, 10, nominator(1)), 100),
+            ((1, 10, nominator(2)), 200),
+            ((2, 10, nominator(3)), 300),
+            ((2, 20, nominator(3)), 500),
+        ]);
+        let to = BTreeMap::from([
+            ((1, 10, nominator(1)), 100),
+            ((1, 10, nominator(2)), 250),
+            ((2, 10, nominator(4)), 400),
+            ((2, 20, nominator(3)), 500),
+        ]);
+        assert_eq!(
+            diff_payouts(&from, &to),
+            vec![
+                PayoutDiff {
+                    operator_id: 1,
+                    slashed_at: 10,
+                    nominator_id: nominator(2),
+                    from: Some(200),
+                    to: Some(250),
+                },
+                PayoutDiff {
+                    operator_id: 2,
+                    slashed_at: 10,
+                    nominator_id: nominator(3),
+                    from: Some(300),
+                    to: None,
+                },
+                PayoutDiff {
+                    operator_id: 2,
+                    slashed_at: 10,
+                    nominator_id: nominator(4),
+                    from: None,
+                    to: Some(400),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn payouts_of_unknown_run_are_not_found() {
+        let ledger = Ledger::open(Path::new(":memory:"), "1", "calculate").unwrap();
+        assert!(ledger.payouts("1").unwrap().is_empty());
+        assert!(matches!(
+            ledger.payouts("2"),
+            Err(SlashError::RunNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn payouts_of_previous_schema_are_migrated() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "
+CREATE TABLE runs (run_id TEXT PRIMARY KEY, command TEXT NOT NULL, started_at INTEGER NOT NULL);
+CREATE TABLE payouts (run_id TEXT NOT NULL, payload_hash TEXT NOT NULL, operator_id INTEGER NOT NULL, nominator TEXT NOT NULL, staked TEXT NOT NULL, withdrawable TEXT NOT NULL, storage_fund TEXT NOT NULL, refund TEXT NOT NULL, PRIMARY KEY (run_id, operator_id, nominator));
+INSERT INTO payouts VALUES ('1', '0x00', 3, 'nominator', '1', '2', '3', '4');
+",
+            )
+            .unwrap();
+        migrate(&connection).unwrap();
+        connection.execute_batch(SCHEMA).unwrap();
+        let slashed_at: Number = connection
+            .query_row(
+                "SELECT slashed_at FROM payouts WHERE run_id = '1' AND operator_id = 3",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(slashed_at, 0);
+    }
+}
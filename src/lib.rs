@@ -0,0 +1,57 @@
+//! Calculates the balances slashed from the nominators of slashed operators so that they
+//! can be refunded from the treasury.
+
+#![deny(unused_crate_dependencies)]
+
+pub mod artifacts;
+pub mod backend;
+mod calculator;
+pub mod calls;
+pub mod checkpoint;
+pub mod claims;
+pub mod compat;
+pub mod config;
+pub mod crosscheck;
+pub mod error;
+pub mod keystore;
+pub mod ledger;
+pub mod manifest;
+pub mod merkle;
+pub mod network;
+pub mod nonces;
+pub mod notify;
+pub mod offline;
+pub mod operators;
+pub mod outcome;
+pub mod overrides;
+pub mod progress;
+pub mod properties;
+pub mod quarantine;
+pub mod reconcile;
+pub mod redirect;
+pub mod remote_signer;
+pub mod report;
+pub mod review;
+pub mod rpc;
+pub mod scanner;
+pub mod shutdown;
+pub mod snapshot;
+pub mod statistics;
+pub mod types;
+
+pub use crate::calculator::{
+    Audit, Breakdown, Calculation, NominatorBreakdown, OperatorAudit, SlashRefundCalculator,
+};
+use crate::rpc::ReconnectingClient;
+use substrate_api_client::ac_primitives::{AssetRuntimeConfig, Config};
+use substrate_api_client::Api as SApi;
+
+// Dependencies only used by the binary.
+use rpassword as _;
+use tracing_subscriber as _;
+
+pub type Balance = <AssetRuntimeConfig as Config>::Balance;
+pub type Number = <AssetRuntimeConfig as Config>::BlockNumber;
+pub type Hash = <AssetRuntimeConfig as Config>::Hash;
+pub type AccountId = <AssetRuntimeConfig as Config>::AccountId;
+pub type Api = SApi<AssetRuntimeConfig, ReconnectingClient>;
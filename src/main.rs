@@ -1,542 +1,5411 @@
-#![deny(unused_crate_dependencies)]
-
-mod types;
-
-use crate::types::{
-    Deposit, DomainEpoch, NominatorStorage, Operator, OperatorNominators, PendingDeposit,
-    SharePrice, StorageFundRedeemPrice, Withdrawal, WithdrawalInBalance, WithdrawalInShares,
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use codec::{Compact, Decode, Encode};
+use futures::future::{join_all, select, Either};
+use futures::pin_mut;
+use gemini_3h_slash::artifacts::RunArtifacts;
+use gemini_3h_slash::backend::{CachedBackend, ChainBackend, RecordingBackend};
+use gemini_3h_slash::calls::{
+    decode_transfers, BalancesTransfer, MultisigAccount, OpaqueCall, ProxyAccount, Vesting,
+};
+use gemini_3h_slash::checkpoint::{CheckpointFile, Inclusion, Submission, SubmissionStatus};
+use gemini_3h_slash::claims::ClaimsManifest;
+use gemini_3h_slash::compat::{self, DynamicDecoder};
+use gemini_3h_slash::config::{OperatorsConfig, OutputConfig, RefundConfig};
+use gemini_3h_slash::crosscheck::{crosscheck, Indexer};
+use gemini_3h_slash::error::SlashError;
+use gemini_3h_slash::keystore;
+use gemini_3h_slash::ledger::{diff_payouts, Ledger};
+use gemini_3h_slash::manifest::{
+    NominatorPayout, OperatorPayout, PayoutManifest, PendingKind, UnconvertedPending,
+    UnlockableWithdrawal,
+};
+use gemini_3h_slash::merkle::PayoutTree;
+use gemini_3h_slash::network::{check_genesis_hash, Network};
+use gemini_3h_slash::nonces::NonceTracker;
+use gemini_3h_slash::notify::{Milestone, Notifier};
+use gemini_3h_slash::offline::{decode_tip, UnsignedBatch};
+use gemini_3h_slash::outcome::{Outcome, RunResult};
+use gemini_3h_slash::overrides::Overrides;
+use gemini_3h_slash::progress::Progress;
+use gemini_3h_slash::properties::ChainProperties;
+use gemini_3h_slash::quarantine::{Quarantine, Redecoded};
+use gemini_3h_slash::reconcile::{
+    check_slash_inflow, deduct_paid_refunds, reconcile_transfers, slashed_by_block,
+    TransferMismatch,
+};
+use gemini_3h_slash::redirect::Redirections;
+use gemini_3h_slash::remote_signer::RemoteSigner;
+use gemini_3h_slash::report::{DomainSummary, OperatorReport};
+use gemini_3h_slash::review::ManifestSignature;
+use gemini_3h_slash::rpc::{self, ReconnectingClient};
+use gemini_3h_slash::scanner::BlockId;
+use gemini_3h_slash::shutdown;
+use gemini_3h_slash::snapshot::Snapshot;
+use gemini_3h_slash::statistics::PayoutStatistics;
+use gemini_3h_slash::types::{
+    versioned::RuntimeUpgrades, BlockLength, BlockWeights, Multisig, OperatorStatus,
+    RuntimeDispatchInfo, Weight,
+};
+use gemini_3h_slash::{
+    operators, scanner, AccountId, Api, Audit, Balance, Breakdown, Calculation, Hash,
+    NominatorBreakdown, Number, SlashRefundCalculator,
 };
-use clap::Parser;
-use codec::{Decode, Encode};
-use futures::future::join_all;
 use sp_core::crypto::{ExposeSecret, SecretString};
+use sp_core::hashing::blake2_256;
+use sp_core::hexdisplay::HexDisplay;
 use sp_core::sr25519::Pair;
+use sp_core::Bytes;
 use sp_core::Pair as PairT;
-use sp_domains::OperatorId;
+use sp_domains::{DomainId, OperatorId};
+use sp_runtime::generic::Era;
 use sp_runtime::traits::Zero;
-use std::collections::BTreeMap;
-use substrate_api_client::ac_compose_macros::log::{debug, error, info};
-use substrate_api_client::ac_compose_macros::{compose_call, compose_extrinsic_with_nonce};
-use substrate_api_client::ac_primitives::{AssetRuntimeConfig, Config, ExtrinsicSigner};
+use sp_runtime::transaction_validity::{InvalidTransaction, TransactionValidityError};
+use sp_runtime::{ApplyExtrinsicResult, MultiAddress};
+use std::collections::{BTreeMap, BTreeSet};
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use substrate_api_client::ac_compose_macros::{compose_call, compose_extrinsic_offline};
+use substrate_api_client::ac_node_api::Metadata;
+use substrate_api_client::ac_primitives::{
+    AssetRuntimeConfig, AssetTip, ExtrinsicParams, ExtrinsicSigner, GenericAdditionalParams,
+    RpcParams,
+};
 use substrate_api_client::extrinsic::utility::Batch;
-use substrate_api_client::rpc::JsonrpseeClient;
+use substrate_api_client::rpc::{HandleSubscription, Request};
 use substrate_api_client::runtime_api::RuntimeApi;
 use substrate_api_client::{
-    Api as SApi, GetAccountInformation, GetChainInfo, GetStorage, SubmitAndWatch, XtStatus,
+    Api as SApi, GetAccountInformation, GetChainInfo, GetStorage, SubmitAndWatch, SubmitExtrinsic,
+    SubscribeChain, XtStatus,
 };
+use tracing::{debug, error, info, instrument, warn};
+use tracing_subscriber::fmt::writer::{BoxMakeWriter, MakeWriterExt};
+use tracing_subscriber::EnvFilter;
+
+/// Maximum encoded length of the transfer calls in a single batch.
+///
+/// Kept well below the block length limit so that the batch fits in a block along with
+/// the other extrinsics.
+const MAX_BATCH_LENGTH: usize = 512 * 1024;
 
-type Balance = <AssetRuntimeConfig as Config>::Balance;
-type Number = <AssetRuntimeConfig as Config>::BlockNumber;
-type Hash = <AssetRuntimeConfig as Config>::Hash;
-type AccountId = <AssetRuntimeConfig as Config>::AccountId;
-type Api = SApi<AssetRuntimeConfig, JsonrpseeClient>;
+/// Number of blocks after the submission in which a batch extrinsic is looked up by its hash
+/// when its watch is lost to a dropped connection.
+const WATCH_RESUME_BLOCKS: Number = 50;
+
+/// Interval at which the next nonce of the signer is checked for gaps left by dropped
+/// batches.
+const NONCE_GAP_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Interval at which the treasury balance is checked while waiting for it to be refilled.
+const TREASURY_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Maximum number of times the dropped batches are submitted.
+const MAX_SUBMISSION_ROUNDS: usize = 3;
+
+/// Delay before subscribing to the finalized heads again once the subscription ends.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(6);
+
+/// File in the output directory of `watch` recording the last block watched for slashes.
+const LAST_WATCHED_FILE: &str = "last-watched-block";
 
 #[derive(Debug, Parser)]
 pub struct Args {
-    /// Sudo key
+    /// TOML file with the settings of the run, such as the endpoints, the slashed operators,
+    /// the batch size, the output paths and the safety thresholds.
+    ///
+    /// The flags given on the command line take precedence over the settings of the file.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Network the refunds are run on, selecting the default endpoints, genesis hash, SS58
+    /// prefix, token decimals and layout of the `Domains` pallet storage.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    network: Network,
+
+    /// WebSocket RPC endpoint of the node, or a comma separated list of endpoints tried in
+    /// order until one of them connects, the public endpoints of the network by default.
+    ///
+    /// Node must be an archive node since storage is queried at the slashed blocks.
+    #[arg(long, global = true, value_delimiter = ',')]
+    rpc_url: Vec<String>,
+
+    /// Directory of the on-disk cache of the chain state read for the calculation.
+    ///
+    /// The state is read at fixed historical blocks, so it is reused by later runs, such as
+    /// `verify` after `calculate`, instead of being fetched from the node again.
+    #[arg(long, global = true)]
+    cache: Option<PathBuf>,
+
+    /// Maximum number of RPC requests per second sent to the node, such as to stay within
+    /// the abuse protection of public endpoints.
+    #[arg(long, global = true)]
+    max_rps: Option<NonZeroU32>,
+
+    /// Seconds after which an RPC request, including a storage read, a runtime call or the
+    /// watch of a submitted extrinsic, times out.
+    ///
+    /// Timed out requests are retried on a new connection, and the extrinsics whose watch
+    /// timed out are looked up by their hash.
+    #[arg(long, global = true)]
+    rpc_timeout: Option<u64>,
+
+    /// Number of connections to the node the reads are spread across, 1 by default, since a
+    /// single connection limits their throughput once the concurrency is raised.
+    ///
+    /// The connections are opened to the endpoints of `--rpc-url` in turn, starting at the
+    /// one that connected first. Submissions and subscriptions use the first connection.
+    #[arg(long, global = true)]
+    rpc_connections: Option<NonZeroUsize>,
+
+    /// Number of replays of the requests that dropped the connection or timed out allowed for
+    /// the whole run, on top of the attempts of each request, unlimited by default.
+    #[arg(long, global = true)]
+    retry_budget: Option<usize>,
+
+    /// Number of requests failing in a row, by dropping the connection or timing out, after
+    /// which no more requests are sent to the node and the run is stopped.
+    ///
+    /// `submit` stops issuing new batches like on Ctrl-C, so that it can be resumed once the
+    /// node is back instead of hammering it.
+    #[arg(long, global = true)]
+    circuit_breaker: Option<NonZeroUsize>,
+
+    /// Block number or `0x` prefixed hash of the block to read the chain state at, such as
+    /// to repeat a calculation at another point of the chain.
+    ///
+    /// The refunds are calculated from the state of this block instead of the block before
+    /// each slash, the slashed operators are scanned up to this block and the operators are
+    /// listed and inspected at this block. Submissions always read the current state.
+    #[arg(long, global = true)]
+    at_block: Option<BlockId>,
+
+    /// Genesis hash of the network the refunds are for, the one of `--network` by default.
+    ///
+    /// `submit` and `submit-signed` refuse to run against a node of another network, so that
+    /// the refunds are never replayed on a network where the same sudo key works.
+    #[arg(long, global = true)]
+    genesis_hash: Option<Hash>,
+
+    /// ID of the domain to restrict the run to, such as when the refunds are approved domain
+    /// by domain.
+    ///
+    /// Operators of the other domains are skipped by the calculation, the reports and
+    /// `list-slashed`, and `submit` refuses a manifest with payouts of other domains.
+    #[arg(long, global = true)]
+    domain_id: Option<u32>,
+
+    /// Format of the logs written to stderr.
+    ///
+    /// The log level is set with `RUST_LOG` and defaults to `info`.
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    log_format: LogFormat,
+
+    /// Path to the SQLite ledger the payouts, submissions and verifications of the run are
+    /// recorded in.
+    #[arg(long, global = true)]
+    ledger: Option<PathBuf>,
+
+    /// ID the run is recorded under in the ledger, defaulting to the Unix time it started
+    /// at.
+    ///
+    /// Reuse the ID of a previous run to record a resumed submission along with it.
+    #[arg(long, global = true)]
+    run_id: Option<String>,
+
+    /// Directory to write the artifacts of the run to, in a directory named after the run ID.
+    ///
+    /// The payout manifest is written to `manifest.json`, the payouts to `payouts.csv`, the
+    /// receipt of every included batch to `receipts/`, the logs of every command to `logs/`
+    /// and the chain state calculated from to `snapshot/`, unless their paths are given.
+    #[arg(long, global = true)]
+    output_dir: Option<PathBuf>,
+
+    /// Path to write the summary of the run to as JSON once it is over, along with its
+    /// outcome and exit code, `result.json` in the directory of `--output-dir` by default.
+    #[arg(long, global = true)]
+    result: Option<PathBuf>,
+
+    /// URL of a webhook, such as a Slack incoming webhook, to post the milestones of the run
+    /// to.
+    ///
+    /// Posts when the calculation completes with its totals, when each batch is included or
+    /// fails, and when the verification completes. Failed posts are only logged.
+    #[arg(long, global = true)]
+    notify_url: Option<String>,
+
+    /// Path of the file to append the storage entries of the nominators that fail to be
+    /// decoded to, as JSON lines with their raw key and value.
+    ///
+    /// The operators of the quarantined entries fail while the others are calculated. The
+    /// entries can be decoded again with `decode-quarantine`.
+    #[arg(long, global = true)]
+    quarantine: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Calculate the slashed balances of each nominator and write them to the payout manifest.
+    ///
+    /// No extrinsics are submitted.
+    Calculate {
+        #[command(flatten)]
+        operators: SlashedOperatorsArgs,
+
+        /// Calculate from the slashed operators and the chain state in the snapshot written by
+        /// `snapshot export` instead of the node.
+        #[arg(
+            long,
+            conflicts_with_all = ["operators_file", "operators", "operator_ids", "scan_from", "record_state"]
+        )]
+        snapshot: Option<PathBuf>,
+
+        /// Path to write the payout manifest to.
+        #[arg(long, default_value = "payouts.json")]
+        manifest: PathBuf,
+
+        /// Path to additionally write the slashed balance of each nominator as CSV to.
+        #[arg(long)]
+        csv: Option<PathBuf>,
+
+        /// File with the accounts that must not receive refunds, one account per line.
+        ///
+        /// Their payouts are recorded separately in the manifest and are not transferred. Empty
+        /// lines and lines starting with `#` are ignored.
+        #[arg(long)]
+        exclude_file: Option<PathBuf>,
+
+        /// Percentage of the slashed balances to refund, such as when governance decided to
+        /// only partially reimburse the nominators.
+        #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u8).range(0..=100))]
+        refund_percent: u8,
+
+        /// Where the dust left over after rounding down the payouts of each operator goes.
+        #[arg(long, value_enum, default_value_t = DustAssignment::Treasury)]
+        assign_dust: DustAssignment,
+
+        /// TOML file overriding the refunds of specific nominators, such as disputed ones,
+        /// with an `[[override]]` table of `operator_id`, `account`, `amount` and an optional
+        /// `reason` per nominator.
+        ///
+        /// The overrides are applied after the refund percentage, and the overridden refunds
+        /// are marked as such in the manifest and the CSV.
+        #[arg(long)]
+        overrides: Option<PathBuf>,
+
+        /// What to do with the refunds below the existential deposit, including the refunds
+        /// of nothing, which would be burned if the account does not exist.
+        #[arg(long, value_enum, default_value_t = BelowExistentialDeposit::Flag)]
+        below_existential_deposit: BelowExistentialDeposit,
+
+        /// Path to write the refunds below the existential deposit to as CSV, before they are
+        /// skipped or rounded up.
+        #[arg(long)]
+        dust_report: Option<PathBuf>,
+
+        /// Refund the pending deposits whose epoch share price is not found as their raw
+        /// amount, and the pending withdrawals at the share price of the operator, instead of
+        /// leaving them out.
+        #[arg(long, default_value_t = false)]
+        fallback_raw_amount: bool,
+
+        /// Leave the withdrawals that were unlockable before the slash out of the refunds,
+        /// since the nominators could have claimed them.
+        #[arg(long, default_value_t = false)]
+        exclude_unlockable_withdrawals: bool,
+
+        /// Path to write the chain state read for the calculation to as a SCALE encoded state
+        /// dump, such as to capture the fixtures of the regression tests.
+        #[arg(long)]
+        record_state: Option<PathBuf>,
+    },
+    /// Sign the reviewed payout manifest with the key of the reviewer, without which `submit`
+    /// refuses to transfer its payouts.
+    SignManifest {
+        /// Path to the reviewed payout manifest.
+        #[arg(long, default_value = "payouts.json")]
+        manifest: PathBuf,
+
+        /// Key of the reviewer, prompted for without echoing it if not given.
+        ///
+        /// Example: "//Alice".
+        #[arg(long, env = "REVIEWER_SURI", hide_env_values = true)]
+        reviewer_suri: Option<SecretString>,
+
+        /// Path to write the signature to, `<manifest>.sig` by default.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Transfer the slashed balances in the reviewed payout manifest from the treasury.
+    Submit {
+        /// Sudo key, or the key of one of the signatories if `--multisig-signatories` is given.
+        ///
+        /// If `--proxy-for` is given, the key of the proxy of that account instead.
+        ///
+        /// Example: "//Alice".
+        ///
+        /// Prefer `KEYSTORE_SURI`, `--keystore-suri-stdin` or the prompt shown when no key is
+        /// given, so that the secret does not appear in the process arguments.
+        #[arg(
+            long,
+            env = "KEYSTORE_SURI",
+            hide_env_values = true,
+            conflicts_with_all = ["keystore_suri_stdin", "keystore_path", "export_unsigned"]
+        )]
+        keystore_suri: Option<SecretString>,
+
+        /// Read the keystore SURI from the first line of stdin.
+        #[arg(
+            long,
+            default_value_t = false,
+            conflicts_with_all = ["keystore_path", "export_unsigned"]
+        )]
+        keystore_suri_stdin: bool,
+
+        /// Encrypted JSON keystore of the signing key, as exported by polkadot-js, instead of
+        /// `--keystore-suri`.
+        ///
+        /// The password is prompted for without echoing it.
+        #[arg(long, conflicts_with = "export_unsigned")]
+        keystore_path: Option<PathBuf>,
+
+        /// Path to the reviewed payout manifest.
+        #[arg(long, default_value = "payouts.json")]
+        manifest: PathBuf,
+
+        /// CSV of the payouts to submit instead of the payout manifest, in the format written
+        /// by `calculate --csv`, such as after the refunds were adjusted by hand.
+        ///
+        /// Only the `operator_id`, `slashed_at`, `nominator` and `refund` columns are read, and
+        /// the reviewer signs the CSV instead of the manifest.
+        #[arg(long, conflicts_with = "manifest")]
+        from_csv: Option<PathBuf>,
+
+        /// Account of the reviewer, whose signature of the payout manifest is required before
+        /// anything is submitted.
+        #[arg(
+            long,
+            env = "MANIFEST_REVIEWER",
+            required_unless_present = "print_calls"
+        )]
+        reviewer: Option<AccountId>,
+
+        /// Path to the reviewer's signature of the payout manifest, `<manifest>.sig` by
+        /// default.
+        #[arg(long)]
+        manifest_signature: Option<PathBuf>,
+
+        /// TOML file of the nominators whose refunds are paid to another account, each
+        /// authorized by a signature of the nominator or a governance note.
+        ///
+        /// The redirections are verified before anything is submitted.
+        #[arg(long)]
+        redirections: Option<PathBuf>,
+
+        /// Path to the checkpoint file recording the submission of each operator's batch.
+        #[arg(long, default_value = "checkpoint.json")]
+        checkpoint: PathBuf,
+
+        /// Resume the previous run from the checkpoint, skipping the operators whose batch
+        /// was already submitted.
+        #[arg(long, default_value_t = false)]
+        resume: bool,
+
+        /// Resume the previous run from the checkpoint, submitting only the operators with a
+        /// failed batch and leaving the operators that were not submitted yet untouched.
+        #[arg(long, conflicts_with = "resume")]
+        retry_failed: bool,
+
+        /// Scan the blocks from this block number up to the best block for transfers from the
+        /// treasury and skip the nominators that already received their slashed balance.
+        #[arg(long)]
+        refunds_scan_from: Option<Number>,
+
+        /// Scan the blocks from the earliest slash of the payouts, or from
+        /// `--refunds-scan-from`, up to the best block for transfers from the treasury and
+        /// deduct the balance already transferred to each nominator from its refunds.
+        ///
+        /// Covers the refunds paid by hand or by a run whose checkpoint is lost: a nominator
+        /// paid in full is skipped and a nominator paid in part is refunded the rest. The
+        /// transfers of the batches recorded in the checkpoint are not deducted again.
+        #[arg(long)]
+        deduct_paid: bool,
+
+        /// Maximum number of transfers in a single `batch_all` extrinsic.
+        ///
+        /// Transfers of an operator are split into multiple batches if there are more
+        /// nominators or if the encoded batch would exceed the length limit.
+        #[arg(long, default_value = "256")]
+        max_batch_size: NonZeroUsize,
+
+        /// Signatories of the multisig account holding the sudo key, including the signer.
+        ///
+        /// Each batch is wrapped in `Multisig::as_multi` and approved by the signer. The batch
+        /// is executed by the approval that reaches `--multisig-threshold`, so the same command
+        /// is run by each signatory in turn.
+        #[arg(long, value_delimiter = ',', requires = "multisig_threshold")]
+        multisig_signatories: Vec<AccountId>,
+
+        /// Number of approvals required by the multisig account.
+        #[arg(long, requires = "multisig_signatories")]
+        multisig_threshold: Option<u16>,
+
+        /// Sign as a proxy of this account, which is either the sudo account or one of the
+        /// multisig signatories.
+        ///
+        /// Each call is wrapped in `Proxy::proxy` so that the key of this account can be
+        /// kept cold.
+        #[arg(long)]
+        proxy_for: Option<AccountId>,
+
+        /// Proxy type of the proxy, as named in the runtime's `ProxyType`, such as `Any`.
+        #[arg(long, requires = "proxy_for")]
+        proxy_type: Option<String>,
+
+        /// Key of another proxy of the `--proxy-for` account to distribute the batches to, so
+        /// that they are not submitted behind the nonces of a single account. Pass once per key.
+        ///
+        /// Each key must hold enough balance to pay the fees of its batches.
+        #[arg(
+            long = "extra-keystore-suri",
+            requires = "proxy_for",
+            conflicts_with_all = ["multisig_threshold", "signer"]
+        )]
+        extra_keystore_suris: Vec<SecretString>,
+
+        /// Export each batch unsigned to this directory instead of submitting it, so that it
+        /// can be signed on an offline machine and submitted with `submit-signed`.
+        #[arg(long, requires = "signer")]
+        export_unsigned: Option<PathBuf>,
+
+        /// Account that will sign the exported batches, or that the remote signer signs with.
+        #[arg(long)]
+        signer: Option<AccountId>,
+
+        /// WebSocket URL of a remote signing service holding the key of `--signer`, such as
+        /// one backed by an HSM or Vault, to sign the batches instead of a local key.
+        ///
+        /// The service is called with the JSON-RPC method `signer_signPayload(account,
+        /// payload)` and returns the hex encoded sr25519 signature of the payload.
+        #[arg(
+            long,
+            requires = "signer",
+            conflicts_with_all = [
+                "keystore_suri",
+                "keystore_suri_stdin",
+                "keystore_path",
+                "export_unsigned",
+            ]
+        )]
+        remote_signer_url: Option<String>,
+
+        /// Print the hex encoded `Sudo::sudo(Utility::batch_all(..))` call of each batch,
+        /// which can be decoded in polkadot-js apps, instead of submitting anything.
+        #[arg(
+            long,
+            conflicts_with_all = [
+                "keystore_suri",
+                "keystore_suri_stdin",
+                "keystore_path",
+                "export_unsigned",
+                "resume",
+                "retry_failed",
+                "multisig_threshold",
+                "proxy_for",
+            ]
+        )]
+        print_calls: bool,
+
+        /// Print the batches as calls for the governance path instead of `Sudo::sudo`.
+        #[arg(long, value_enum, requires = "print_calls")]
+        proposal: Option<ProposalKind>,
+
+        /// Number of council members required to approve the `Council::propose` proposals.
+        #[arg(long, required_if_eq("proposal", "council"))]
+        council_threshold: Option<u32>,
+
+        /// Call transferring the refunds from the treasury.
+        ///
+        /// With `--vesting-period`, only the remainder of the refunds transferred at once is
+        /// affected, and `treasury-funds` transfers it with keep-alive semantics.
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = TransferKind::TreasuryFunds,
+            conflicts_with = "proposal"
+        )]
+        transfer: TransferKind,
+
+        /// Release each refund gradually from the treasury with `Vesting::vested_transfer` in
+        /// periods of this many blocks instead of transferring it at once.
+        #[arg(
+            long,
+            requires_all = ["per_block", "vesting_start"],
+            conflicts_with = "proposal"
+        )]
+        vesting_period: Option<Number>,
+
+        /// Balance released per block by the vesting schedule.
+        #[arg(long, requires = "vesting_period")]
+        per_block: Option<Balance>,
+
+        /// Block number at which the vesting schedule starts.
+        #[arg(long, requires = "vesting_period")]
+        vesting_start: Option<Number>,
+
+        /// Consolidate the payouts of each nominator across all the operators into a single
+        /// transfer, batched along with the first operator it nominated.
+        ///
+        /// A resumed run must use the same setting as the previous run.
+        #[arg(long, default_value_t = false)]
+        consolidate: bool,
+
+        /// Maximum total balance to transfer from the treasury.
+        ///
+        /// If the total of the payout manifest exceeds it, the total must be typed in to
+        /// confirm the submission, or `--force` passed.
+        #[arg(long)]
+        max_total: Option<Balance>,
+
+        /// Submit even if the total exceeds `--max-total` without asking for confirmation.
+        #[arg(long, default_value_t = false)]
+        force: bool,
+
+        /// Print the nominator count, total and largest refund of each operator and ask for
+        /// approval before its batches are submitted.
+        ///
+        /// Declined operators are skipped and left pending in the checkpoint for a later run
+        /// with `--resume`.
+        #[arg(long, default_value_t = false, conflicts_with = "print_calls")]
+        interactive: bool,
+
+        /// Sign mortal extrinsics valid for this many blocks from the finalized block, rounded
+        /// up to a power of two between 4 and 65536, instead of immortal ones.
+        ///
+        /// Batches of an aborted run can then no longer be included once the period is over.
+        #[arg(long, conflicts_with = "print_calls")]
+        era_period: Option<u64>,
+
+        /// Tip added to the fee of each extrinsic to prioritize its inclusion.
+        #[arg(long, default_value_t = 0, conflicts_with = "print_calls")]
+        tip: Balance,
+
+        /// Block status a batch must reach before it is recorded as included.
+        #[arg(long, value_enum, default_value_t = WaitFor::InBlock)]
+        wait_for: WaitFor,
+
+        /// Submit one batch at a time, waiting for it to be finalized and its events to be
+        /// checked before submitting the next, instead of submitting all the batches at once.
+        ///
+        /// The submission stops at the first batch that fails.
+        #[arg(long, conflicts_with_all = ["wait_for", "extra_keystore_suris"])]
+        sequential: bool,
+
+        /// Seconds to wait for the treasury to be refilled when its balance is not sufficient
+        /// for the next batch, such as after other outflows during a long run.
+        ///
+        /// The batch is failed without being submitted once the wait is over, to be submitted
+        /// again with `--resume`.
+        #[arg(long, default_value_t = 0)]
+        treasury_wait: u64,
+
+        /// Skip the dry run of the batches with `system_dryRun` before they are submitted, such
+        /// as on nodes not exposing the unsafe RPC methods.
+        #[arg(long)]
+        skip_dry_run: bool,
+    },
+    /// Submit the batches exported with `--export-unsigned` once they are signed.
+    SubmitSigned {
+        /// Directory with the signed batches.
+        #[arg(long)]
+        signed: PathBuf,
+
+        /// Path to the reviewed payout manifest the batches were exported from.
+        #[arg(long, default_value = "payouts.json")]
+        manifest: PathBuf,
+
+        /// Account of the reviewer, whose signature of the payout manifest is required before
+        /// anything is submitted.
+        #[arg(long, env = "MANIFEST_REVIEWER")]
+        reviewer: AccountId,
+
+        /// Path to the reviewer's signature of the payout manifest, `<manifest>.sig` by
+        /// default.
+        #[arg(long)]
+        manifest_signature: Option<PathBuf>,
+
+        /// Path to the checkpoint file created when the batches were exported.
+        #[arg(long, default_value = "checkpoint.json")]
+        checkpoint: PathBuf,
+
+        /// Redirections the batches were exported with, so that the transfers to the
+        /// redirected accounts are attributed to their nominators.
+        #[arg(long)]
+        redirections: Option<PathBuf>,
+
+        /// Block status a batch must reach before it is recorded as included.
+        #[arg(long, value_enum, default_value_t = WaitFor::InBlock)]
+        wait_for: WaitFor,
+
+        /// Submit one batch at a time, waiting for it to be finalized and its events to be
+        /// checked before submitting the next, instead of submitting all the batches at once.
+        ///
+        /// The submission stops at the first batch that fails.
+        #[arg(long, conflicts_with = "wait_for")]
+        sequential: bool,
+
+        /// Seconds to wait for the treasury to be refilled when its balance is not sufficient
+        /// for the next batch, such as after other outflows during a long run.
+        ///
+        /// The batch is failed without being submitted once the wait is over, to be submitted
+        /// again with `--resume`.
+        #[arg(long, default_value_t = 0)]
+        treasury_wait: u64,
+
+        /// Skip the dry run of the batches with `system_dryRun` before they are submitted, such
+        /// as on nodes not exposing the unsafe RPC methods.
+        #[arg(long)]
+        skip_dry_run: bool,
+    },
+    /// Print how the slashed balance of a nominator is derived for each slashed operator it
+    /// nominated.
+    RefundFor {
+        #[command(flatten)]
+        operators: SlashedOperatorsArgs,
+
+        /// Account of the nominator.
+        #[arg(long)]
+        account: AccountId,
+
+        /// Refund the pending deposits whose epoch share price is not found as their raw
+        /// amount, and the pending withdrawals at the share price of the operator, instead of
+        /// leaving them out.
+        #[arg(long, default_value_t = false)]
+        fallback_raw_amount: bool,
+
+        /// Leave the withdrawals that were unlockable before the slash out of the refunds,
+        /// since the nominators could have claimed them.
+        #[arg(long, default_value_t = false)]
+        exclude_unlockable_withdrawals: bool,
+    },
+    /// Write a report of the refund of the nominators of each slashed operator, to be
+    /// published along with the refund announcement.
+    Report {
+        #[command(flatten)]
+        operators: SlashedOperatorsArgs,
+
+        /// Directory the report of each operator is written to.
+        #[arg(long, default_value = "reports")]
+        output: PathBuf,
+
+        #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+
+        /// TOML file overriding the refunds of specific nominators, as passed to `calculate`.
+        #[arg(long)]
+        overrides: Option<PathBuf>,
+
+        /// Refund the pending deposits whose epoch share price is not found as their raw
+        /// amount, and the pending withdrawals at the share price of the operator, instead of
+        /// leaving them out.
+        #[arg(long, default_value_t = false)]
+        fallback_raw_amount: bool,
+
+        /// Leave the withdrawals that were unlockable before the slash out of the refunds,
+        /// since the nominators could have claimed them.
+        #[arg(long, default_value_t = false)]
+        exclude_unlockable_withdrawals: bool,
+    },
+    /// Generate the inclusion proofs of the payouts in the Merkle tree whose root is published
+    /// on-chain along with the first batch.
+    Proofs {
+        /// Path to the payout manifest.
+        #[arg(long, default_value = "payouts.json")]
+        manifest: PathBuf,
+
+        /// Print the proof of this account only instead of writing the proofs of all the
+        /// accounts.
+        #[arg(long)]
+        account: Option<AccountId>,
+
+        /// Path to write the proofs of all the accounts to.
+        #[arg(long, default_value = "proofs.json")]
+        output: PathBuf,
+    },
+    /// Generate a claims manifest of the payouts along with the proof of each account, to be
+    /// claimed from a claim pallet or an airdrop contract instead of transferred from the
+    /// treasury.
+    Claims {
+        /// Path to the payout manifest.
+        #[arg(long, default_value = "payouts.json")]
+        manifest: PathBuf,
+
+        /// Path to write the claims manifest to.
+        #[arg(long, default_value = "claims.json")]
+        output: PathBuf,
+    },
+    /// Export or inspect the chain state of the slashed operators for offline calculation.
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommand,
+    },
+    /// Recalculate the slashed balances from the chain and reconcile them with the payout
+    /// manifest.
+    ///
+    /// If the checkpoint of the submission is given, the transfers made from the treasury in
+    /// the blocks the batches were included in are reconciled with the manifest as well.
+    Verify {
+        /// Path to the payout manifest.
+        #[arg(long, default_value = "payouts.json")]
+        manifest: PathBuf,
+
+        /// Path to the checkpoint file of the submission.
+        #[arg(long)]
+        checkpoint: Option<PathBuf>,
+
+        /// Redirections the submission was made with, so that the transfers to the redirected
+        /// accounts are attributed to their nominators.
+        #[arg(long, requires = "checkpoint")]
+        redirections: Option<PathBuf>,
+    },
+    /// Cross-check the balances slashed from the operators in the payout manifest against the
+    /// balance the treasury received in the blocks they were slashed at.
+    ///
+    /// Operators slashed in the same block are checked together.
+    CheckSlashes {
+        /// Path to the payout manifest.
+        #[arg(long, default_value = "payouts.json")]
+        manifest: PathBuf,
+
+        /// Number of blocks after the slash to include, for the slashes processed over
+        /// multiple blocks.
+        #[arg(long, default_value_t = 0)]
+        window: Number,
+
+        /// Deviation allowed between the slashed balance and the treasury inflow, in basis
+        /// points of the slashed balance.
+        #[arg(long, default_value_t = 1)]
+        tolerance_bps: u32,
+    },
+    /// List the operators that are slashed or pending slash at the best block, along with
+    /// the block at which they were slashed.
+    ListSlashed {
+        /// Path to additionally write the slashed operators to, in the format of
+        /// `--operators-file`.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Print the state of an operator, such as to answer the staking questions of its
+    /// nominators.
+    InspectOperator {
+        /// ID of the operator.
+        #[arg(long)]
+        id: OperatorId,
+
+        /// Block number to inspect the operator at.
+        ///
+        /// Defaults to the best block.
+        #[arg(long)]
+        at: Option<Number>,
+    },
+    /// Calculate the refunds the nominators of an operator pending slash are expected to get
+    /// once the slash is executed, so that they can be published ahead of it.
     ///
-    /// Example: "//Alice".
-    #[arg(long, required = true)]
-    keystore_suri: SecretString,
+    /// The refunds are calculated from the state before the operator was marked for slash,
+    /// as `calculate` does once it is slashed.
+    PreviewSlash {
+        /// ID of the operator pending slash.
+        #[arg(long)]
+        operator: OperatorId,
+
+        /// Path to write the report of the expected refunds to, printed as Markdown if not
+        /// given.
+        #[arg(long)]
+        output: Option<PathBuf>,
+
+        #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+
+        /// Refund the pending deposits whose epoch share price is not found as their raw
+        /// amount, and the pending withdrawals at the share price of the operator, instead of
+        /// leaving them out.
+        #[arg(long, default_value_t = false)]
+        fallback_raw_amount: bool,
+
+        /// Leave the withdrawals that were unlockable before the slash out of the refunds,
+        /// since the nominators could have claimed them.
+        #[arg(long, default_value_t = false)]
+        exclude_unlockable_withdrawals: bool,
+    },
+    /// Watch the finalized blocks for new slashes and capture the state needed to refund
+    /// them as soon as they happen.
+    ///
+    /// The state before each slash is written as a snapshot along with a payout manifest
+    /// calculated from it, so that the refund can be prepared even once the node has pruned
+    /// that state.
+    Watch {
+        /// Directory to write the snapshot and the payout manifest of each slash block to.
+        #[arg(long, default_value = "slashes")]
+        output: PathBuf,
+
+        /// First block to watch for slashes.
+        ///
+        /// Defaults to the block after the last one watched into `--output`, or the
+        /// finalized block.
+        #[arg(long)]
+        from: Option<Number>,
+    },
+    /// Compare the deposits and withdrawals of the nominators of the slashed operators read
+    /// from the node with the ones of a SubQuery or Subsquid indexer, before any funds move.
+    ///
+    /// Exits with the verification mismatch code if any of them diverge.
+    Crosscheck {
+        #[command(flatten)]
+        operators: SlashedOperatorsArgs,
+
+        /// GraphQL endpoint of the indexer.
+        #[arg(long)]
+        indexer_url: String,
+
+        /// File with the GraphQL query of the nominations of an operator, for indexers whose
+        /// schema differs from the default query.
+        ///
+        /// The query is sent with the `operatorId` and `blockHeight` variables and its
+        /// `nominations` must alias the fields of the indexer to `accountId`, `knownShares`,
+        /// `storageFeeDeposit`, `pendingDeposit`, `withdrawalInShares` and
+        /// `totalWithdrawalAmount`.
+        #[arg(long)]
+        query: Option<PathBuf>,
+    },
+    /// Print the refunds that differ between two runs recorded in the ledger.
+    DiffRuns {
+        /// ID of the earlier run.
+        from: String,
+
+        /// ID of the later run.
+        to: String,
+    },
+    /// Decode the entries of the quarantine file again with each of the type layouts their
+    /// storage had across the runtime upgrades.
+    ///
+    /// No connection to the node is needed.
+    DecodeQuarantine {
+        /// Quarantine file written with `--quarantine`.
+        #[arg(long, default_value = "quarantine.jsonl")]
+        file: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum SnapshotCommand {
+    /// Download the chain state of the slashed operators into a single file, from which
+    /// `calculate --snapshot` calculates the slashed balances without access to the node.
+    Export {
+        #[command(flatten)]
+        operators: SlashedOperatorsArgs,
+
+        /// Path to write the snapshot to.
+        #[arg(long, default_value = "snapshot.scale")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+struct SlashedOperatorsArgs {
+    /// CSV file with the slashed operators.
+    ///
+    /// Each line is of the form `operator_id,block_number` where `block_number` is the
+    /// block at which the operator was slashed. Empty lines and lines starting with `#`
+    /// are ignored.
+    #[arg(long)]
+    operators_file: Option<PathBuf>,
+
+    /// Slashed operator of the form `operator_id:block_number`.
+    ///
+    /// Can be passed multiple times and is combined with the operators from `--operators-file`.
+    #[arg(long = "operator", value_parser = parse_slashed_operator)]
+    operators: Vec<(OperatorId, Number)>,
+
+    /// ID of a slashed operator whose slash block is not known.
+    ///
+    /// The block at which it was slashed is found by bisecting the blocks up to the block of
+    /// `--search-to`, `--at-block` or the best block, for the first one at which the operator
+    /// is slashed. Can be passed multiple times.
+    #[arg(long = "operator-id")]
+    operator_ids: Vec<OperatorId>,
+
+    /// Last block the slashes of `--operator-id` are searched up to, at which the operators
+    /// must still be in `Domains::Operators`.
+    ///
+    /// Operators are removed once their slash is processed, so pass a block before that, or
+    /// find their slashes with `--scan-from` instead. Defaults to the block of `--at-block`,
+    /// or the best block.
+    #[arg(long, requires = "operator_ids")]
+    search_to: Option<Number>,
+
+    /// Scan the blocks starting from this block number for `Domains::OperatorSlashed` events
+    /// and include the slashed operators found.
+    #[arg(long)]
+    scan_from: Option<Number>,
+
+    /// Last block number to scan for slashed operators.
+    ///
+    /// Defaults to the block of `--at-block`, or the best block.
+    #[arg(long, requires = "scan_from")]
+    scan_to: Option<Number>,
+}
+
+impl SlashedOperatorsArgs {
+    /// Returns the slashed operators from all the sources along with the block number at
+    /// which each operator was slashed.
+    ///
+    /// Blocks are scanned up to `at_block` if `--scan-to` is not given.
+    async fn load(
+        self,
+        api: &Api,
+        at_block: Option<Number>,
+        upgrades: RuntimeUpgrades,
+    ) -> Result<Vec<(OperatorId, Number)>, SlashError> {
+        if self.operators_file.is_none()
+            && self.operators.is_empty()
+            && self.operator_ids.is_empty()
+            && self.scan_from.is_none()
+        {
+            return Err(SlashError::NoSlashedOperators);
+        }
+        let mut slashed_operators = match self.operators_file {
+            Some(operators_file) => read_slashed_operators_file(&operators_file)?,
+            None => vec![],
+        };
+        slashed_operators.extend(self.operators);
+        if !self.operator_ids.is_empty() {
+            let at = match self.search_to.or(at_block) {
+                Some(at) => at,
+                None => scanner::best_block_number(api).await?,
+            };
+            let slash_blocks =
+                join_all(self.operator_ids.into_iter().map(|operator_id| async move {
+                    operators::find_slash_block(api, operator_id, at, upgrades)
+                        .await
+                        .map(|slashed_at| (operator_id, slashed_at))
+                        .map_err(|err| SlashError::operator(operator_id, err))
+                }))
+                .await;
+            for slash_block in slash_blocks {
+                let (operator_id, slashed_at) = slash_block?;
+                info!("Found the slash of Operator[{operator_id:?}] at block #{slashed_at}");
+                slashed_operators.push((operator_id, slashed_at));
+            }
+        }
+        if let Some(scan_from) = self.scan_from {
+            let scan_to = match self.scan_to.or(at_block) {
+                Some(scan_to) => scan_to,
+                None => scanner::best_block_number(api).await?,
+            };
+            slashed_operators
+                .extend(scanner::scan_slashed_operators(api, scan_from, scan_to).await?);
+        }
+        slashed_operators.sort();
+        slashed_operators.dedup();
+        warn_pending_slash_mismatch(api, at_block, &slashed_operators, upgrades).await;
+        Ok(slashed_operators)
+    }
+
+    /// Takes the slashed operators from the config file unless any is given on the command
+    /// line.
+    fn apply_config(&mut self, config: OperatorsConfig, matches: &ArgMatches) {
+        if ["operators_file", "operators", "operator_ids", "scan_from"]
+            .into_iter()
+            .any(|id| is_given(matches, id))
+        {
+            return;
+        }
+        self.operators_file = config.file;
+        self.operators = config
+            .slashed
+            .into_iter()
+            .map(|slashed_operator| (slashed_operator.operator_id, slashed_operator.slashed_at))
+            .collect();
+        self.operator_ids = config.ids;
+    }
+}
+
+/// Warns about the slashed operators that are not marked for slash at the block they are
+/// listed as slashed at, and about the operators marked for slash at these blocks, or at the
+/// block or the best block, that are not among them, such as when an operator id is
+/// mistyped.
+async fn warn_pending_slash_mismatch(
+    api: &Api,
+    at_block: Option<Number>,
+    slashed_operators: &[(OperatorId, Number)],
+    upgrades: RuntimeUpgrades,
+) {
+    let mismatch = async {
+        let at = match at_block {
+            Some(at) => at,
+            None => scanner::best_block_number(api).await?,
+        };
+        operators::check_pending_slashes(api, at, slashed_operators, upgrades).await
+    };
+    match mismatch.await {
+        Ok(mismatch) => {
+            for (operator_id, at) in mismatch.not_slashed {
+                warn!(
+                    "Operator[{operator_id:?}] is neither pending slash nor slashed at #{at}, check its id"
+                );
+            }
+            for (domain_id, operator_id, at) in mismatch.not_listed {
+                warn!(
+                    "Operator[{operator_id:?}] of Domain[{domain_id:?}] is pending slash at #{at} but is not among the slashed operators"
+                );
+            }
+        }
+        // the operators are still calculated, the check only guards against a mistyped id
+        Err(err) => warn!(
+            "Failed to cross-reference the slashed operators with Domains::PendingSlashes: {err}"
+        ),
+    }
+}
+
+impl Args {
+    /// Lays out the paths given neither on the command line nor in the config file in the
+    /// directory of the run artifacts.
+    fn apply_artifacts(
+        &mut self,
+        artifacts: &RunArtifacts,
+        matches: &ArgMatches,
+        output: &OutputConfig,
+    ) {
+        let Some((_, matches)) = matches.subcommand() else {
+            return;
+        };
+        let set_manifest = |manifest: &mut PathBuf| {
+            if !is_given(matches, "manifest") && output.manifest.is_none() {
+                *manifest = artifacts.manifest();
+            }
+        };
+        match &mut self.command {
+            Command::Calculate {
+                snapshot,
+                manifest,
+                csv,
+                record_state,
+                ..
+            } => {
+                set_manifest(manifest);
+                csv.get_or_insert_with(|| artifacts.payouts_csv());
+                if snapshot.is_none() {
+                    record_state
+                        .get_or_insert_with(|| artifacts.snapshot_dir().join("state.scale"));
+                }
+            }
+            Command::Snapshot {
+                command: SnapshotCommand::Export { output, .. },
+            } => {
+                if let Some((_, matches)) = matches.subcommand() {
+                    if !is_given(matches, "output") {
+                        *output = artifacts.snapshot_dir().join("snapshot.scale");
+                    }
+                }
+            }
+            Command::Submit { manifest, .. }
+            | Command::SubmitSigned { manifest, .. }
+            | Command::SignManifest { manifest, .. }
+            | Command::Proofs { manifest, .. }
+            | Command::Claims { manifest, .. }
+            | Command::Verify { manifest, .. }
+            | Command::CheckSlashes { manifest, .. } => set_manifest(manifest),
+            _ => {}
+        }
+    }
+
+    /// Applies the defaults of the selected network to the settings given neither on the
+    /// command line nor in the config file.
+    fn apply_network(&mut self) {
+        let preset = self.network.preset();
+        if self.rpc_url.is_empty() {
+            self.rpc_url = preset.endpoints.iter().map(ToString::to_string).collect();
+        }
+    }
+
+    /// Returns the genesis hash the node must have to sign or submit against it.
+    fn expected_genesis_hash(&self) -> Option<Hash> {
+        self.genesis_hash.or_else(|| self.network.genesis_hash())
+    }
+
+    /// Applies the settings of the config file that are not given on the command line.
+    fn apply_config(&mut self, config: RefundConfig, matches: &ArgMatches) {
+        let RefundConfig {
+            network,
+            endpoints,
+            max_rps,
+            rpc_timeout,
+            rpc_connections,
+            retry_budget,
+            circuit_breaker,
+            operators,
+            submit,
+            output,
+        } = config;
+        if let Some(network) = network {
+            if !is_given(matches, "network") {
+                self.network = network;
+            }
+        }
+        if !endpoints.is_empty() && !is_given(matches, "rpc_url") {
+            self.rpc_url = endpoints;
+        }
+        self.max_rps = self.max_rps.or(max_rps);
+        self.rpc_timeout = self.rpc_timeout.or(rpc_timeout);
+        self.rpc_connections = self.rpc_connections.or(rpc_connections);
+        self.retry_budget = self.retry_budget.or(retry_budget);
+        self.circuit_breaker = self.circuit_breaker.or(circuit_breaker);
+        self.cache = self.cache.take().or(output.cache);
+        self.ledger = self.ledger.take().or(output.ledger);
+
+        let Some((_, matches)) = matches.subcommand() else {
+            return;
+        };
+        let set = |path: &mut PathBuf, id: &str, value: &Option<PathBuf>| {
+            if let Some(value) = value {
+                if !is_given(matches, id) {
+                    *path = value.clone();
+                }
+            }
+        };
+        match &mut self.command {
+            Command::Calculate {
+                operators: slashed_operators,
+                snapshot,
+                manifest,
+                csv,
+                ..
+            } => {
+                if snapshot.is_none() {
+                    slashed_operators.apply_config(operators, matches);
+                }
+                set(manifest, "manifest", &output.manifest);
+                if csv.is_none() {
+                    *csv = output.csv;
+                }
+            }
+            Command::Submit {
+                manifest,
+                checkpoint,
+                max_batch_size,
+                sequential,
+                max_total,
+                ..
+            } => {
+                set(manifest, "manifest", &output.manifest);
+                set(checkpoint, "checkpoint", &output.checkpoint);
+                if let Some(batch_size) = submit.max_batch_size {
+                    if !is_given(matches, "max_batch_size") {
+                        *max_batch_size = batch_size;
+                    }
+                }
+                // `--sequential` conflicts with `--wait-for`
+                if let Some(submit_sequentially) = submit.sequential {
+                    if !is_given(matches, "sequential") && !is_given(matches, "wait_for") {
+                        *sequential = submit_sequentially;
+                    }
+                }
+                *max_total = max_total.or(submit.max_total);
+            }
+            Command::SubmitSigned {
+                manifest,
+                checkpoint,
+                sequential,
+                ..
+            } => {
+                set(manifest, "manifest", &output.manifest);
+                set(checkpoint, "checkpoint", &output.checkpoint);
+                if let Some(submit_sequentially) = submit.sequential {
+                    if !is_given(matches, "sequential") && !is_given(matches, "wait_for") {
+                        *sequential = submit_sequentially;
+                    }
+                }
+            }
+            Command::RefundFor {
+                operators: slashed_operators,
+                ..
+            }
+            | Command::Crosscheck {
+                operators: slashed_operators,
+                ..
+            } => slashed_operators.apply_config(operators, matches),
+            Command::Report {
+                operators: slashed_operators,
+                output: reports,
+                ..
+            } => {
+                slashed_operators.apply_config(operators, matches);
+                set(reports, "output", &output.reports);
+            }
+            Command::Snapshot {
+                command:
+                    SnapshotCommand::Export {
+                        operators: slashed_operators,
+                        ..
+                    },
+            } => {
+                if let Some((_, matches)) = matches.subcommand() {
+                    slashed_operators.apply_config(operators, matches);
+                }
+            }
+            Command::SignManifest { manifest, .. }
+            | Command::Proofs { manifest, .. }
+            | Command::Claims { manifest, .. }
+            | Command::Verify { manifest, .. }
+            | Command::CheckSlashes { manifest, .. } => set(manifest, "manifest", &output.manifest),
+            _ => {}
+        }
+    }
+}
+
+/// Returns whether the argument was given on the command line or in its environment
+/// variable, in which case it takes precedence over the config file.
+fn is_given(matches: &ArgMatches, id: &str) -> bool {
+    matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    /// Human readable lines.
+    Text,
+    /// One JSON object per line, along with the fields of the enclosing spans.
+    Json,
+}
+
+/// Governance call the batches are printed as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ProposalKind {
+    /// `Utility::batch_all` of `Treasury::spend_local` to each nominator, to be dispatched by
+    /// the spend origin.
+    TreasurySpend,
+    /// `Council::propose` of the `Utility::batch_all` of the transfers from the treasury.
+    Council,
+    /// `Preimage::note_preimage` of the `Utility::batch_all` of the transfers from the
+    /// treasury, to be referenced by its hash and length in a referendum.
+    Preimage,
+}
+
+/// Format of the operator reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+/// Block status a submitted batch is waited for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum WaitFor {
+    /// Included in a block of the best chain.
+    InBlock,
+    /// Included in a finalized block, which cannot be reverted.
+    Finalized,
+}
+
+/// Where the dust left over after rounding down the payouts of each operator goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DustAssignment {
+    /// Left in the treasury.
+    Treasury,
+    /// Refunded to the nominator of the operator with the largest payout.
+    LargestNominator,
+}
+
+/// Call transferring the refunds from the treasury.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TransferKind {
+    /// `Domains::transfer_treasury_funds` dispatched with `Sudo::sudo`.
+    TreasuryFunds,
+    /// `Balances::transfer_keep_alive` dispatched from the treasury with `Sudo::sudo_as`, which
+    /// fails rather than reaping the treasury.
+    KeepAlive,
+    /// `Balances::transfer_allow_death` dispatched from the treasury with `Sudo::sudo_as`.
+    AllowDeath,
+}
+
+/// What to do with the refunds below the existential deposit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BelowExistentialDeposit {
+    /// Transferred as is, and flagged in the printed payouts.
+    Flag,
+    /// Recorded separately in the manifest and not transferred.
+    Skip,
+    /// Rounded up to the existential deposit by overriding them, unless they were overridden
+    /// by hand. Refunds of nothing are skipped.
+    RoundUp,
+}
+
+fn parse_slashed_operator(s: &str) -> Result<(OperatorId, Number), String> {
+    parse_operator_and_block(s, ':')
+}
+
+fn parse_operator_and_block(s: &str, separator: char) -> Result<(OperatorId, Number), String> {
+    let (operator_id, number) = s
+        .split_once(separator)
+        .ok_or_else(|| format!("expected `operator_id{separator}block_number`, got `{s}`"))?;
+    let operator_id = operator_id
+        .trim()
+        .parse()
+        .map_err(|err| format!("invalid operator id `{operator_id}`: {err}"))?;
+    let number = number
+        .trim()
+        .parse()
+        .map_err(|err| format!("invalid block number `{number}`: {err}"))?;
+    Ok((operator_id, number))
+}
+
+fn read_slashed_operators_file(path: &Path) -> Result<Vec<(OperatorId, Number)>, SlashError> {
+    let content = std::fs::read_to_string(path)?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map(|(idx, line)| {
+            parse_operator_and_block(line, ',').map_err(|reason| SlashError::InvalidOperatorsFile {
+                path: path.to_path_buf(),
+                line: idx + 1,
+                reason,
+            })
+        })
+        .collect()
+}
+
+fn read_exclude_file(path: &Path) -> Result<BTreeSet<AccountId>, SlashError> {
+    let content = std::fs::read_to_string(path)?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map(|(idx, line)| {
+            AccountId::from_str(line.trim()).map_err(|err| SlashError::InvalidExcludeFile {
+                path: path.to_path_buf(),
+                line: idx + 1,
+                reason: format!("invalid account `{}`: {err}", line.trim()),
+            })
+        })
+        .collect()
 }
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
-    let sudoer = Pair::from_string(args.keystore_suri.expose_secret(), None).unwrap();
-    debug!("Sudo public key: {:?}", sudoer.public().to_string());
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let started_at = unix_time();
+    let run_id = args
+        .run_id
+        .clone()
+        .unwrap_or_else(|| started_at.to_string());
+    let command = matches.subcommand_name().unwrap_or("run").to_string();
+    let artifacts = args
+        .output_dir
+        .as_deref()
+        .map(|output_dir| RunArtifacts::new(output_dir, &run_id));
+    let result_path = args
+        .result
+        .clone()
+        .or_else(|| artifacts.as_ref().map(RunArtifacts::result));
+    let log_file = artifacts
+        .as_ref()
+        .map(|artifacts| {
+            artifacts.create()?;
+            artifacts.open_log(&command)
+        })
+        .transpose()
+        .unwrap_or_else(|err| {
+            eprintln!("Error: {err}");
+            std::process::exit(1);
+        });
+    // the logs are written to the log of the run as well
+    let (writer, ansi) = match log_file {
+        Some(log_file) => (
+            BoxMakeWriter::new(std::io::stderr.and(Mutex::new(log_file))),
+            false,
+        ),
+        None => (BoxMakeWriter::new(std::io::stderr), true),
+    };
+    // progress of the long running phases is logged at info level
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
+        .with_ansi(ansi)
+        .with_writer(writer);
+    match args.log_format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+    let result = async move {
+        let config = args.config.as_deref().map(RefundConfig::read).transpose()?;
+        let output = config
+            .as_ref()
+            .map(|config| config.output.clone())
+            .unwrap_or_default();
+        if let Some(config) = config {
+            args.apply_config(config, &matches);
+        }
+        if let Some(artifacts) = &artifacts {
+            args.apply_artifacts(artifacts, &matches, &output);
+        }
+        args.apply_network();
+        run(args, run_id.clone(), artifacts).await
+    };
+    let result = result.await;
+    if let Err(err) = &result {
+        eprintln!("Error: {err}");
+    }
+    if let Some(result_path) = result_path {
+        let run_result = RunResult::new(&run_id, &command, &result, started_at, unix_time());
+        if let Err(err) = run_result.write(&result_path) {
+            eprintln!(
+                "Failed to write the result to {}: {err}",
+                result_path.display()
+            );
+        }
+    }
+    let outcome = Outcome::of(&result);
+    if outcome != Outcome::Success {
+        std::process::exit(outcome.exit_code());
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Options of the connections to the node, shared by every command.
+struct ConnectOptions {
+    rpc_urls: Vec<String>,
+    max_rps: Option<NonZeroU32>,
+    rpc_timeout: Option<u64>,
+    rpc_connections: Option<NonZeroUsize>,
+    retry_budget: Option<usize>,
+    circuit_breaker: Option<NonZeroUsize>,
+    expected_genesis_hash: Option<Hash>,
+}
+
+impl ConnectOptions {
+    fn new(args: &Args) -> Self {
+        ConnectOptions {
+            rpc_urls: args.rpc_url.clone(),
+            max_rps: args.max_rps,
+            rpc_timeout: args.rpc_timeout,
+            rpc_connections: args.rpc_connections,
+            retry_budget: args.retry_budget,
+            circuit_breaker: args.circuit_breaker,
+            expected_genesis_hash: args.expected_genesis_hash(),
+        }
+    }
+
+    /// Connects to the first of the endpoints that accepts the connection.
+    async fn connect(&self) -> Result<Api, SlashError> {
+        let rpc_urls = &self.rpc_urls;
+        if rpc_urls.is_empty() {
+            return Err(SlashError::Connection(
+                "no RPC endpoint given, pass `--rpc-url`".to_string(),
+            ));
+        }
+        let mut errors = vec![];
+        let mut client = None;
+        for (index, rpc_url) in rpc_urls.iter().enumerate() {
+            match ReconnectingClient::new(rpc_url).await {
+                Ok(connected) => {
+                    client = Some((index, connected));
+                    break;
+                }
+                Err(err) => {
+                    warn!("Failed to connect to {rpc_url}: {err:?}");
+                    errors.push(format!("{rpc_url}: {err:?}"));
+                }
+            }
+        }
+        let (connected, mut client) =
+            client.ok_or_else(|| SlashError::Connection(errors.join(", ")))?;
+        // the pool is opened to the endpoints in turn, a failed one leaving the pool smaller
+        let rpc_connections = self.rpc_connections.map_or(1, NonZeroUsize::get);
+        for offset in 1..rpc_connections {
+            let rpc_url = &rpc_urls[(connected + offset) % rpc_urls.len()];
+            if let Err(err) = client.add_connection(rpc_url).await {
+                warn!("Failed to open a pooled connection to {rpc_url}: {err:?}");
+            }
+        }
+        if client.connections() > 1 {
+            info!(
+                "Spreading the reads across {} connections",
+                client.connections()
+            );
+        }
+        if let Some(max_rps) = self.max_rps {
+            client = client.rate_limit(max_rps);
+        }
+        if let Some(rpc_timeout) = self.rpc_timeout {
+            client = client.request_timeout(Duration::from_secs(rpc_timeout));
+        }
+        if let Some(retry_budget) = self.retry_budget {
+            client = client.retry_budget(retry_budget);
+        }
+        if let Some(circuit_breaker) = self.circuit_breaker {
+            client = client.circuit_breaker(circuit_breaker);
+        }
+        Ok(SApi::<AssetRuntimeConfig, _>::new(client).await?)
+    }
+
+    /// Connects like [`Self::connect`] and checks that the node is of the expected network, to
+    /// sign or submit against it.
+    async fn connect_checked(&self) -> Result<Api, SlashError> {
+        let api = self.connect().await?;
+        check_genesis_hash(api.genesis_hash(), self.expected_genesis_hash)?;
+        Ok(api)
+    }
+}
+
+async fn run(
+    args: Args,
+    run_id: String,
+    artifacts: Option<RunArtifacts>,
+) -> Result<(), SlashError> {
+    let domain_id = args.domain_id.map(DomainId::new);
+    let notifier = Notifier::new(args.notify_url.clone(), &run_id);
+    let notifier = &notifier;
+    let quarantine = args
+        .quarantine
+        .as_deref()
+        .map(Quarantine::open)
+        .transpose()?;
+    let quarantine = quarantine.as_ref();
+    let open_ledger = |command| {
+        args.ledger
+            .as_deref()
+            .map(|path| Ledger::open(path, &run_id, command))
+            .transpose()
+    };
+    let connection = ConnectOptions::new(&args);
+    let upgrades = args.network.upgrades();
+    match args.command {
+        Command::Calculate {
+            operators,
+            snapshot,
+            manifest,
+            csv,
+            exclude_file,
+            refund_percent,
+            assign_dust,
+            overrides,
+            below_existential_deposit,
+            dust_report,
+            fallback_raw_amount,
+            exclude_unlockable_withdrawals,
+            record_state,
+        } => {
+            let excluded_accounts = match exclude_file {
+                Some(exclude_file) => read_exclude_file(&exclude_file)?,
+                None => BTreeSet::new(),
+            };
+            let (
+                Calculation { payouts, failures },
+                total_operators,
+                treasury_balance,
+                existential_deposit,
+            ) = match snapshot {
+                Some(snapshot) => {
+                    let snapshot = Snapshot::read(&snapshot)?;
+                    let total_operators = snapshot.operators.len();
+                    let calculation = SlashRefundCalculator::new(&snapshot.state)
+                        .runtime_upgrades(upgrades)
+                        .fallback_raw_amount(fallback_raw_amount)
+                        .exclude_unlockable_withdrawals(exclude_unlockable_withdrawals)
+                        .domain_id(domain_id)
+                        .quarantine(quarantine)
+                        .calculate(snapshot.operators)
+                        .await;
+                    (
+                        calculation,
+                        total_operators,
+                        snapshot.treasury_balance,
+                        snapshot.existential_deposit,
+                    )
+                }
+                None => {
+                    let api = connection.connect().await?;
+                    let at_block = resolve_at_block(&api, args.at_block).await?;
+                    let slashed_operators = operators
+                        .load(&api, at_block.map(|(number, _)| number), upgrades)
+                        .await?;
+                    let total_operators = slashed_operators.len();
+                    let calculation = calculate(
+                        &api,
+                        args.cache.as_deref(),
+                        record_state.as_deref(),
+                        fallback_raw_amount,
+                        exclude_unlockable_withdrawals,
+                        at_block.map(|(_, block_hash)| block_hash),
+                        domain_id,
+                        quarantine,
+                        upgrades,
+                        slashed_operators,
+                    )
+                    .await?;
+                    let treasury_balance = get_treasury_balance(&api).await?;
+                    let existential_deposit = get_existential_deposit(&api).await?;
+                    (
+                        calculation,
+                        total_operators,
+                        treasury_balance,
+                        existential_deposit,
+                    )
+                }
+            };
+            let payouts = payouts.exclude(&excluded_accounts)?;
+            let payouts = match assign_dust {
+                DustAssignment::Treasury => payouts,
+                DustAssignment::LargestNominator => payouts.assign_dust_to_largest_nominator()?,
+            };
+            let payouts = payouts.scale(refund_percent)?;
+            let payouts = match overrides {
+                Some(overrides) => payouts.apply_overrides(&Overrides::read(&overrides)?)?,
+                None => payouts,
+            };
+            let below = payouts.refunds_below(existential_deposit)?;
+            if let Some(dust_report) = dust_report {
+                PayoutManifest::new(below.clone())?.write_csv(&dust_report)?;
+                println!(
+                    "Refunds below the existential deposit written to {}",
+                    dust_report.display()
+                );
+            }
+            let below_payouts = |include: fn(&NominatorPayout) -> bool| {
+                below
+                    .iter()
+                    .flat_map(|operator_payout| {
+                        operator_payout
+                            .nominators
+                            .iter()
+                            .filter(move |(_, nominator_payout)| include(nominator_payout))
+                            .map(move |(nominator_id, _)| {
+                                (
+                                    operator_payout.operator_id,
+                                    operator_payout.slashed_at,
+                                    nominator_id.clone(),
+                                )
+                            })
+                    })
+                    .collect::<BTreeSet<_>>()
+            };
+            let payouts = match below_existential_deposit {
+                BelowExistentialDeposit::Flag => payouts,
+                BelowExistentialDeposit::Skip => payouts.skip(&below_payouts(|_| true))?,
+                BelowExistentialDeposit::RoundUp => {
+                    let round_ups: Overrides = below_payouts(|nominator_payout| {
+                        nominator_payout
+                            .refund()
+                            .is_ok_and(|refund| !refund.is_zero())
+                            && nominator_payout.overridden.is_none()
+                    })
+                    .into_iter()
+                    .map(|(operator_id, slashed_at, nominator_id)| {
+                        (
+                            (operator_id, Some(slashed_at), nominator_id),
+                            existential_deposit,
+                        )
+                    })
+                    .collect();
+                    payouts.apply_overrides(&round_ups)?.skip(&below_payouts(
+                        |nominator_payout| {
+                            nominator_payout
+                                .refund()
+                                .is_ok_and(|refund| refund.is_zero())
+                        },
+                    ))?
+                }
+            };
+            print_payouts(&payouts, treasury_balance, existential_deposit)?;
+            payouts.write(&manifest)?;
+            println!("Payout manifest written to {}", manifest.display());
+            let statistics = PayoutStatistics::of_manifest(&payouts);
+            notifier
+                .notify(Milestone::CalculationComplete {
+                    operators: payouts.operators.len(),
+                    nominators: statistics.nominators,
+                    total: statistics.total_refund,
+                    payload_hash: payouts.payload_hash,
+                })
+                .await;
+            if let Some(ledger) = open_ledger("calculate")? {
+                ledger.record_payouts(&payouts)?;
+                println!("Payouts recorded in the ledger as run {}", ledger.run_id());
+            }
+            if let Some(csv) = csv {
+                payouts.write_csv(&csv)?;
+                println!("Payouts CSV written to {}", csv.display());
+            }
+            report_failures(&failures, total_operators)?;
+        }
+        Command::SignManifest {
+            manifest,
+            reviewer_suri,
+            output,
+        } => {
+            let reviewer_suri = match reviewer_suri {
+                Some(reviewer_suri) => reviewer_suri,
+                None => SecretString::new(rpassword::prompt_password("Reviewer SURI: ")?),
+            };
+            let reviewer = Pair::from_string(reviewer_suri.expose_secret(), None)
+                .map_err(SlashError::InvalidSuri)?;
+            // the manifest must be valid before it is signed
+            let payouts = if manifest
+                .extension()
+                .is_some_and(|extension| extension == "csv")
+            {
+                PayoutManifest::read_csv(&manifest)?
+            } else {
+                PayoutManifest::read(&manifest)?
+            };
+            let signature = ManifestSignature::new(&std::fs::read(&manifest)?, &reviewer);
+            let output = output.unwrap_or_else(|| ManifestSignature::path(&manifest));
+            signature.write(&output)?;
+            println!(
+                "Payout manifest with payload hash {:?} signed by {} to {}",
+                payouts.payload_hash,
+                signature.reviewer,
+                output.display()
+            );
+        }
+        Command::Submit {
+            keystore_suri,
+            keystore_suri_stdin,
+            keystore_path,
+            manifest,
+            from_csv,
+            reviewer,
+            manifest_signature,
+            redirections,
+            checkpoint,
+            resume,
+            retry_failed,
+            refunds_scan_from,
+            deduct_paid,
+            max_batch_size,
+            multisig_signatories,
+            multisig_threshold,
+            proxy_for,
+            proxy_type,
+            extra_keystore_suris,
+            export_unsigned,
+            signer,
+            remote_signer_url,
+            print_calls,
+            proposal,
+            council_threshold,
+            transfer,
+            vesting_period,
+            per_block,
+            vesting_start,
+            consolidate,
+            max_total,
+            force,
+            interactive,
+            era_period,
+            tip,
+            wait_for,
+            sequential,
+            treasury_wait,
+            skip_dry_run,
+        } => {
+            let mut api = connection.connect_checked().await?;
+            shutdown::listen();
+            let vesting = match (vesting_period, per_block, vesting_start) {
+                (Some(period), Some(per_block), Some(start)) => {
+                    let treasury_account = get_treasury_account(&api).await?;
+                    Some(
+                        Vesting::new(treasury_account, start, period, per_block)?
+                            .keep_alive(transfer != TransferKind::AllowDeath),
+                    )
+                }
+                _ => None,
+            };
+            let balances_transfer = match transfer {
+                TransferKind::TreasuryFunds => None,
+                TransferKind::KeepAlive | TransferKind::AllowDeath => Some(BalancesTransfer::new(
+                    get_treasury_account(&api).await?,
+                    transfer == TransferKind::KeepAlive,
+                )),
+            };
+            let redirections = redirections
+                .map(|redirections| Redirections::read(&redirections))
+                .transpose()?
+                .unwrap_or_default();
+            if print_calls {
+                let payouts = read_payouts(&manifest, from_csv.as_deref(), domain_id)?;
+                return print_batch_calls(
+                    &api,
+                    &payouts,
+                    max_batch_size,
+                    proposal,
+                    council_threshold,
+                    &trace_remark(&run_id, payouts.payload_hash),
+                    &redirections,
+                    vesting.as_ref(),
+                    balances_transfer.as_ref(),
+                    consolidate,
+                );
+            }
+
+            let keystore_suri = match keystore_suri {
+                Some(keystore_suri) => Some(keystore_suri),
+                None if keystore_suri_stdin => {
+                    let mut keystore_suri = String::new();
+                    std::io::stdin().read_line(&mut keystore_suri)?;
+                    Some(SecretString::new(keystore_suri.trim_end().to_string()))
+                }
+                // the signer of the exported batches signs them offline
+                None if keystore_path.is_none() && signer.is_none() => Some(SecretString::new(
+                    rpassword::prompt_password("Keystore SURI: ")?,
+                )),
+                None => None,
+            };
+            let signer_pair = match (keystore_suri, keystore_path) {
+                (Some(keystore_suri), _) => Some(
+                    Pair::from_string(keystore_suri.expose_secret(), None)
+                        .map_err(SlashError::InvalidSuri)?,
+                ),
+                (None, Some(keystore_path)) => {
+                    let password = rpassword::prompt_password("Keystore password: ")?;
+                    Some(keystore::read_pair(&keystore_path, &password)?)
+                }
+                (None, None) => None,
+            };
+            let signer_account = match (signer_pair, signer) {
+                (Some(signer), _) => {
+                    debug!("Signer public key: {:?}", signer.public().to_string());
+                    let signer_account = AccountId::from(signer.public());
+                    api.set_signer(ExtrinsicSigner::<AssetRuntimeConfig>::new(signer));
+                    signer_account
+                }
+                (None, Some(signer_account)) => signer_account,
+                (None, None) => return Err(SlashError::SignerNotSet),
+            };
+            set_extrinsic_params(&mut api, era_period, tip).await?;
+            // each extra signer submits its batches over its own connection
+            let mut extra_signers = vec![];
+            for extra_keystore_suri in extra_keystore_suris {
+                let extra_signer = Pair::from_string(extra_keystore_suri.expose_secret(), None)
+                    .map_err(SlashError::InvalidSuri)?;
+                let extra_signer_account = AccountId::from(extra_signer.public());
+                let mut extra_api = connection.connect().await?;
+                extra_api.set_signer(ExtrinsicSigner::<AssetRuntimeConfig>::new(extra_signer));
+                set_extrinsic_params(&mut extra_api, era_period, tip).await?;
+                info!("Extra signer: {extra_signer_account}");
+                extra_signers.push((extra_api, extra_signer_account));
+            }
+            let proxy = proxy_for
+                .map(|real| ProxyAccount::new(api.metadata(), real, proxy_type.as_deref()))
+                .transpose()?;
+            // account the calls are dispatched from, which is the proxied account if any
+            let origin_account = proxy
+                .as_ref()
+                .map(|proxy| proxy.real.clone())
+                .unwrap_or_else(|| signer_account.clone());
+            let multisig = match multisig_threshold {
+                None => None,
+                Some(threshold) => {
+                    let multisig = MultisigAccount::new(threshold, multisig_signatories)?;
+                    // fail early if the origin is not one of the signatories
+                    multisig.other_signatories(&origin_account)?;
+                    info!("Multisig account: {}", multisig.account_id());
+                    Some(multisig)
+                }
+            };
+            let sudo_account = multisig
+                .as_ref()
+                .map(MultisigAccount::account_id)
+                .unwrap_or_else(|| origin_account.clone());
+            check_sudo_key(&api, &sudo_account).await?;
+
+            // the reviewer is only optional when printing the calls, and signs the CSV itself
+            // if the payouts are read from one
+            if let Some(reviewer) = &reviewer {
+                let reviewed = from_csv.as_deref().unwrap_or(&manifest);
+                verify_manifest_signature(reviewed, manifest_signature, reviewer)?;
+            }
+            let payouts = read_payouts(&manifest, from_csv.as_deref(), domain_id)?;
+            if let Some(max_total) = max_total {
+                confirm_total(payouts.total, max_total, force)?;
+            }
+            let commitment = PayoutCommitment::new(&payouts)?;
+            // every signatory must compose the same calls
+            if multisig.is_some() && args.run_id.is_none() {
+                return Err(SlashError::InvalidMultisig(
+                    "`--run-id` must be given so that every signatory composes the same calls",
+                ));
+            }
+            let trace = trace_remark(&run_id, payouts.payload_hash);
+            let checkpoint = if resume || retry_failed {
+                let checkpoint_file = CheckpointFile::open(&checkpoint, payouts.payload_hash)?;
+                // nominators would be transferred twice if batched differently
+                if checkpoint_file.consolidated() != consolidate {
+                    return Err(SlashError::InvalidCheckpoint {
+                        path: checkpoint,
+                        reason: "was submitted with a different `--consolidate`",
+                    });
+                }
+                checkpoint_file
+            } else {
+                CheckpointFile::create(&checkpoint, payouts.payload_hash, consolidate)?
+            };
+            let checkpoint = checkpoint
+                .with_ledger(open_ledger("submit")?)
+                .with_receipts(
+                    artifacts.as_ref().map(RunArtifacts::receipts_dir),
+                    args.network,
+                );
+            let operator_payouts = if consolidate {
+                payouts.consolidated_operators()?
+            } else {
+                payouts.operators
+            };
+            let scan_paid_from = refunds_scan_from.or_else(|| {
+                operator_payouts
+                    .iter()
+                    .map(|operator_payout| operator_payout.slashed_at)
+                    .min()
+            });
+            let paid_refunds = match scan_paid_from {
+                Some(scan_from) if deduct_paid => Some(
+                    scan_paid_refunds(
+                        &api,
+                        &checkpoint,
+                        &operator_payouts,
+                        &redirections,
+                        scan_from,
+                    )
+                    .await?,
+                ),
+                _ => None,
+            };
+            let mut pending_payouts = skip_submitted_nominators(&checkpoint, operator_payouts);
+            if retry_failed {
+                pending_payouts.retain(|operator_payout| {
+                    let failed = checkpoint
+                        .submissions(operator_payout.operator_id, operator_payout.slashed_at)
+                        .iter()
+                        .any(|submission| submission.status == SubmissionStatus::Failed);
+                    if !failed {
+                        info!(
+                            "Skipping Operator[{:?}] since none of its batches failed",
+                            operator_payout.operator_id
+                        );
+                    }
+                    failed
+                });
+            }
+            let pending_payouts = match (paid_refunds, refunds_scan_from) {
+                (Some(paid_refunds), _) => {
+                    deduct_paid_refunds(&mut pending_payouts, paid_refunds)?
+                        .iter()
+                        .for_each(|deduction| warn!("{deduction}"));
+                    pending_payouts
+                }
+                (None, Some(scan_from)) => {
+                    skip_refunded_nominators(&api, pending_payouts, scan_from).await?
+                }
+                (None, None) => pending_payouts,
+            };
+            let pending_payouts = if interactive {
+                let mut approved_payouts = vec![];
+                for operator_payout in pending_payouts {
+                    if approve_operator(&operator_payout)? {
+                        approved_payouts.push(operator_payout);
+                    } else {
+                        info!(
+                            "Skipping Operator[{:?}] since it was declined",
+                            operator_payout.operator_id
+                        );
+                    }
+                }
+                approved_payouts
+            } else {
+                pending_payouts
+            };
+            let total_pending =
+                pending_payouts
+                    .iter()
+                    .try_fold(Balance::zero(), |acc, operator_payout| {
+                        acc.checked_add(operator_payout.total()?)
+                            .ok_or(SlashError::Overflow)
+                    })?;
+
+            let treasury_balance = get_treasury_balance(&api).await?;
+            info!("Treasury Balance: {:?}", treasury_balance);
+            info!("Total Slashed: {:?}", payouts.total);
+            info!("Total Pending: {:?}", total_pending);
+            info!("Payload Hash: {:?}", payouts.payload_hash);
+            // the treasury balance is checked again before each batch, as it may be refilled
+            // during the submission, but a reviewed list is expected to be paid in full
+            if from_csv.is_some() && treasury_balance < total_pending {
+                return Err(SlashError::InsufficientTreasuryBalance {
+                    balance: treasury_balance,
+                    required: total_pending,
+                });
+            }
+            if treasury_balance < total_pending {
+                warn!(
+                    "Treasury balance {treasury_balance} not sufficient to transfer the total pending {total_pending}"
+                );
+            }
+            warn_missing_accounts(&api, &pending_payouts, &redirections).await?;
+
+            let mut batches = vec![];
+            for operator_payout in pending_payouts {
+                let operator_id = operator_payout.operator_id;
+                let slashed_at = operator_payout.slashed_at;
+                let operator_batches =
+                    split_into_batches(&api, operator_payout.nominator_totals()?, max_batch_size)
+                        .map_err(|err| SlashError::operator(operator_id, err))?;
+                debug!(
+                    "Split transfers of Operator[{operator_id:?}] into {} batches",
+                    operator_batches.len()
+                );
+                batches.extend(
+                    operator_batches
+                        .into_iter()
+                        .map(|batch| (operator_id, slashed_at, batch)),
+                );
+            }
+
+            // balances of the transfers, to retry them individually if their batch fails
+            let transfers: BTreeMap<_, _> = batches
+                .iter()
+                .flat_map(|(operator_id, slashed_at, batch)| {
+                    batch.iter().map(move |(nominator_id, balance)| {
+                        ((*operator_id, *slashed_at, nominator_id.clone()), *balance)
+                    })
+                })
+                .collect();
+
+            // get the starting nonce of the signer and compose each batch call with the next nonce
+            let mut nonce = api.get_account_nonce(&signer_account).await?;
+            let mut batch_calls = vec![];
+            for (operator_id, slashed_at, batch) in batches {
+                let payout_root = commitment
+                    .as_ref()
+                    .and_then(|commitment| commitment.remark_for(operator_id, slashed_at, &batch));
+                let batch_call = compose_batch_call(
+                    &api,
+                    nonce,
+                    operator_id,
+                    slashed_at,
+                    batch,
+                    payout_root,
+                    &trace,
+                    &redirections,
+                    vesting.as_ref(),
+                    balances_transfer.as_ref(),
+                    &origin_account,
+                    multisig.as_ref(),
+                    proxy.as_ref(),
+                )
+                .await
+                .map_err(|err| SlashError::operator(operator_id, err))?;
+                if let Some(batch_call) = batch_call {
+                    batch_calls.push(batch_call);
+                    nonce += 1;
+                }
+            }
+
+            if let Some(export_dir) = export_unsigned {
+                std::fs::create_dir_all(&export_dir)?;
+                for batch_call in batch_calls {
+                    let unsigned_batch =
+                        export_batch_call(&api, &signer_account, &sudo_account, batch_call);
+                    let path = UnsignedBatch::path(&export_dir, unsigned_batch.nonce);
+                    unsigned_batch.write(&path)?;
+                    println!(
+                        "Unsigned batch for Operator[{:?}] with Nonce[{}] written to {}",
+                        unsigned_batch.operator_id,
+                        unsigned_batch.nonce,
+                        path.display()
+                    );
+                }
+                return Ok(());
+            }
+
+            let signer_batches = match remote_signer_url {
+                Some(remote_signer_url) => {
+                    let remote_signer = RemoteSigner::connect(&remote_signer_url).await?;
+                    let mut batch_extrinsics = vec![];
+                    for batch_call in batch_calls {
+                        let operator_id = batch_call.operator_id;
+                        let batch_extrinsic = sign_batch_call_remotely(
+                            &api,
+                            &remote_signer,
+                            &signer_account,
+                            &sudo_account,
+                            batch_call,
+                        )
+                        .await
+                        .map_err(|err| SlashError::operator(operator_id, err))?;
+                        batch_extrinsics.push(batch_extrinsic);
+                    }
+                    vec![(&api, &signer_account, batch_extrinsics)]
+                }
+                None => {
+                    distribute_batch_calls(&api, &signer_account, &extra_signers, batch_calls)
+                        .await?
+                }
+            };
+            let treasury = TreasuryReserve::new(
+                get_treasury_account(&api).await?,
+                Duration::from_secs(treasury_wait),
+            );
+            for (signer_api, signer_account, batch_extrinsics) in &signer_batches {
+                check_signer(signer_api, signer_account, batch_extrinsics).await?;
+            }
+            if !skip_dry_run {
+                for (signer_api, _, batch_extrinsics) in &signer_batches {
+                    dry_run_batch_extrinsics(signer_api, batch_extrinsics).await?;
+                }
+            }
+            let mut total_batches = signer_batches
+                .iter()
+                .map(|(_, _, batch_extrinsics)| batch_extrinsics.len())
+                .sum::<usize>();
+            let futs: Vec<_> = signer_batches
+                .into_iter()
+                .map(
+                    |(signer_api, signer_account, batch_extrinsics)| async move {
+                        if sequential {
+                            submit_batch_extrinsics_sequentially(
+                                signer_api,
+                                &checkpoint,
+                                signer_account,
+                                batch_extrinsics,
+                                &treasury,
+                                notifier,
+                            )
+                            .await
+                        } else {
+                            submit_batch_extrinsics(
+                                signer_api,
+                                &checkpoint,
+                                signer_account,
+                                batch_extrinsics,
+                                wait_for,
+                                &treasury,
+                                notifier,
+                            )
+                            .await
+                        }
+                    },
+                )
+                .collect();
+            let mut failed_batches = vec![];
+            for result in join_all(futs).await {
+                failed_batches.extend(result?);
+            }
+
+            // a single transfer that cannot be made, such as one below the existential deposit,
+            // reverts the whole `batch_all`, so the transfers of the batches that failed to
+            // dispatch are retried individually to isolate it
+            let mut failures = vec![];
+            let mut fallback_batches = vec![];
+            for (batch_extrinsic, err) in failed_batches {
+                let operator_id = batch_extrinsic.operator_id;
+                let retries_individually = matches!(err, SlashError::BatchFailed { .. })
+                    && api.signer().is_some()
+                    && multisig.is_none();
+                if retries_individually {
+                    warn!(
+                        "Retrying the {} transfers of Operator[{operator_id:?}] individually: {err}",
+                        batch_extrinsic.nominators.len()
+                    );
+                    fallback_batches.push(batch_extrinsic);
+                } else {
+                    failures.push(SlashError::operator(operator_id, err));
+                }
+            }
+            if !fallback_batches.is_empty() {
+                let mut nonce = get_account_next_index(&api, &signer_account).await?;
+                let mut batch_extrinsics = vec![];
+                for batch_extrinsic in fallback_batches {
+                    let operator_id = batch_extrinsic.operator_id;
+                    let slashed_at = batch_extrinsic.slashed_at;
+                    for nominator_id in batch_extrinsic.nominators {
+                        let Some(&balance) =
+                            transfers.get(&(operator_id, slashed_at, nominator_id.clone()))
+                        else {
+                            continue;
+                        };
+                        let transfer = vec![(nominator_id, balance)];
+                        let payout_root = commitment.as_ref().and_then(|commitment| {
+                            commitment.remark_for(operator_id, slashed_at, &transfer)
+                        });
+                        let batch_call = compose_batch_call(
+                            &api,
+                            nonce,
+                            operator_id,
+                            slashed_at,
+                            transfer,
+                            payout_root,
+                            &trace,
+                            &redirections,
+                            vesting.as_ref(),
+                            balances_transfer.as_ref(),
+                            &origin_account,
+                            None,
+                            proxy.as_ref(),
+                        )
+                        .await
+                        .map_err(|err| SlashError::operator(operator_id, err))?;
+                        // calls are always composed without a multisig
+                        if let Some(batch_call) = batch_call {
+                            batch_extrinsics.push(sign_batch_call(&api, batch_call)?);
+                            nonce += 1;
+                        }
+                    }
+                }
+
+                total_batches += batch_extrinsics.len();
+                let failed_transfers = submit_batch_extrinsics(
+                    &api,
+                    &checkpoint,
+                    &signer_account,
+                    batch_extrinsics,
+                    wait_for,
+                    &treasury,
+                    notifier,
+                )
+                .await?;
+                failures.extend(failed_transfers.into_iter().map(|(batch_extrinsic, err)| {
+                    let err = match batch_extrinsic.nominators.as_slice() {
+                        [nominator_id] => SlashError::nominator(nominator_id.clone(), err),
+                        _ => err,
+                    };
+                    SlashError::operator(batch_extrinsic.operator_id, err)
+                }));
+            }
+            check_batch_failures(&checkpoint, failures, total_batches)?;
+        }
+        Command::SubmitSigned {
+            signed,
+            manifest,
+            reviewer,
+            manifest_signature,
+            checkpoint,
+            redirections,
+            wait_for,
+            sequential,
+            treasury_wait,
+            skip_dry_run,
+        } => {
+            verify_manifest_signature(&manifest, manifest_signature, &reviewer)?;
+            let redirections = redirections
+                .map(|redirections| Redirections::read(&redirections))
+                .transpose()?
+                .unwrap_or_default();
+            let api = connection.connect_checked().await?;
+            shutdown::listen();
+            let payouts = PayoutManifest::read(&manifest)?;
+            let checkpoint = CheckpointFile::open(&checkpoint, payouts.payload_hash)?
+                .with_ledger(open_ledger("submit-signed")?)
+                .with_receipts(
+                    artifacts.as_ref().map(RunArtifacts::receipts_dir),
+                    args.network,
+                );
+            let signed_batches = UnsignedBatch::read_dir(&signed)?;
+            let (signer_account, sudo_account) = match signed_batches.first() {
+                None => return Ok(()),
+                Some(signed_batch) => (
+                    signed_batch.signer.clone(),
+                    signed_batch.sudo_account().clone(),
+                ),
+            };
+
+            let mut batch_extrinsics = vec![];
+            let mut transferred = BTreeMap::new();
+            for signed_batch in signed_batches {
+                let operator_id = signed_batch.operator_id;
+                if signed_batch.genesis_hash != api.genesis_hash() {
+                    return Err(SlashError::operator(
+                        operator_id,
+                        SlashError::InvalidSignature("batch is signed for another chain"),
+                    ));
+                }
+                if signed_batch.signer != signer_account {
+                    return Err(SlashError::operator(
+                        operator_id,
+                        SlashError::InvalidSignature("batches are signed by different signers"),
+                    ));
+                }
+                if *signed_batch.sudo_account() != sudo_account {
+                    return Err(SlashError::operator(
+                        operator_id,
+                        SlashError::InvalidSignature(
+                            "batches are dispatched from different sudo accounts",
+                        ),
+                    ));
+                }
+                let encoded = signed_batch
+                    .signed_extrinsic()
+                    .map_err(|err| SlashError::operator(operator_id, err))?;
+                // the signature only covers the call, which must transfer the reviewed refunds
+                check_signed_batch(
+                    api.metadata(),
+                    &signed_batch,
+                    &payouts,
+                    &redirections,
+                    &mut transferred,
+                )
+                .map_err(|err| SlashError::operator(operator_id, err))?;
+                let extrinsic_hash = Hash::from(blake2_256(&encoded));
+                let submitted = checkpoint
+                    .submissions(operator_id, signed_batch.slashed_at)
+                    .into_iter()
+                    .any(|submission| {
+                        submission.extrinsic_hash == extrinsic_hash
+                            && submission.status != SubmissionStatus::Failed
+                    });
+                if submitted {
+                    info!(
+                        "Skipping batch for Operator[{operator_id:?}] with Nonce[{}] since it was already submitted",
+                        signed_batch.nonce
+                    );
+                    continue;
+                }
+                batch_extrinsics.push(BatchExtrinsic {
+                    operator_id,
+                    slashed_at: signed_batch.slashed_at,
+                    nonce: signed_batch.nonce,
+                    nominators: signed_batch.nominators,
+                    executes: signed_batch.executes,
+                    total: signed_batch.total,
+                    call: OpaqueCall(signed_batch.call.0.clone()),
+                    tip: signed_batch
+                        .tip()
+                        .map_err(|err| SlashError::operator(operator_id, err))?,
+                    extrinsic_hash,
+                    encoded,
+                });
+            }
+
+            let treasury = TreasuryReserve::new(
+                get_treasury_account(&api).await?,
+                Duration::from_secs(treasury_wait),
+            );
+            // the batches were signed offline against a state that may have changed since
+            check_sudo_key(&api, &sudo_account).await?;
+            check_signer(&api, &signer_account, &batch_extrinsics).await?;
+            if !skip_dry_run {
+                dry_run_batch_extrinsics(&api, &batch_extrinsics).await?;
+            }
+            let total_batches = batch_extrinsics.len();
+            let failed_batches = if sequential {
+                submit_batch_extrinsics_sequentially(
+                    &api,
+                    &checkpoint,
+                    &signer_account,
+                    batch_extrinsics,
+                    &treasury,
+                    notifier,
+                )
+                .await?
+            } else {
+                submit_batch_extrinsics(
+                    &api,
+                    &checkpoint,
+                    &signer_account,
+                    batch_extrinsics,
+                    wait_for,
+                    &treasury,
+                    notifier,
+                )
+                .await?
+            };
+            let failures = failed_batches
+                .into_iter()
+                .map(|(batch_extrinsic, err)| {
+                    SlashError::operator(batch_extrinsic.operator_id, err)
+                })
+                .collect();
+            check_batch_failures(&checkpoint, failures, total_batches)?;
+        }
+        Command::RefundFor {
+            operators,
+            account,
+            fallback_raw_amount,
+            exclude_unlockable_withdrawals,
+        } => {
+            let api = connection.connect().await?;
+            let at_block = resolve_at_block(&api, args.at_block).await?;
+            let slashed_operators = operators
+                .load(&api, at_block.map(|(number, _)| number), upgrades)
+                .await?;
+            let total_operators = slashed_operators.len();
+            let dynamic_decoder = DynamicDecoder::new(api.metadata());
+            let Breakdown {
+                breakdowns,
+                failures,
+            } = match args.cache.as_deref() {
+                None => {
+                    SlashRefundCalculator::new(&api)
+                        .runtime_upgrades(upgrades)
+                        .fallback_raw_amount(fallback_raw_amount)
+                        .exclude_unlockable_withdrawals(exclude_unlockable_withdrawals)
+                        .at_block(at_block.map(|(_, block_hash)| block_hash))
+                        .domain_id(domain_id)
+                        .quarantine(quarantine)
+                        .dynamic_decoder(Some(&dynamic_decoder))
+                        .breakdown(slashed_operators, &account)
+                        .await
+                }
+                Some(cache) => {
+                    let backend = CachedBackend::open(&api, cache)?;
+                    SlashRefundCalculator::new(&backend)
+                        .runtime_upgrades(upgrades)
+                        .fallback_raw_amount(fallback_raw_amount)
+                        .exclude_unlockable_withdrawals(exclude_unlockable_withdrawals)
+                        .at_block(at_block.map(|(_, block_hash)| block_hash))
+                        .domain_id(domain_id)
+                        .quarantine(quarantine)
+                        .dynamic_decoder(Some(&dynamic_decoder))
+                        .breakdown(slashed_operators, &account)
+                        .await
+                }
+            };
+            print_breakdowns(&account, &breakdowns)?;
+            report_failures(&failures, total_operators)?;
+        }
+        Command::Report {
+            operators,
+            output,
+            format,
+            overrides,
+            fallback_raw_amount,
+            exclude_unlockable_withdrawals,
+        } => {
+            let api = connection.connect().await?;
+            let properties = ChainProperties::fetch(&api, args.network.properties()).await?;
+            let at_block = resolve_at_block(&api, args.at_block).await?;
+            let slashed_operators = operators
+                .load(&api, at_block.map(|(number, _)| number), upgrades)
+                .await?;
+            let total_operators = slashed_operators.len();
+            let overrides = overrides
+                .map(|overrides| Overrides::read(&overrides))
+                .transpose()?
+                .unwrap_or_default();
+            let dynamic_decoder = DynamicDecoder::new(api.metadata());
+            let Audit {
+                mut audits,
+                failures,
+            } = match args.cache.as_deref() {
+                None => {
+                    SlashRefundCalculator::new(&api)
+                        .runtime_upgrades(upgrades)
+                        .fallback_raw_amount(fallback_raw_amount)
+                        .exclude_unlockable_withdrawals(exclude_unlockable_withdrawals)
+                        .at_block(at_block.map(|(_, block_hash)| block_hash))
+                        .domain_id(domain_id)
+                        .quarantine(quarantine)
+                        .dynamic_decoder(Some(&dynamic_decoder))
+                        .audit(slashed_operators)
+                        .await
+                }
+                Some(cache) => {
+                    let backend = CachedBackend::open(&api, cache)?;
+                    SlashRefundCalculator::new(&backend)
+                        .runtime_upgrades(upgrades)
+                        .fallback_raw_amount(fallback_raw_amount)
+                        .exclude_unlockable_withdrawals(exclude_unlockable_withdrawals)
+                        .at_block(at_block.map(|(_, block_hash)| block_hash))
+                        .domain_id(domain_id)
+                        .quarantine(quarantine)
+                        .dynamic_decoder(Some(&dynamic_decoder))
+                        .audit(slashed_operators)
+                        .await
+                }
+            };
+            for audit in &mut audits {
+                for (nominator_id, breakdown) in &mut audit.nominators {
+                    breakdown.payout.overridden =
+                        overrides.get(audit.operator_id, audit.slashed_at, nominator_id);
+                }
+            }
+            std::fs::create_dir_all(&output)?;
+            for audit in &audits {
+                let report = OperatorReport::new(audit, &properties);
+                let (contents, extension) = match format {
+                    ReportFormat::Markdown => (report.markdown(), "md"),
+                    ReportFormat::Html => (report.html(), "html"),
+                };
+                // an operator slashed more than once has a report per slash
+                let path = output.join(format!(
+                    "operator-{}-{}.{extension}",
+                    audit.operator_id, audit.slashed_at
+                ));
+                std::fs::write(&path, contents)?;
+            }
+            let summary = DomainSummary::new(&audits, &properties);
+            let (contents, extension) = match format {
+                ReportFormat::Markdown => (summary.markdown(), "md"),
+                ReportFormat::Html => (summary.html(), "html"),
+            };
+            std::fs::write(output.join(format!("summary.{extension}")), contents)?;
+            println!(
+                "Reports of {} operators written to {}",
+                audits.len(),
+                output.display()
+            );
+            report_failures(&failures, total_operators)?;
+        }
+        Command::Proofs {
+            manifest,
+            account,
+            output,
+        } => {
+            let payouts = PayoutManifest::read(&manifest)?;
+            let tree = PayoutTree::new(payouts.account_totals()?);
+            println!("Payout Root: {:?}", tree.root());
+            match account {
+                Some(account) => {
+                    let proof = tree
+                        .proof(&account)
+                        .ok_or(SlashError::AccountNotFound(account))?;
+                    println!("{}", serde_json::to_string_pretty(&proof)?);
+                }
+                None => {
+                    let proofs = tree.proofs();
+                    std::fs::write(&output, serde_json::to_vec_pretty(&proofs)?)?;
+                    println!(
+                        "Proofs of {} accounts written to {}",
+                        proofs.len(),
+                        output.display()
+                    );
+                }
+            }
+        }
+        Command::Claims { manifest, output } => {
+            let payouts = PayoutManifest::read(&manifest)?;
+            let claims = ClaimsManifest::new(&payouts)?;
+            claims.write(&output)?;
+            println!("Claims Root: {:?}", claims.root);
+            println!("Total Claimable: {}", claims.total);
+            println!(
+                "Claims of {} accounts written to {}",
+                claims.claims.len(),
+                output.display()
+            );
+        }
+        Command::Snapshot {
+            command: SnapshotCommand::Export { operators, output },
+        } => {
+            let api = connection.connect().await?;
+            let at_block = resolve_at_block(&api, args.at_block).await?;
+            let slashed_operators = operators
+                .load(&api, at_block.map(|(number, _)| number), upgrades)
+                .await?;
+            let total_operators = slashed_operators.len();
+            let treasury_balance = get_treasury_balance(&api).await?;
+            let existential_deposit = get_existential_deposit(&api).await?;
+            let (snapshot, failures) = Snapshot::export(
+                &api,
+                slashed_operators,
+                treasury_balance,
+                existential_deposit,
+                upgrades,
+            )
+            .await;
+            snapshot.write(&output)?;
+            println!(
+                "Snapshot of {} operators written to {}",
+                snapshot.operators.len(),
+                output.display()
+            );
+            report_failures(&failures, total_operators)?;
+        }
+        Command::Verify {
+            manifest,
+            checkpoint,
+            redirections,
+        } => {
+            let api = connection.connect().await?;
+            let payouts = PayoutManifest::read(&manifest)?;
+            let slashed_operators: Vec<_> = payouts
+                .operators
+                .iter()
+                .map(|operator_payout| (operator_payout.operator_id, operator_payout.slashed_at))
+                .collect();
+            let total_operators = slashed_operators.len();
+            let at_block = resolve_at_block(&api, args.at_block).await?;
+            let Calculation {
+                payouts: calculated_payouts,
+                failures,
+            } = calculate(
+                &api,
+                args.cache.as_deref(),
+                None,
+                payouts.fallback_raw_amount(),
+                payouts.exclude_unlockable_withdrawals(),
+                at_block.map(|(_, block_hash)| block_hash),
+                domain_id,
+                quarantine,
+                upgrades,
+                slashed_operators,
+            )
+            .await?;
+            let calculated_payouts = calculated_payouts.exclude(&payouts.excluded_accounts())?;
+            let calculated_payouts = if payouts.dust_assigned() {
+                calculated_payouts.assign_dust_to_largest_nominator()?
+            } else {
+                calculated_payouts
+            };
+            let calculated_payouts = calculated_payouts.scale(payouts.refund_percent)?;
+            // nominators missing from the calculated payouts are reported as mismatches
+            let overrides: Overrides = payouts
+                .overrides()
+                .iter()
+                .filter(|((operator_id, slashed_at, nominator_id), _)| {
+                    calculated_payouts.operators.iter().any(|operator_payout| {
+                        operator_payout.operator_id == *operator_id
+                            && slashed_at
+                                .map_or(true, |slashed_at| operator_payout.slashed_at == slashed_at)
+                            && operator_payout.nominators.contains_key(nominator_id)
+                    })
+                })
+                .map(|(key, refund)| (key.clone(), *refund))
+                .collect();
+            let calculated_payouts = calculated_payouts
+                .apply_overrides(&overrides)?
+                .skip(&payouts.skipped_payouts())?;
+            let mut mismatches = payouts.reconcile(&calculated_payouts);
+            // operators missing from the calculated payouts failed and are reported separately
+            mismatches.retain(|mismatch| {
+                calculated_payouts.operators.iter().any(|operator_payout| {
+                    operator_payout.operator_id == mismatch.operator_id
+                        && operator_payout.slashed_at == mismatch.slashed_at
+                })
+            });
+            if mismatches.is_empty() {
+                println!("Payout manifest matches the on-chain state");
+            } else {
+                mismatches
+                    .iter()
+                    .for_each(|mismatch| println!("{mismatch}"));
+            }
+
+            let checkpoint_given = checkpoint.is_some();
+            let transfer_mismatches = match checkpoint {
+                None => vec![],
+                Some(checkpoint) => {
+                    let checkpoint = CheckpointFile::open(&checkpoint, payouts.payload_hash)?;
+                    let redirections = redirections
+                        .map(|redirections| Redirections::read(&redirections))
+                        .transpose()?
+                        .unwrap_or_default();
+                    let transfer_mismatches =
+                        verify_transfers(&api, &payouts, &checkpoint, &redirections).await?;
+                    if transfer_mismatches.is_empty() {
+                        println!("Treasury transfers match the payout manifest");
+                    } else {
+                        transfer_mismatches
+                            .iter()
+                            .for_each(|mismatch| println!("{mismatch}"));
+                    }
+                    transfer_mismatches
+                }
+            };
+            notifier
+                .notify(Milestone::VerificationComplete {
+                    payout_mismatches: mismatches.len(),
+                    transfer_mismatches: transfer_mismatches.len(),
+                })
+                .await;
+
+            if let Some(ledger) = open_ledger("verify")? {
+                ledger.record_payouts(&calculated_payouts)?;
+                ledger.record_verification("payouts", &mismatches)?;
+                if checkpoint_given {
+                    ledger.record_verification("transfers", &transfer_mismatches)?;
+                }
+                println!(
+                    "Verification recorded in the ledger as run {}",
+                    ledger.run_id()
+                );
+            }
+
+            report_failures(&failures, total_operators)?;
+            if !mismatches.is_empty() || !transfer_mismatches.is_empty() {
+                return Err(SlashError::Mismatches {
+                    payouts: mismatches.len(),
+                    transfers: transfer_mismatches.len(),
+                });
+            }
+        }
+        Command::ListSlashed { output } => {
+            let api = connection.connect().await?;
+            let at = match resolve_at_block(&api, args.at_block).await? {
+                Some((number, _)) => number,
+                None => scanner::best_block_number(&api).await?,
+            };
+            let slashed_operators =
+                operators::list_slashed_operators(&api, at, domain_id, upgrades).await?;
+            if slashed_operators.is_empty() {
+                println!("No slashed operators at #{at}");
+            }
+            for slashed_operator in &slashed_operators {
+                let status_changed_at = match slashed_operator.status_changed_at {
+                    Some(number) => format!("#{number}"),
+                    None => "unknown block".to_string(),
+                };
+                println!(
+                    "Operator[{:?}] of Domain[{:?}] {:?} since {status_changed_at}, Stake: {}, Nominators: {}",
+                    slashed_operator.operator_id,
+                    slashed_operator.domain_id,
+                    slashed_operator.status,
+                    slashed_operator.total_stake,
+                    slashed_operator.nominator_count,
+                );
+            }
+            let mut domains = BTreeMap::<DomainId, (usize, Balance)>::new();
+            for slashed_operator in &slashed_operators {
+                let (count, stake) = domains.entry(slashed_operator.domain_id).or_default();
+                *count += 1;
+                *stake = stake.saturating_add(slashed_operator.total_stake);
+            }
+            for (domain_id, (count, stake)) in domains {
+                println!("Domain[{domain_id:?}]: {count} Operators, Stake: {stake}");
+            }
+
+            if let Some(output) = output {
+                // operators whose slash block is unknown cannot be calculated
+                let lines: String = slashed_operators
+                    .iter()
+                    .filter_map(|slashed_operator| {
+                        let number = slashed_operator.status_changed_at?;
+                        Some(format!("{:?},{number}\n", slashed_operator.operator_id))
+                    })
+                    .collect();
+                std::fs::write(&output, format!("# operator_id,block_number\n{lines}"))?;
+                info!("Slashed operators written to {}", output.display());
+            }
+        }
+        Command::InspectOperator { id, at } => {
+            let api = connection.connect().await?;
+            let at = match (at, resolve_at_block(&api, args.at_block).await?) {
+                (Some(at), _) | (None, Some((at, _))) => at,
+                (None, None) => scanner::best_block_number(&api).await?,
+            };
+            let inspection = operators::inspect_operator(&api, id, at, upgrades).await?;
+            println!(
+                "Operator[{id:?}] at #{} ({:?}), spec version {}",
+                inspection.block_number, inspection.block_hash, inspection.spec_version
+            );
+            println!("{:#?}", inspection.operator);
+            println!("Status: {:?}", inspection.status);
+            println!("Nominators: {}", inspection.nominator_count);
+            println!("Storage Fund Balance: {}", inspection.storage_fund_balance);
+            println!("Current Epoch Share Price: {:?}", inspection.share_price);
+        }
+        Command::PreviewSlash {
+            operator,
+            output,
+            format,
+            fallback_raw_amount,
+            exclude_unlockable_withdrawals,
+        } => {
+            let api = connection.connect().await?;
+            let properties = ChainProperties::fetch(&api, args.network.properties()).await?;
+            let at = match resolve_at_block(&api, args.at_block).await? {
+                Some((number, _)) => number,
+                None => scanner::best_block_number(&api).await?,
+            };
+            // once slashed, the refunds are calculated with `calculate` instead
+            let status = operators::inspect_operator(&api, operator, at, upgrades)
+                .await?
+                .status;
+            if status != OperatorStatus::PendingSlash {
+                return Err(SlashError::operator(
+                    operator,
+                    SlashError::NotPendingSlash { status, at },
+                ));
+            }
+            let pending_since = operators::find_slash_block(&api, operator, at, upgrades).await?;
+            info!("Operator[{operator:?}] pending slash since #{pending_since}");
+            let dynamic_decoder = DynamicDecoder::new(api.metadata());
+            let Audit { audits, failures } = match args.cache.as_deref() {
+                None => {
+                    SlashRefundCalculator::new(&api)
+                        .runtime_upgrades(upgrades)
+                        .fallback_raw_amount(fallback_raw_amount)
+                        .exclude_unlockable_withdrawals(exclude_unlockable_withdrawals)
+                        .quarantine(quarantine)
+                        .dynamic_decoder(Some(&dynamic_decoder))
+                        .audit(vec![(operator, pending_since)])
+                        .await
+                }
+                Some(cache) => {
+                    let backend = CachedBackend::open(&api, cache)?;
+                    SlashRefundCalculator::new(&backend)
+                        .runtime_upgrades(upgrades)
+                        .fallback_raw_amount(fallback_raw_amount)
+                        .exclude_unlockable_withdrawals(exclude_unlockable_withdrawals)
+                        .quarantine(quarantine)
+                        .dynamic_decoder(Some(&dynamic_decoder))
+                        .audit(vec![(operator, pending_since)])
+                        .await
+                }
+            };
+            report_failures(&failures, 1)?;
+            for audit in &audits {
+                let report = OperatorReport::preview(audit, &properties);
+                let contents = match format {
+                    ReportFormat::Markdown => report.markdown(),
+                    ReportFormat::Html => report.html(),
+                };
+                match &output {
+                    None => println!("{contents}"),
+                    Some(output) => {
+                        std::fs::write(output, contents)?;
+                        println!(
+                            "Expected refunds of Operator[{operator:?}] written to {}",
+                            output.display()
+                        );
+                    }
+                }
+            }
+        }
+        Command::Watch { output, from } => {
+            let api = connection.connect().await?;
+            std::fs::create_dir_all(&output)?;
+            let last_watched_file = output.join(LAST_WATCHED_FILE);
+            let last_watched = std::fs::read_to_string(&last_watched_file)
+                .ok()
+                .and_then(|number| number.trim().parse::<Number>().ok());
+            let mut next = match (from, last_watched) {
+                (Some(from), _) => from,
+                (None, Some(last_watched)) => last_watched + 1,
+                (None, None) => scanner::finalized_block_number(&api).await?,
+            };
+            info!("Watching the finalized blocks for slashes from #{next}");
+            loop {
+                let mut finalized_heads = api.subscribe_finalized_heads().await?;
+                while let Some(header) = finalized_heads.next().await {
+                    let finalized = match header {
+                        Ok(header) => header.number,
+                        Err(err) => {
+                            warn!("Subscription to the finalized heads failed: {err:?}");
+                            break;
+                        }
+                    };
+                    if finalized < next {
+                        continue;
+                    }
+                    // blocks missed while resubscribing are captured along with the new ones
+                    match capture_slashes(&api, &output, next, finalized, domain_id, upgrades).await {
+                        Ok(()) => {
+                            std::fs::write(&last_watched_file, finalized.to_string())?;
+                            next = finalized + 1;
+                        }
+                        Err(err) => error!(
+                            "Failed to capture the slashes of #{next}..=#{finalized}, retrying at the next finalized block: {err}"
+                        ),
+                    }
+                }
+                warn!("Subscription to the finalized heads ended, subscribing again");
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            }
+        }
+        Command::DiffRuns { from, to } => {
+            let ledger = open_ledger("diff-runs")?.ok_or(SlashError::LedgerNotSet)?;
+            let diffs = diff_payouts(&ledger.payouts(&from)?, &ledger.payouts(&to)?);
+            if diffs.is_empty() {
+                println!("Refunds of runs {from} and {to} match");
+            } else {
+                diffs.iter().for_each(|diff| println!("{diff}"));
+                println!(
+                    "{} refunds differ between runs {from} and {to}",
+                    diffs.len()
+                );
+            }
+        }
+        Command::Crosscheck {
+            operators,
+            indexer_url,
+            query,
+        } => {
+            let api = connection.connect().await?;
+            let at_block = resolve_at_block(&api, args.at_block).await?;
+            let slashed_operators = operators
+                .load(&api, at_block.map(|(number, _)| number), upgrades)
+                .await?;
+            let total_operators = slashed_operators.len();
+            let query = query.map(std::fs::read_to_string).transpose()?;
+            let indexer = Indexer::new(indexer_url, query);
+            let dynamic_decoder = DynamicDecoder::new(api.metadata());
+            let calculator = SlashRefundCalculator::new(&api)
+                .runtime_upgrades(upgrades)
+                .at_block(at_block.map(|(_, block_hash)| block_hash))
+                .quarantine(quarantine)
+                .dynamic_decoder(Some(&dynamic_decoder));
+            let mut divergences = vec![];
+            for (operator_id, slashed_at) in slashed_operators {
+                // the indexer is read at the block the node state is read at
+                let state_at = at_block.map_or(slashed_at - 1, |(number, _)| number);
+                let (node, indexed) = futures::try_join!(
+                    calculator.nominator_storage(operator_id, slashed_at),
+                    indexer.nominations(operator_id, state_at),
+                )
+                .map_err(|err| SlashError::operator(operator_id, err))?;
+                info!(
+                    "Comparing {} nominators of Operator[{operator_id:?}] with {} indexed",
+                    node.len(),
+                    indexed.len()
+                );
+                divergences.extend(crosscheck(operator_id, &node, indexed));
+            }
+            divergences
+                .iter()
+                .for_each(|divergence| println!("{divergence}"));
+            if !divergences.is_empty() {
+                return Err(SlashError::Divergences(divergences.len()));
+            }
+            println!("Nominators of {total_operators} operators match the indexer");
+        }
+        Command::DecodeQuarantine { file } => {
+            let entries = Quarantine::read(&file)?;
+            for entry in &entries {
+                println!(
+                    "{}.{} of Operator[{:?}] at {:?} (spec version {}), failed with: {}",
+                    entry.pallet,
+                    entry.storage,
+                    entry.operator_id,
+                    entry.block_hash,
+                    entry.spec_version,
+                    entry.error
+                );
+                match entry.nominator() {
+                    Ok(nominator_id) => println!("  nominator: {nominator_id}"),
+                    Err(err) => println!("  nominator: failed to decode: {err}"),
+                }
+                for Redecoded { layout, value } in entry.redecode() {
+                    match value {
+                        Ok(value) => println!("  {layout}: {value}"),
+                        Err(err) => println!("  {layout}: failed to decode: {err}"),
+                    }
+                }
+            }
+            println!("Decoded {} quarantined entries", entries.len());
+        }
+        Command::CheckSlashes {
+            manifest,
+            window,
+            tolerance_bps,
+        } => {
+            let api = connection.connect().await?;
+            let payouts = PayoutManifest::read(&manifest)?;
+            let treasury_account = get_treasury_account(&api).await?;
+            let mut mismatches = vec![];
+            for (slashed_at, (operator_ids, expected)) in slashed_by_block(&payouts) {
+                let inflow = scanner::inflow_to(
+                    &api,
+                    &treasury_account,
+                    slashed_at,
+                    slashed_at.saturating_add(window),
+                )
+                .await?;
+                info!(
+                    "Operators{operator_ids:?} slashed at #{slashed_at}: {expected} slashed, {inflow} received by the treasury"
+                );
+                mismatches.extend(check_slash_inflow(
+                    slashed_at,
+                    operator_ids,
+                    expected,
+                    inflow,
+                    tolerance_bps,
+                ));
+            }
+
+            if mismatches.is_empty() {
+                println!("Slashed balances match the treasury inflows");
+            } else {
+                mismatches
+                    .iter()
+                    .for_each(|mismatch| println!("{mismatch}"));
+                return Err(SlashError::SlashInflowMismatches(mismatches.len()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Calculates the slashed balances, reading the chain state through the cache if any and
+/// recording it to `record_state` if given.
+#[allow(clippy::too_many_arguments)]
+async fn calculate(
+    api: &Api,
+    cache: Option<&Path>,
+    record_state: Option<&Path>,
+    fallback_raw_amount: bool,
+    exclude_unlockable_withdrawals: bool,
+    at_block: Option<Hash>,
+    domain_id: Option<DomainId>,
+    quarantine: Option<&Quarantine>,
+    upgrades: RuntimeUpgrades,
+    slashed_operators: Vec<(OperatorId, Number)>,
+) -> Result<Calculation, SlashError> {
+    // types of another layout are decoded with the runtime metadata instead
+    if let Err(err) =
+        compat::check_domains_types(api.metadata(), api.runtime_version().spec_version, upgrades)
+    {
+        warn!("{err}, decoding the storage that fails to be decoded with the runtime metadata");
+    }
+    let dynamic_decoder = DynamicDecoder::new(api.metadata());
+    match cache {
+        None => {
+            calculate_with(
+                api,
+                record_state,
+                fallback_raw_amount,
+                exclude_unlockable_withdrawals,
+                at_block,
+                domain_id,
+                quarantine,
+                Some(&dynamic_decoder),
+                upgrades,
+                slashed_operators,
+            )
+            .await
+        }
+        Some(cache) => {
+            let backend = CachedBackend::open(api, cache)?;
+            calculate_with(
+                &backend,
+                record_state,
+                fallback_raw_amount,
+                exclude_unlockable_withdrawals,
+                at_block,
+                domain_id,
+                quarantine,
+                Some(&dynamic_decoder),
+                upgrades,
+                slashed_operators,
+            )
+            .await
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn calculate_with<B: ChainBackend>(
+    backend: &B,
+    record_state: Option<&Path>,
+    fallback_raw_amount: bool,
+    exclude_unlockable_withdrawals: bool,
+    at_block: Option<Hash>,
+    domain_id: Option<DomainId>,
+    quarantine: Option<&Quarantine>,
+    dynamic_decoder: Option<&DynamicDecoder>,
+    upgrades: RuntimeUpgrades,
+    slashed_operators: Vec<(OperatorId, Number)>,
+) -> Result<Calculation, SlashError> {
+    match record_state {
+        None => Ok(SlashRefundCalculator::new(backend)
+            .runtime_upgrades(upgrades)
+            .fallback_raw_amount(fallback_raw_amount)
+            .exclude_unlockable_withdrawals(exclude_unlockable_withdrawals)
+            .at_block(at_block)
+            .domain_id(domain_id)
+            .quarantine(quarantine)
+            .dynamic_decoder(dynamic_decoder)
+            .calculate(slashed_operators)
+            .await),
+        Some(record_state) => {
+            let backend = RecordingBackend::new(backend);
+            let calculation = SlashRefundCalculator::new(&backend)
+                .runtime_upgrades(upgrades)
+                .fallback_raw_amount(fallback_raw_amount)
+                .exclude_unlockable_withdrawals(exclude_unlockable_withdrawals)
+                .at_block(at_block)
+                .domain_id(domain_id)
+                .quarantine(quarantine)
+                .dynamic_decoder(dynamic_decoder)
+                .calculate(slashed_operators)
+                .await;
+            backend.into_state().write(record_state)?;
+            println!("Chain state written to {}", record_state.display());
+            Ok(calculation)
+        }
+    }
+}
+
+/// Captures the state before the slash of the operators slashed in the blocks `from..=to`,
+/// writing a snapshot and a payout manifest per slash block to `output`.
+///
+/// Operators that failed to be captured are logged and left out of the snapshot.
+async fn capture_slashes(
+    api: &Api,
+    output: &Path,
+    from: Number,
+    to: Number,
+    domain_id: Option<DomainId>,
+    upgrades: RuntimeUpgrades,
+) -> Result<(), SlashError> {
+    let mut slashes = BTreeMap::<Number, Vec<(OperatorId, Number)>>::new();
+    for (operator_id, slashed_at) in scanner::scan_slashed_operators(api, from, to).await? {
+        slashes
+            .entry(slashed_at)
+            .or_default()
+            .push((operator_id, slashed_at));
+    }
+    if slashes.is_empty() {
+        return Ok(());
+    }
+
+    let treasury_balance = get_treasury_balance(api).await?;
+    let existential_deposit = get_existential_deposit(api).await?;
+    for (slashed_at, slashed_operators) in slashes {
+        info!(
+            "Capturing the state of {} operators slashed at #{slashed_at}",
+            slashed_operators.len()
+        );
+        let (snapshot, failures) = Snapshot::export(
+            api,
+            slashed_operators,
+            treasury_balance,
+            existential_deposit,
+            upgrades,
+        )
+        .await;
+        failures.iter().for_each(|err| error!("{err}"));
+        if snapshot.operators.is_empty() {
+            continue;
+        }
+        let snapshot_path = output.join(format!("snapshot-{slashed_at}.scale"));
+        snapshot.write(&snapshot_path)?;
+
+        let Calculation { payouts, failures } = SlashRefundCalculator::new(&snapshot.state)
+            .runtime_upgrades(upgrades)
+            .domain_id(domain_id)
+            .calculate(snapshot.operators.clone())
+            .await;
+        failures.iter().for_each(|err| error!("{err}"));
+        let manifest_path = output.join(format!("payouts-{slashed_at}.json"));
+        payouts.write(&manifest_path)?;
+        println!(
+            "Snapshot and payout manifest of the slash at #{slashed_at} written to {} and {}",
+            snapshot_path.display(),
+            manifest_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Returns the number and hash of the block given with `--at-block`, if any.
+async fn resolve_at_block(
+    api: &Api,
+    at_block: Option<BlockId>,
+) -> Result<Option<(Number, Hash)>, SlashError> {
+    match at_block {
+        Some(at_block) => Ok(Some(scanner::resolve_block(api, at_block).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Logs the error of each failed operator and fails if there is any.
+fn report_failures(failures: &[SlashError], total: usize) -> Result<(), SlashError> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    failures.iter().for_each(|err| error!("{err}"));
+    Err(SlashError::OperatorsFailed {
+        failed: failures.len(),
+        total,
+    })
+}
+
+/// Submits the batch extrinsics one at a time in the order of their nonces, waiting for each
+/// to be finalized and its events to be checked before submitting the next.
+///
+/// Stops at the first batch that fails, leaving the batches after it unsubmitted.
+async fn submit_batch_extrinsics_sequentially(
+    api: &Api,
+    checkpoint: &CheckpointFile,
+    signer_account: &AccountId,
+    batch_extrinsics: Vec<BatchExtrinsic>,
+    treasury: &TreasuryReserve,
+    notifier: &Notifier,
+) -> Result<Vec<(BatchExtrinsic, SlashError)>, SlashError> {
+    let total = batch_extrinsics.len();
+    let mut batch_extrinsics = batch_extrinsics.into_iter().enumerate();
+    while let Some((index, batch_extrinsic)) = batch_extrinsics.next() {
+        let operator_id = batch_extrinsic.operator_id;
+        let nonce = batch_extrinsic.nonce;
+        let mut failed_batches = submit_batch_extrinsics(
+            api,
+            checkpoint,
+            signer_account,
+            vec![batch_extrinsic],
+            WaitFor::Finalized,
+            treasury,
+            notifier,
+        )
+        .await?;
+        // the batches left are reported so that the run can be resumed from them
+        if shutdown::is_requested() {
+            failed_batches.extend(batch_extrinsics.map(|(_, batch_extrinsic)| {
+                let nonce = batch_extrinsic.nonce;
+                (batch_extrinsic, SlashError::NotSubmitted { nonce })
+            }));
+            return Ok(failed_batches);
+        }
+        if !failed_batches.is_empty() {
+            warn!(
+                "Stopping since the batch for Operator[{operator_id:?}] with Nonce[{nonce}] failed, {} batches left unsubmitted",
+                total - index - 1
+            );
+            return Ok(failed_batches);
+        }
+        info!(
+            "Batch for Operator[{operator_id:?}] with Nonce[{nonce}] finalized ({}/{total})",
+            index + 1
+        );
+    }
+    Ok(vec![])
+}
+
+/// Submits the batch extrinsics after checking that the signer can pay the estimated fees,
+/// recording the submission of each batch in the checkpoint.
+///
+/// Batches dropped from the transaction pool without being included leave a gap in the
+/// nonces that blocks the batches after them, so the gap is filled with a remark while they
+/// are pending. The dropped batches are then re-signed with the next nonces and submitted
+/// again if the signer's key is available.
+///
+/// Returns the batches that failed along with their errors.
+async fn submit_batch_extrinsics(
+    api: &Api,
+    checkpoint: &CheckpointFile,
+    signer_account: &AccountId,
+    batch_extrinsics: Vec<BatchExtrinsic>,
+    wait_for: WaitFor,
+    treasury: &TreasuryReserve,
+    notifier: &Notifier,
+) -> Result<Vec<(BatchExtrinsic, SlashError)>, SlashError> {
+    let estimated_fees = estimate_batch_extrinsics(api, &batch_extrinsics).await?;
+    let signer_balance = get_free_balance(api, signer_account).await?;
+    info!("Signer Balance: {:?}", signer_balance);
+    info!("Estimated Fees: {:?}", estimated_fees);
+    if signer_balance < estimated_fees {
+        return Err(SlashError::InsufficientFeeBalance {
+            balance: signer_balance,
+            required: estimated_fees,
+        });
+    }
+
+    let mut failures = vec![];
+    let mut batch_extrinsics = batch_extrinsics;
+    for round in 1..=MAX_SUBMISSION_ROUNDS {
+        let dropped = submit_batch_extrinsics_round(
+            api,
+            checkpoint,
+            signer_account,
+            batch_extrinsics,
+            wait_for,
+            treasury,
+            notifier,
+            &mut failures,
+        )
+        .await;
+        if dropped.is_empty() {
+            break;
+        }
+        if api.signer().is_none() || round == MAX_SUBMISSION_ROUNDS || shutdown::is_requested() {
+            failures.extend(dropped);
+            break;
+        }
+
+        // batches are re-signed once all the pending ones are resolved, so the next nonce
+        // is not used by any of them
+        let mut nonce = get_account_next_index(api, signer_account).await?;
+        warn!(
+            "Resubmitting {} dropped batches from Nonce[{nonce}]",
+            dropped.len()
+        );
+        batch_extrinsics = vec![];
+        for (batch_extrinsic, _) in dropped {
+            let BatchExtrinsic {
+                operator_id,
+                slashed_at,
+                nominators,
+                executes,
+                total,
+                call,
+                ..
+            } = batch_extrinsic;
+            let batch_call = BatchCall {
+                operator_id,
+                slashed_at,
+                nonce,
+                nominators,
+                executes,
+                total,
+                call,
+            };
+            batch_extrinsics.push(sign_batch_call(api, batch_call)?);
+            nonce += 1;
+        }
+    }
+
+    Ok(failures)
+}
+
+/// Logs the failures of the batches and fails if there are any.
+///
+/// If the run was stopped, by Ctrl-C or the circuit breaker, the checkpoint is written again and
+/// the batches left unsubmitted are printed, so that the run can be resumed from where it stopped.
+fn check_batch_failures(
+    checkpoint: &CheckpointFile,
+    failures: Vec<SlashError>,
+    total: usize,
+) -> Result<(), SlashError> {
+    if shutdown::is_requested() {
+        checkpoint.persist()?;
+        let (not_submitted, failures): (Vec<_>, Vec<_>) =
+            failures.into_iter().partition(is_not_submitted);
+        failures.iter().for_each(|err| error!("{err}"));
+        println!(
+            "Run stopped with {} of {total} batches not submitted:",
+            not_submitted.len()
+        );
+        for err in &not_submitted {
+            println!("  {err}");
+        }
+        println!(
+            "Submissions are recorded in {}, resume with `--resume`",
+            checkpoint.path().display()
+        );
+        return Err(SlashError::Interrupted {
+            not_submitted: not_submitted.len(),
+            failed: failures.len(),
+            total,
+        });
+    }
+    if !failures.is_empty() {
+        failures.iter().for_each(|err| error!("{err}"));
+        return Err(SlashError::BatchesFailed {
+            failed: failures.len(),
+            total,
+        });
+    }
+
+    Ok(())
+}
+
+/// Whether the batch was left unsubmitted since the run was interrupted.
+fn is_not_submitted(err: &SlashError) -> bool {
+    match err {
+        SlashError::Operator { source, .. } | SlashError::Nominator { source, .. } => {
+            is_not_submitted(source)
+        }
+        SlashError::NotSubmitted { .. } => true,
+        _ => false,
+    }
+}
+
+/// Submits the batch extrinsics concurrently while filling the nonce gaps left by the
+/// dropped batches, and returns the dropped batches.
+///
+/// Failures of the batches that may have been included are added to `failures`.
+async fn submit_batch_extrinsics_round(
+    api: &Api,
+    checkpoint: &CheckpointFile,
+    signer_account: &AccountId,
+    batch_extrinsics: Vec<BatchExtrinsic>,
+    wait_for: WaitFor,
+    treasury: &TreasuryReserve,
+    notifier: &Notifier,
+    failures: &mut Vec<(BatchExtrinsic, SlashError)>,
+) -> Vec<(BatchExtrinsic, SlashError)> {
+    let progress = Progress::new("Submitted batches", batch_extrinsics.len());
+    let progress = &progress;
+    let nonces = Mutex::new(NonceTracker::new(
+        batch_extrinsics
+            .iter()
+            .map(|batch_extrinsic| batch_extrinsic.nonce),
+    ));
+    let nonces = &nonces;
+    let futs: Vec<_> = batch_extrinsics
+        .into_iter()
+        .map(|batch_extrinsic| async move {
+            let result = match treasury.reserve(api, &batch_extrinsic).await {
+                Ok(()) => {
+                    let result =
+                        submit_batch_extrinsic(api, checkpoint, &batch_extrinsic, wait_for).await;
+                    treasury.release(&batch_extrinsic).await;
+                    result
+                }
+                Err(err) => Err(err),
+            };
+            // the nonce of a batch that was not submitted is left unused
+            nonces.lock().unwrap().resolve(
+                batch_extrinsic.nonce,
+                matches!(
+                    result,
+                    Err(SlashError::ExtrinsicDropped(_)
+                        | SlashError::InsufficientTreasuryBalance { .. }
+                        | SlashError::NotSubmitted { .. })
+                ),
+            );
+            progress.advance(1);
+            notify_batch_result(notifier, &batch_extrinsic, &result).await;
+            (batch_extrinsic, result)
+        })
+        .collect();
+
+    let submissions = join_all(futs);
+    let gap_filler = fill_nonce_gaps(api, signer_account, nonces);
+    pin_mut!(submissions, gap_filler);
+    let results = match select(submissions, gap_filler).await {
+        Either::Left((results, _)) => results,
+        Either::Right((result, submissions)) => {
+            if let Err(err) = result {
+                warn!("Stopped checking for nonce gaps: {err}");
+            }
+            submissions.await
+        }
+    };
+
+    let mut dropped = vec![];
+    for (batch_extrinsic, result) in results {
+        match result {
+            Ok(_) => {}
+            Err(err @ SlashError::ExtrinsicDropped(_)) => dropped.push((batch_extrinsic, err)),
+            Err(err) => failures.push((batch_extrinsic, err)),
+        }
+    }
+    dropped
+}
+
+/// Notifies the inclusion or the failure of the batch.
+///
+/// Dropped batches are not notified since they are submitted again, nor the batches left
+/// unsubmitted when the run is interrupted.
+async fn notify_batch_result(
+    notifier: &Notifier,
+    batch_extrinsic: &BatchExtrinsic,
+    result: &Result<Hash, SlashError>,
+) {
+    let milestone = match result {
+        Ok(block_hash) => Milestone::BatchIncluded {
+            operator_id: batch_extrinsic.operator_id,
+            nonce: batch_extrinsic.nonce,
+            nominators: batch_extrinsic.nominators.len(),
+            total: batch_extrinsic.total,
+            block_hash: *block_hash,
+        },
+        Err(SlashError::ExtrinsicDropped(_) | SlashError::NotSubmitted { .. }) => return,
+        Err(err) => Milestone::BatchFailed {
+            operator_id: batch_extrinsic.operator_id,
+            nonce: batch_extrinsic.nonce,
+            nominators: batch_extrinsic.nominators.len(),
+            reason: err.to_string(),
+        },
+    };
+    notifier.notify(milestone).await;
+}
+
+/// Balance of the treasury reserved for the batches being submitted.
+///
+/// The treasury balance is checked before each batch rather than once upfront, since other
+/// outflows of the treasury may happen during a long run.
+struct TreasuryReserve {
+    account: AccountId,
+    /// Time to wait for the treasury to be refilled before failing a batch.
+    wait: Duration,
+    /// Total of the batches submitted but not resolved yet.
+    reserved: futures::lock::Mutex<Balance>,
+}
+
+impl TreasuryReserve {
+    fn new(account: AccountId, wait: Duration) -> Self {
+        TreasuryReserve {
+            account,
+            wait,
+            reserved: futures::lock::Mutex::new(Balance::zero()),
+        }
+    }
+
+    /// Reserves the total of the batch once the treasury balance is sufficient for it along
+    /// with the batches being submitted.
+    ///
+    /// Other batches wait while the treasury is refilled.
+    async fn reserve(&self, api: &Api, batch_extrinsic: &BatchExtrinsic) -> Result<(), SlashError> {
+        if !batch_extrinsic.executes {
+            return Ok(());
+        }
+
+        let mut reserved = self.reserved.lock().await;
+        let required = reserved
+            .checked_add(batch_extrinsic.total)
+            .ok_or(SlashError::Overflow)?;
+        let waited_at = Instant::now();
+        loop {
+            let balance = get_free_balance(api, &self.account).await?;
+            if balance >= required {
+                *reserved = required;
+                return Ok(());
+            }
+            if waited_at.elapsed() >= self.wait {
+                return Err(SlashError::InsufficientTreasuryBalance { balance, required });
+            }
+            if shutdown::is_requested() {
+                return Err(SlashError::NotSubmitted {
+                    nonce: batch_extrinsic.nonce,
+                });
+            }
+
+            warn!(
+                "Treasury balance {balance} not sufficient to transfer {required}, waiting for Operator[{:?}] with Nonce[{}]",
+                batch_extrinsic.operator_id, batch_extrinsic.nonce
+            );
+            tokio::time::sleep(TREASURY_CHECK_INTERVAL).await;
+        }
+    }
+
+    /// Releases the total of the batch once it is resolved, when it is either deducted from
+    /// the treasury balance or not transferred.
+    async fn release(&self, batch_extrinsic: &BatchExtrinsic) {
+        if batch_extrinsic.executes {
+            let mut reserved = self.reserved.lock().await;
+            *reserved = reserved.saturating_sub(batch_extrinsic.total);
+        }
+    }
+}
+
+/// Periodically checks the next nonce of the signer and fills the gaps left by the dropped
+/// batches with a `System::remark`, so that the pending batches after them are not stuck.
+///
+/// Runs until it fails, such as when the signer's key is not available.
+async fn fill_nonce_gaps(
+    api: &Api,
+    signer_account: &AccountId,
+    nonces: &Mutex<NonceTracker>,
+) -> Result<(), SlashError> {
+    loop {
+        tokio::time::sleep(NONCE_GAP_CHECK_INTERVAL).await;
+        let next_nonce = get_account_next_index(api, signer_account).await?;
+        if !nonces.lock().unwrap().is_gap(next_nonce) {
+            continue;
+        }
+
+        warn!("Pending batches are stuck behind the dropped batch with Nonce[{next_nonce}], filling it with a remark");
+        let signer = api.signer().ok_or(SlashError::SignerNotSet)?;
+        let remark_call = compose_call!(api.metadata(), "System", "remark", Vec::<u8>::new())
+            .ok_or(SlashError::CallNotFound {
+                pallet: "System",
+                call: "remark",
+            })?;
+        let encoded = compose_extrinsic_offline!(
+            signer,
+            OpaqueCall::new(remark_call),
+            api.extrinsic_params(next_nonce)
+        )
+        .encode();
+        api.submit_opaque_extrinsic(&encoded.into()).await?;
+        nonces.lock().unwrap().fill(next_nonce);
+    }
+}
+
+/// Checks that the signed batch only transfers the refunds of the payout manifest to the
+/// nominators it lists, or to the accounts their refunds are redirected to, and that its
+/// total is the one of its transfers.
+///
+/// The refunds already paid may have been deducted when the batches were exported, so the
+/// balance transferred to a nominator across the batches is bounded by its refunds in the
+/// manifest instead of being equal to them. `transferred` accumulates it across the batches.
+fn check_signed_batch(
+    metadata: &Metadata,
+    signed_batch: &UnsignedBatch,
+    payouts: &PayoutManifest,
+    redirections: &Redirections,
+    transferred: &mut BTreeMap<AccountId, Balance>,
+) -> Result<(), SlashError> {
+    let in_manifest = payouts.operators.iter().any(|operator_payout| {
+        operator_payout.operator_id == signed_batch.operator_id
+            && operator_payout.slashed_at == signed_batch.slashed_at
+    });
+    if !in_manifest {
+        return Err(SlashError::BatchMismatch(format!(
+            "slash at #{} not found",
+            signed_batch.slashed_at
+        )));
+    }
+
+    let mut batch_transfers = BTreeMap::<AccountId, Balance>::new();
+    for (to, balance) in decode_transfers(metadata, &signed_batch.call)? {
+        let nominator_id = redirections.nominator_of(&to).cloned().unwrap_or(to);
+        let nominator_total = batch_transfers.entry(nominator_id).or_default();
+        *nominator_total = nominator_total
+            .checked_add(balance)
+            .ok_or(SlashError::Overflow)?;
+    }
+    let total = batch_transfers
+        .values()
+        .try_fold(Balance::zero(), |acc, balance| acc.checked_add(*balance))
+        .ok_or(SlashError::Overflow)?;
+    if total != signed_batch.total {
+        return Err(SlashError::BatchMismatch(format!(
+            "transfers total {total} instead of {}",
+            signed_batch.total
+        )));
+    }
+
+    for (nominator_id, balance) in batch_transfers {
+        if !signed_batch.nominators.contains(&nominator_id) {
+            return Err(SlashError::BatchMismatch(format!(
+                "{nominator_id} is transferred {balance} without being among the nominators of the batch"
+            )));
+        }
+        let refund = payouts
+            .operators
+            .iter()
+            .filter_map(|operator_payout| operator_payout.nominators.get(&nominator_id))
+            .try_fold(Balance::zero(), |acc, nominator_payout| {
+                acc.checked_add(nominator_payout.refund()?)
+                    .ok_or(SlashError::Overflow)
+            })?;
+        let paid = transferred.entry(nominator_id.clone()).or_default();
+        *paid = paid.checked_add(balance).ok_or(SlashError::Overflow)?;
+        if *paid > refund {
+            return Err(SlashError::BatchMismatch(format!(
+                "{nominator_id} is transferred {paid}, more than its refund of {refund}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the calls are dispatched from the sudo key, so that a wrong key is reported
+/// before any batch is signed instead of when the first one is rejected.
+///
+/// The key is read at the finalized head, like the nonce of the signer, so that a key change
+/// in a block that is not finalized yet is not trusted.
+async fn check_sudo_key(api: &Api, account: &AccountId) -> Result<(), SlashError> {
+    let finalized_hash = scanner::finalized_block_hash(api).await?;
+    let sudo_key = api
+        .get_storage::<AccountId>("Sudo", "Key", Some(finalized_hash))
+        .await?;
+    if sudo_key.as_ref() != Some(account) {
+        return Err(SlashError::NotSudoKey {
+            account: account.clone(),
+            sudo_key,
+        });
+    }
+    info!("Sudo key: {account}");
+    Ok(())
+}
+
+/// Checks that the signer can submit all of its batches before any of them is submitted.
+///
+/// The signer must be able to pay the estimated fees of all its batches, and its nonce at the
+/// finalized head must be the one of its first batch, without extrinsics of its own pending in
+/// the pool or in blocks that are not finalized, which the batches would collide with.
+async fn check_signer(
+    api: &Api,
+    signer_account: &AccountId,
+    batch_extrinsics: &[BatchExtrinsic],
+) -> Result<(), SlashError> {
+    let Some(first_nonce) = batch_extrinsics.iter().map(|batch| batch.nonce).min() else {
+        return Ok(());
+    };
+    let finalized_hash = scanner::finalized_block_hash(api).await?;
+    let finalized_nonce = api
+        .runtime_api()
+        .runtime_call::<u32>(
+            "AccountNonceApi_account_nonce",
+            vec![signer_account.encode()],
+            Some(finalized_hash),
+        )
+        .await?;
+    let next_nonce = get_account_next_index(api, signer_account).await?;
+    if finalized_nonce != next_nonce || finalized_nonce != first_nonce {
+        return Err(SlashError::SignerNoncePending {
+            account: signer_account.clone(),
+            finalized: finalized_nonce,
+            next: next_nonce.max(first_nonce),
+        });
+    }
+
+    let estimated_fees = estimate_batch_extrinsics(api, batch_extrinsics).await?;
+    let signer_balance = get_free_balance(api, signer_account).await?;
+    if signer_balance < estimated_fees {
+        error!(
+            "Signer {signer_account} cannot pay the fees of its {} batches",
+            batch_extrinsics.len()
+        );
+        return Err(SlashError::InsufficientFeeBalance {
+            balance: signer_balance,
+            required: estimated_fees,
+        });
+    }
+    Ok(())
+}
+
+/// Returns the next nonce of the account, including its transactions in the pool.
+async fn get_account_next_index(api: &Api, account: &AccountId) -> Result<u32, SlashError> {
+    let mut params = RpcParams::new();
+    params.insert(account)?;
+    let next_index = api
+        .client()
+        .request("system_accountNextIndex", params)
+        .await
+        .map_err(substrate_api_client::Error::RpcClient)?;
+    Ok(next_index)
+}
+
+/// Dry runs the batch extrinsics with `system_dryRun` at the best block, so that the batches
+/// failing to be applied, such as with `BadOrigin`, are reported before paying any fees.
+///
+/// Only the batch with the next nonce of the signer can be applied on the state of the best
+/// block, the ones after it are checked up to their nonce. Errors of the calls dispatched by
+/// `Sudo` are only reported in its events and are not caught by the dry run.
+async fn dry_run_batch_extrinsics(
+    api: &Api,
+    batch_extrinsics: &[BatchExtrinsic],
+) -> Result<(), SlashError> {
+    let futs: Vec<_> = batch_extrinsics
+        .iter()
+        .map(|batch_extrinsic| async move {
+            let mut params = RpcParams::new();
+            params.insert(Bytes(batch_extrinsic.encoded.clone()))?;
+            let result: Bytes = api
+                .client()
+                .request("system_dryRun", params)
+                .await
+                .map_err(substrate_api_client::Error::RpcClient)?;
+            let result = ApplyExtrinsicResult::decode(&mut result.as_ref())?;
+            Ok::<_, SlashError>((batch_extrinsic, result))
+        })
+        .collect();
+
+    let mut failed = 0;
+    for result in join_all(futs).await {
+        let (batch_extrinsic, result) = result?;
+        let operator_id = batch_extrinsic.operator_id;
+        let nonce = batch_extrinsic.nonce;
+        match result {
+            Ok(Ok(())) => {
+                debug!("Batch extrinsic for Operator[{operator_id:?}] with Nonce[{nonce}] passed the dry run");
+            }
+            Err(TransactionValidityError::Invalid(InvalidTransaction::Future)) => {
+                debug!("Batch extrinsic for Operator[{operator_id:?}] with Nonce[{nonce}] checked up to its nonce");
+            }
+            Ok(Err(err)) => {
+                error!("Batch extrinsic for Operator[{operator_id:?}] with Nonce[{nonce}] failed to dispatch in the dry run: {err:?}");
+                failed += 1;
+            }
+            Err(err) => {
+                error!("Batch extrinsic for Operator[{operator_id:?}] with Nonce[{nonce}] is invalid in the dry run: {err:?}");
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(SlashError::DryRunFailed {
+            failed,
+            total: batch_extrinsics.len(),
+        });
+    }
+    info!("{} batches passed the dry run", batch_extrinsics.len());
+    Ok(())
+}
+
+/// Reads the payouts from the CSV if given, or from the payout manifest.
+///
+/// Fails if a domain is given and any of the payouts is not known to be of that domain, since
+/// the manifest was then not calculated for it.
+fn read_payouts(
+    manifest: &Path,
+    from_csv: Option<&Path>,
+    domain_id: Option<DomainId>,
+) -> Result<PayoutManifest, SlashError> {
+    let payouts = match from_csv {
+        Some(from_csv) => PayoutManifest::read_csv(from_csv)?,
+        None => PayoutManifest::read(manifest)?,
+    };
+    if let Some(domain_id) = domain_id {
+        if payouts
+            .domain_ids()
+            .iter()
+            .any(|payouts_domain_id| *payouts_domain_id != Some(domain_id))
+        {
+            return Err(SlashError::InvalidManifest(
+                "payouts are not all of the domain given with `--domain-id`",
+            ));
+        }
+    }
+    Ok(payouts)
+}
+
+/// Checks that the payout manifest at `manifest` was signed by `reviewer`, with the signature
+/// at `signature` or next to the manifest.
+fn verify_manifest_signature(
+    manifest: &Path,
+    signature: Option<PathBuf>,
+    reviewer: &AccountId,
+) -> Result<(), SlashError> {
+    let signature = signature.unwrap_or_else(|| ManifestSignature::path(manifest));
+    ManifestSignature::read(&signature)?.verify(&std::fs::read(manifest)?, reviewer)?;
+    info!("Payout manifest signed by reviewer {reviewer}");
+    Ok(())
+}
+
+/// Asks for the total to be typed in to confirm it if it exceeds the maximum total, unless
+/// forced.
+fn confirm_total(total: Balance, max_total: Balance, force: bool) -> Result<(), SlashError> {
+    if total <= max_total {
+        return Ok(());
+    }
+    if force {
+        warn!("Total {total} exceeds the maximum total {max_total}, submitting anyway");
+        return Ok(());
+    }
+
+    println!("Total {total} exceeds the maximum total {max_total}.");
+    println!("Type the total to confirm the submission:");
+    let mut confirmation = String::new();
+    std::io::stdin().read_line(&mut confirmation)?;
+    if confirmation.trim() != total.to_string() {
+        return Err(SlashError::ExceedsMaximumTotal { total, max_total });
+    }
+    Ok(())
+}
+
+/// Prints the summary of the payouts of the operator and asks whether to submit them.
+fn approve_operator(operator_payout: &OperatorPayout) -> Result<bool, SlashError> {
+    let largest_refund = operator_payout
+        .nominator_totals()?
+        .into_iter()
+        .max_by_key(|(_, refund)| *refund);
+    println!(
+        "Operator[{:?}]: {} Nominators, Total: {}",
+        operator_payout.operator_id,
+        operator_payout.nominators.len(),
+        operator_payout.total()?
+    );
+    if let Some((nominator_id, refund)) = largest_refund {
+        println!("  Largest refund: {refund} to {nominator_id}");
+    }
+
+    loop {
+        println!(
+            "Submit the batches of Operator[{:?}]? [y/n]",
+            operator_payout.operator_id
+        );
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            // stdin closed
+            return Ok(false);
+        }
+        match answer.trim() {
+            "y" | "Y" => return Ok(true),
+            "n" | "N" => return Ok(false),
+            _ => continue,
+        }
+    }
+}
+
+async fn get_treasury_account(api: &Api) -> Result<AccountId, SlashError> {
+    Ok(api
+        .get_constant::<AccountId>("Domains", "TreasuryAccount")
+        .await?)
+}
+
+/// Warns about the refunds below the existential deposit to accounts that do not exist, which
+/// fail to be transferred unless the account is topped up to the existential deposit first.
+async fn warn_missing_accounts(
+    api: &Api,
+    operator_payouts: &[OperatorPayout],
+    redirections: &Redirections,
+) -> Result<(), SlashError> {
+    let existential_deposit = get_existential_deposit(api).await?;
+    // smallest refund of each account, since every transfer must create the account
+    let mut smallest_refunds = BTreeMap::<AccountId, Balance>::new();
+    for operator_payout in operator_payouts {
+        for (nominator_id, nominator_payout) in &operator_payout.nominators {
+            let refund = nominator_payout.refund()?;
+            if refund < existential_deposit {
+                smallest_refunds
+                    .entry(redirections.destination(nominator_id).clone())
+                    .and_modify(|smallest| *smallest = (*smallest).min(refund))
+                    .or_insert(refund);
+            }
+        }
+    }
+
+    let futs: Vec<_> = smallest_refunds
+        .into_iter()
+        .map(|(account, refund)| async move {
+            let exists = api.get_account_data(&account).await?.is_some();
+            Ok::<_, SlashError>((!exists).then_some((account, refund)))
+        })
+        .collect();
+    let mut missing_accounts = 0;
+    for result in join_all(futs).await {
+        if let Some((account, refund)) = result? {
+            warn!(
+                "Account {account} does not exist and needs a top-up of the existential deposit {existential_deposit} to receive its refund of {refund}"
+            );
+            missing_accounts += 1;
+        }
+    }
+    if missing_accounts > 0 {
+        warn!("{missing_accounts} accounts need a top-up before their refunds can be transferred");
+    }
+    Ok(())
+}
+
+async fn get_existential_deposit(api: &Api) -> Result<Balance, SlashError> {
+    Ok(api
+        .get_constant::<Balance>("Balances", "ExistentialDeposit")
+        .await?)
+}
+
+async fn get_treasury_balance(api: &Api) -> Result<Balance, SlashError> {
+    let treasury_account = get_treasury_account(api).await?;
+    get_free_balance(api, &treasury_account).await
+}
+
+async fn get_free_balance(api: &Api, account: &AccountId) -> Result<Balance, SlashError> {
+    // account without any data has no balance
+    Ok(api
+        .get_account_data(account)
+        .await?
+        .map(|account_data| account_data.free)
+        .unwrap_or_default())
+}
+
+/// Removes the nominators whose batch was included in a block or submitted in the previous
+/// runs.
+///
+/// Nominators of the failed batches are kept so that they are retried.
+fn skip_submitted_nominators(
+    checkpoint: &CheckpointFile,
+    operator_payouts: Vec<OperatorPayout>,
+) -> Vec<OperatorPayout> {
+    operator_payouts
+        .into_iter()
+        .filter_map(|mut operator_payout| {
+            let operator_id = operator_payout.operator_id;
+            for submission in checkpoint.submissions(operator_id, operator_payout.slashed_at) {
+                match submission.status {
+                    // multisig approvals of the signer are skipped based on the on-chain state
+                    SubmissionStatus::Failed | SubmissionStatus::Approved => continue,
+                    SubmissionStatus::InBlock => info!(
+                        "Skipping {} Nominators of Operator[{operator_id:?}] since batch was included in block: {:?}",
+                        submission.nominators.len(),
+                        submission.block_hash
+                    ),
+                    // outcome of the batch is unknown, so do not risk transferring twice
+                    SubmissionStatus::Submitted => warn!(
+                        "Skipping {} Nominators of Operator[{operator_id:?}] since batch extrinsic {:?} was submitted but not confirmed, check its status manually",
+                        submission.nominators.len(),
+                        submission.extrinsic_hash
+                    ),
+                }
+                submission.nominators.iter().for_each(|nominator_id| {
+                    operator_payout.nominators.remove(nominator_id);
+                });
+            }
+            if operator_payout.nominators.is_empty() {
+                None
+            } else {
+                Some(operator_payout)
+            }
+        })
+        .collect()
+}
+
+/// Removes the nominators that already received their slashed balance from the treasury
+/// since the block `scan_from`.
+///
+/// A transfer of the exact slashed balance from the treasury to the nominator is considered
+/// a refund and each transfer is matched against a single payout.
+async fn skip_refunded_nominators(
+    api: &Api,
+    operator_payouts: Vec<OperatorPayout>,
+    scan_from: Number,
+) -> Result<Vec<OperatorPayout>, SlashError> {
+    let treasury_account = get_treasury_account(api).await?;
+    let scan_to = scanner::best_block_number(api).await?;
+    let mut refunds = BTreeMap::<_, usize>::new();
+    scanner::scan_transfers_from(api, &treasury_account, scan_from, scan_to)
+        .await?
+        .into_iter()
+        .for_each(|refund| *refunds.entry(refund).or_default() += 1);
+
+    let mut pending_payouts = vec![];
+    for mut operator_payout in operator_payouts {
+        let operator_id = operator_payout.operator_id;
+        let nominator_totals = operator_payout.nominator_totals()?;
+        operator_payout.nominators.retain(|nominator_id, _| {
+            let refund = (nominator_id.clone(), nominator_totals[nominator_id]);
+            match refunds.get_mut(&refund) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    warn!(
+                        "Skipping Nominator[{nominator_id}] of Operator[{operator_id:?}] since {} was already transferred from treasury",
+                        refund.1
+                    );
+                    false
+                }
+                _ => true,
+            }
+        });
+        if !operator_payout.nominators.is_empty() {
+            pending_payouts.push(operator_payout);
+        }
+    }
+    Ok(pending_payouts)
+}
+
+/// Returns the balance transferred from the treasury to each nominator since the block
+/// `scan_from`.
+///
+/// The refunds of the batches recorded in the checkpoint are left out, since their
+/// nominators are already skipped, and the transfers to redirected accounts are attributed
+/// to the nominators they were redirected from.
+async fn scan_paid_refunds(
+    api: &Api,
+    checkpoint: &CheckpointFile,
+    operator_payouts: &[OperatorPayout],
+    redirections: &Redirections,
+    scan_from: Number,
+) -> Result<BTreeMap<AccountId, Balance>, SlashError> {
+    let treasury_account = get_treasury_account(api).await?;
+    let scan_to = scanner::best_block_number(api).await?;
+    let mut paid = BTreeMap::<AccountId, Balance>::new();
+    for (to, amount) in
+        scanner::scan_transfers_from(api, &treasury_account, scan_from, scan_to).await?
+    {
+        let nominator_id = redirections.nominator_of(&to).cloned().unwrap_or(to);
+        let nominator_paid = paid.entry(nominator_id).or_default();
+        *nominator_paid = nominator_paid
+            .checked_add(amount)
+            .ok_or(SlashError::Overflow)?;
+    }
+    for operator_payout in operator_payouts {
+        for submission in
+            checkpoint.submissions(operator_payout.operator_id, operator_payout.slashed_at)
+        {
+            if !matches!(
+                submission.status,
+                SubmissionStatus::InBlock | SubmissionStatus::Submitted
+            ) {
+                continue;
+            }
+            for nominator_id in &submission.nominators {
+                if let (Some(nominator_paid), Some(nominator_payout)) = (
+                    paid.get_mut(nominator_id),
+                    operator_payout.nominators.get(nominator_id),
+                ) {
+                    *nominator_paid = nominator_paid.saturating_sub(nominator_payout.refund()?);
+                }
+            }
+        }
+    }
+    paid.retain(|_, nominator_paid| !nominator_paid.is_zero());
+    Ok(paid)
+}
+
+/// Reconciles the transfers made from the treasury in the blocks the batches were included
+/// in with the payout manifest.
+///
+/// Transfers to redirected accounts are attributed to the nominators they were redirected
+/// from.
+async fn verify_transfers(
+    api: &Api,
+    payouts: &PayoutManifest,
+    checkpoint: &CheckpointFile,
+    redirections: &Redirections,
+) -> Result<Vec<TransferMismatch>, SlashError> {
+    let treasury_account = get_treasury_account(api).await?;
+    let mut mismatches = vec![];
+    // split the payout of each operator by the block its batches were included in
+    let mut included_payouts = BTreeMap::<Hash, Vec<OperatorPayout>>::new();
+    let operator_payouts = if checkpoint.consolidated() {
+        payouts.consolidated_operators()?
+    } else {
+        payouts.operators.clone()
+    };
+    for operator_payout in &operator_payouts {
+        let mut nominators = operator_payout.nominators.clone();
+        for submission in
+            checkpoint.submissions(operator_payout.operator_id, operator_payout.slashed_at)
+        {
+            let block_hash = match (submission.status, submission.block_hash) {
+                (SubmissionStatus::InBlock, Some(block_hash)) => block_hash,
+                _ => continue,
+            };
+            let included_nominators = submission
+                .nominators
+                .iter()
+                .filter_map(|nominator_id| nominators.remove_entry(nominator_id))
+                .collect();
+            included_payouts
+                .entry(block_hash)
+                .or_default()
+                .push(OperatorPayout {
+                    nominators: included_nominators,
+                    ..operator_payout.clone()
+                });
+        }
+        mismatches.extend(nominators.into_keys().map(|nominator_id| {
+            TransferMismatch::NotIncluded {
+                operator_id: operator_payout.operator_id,
+                slashed_at: operator_payout.slashed_at,
+                nominator_id,
+            }
+        }));
+    }
+
+    let treasury_account = &treasury_account;
+    let futs: Vec<_> = included_payouts
+        .into_iter()
+        .map(|(block_hash, operator_payouts)| async move {
+            let transfers = scanner::transfers_from_at(api, treasury_account, block_hash)
+                .await?
+                .into_iter()
+                .map(|(to, balance)| match redirections.nominator_of(&to) {
+                    Some(nominator_id) => (nominator_id.clone(), balance),
+                    None => (to, balance),
+                })
+                .collect();
+            let operator_payouts: Vec<_> = operator_payouts.iter().collect();
+            reconcile_transfers(block_hash, &operator_payouts, transfers)
+        })
+        .collect();
+    for block_mismatches in join_all(futs).await {
+        mismatches.extend(block_mismatches?);
+    }
+    Ok(mismatches)
+}
+
+fn print_payouts(
+    payouts: &PayoutManifest,
+    treasury_balance: Balance,
+    existential_deposit: Balance,
+) -> Result<(), SlashError> {
+    for operator_payout in &payouts.operators {
+        println!(
+            "Operator[{:?}] slashed at #{}: {} Nominators, Total: {}",
+            operator_payout.operator_id,
+            operator_payout.slashed_at,
+            operator_payout.nominators.len(),
+            display_refund(operator_payout.slashed_total()?, operator_payout.total()?)
+        );
+        for (nominator_id, nominator_payout) in &operator_payout.nominators {
+            println!(
+                "  {nominator_id}: {}{}",
+                display_refund(nominator_payout.total()?, nominator_payout.refund()?),
+                if nominator_payout.overridden.is_some() {
+                    " (overridden)"
+                } else {
+                    ""
+                }
+            );
+            for unconverted in operator_payout
+                .unconverted
+                .get(nominator_id)
+                .into_iter()
+                .flatten()
+            {
+                println!("    {}", display_unconverted(unconverted));
+            }
+            if let Some(unlockable) = operator_payout.unlockable.get(nominator_id) {
+                println!("    {}", display_unlockable(unlockable));
+            }
+        }
+        if !operator_payout.dust.is_zero() {
+            if operator_payout
+                .nominators
+                .values()
+                .any(|n| !n.dust.is_zero())
+            {
+                println!(
+                    "  Dust: {} (assigned to the largest nominator)",
+                    operator_payout.dust
+                );
+            } else {
+                println!("  Dust: {} (left in the treasury)", operator_payout.dust);
+            }
+        }
+        if !operator_payout.unexplained.is_zero() {
+            println!(
+                "  Unexplained: {} (left in the treasury, beyond the rounding of the payouts)",
+                operator_payout.unexplained
+            );
+        }
+    }
+
+    println!("Statistics:");
+    for operator_payout in &payouts.operators {
+        println!(
+            "  Operator[{:?}]: {}",
+            operator_payout.operator_id,
+            PayoutStatistics::of_operator(operator_payout)
+        );
+    }
+    for (domain_id, statistics) in PayoutStatistics::of_domains(payouts) {
+        match domain_id {
+            Some(domain_id) => println!("  Domain[{domain_id:?}]: {statistics}"),
+            None => println!("  Unknown Domain: {statistics}"),
+        }
+    }
+    println!(
+        "  All Operators: {}",
+        PayoutStatistics::of_manifest(payouts)
+    );
+
+    if !payouts.excluded.is_empty() {
+        println!("Excluded:");
+        for operator_payout in &payouts.excluded {
+            for (nominator_id, nominator_payout) in &operator_payout.nominators {
+                println!(
+                    "  Operator[{:?}] {nominator_id}: {}",
+                    operator_payout.operator_id,
+                    display_refund(nominator_payout.total()?, nominator_payout.refund()?)
+                );
+            }
+        }
+        println!("Total Excluded: {}", payouts.excluded_total()?);
+    }
+
+    let below = payouts.refunds_below(existential_deposit)?;
+    if !below.is_empty() {
+        println!("Below Existential Deposit ({existential_deposit}):");
+        for operator_payout in &below {
+            for (nominator_id, nominator_payout) in &operator_payout.nominators {
+                println!(
+                    "  Operator[{:?}] {nominator_id}: {}",
+                    operator_payout.operator_id,
+                    nominator_payout.refund()?
+                );
+            }
+        }
+        println!("Warning: Refunds below the existential deposit are burned if the account does not exist");
+    }
+    if !payouts.skipped.is_empty() {
+        println!("Skipped:");
+        for operator_payout in &payouts.skipped {
+            for (nominator_id, nominator_payout) in &operator_payout.nominators {
+                println!(
+                    "  Operator[{:?}] {nominator_id}: {}",
+                    operator_payout.operator_id,
+                    nominator_payout.refund()?
+                );
+            }
+        }
+    }
+
+    if payouts.refund_percent < 100 {
+        let slashed_total =
+            payouts
+                .operators
+                .iter()
+                .try_fold(Balance::zero(), |acc, operator_payout| {
+                    acc.checked_add(operator_payout.slashed_total()?)
+                        .ok_or(SlashError::Overflow)
+                })?;
+        println!("Total Slashed: {slashed_total}");
+        println!(
+            "Total Refund ({}%): {}",
+            payouts.refund_percent, payouts.total
+        );
+    } else {
+        println!("Total Slashed: {}", payouts.total);
+    }
+    println!("Payload Hash: {:?}", payouts.payload_hash);
+    println!(
+        "Payout Root: {:?}",
+        PayoutTree::new(payouts.account_totals()?).root()
+    );
+    println!("Treasury Balance: {treasury_balance}");
+    if treasury_balance < payouts.total {
+        println!("Warning: Treasury balance not sufficient for transfer");
+    }
+    Ok(())
+}
+
+/// Displays the slashed balance along with the refund if only part of it is refunded.
+fn display_refund(slashed: Balance, refund: Balance) -> String {
+    if slashed == refund {
+        slashed.to_string()
+    } else {
+        format!("{slashed} (refund: {refund})")
+    }
+}
+
+fn display_unconverted(unconverted: &UnconvertedPending) -> String {
+    let pending = match unconverted.kind {
+        PendingKind::Deposit => format!("deposit of {}", unconverted.amount),
+        PendingKind::Withdrawal => format!("withdrawal of {} shares", unconverted.amount),
+    };
+    let outcome = if unconverted.refunded {
+        "refunded"
+    } else {
+        "not refunded"
+    };
+    format!(
+        "Warning: share price of epoch {} not found for the pending {pending}, {outcome}",
+        unconverted.epoch
+    )
+}
+
+fn display_unlockable(unlockable: &UnlockableWithdrawal) -> String {
+    let outcome = if unlockable.refunded {
+        "refunded"
+    } else {
+        "not refunded"
+    };
+    format!(
+        "Withdrawals of {} were unlockable at confirmed domain block #{} before the slash, {outcome}",
+        unlockable.amount, unlockable.confirmed_domain_block
+    )
+}
+
+fn print_breakdowns(
+    nominator_id: &AccountId,
+    breakdowns: &[NominatorBreakdown],
+) -> Result<(), SlashError> {
+    if breakdowns.is_empty() {
+        println!("{nominator_id} did not nominate any of the slashed operators");
+        return Ok(());
+    }
 
-    let sudoer = ExtrinsicSigner::<AssetRuntimeConfig>::new(sudoer);
-    let client = JsonrpseeClient::new("wss://rpc-0.gemini-3h.subspace.network/ws")
-        .await
-        .unwrap();
-    let mut api = SApi::<AssetRuntimeConfig, _>::new(client).await.unwrap();
-    api.set_signer(sudoer);
+    for breakdown in breakdowns {
+        let payout = &breakdown.payout;
+        println!(
+            "Operator[{:?}] slashed at #{} (state at {:?}):",
+            breakdown.operator_id, breakdown.slashed_at, breakdown.block_hash
+        );
+        println!(
+            "  Staked: {} ({} shares at share price {:?})",
+            payout.staked, breakdown.shares, breakdown.share_price
+        );
+        println!(
+            "  Withdrawable: {} (withdrawals and their storage fee refunds)",
+            payout.withdrawable
+        );
+        println!(
+            "  Storage Fund: {} (deposited {} of {} total deposits, storage fund balance {})",
+            payout.storage_fund,
+            breakdown.storage_fee_deposit,
+            breakdown.total_storage_fee_deposit,
+            breakdown.storage_fund_balance
+        );
+        for unconverted in &breakdown.unconverted {
+            println!("  {}", display_unconverted(unconverted));
+        }
+        if let Some(unlockable) = &breakdown.unlockable {
+            println!("  {}", display_unlockable(unlockable));
+        }
+        println!("  Total: {}", payout.total()?);
+    }
 
-    let slashed_operators = get_slashed_operators(&api).await;
-    let fut_storages: Vec<_> = slashed_operators
-        .clone()
-        .into_iter()
-        .map(|slashed_operator| {
-            get_nominator_deposits_and_withdrawal(&api, slashed_operator.0, slashed_operator.1)
-        })
-        .collect();
-    let operator_nominators = join_all(fut_storages).await;
+    let total = breakdowns
+        .iter()
+        .try_fold(Balance::zero(), |acc, breakdown| {
+            acc.checked_add(breakdown.payout.total()?)
+                .ok_or(SlashError::Overflow)
+        })?;
+    println!("Total Refund of {nominator_id}: {total}");
+    Ok(())
+}
 
-    let operator_info_futs = slashed_operators
-        .into_iter()
-        .map(|(operator_id, block_hash)| get_operator_info(&api, operator_id, block_hash));
-    let operators_info = BTreeMap::from_iter(join_all(operator_info_futs).await);
+/// Splits the transfers into batches of at most `max_batch_size` transfers each, starting a
+/// new batch early if the encoded calls would exceed `MAX_BATCH_LENGTH`.
+fn split_into_batches(
+    api: &Api,
+    nominator_balances: BTreeMap<AccountId, Balance>,
+    max_batch_size: NonZeroUsize,
+) -> Result<Vec<Vec<(AccountId, Balance)>>, SlashError> {
+    let metadata = api.metadata();
+    let mut batches = vec![];
+    let mut batch = vec![];
+    let mut batch_length = 0;
+    for (nominator_id, balance) in nominator_balances {
+        let call_length = compose_transfer_call(metadata, nominator_id.clone(), balance)?
+            .encode()
+            .len();
+        if !batch.is_empty()
+            && (batch.len() >= max_batch_size.get()
+                || batch_length + call_length > MAX_BATCH_LENGTH)
+        {
+            batches.push(std::mem::take(&mut batch));
+            batch_length = 0;
+        }
+        batch_length += call_length;
+        batch.push((nominator_id, balance));
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
 
-    let futs: Vec<_> = operator_nominators
-        .into_iter()
-        .map(|operator_nominator| {
-            let (operator, block_hash) = operators_info
-                .get(&operator_nominator.operator_id)
-                .cloned()
-                .unwrap();
-            calculate_nominators_slashed_amount(
-                &api,
-                operator_nominator.operator_id,
-                operator,
-                operator_nominator.nominator_storage,
-                block_hash,
-            )
-        })
-        .collect();
+    Ok(batches)
+}
 
-    let nominator_slashed_balances = join_all(futs).await;
-    let total_balance_slashed =
-        nominator_slashed_balances
-            .iter()
-            .fold(Balance::zero(), |acc, (_, nominator_balances)| {
-                acc.checked_add(
-                    nominator_balances
-                        .iter()
-                        .fold(Balance::zero(), |acc, (_, balance)| {
-                            acc.checked_add(*balance).unwrap()
-                        }),
-                )
-                .unwrap()
-            });
-    let treasury_balance = get_treasury_balance(&api).await;
-    info!("Treasury Balance: {:?}", treasury_balance);
-    info!("Total Slashed: {:?}", total_balance_slashed);
-    assert!(
-        treasury_balance >= total_balance_slashed,
-        "Treasury balance not sufficient for transfer"
-    );
+fn compose_transfer_call(
+    metadata: &Metadata,
+    nominator_id: AccountId,
+    balance: Balance,
+) -> Result<impl Encode, SlashError> {
+    compose_call!(
+        metadata,
+        "Domains",
+        "transfer_treasury_funds",
+        nominator_id,
+        balance
+    )
+    .ok_or(SlashError::CallNotFound {
+        pallet: "Domains",
+        call: "transfer_treasury_funds",
+    })
+}
 
-    // get the starting nonce of the sudoer and dispatch batch call for each operator
-    let mut nonce = api.get_nonce().await.unwrap();
-    let futs: Vec<_> = nominator_slashed_balances
-        .into_iter()
-        .map(|(operator_id, nominator_balances)| {
-            let fut = transfer_balance_from_treasury(&api, nonce, operator_id, nominator_balances);
-            nonce += 1;
-            fut
-        })
-        .collect();
-    join_all(futs).await;
-}
-
-async fn get_slashed_operators(api: &Api) -> Vec<(OperatorId, Hash)> {
-    let slashed_operators = vec![
-        (65, 2364057),
-        (41, 2364307),
-        (64, 2364389),
-        (61, 2364389),
-        (30, 2364389),
-        (66, 2364761),
-        (62, 2364761),
-        (78, 2368057),
-        (63, 2368101),
-        (37, 2368542),
-        (77, 2368906),
-        (40, 2369910),
-        (80, 2374768),
-        (81, 2375003),
-        (21, 2375130),
-        (48, 2375244),
-        (71, 2380396),
-        (56, 2381733),
-        (51, 2383817),
-        (6, 2384081),
-        (73, 2384081),
-        (76, 2384081),
-        (10, 2384081),
-        (24, 2384516),
-        (52, 2386856),
-        (79, 2386991),
-        (45, 2387166),
-        (102, 2388238),
-    ];
-
-    let futs: Vec<_> = slashed_operators
-        .into_iter()
-        .map(|(operator_id, number)| async move {
-            (
-                operator_id,
-                api.get_block_hash(Some(number - 1))
-                    .await
-                    .ok()
-                    .flatten()
-                    .unwrap(),
-            )
-        })
-        .collect();
-    join_all(futs).await
+/// Remark added to every batch, attributing its transfers to the run and the payout manifest
+/// they were calculated in.
+fn trace_remark(run_id: &str, payload_hash: Hash) -> Vec<u8> {
+    format!("slash-refund run={run_id} manifest={payload_hash:?}").into_bytes()
 }
 
-async fn get_nominator_deposits_and_withdrawal(
-    api: &Api,
+/// Merkle root of the payouts published on-chain along with the batch of the first nominator
+/// of the first operator in the manifest.
+///
+/// The batch is chosen from the manifest so that every run, and every multisig signatory,
+/// composes the same calls.
+struct PayoutCommitment {
+    root: Hash,
     operator_id: OperatorId,
-    block_hash: Hash,
-) -> OperatorNominators {
-    let deposits = get_nominator_storage::<Deposit>(api, operator_id, block_hash, "Deposits").await;
-    let withdrawals =
-        get_nominator_storage::<Withdrawal>(api, operator_id, block_hash, "Withdrawals").await;
-    let mut storage = BTreeMap::new();
-    deposits.into_iter().for_each(|(nominator_id, deposit)| {
-        storage.insert(
+    slashed_at: Number,
+    nominator_id: AccountId,
+}
+
+impl PayoutCommitment {
+    fn new(payouts: &PayoutManifest) -> Result<Option<Self>, SlashError> {
+        let Some(operator_payout) = payouts.operators.first() else {
+            return Ok(None);
+        };
+        let Some(nominator_id) = operator_payout.nominators.keys().next().cloned() else {
+            return Ok(None);
+        };
+        Ok(Some(PayoutCommitment {
+            root: PayoutTree::new(payouts.account_totals()?).root(),
+            operator_id: operator_payout.operator_id,
+            slashed_at: operator_payout.slashed_at,
             nominator_id,
-            NominatorStorage {
-                deposit,
-                withdrawal: None,
-            },
-        );
-    });
-    withdrawals
-        .into_iter()
-        .for_each(|(nominator_id, withdrawal)| {
-            match storage.get(&nominator_id) {
-                None => panic!(
-                    "there will always be a deposit for this nominator even with zero shares"
-                ),
-                Some(nominator_storage) => storage.insert(
-                    nominator_id,
-                    NominatorStorage {
-                        deposit: nominator_storage.deposit.clone(),
-                        withdrawal: Some(withdrawal),
-                    },
-                ),
-            };
-        });
+        }))
+    }
 
-    OperatorNominators {
-        operator_id,
-        nominator_storage: storage,
+    /// Returns the root to be published in the given batch, if any.
+    fn remark_for(
+        &self,
+        operator_id: OperatorId,
+        slashed_at: Number,
+        batch: &[(AccountId, Balance)],
+    ) -> Option<Hash> {
+        let carries_root = operator_id == self.operator_id
+            && slashed_at == self.slashed_at
+            && batch
+                .iter()
+                .any(|(nominator_id, _)| *nominator_id == self.nominator_id);
+        carries_root.then_some(self.root)
     }
 }
 
-async fn get_nominator_storage<V: Decode>(
-    api: &Api,
+/// `Sudo::sudo(Utility::batch_all(..))` call transferring the slashed balances of a batch of
+/// nominators of an operator, optionally wrapped in `Multisig::as_multi` or `Proxy::proxy`.
+struct BatchCall {
     operator_id: OperatorId,
-    block_hash: Hash,
-    storage: &'static str,
-) -> Vec<(AccountId, V)> {
-    let storage_prefix = api
-        .get_storage_double_map_key_prefix("Domains", storage, operator_id)
-        .await
-        .unwrap();
-    let storage_keys = api
-        .get_storage_keys_paged(
-            Some(storage_prefix.clone()),
-            u32::MAX,
-            None,
-            Some(block_hash),
-        )
-        .await
-        .unwrap();
-
-    let storage_futures: Vec<_> = storage_keys
-        .into_iter()
-        .map(|storage_key| {
-            let api = api.clone();
-            let storage_prefix = storage_prefix.clone();
-
-            async move {
-                let value = api
-                    .get_storage_by_key::<V>(storage_key.clone(), Some(block_hash))
-                    .await
-                    .ok()
-                    .flatten()
-                    .unwrap();
-                let mut nominator_key = &storage_key.0[storage_prefix.0.len()..];
-                let nominator_id = AccountId::decode(&mut nominator_key).unwrap();
-                (nominator_id, value)
-            }
-        })
-        .collect();
-
-    join_all(storage_futures).await
+    /// Block the operator was slashed at.
+    slashed_at: Number,
+    /// Nonce the call is to be signed with.
+    nonce: u32,
+    nominators: Vec<AccountId>,
+    /// Whether the transfers are executed once the extrinsic is included, which is not the
+    /// case for the multisig approvals below the threshold.
+    executes: bool,
+    /// Total balance transferred from the treasury by the batch.
+    total: Balance,
+    call: OpaqueCall,
 }
 
-async fn get_operator_info(
-    api: &Api,
+/// Signed extrinsic of a `BatchCall`.
+struct BatchExtrinsic {
     operator_id: OperatorId,
-    block_hash: Hash,
-) -> (OperatorId, (Operator, Hash)) {
-    (
-        operator_id,
-        (
-            api.get_storage_map::<_, Operator>(
-                "Domains",
-                "Operators",
-                operator_id,
-                Some(block_hash),
-            )
-            .await
-            .ok()
-            .flatten()
-            .unwrap(),
-            block_hash,
-        ),
-    )
+    slashed_at: Number,
+    nonce: u32,
+    nominators: Vec<AccountId>,
+    executes: bool,
+    total: Balance,
+    /// Call of the batch, to re-sign it with another nonce if it is dropped.
+    call: OpaqueCall,
+    tip: Balance,
+    extrinsic_hash: Hash,
+    encoded: Vec<u8>,
 }
 
-async fn calculate_nominators_slashed_amount(
+/// Composes the call of the batch.
+///
+/// The batch is dispatched from `origin_account`, which is either the signer or the account
+/// proxied by the signer, and pays the refunds of redirected nominators to the account they
+/// were redirected to.
+///
+/// Returns `None` if the batch was already approved by the origin as a multisig signatory.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(
+    api,
+    nominator_balances,
+    trace,
+    redirections,
+    vesting,
+    balances_transfer,
+    origin_account,
+    multisig,
+    proxy
+))]
+async fn compose_batch_call(
     api: &Api,
+    nonce: u32,
     operator_id: OperatorId,
-    mut operator: Operator,
-    operator_nominators: BTreeMap<AccountId, NominatorStorage>,
-    block_hash: Hash,
-) -> (OperatorId, BTreeMap<AccountId, Balance>) {
-    let mut total_stake = operator
-        .current_total_stake
-        .checked_add(operator.current_epoch_rewards)
-        .unwrap();
-
-    operator.current_epoch_rewards = Zero::zero();
-    let mut total_shares = operator.current_total_shares;
-    let share_price = SharePrice::new(total_shares, total_stake);
-
-    let operator_storage_fund_balance =
-        get_operator_storage_fund_balance(api, operator_id, block_hash).await;
-    let mut total_storage_fee_deposit = operator.total_storage_fee_deposit;
-
-    let mut nominators_slashed_balances = BTreeMap::new();
-    let mut nominator_storage_fund_deposited_balances = vec![];
-    for (nominator_id, mut nominator_storage) in operator_nominators {
-        do_convert_previous_epoch_deposits(
-            api,
-            operator_id,
-            &mut nominator_storage.deposit,
-            block_hash,
-        )
-        .await;
-
-        let (amount_ready_to_withdraw, shares_withdrew_in_current_epoch, storage_fund_withdrew) =
-            match nominator_storage.withdrawal {
-                None => (Zero::zero(), Zero::zero(), Zero::zero()),
-                Some(mut withdrawal) => {
-                    do_convert_previous_epoch_withdrawal(
-                        api,
-                        operator_id,
-                        &mut withdrawal,
-                        block_hash,
-                    )
-                    .await;
-                    (
-                        withdrawal.total_withdrawal_amount,
-                        withdrawal
-                            .withdrawal_in_shares
-                            .map(|WithdrawalInShares { shares, .. }| shares)
-                            .unwrap_or_default(),
-                        withdrawal.withdrawals.into_iter().fold(
-                            Balance::zero(),
-                            |acc, withdrawal_in_balance| {
-                                acc.checked_add(withdrawal_in_balance.storage_fee_refund)
-                                    .unwrap()
-                            },
-                        ),
-                    )
-                }
-            };
-
-        // deduct any unstaked pending storage fee deposits from the total storage deposits.
-        if let Some(pending_deposit) = nominator_storage.deposit.pending {
-            total_storage_fee_deposit =
-                total_storage_fee_deposit.saturating_sub(pending_deposit.storage_fee_deposit);
+    slashed_at: Number,
+    nominator_balances: Vec<(AccountId, Balance)>,
+    payout_root: Option<Hash>,
+    trace: &[u8],
+    redirections: &Redirections,
+    vesting: Option<&Vesting>,
+    balances_transfer: Option<&BalancesTransfer>,
+    origin_account: &AccountId,
+    multisig: Option<&MultisigAccount>,
+    proxy: Option<&ProxyAccount>,
+) -> Result<Option<BatchCall>, SlashError> {
+    let metadata = api.metadata();
+    let nominators: Vec<_> = nominator_balances
+        .iter()
+        .map(|(nominator_id, _)| nominator_id.clone())
+        .collect();
+    let total = nominator_balances
+        .iter()
+        .try_fold(Balance::zero(), |acc, (_, balance)| {
+            acc.checked_add(*balance)
+        })
+        .ok_or(SlashError::Overflow)?;
+    let sudo_call = compose_sudo_call(
+        metadata,
+        redirections.apply(nominator_balances),
+        payout_root,
+        trace,
+        vesting,
+        balances_transfer,
+    )?;
+    let (call, executes) = match multisig {
+        None => (sudo_call, true),
+        Some(multisig) => {
+            let maybe_call =
+                wrap_in_multisig(api, operator_id, sudo_call, origin_account, multisig).await?;
+            match maybe_call {
+                None => return Ok(None),
+                Some(call) => call,
+            }
         }
+    };
 
-        let nominator_shares = nominator_storage
-            .deposit
-            .known
-            .shares
-            .checked_add(shares_withdrew_in_current_epoch)
-            .unwrap();
+    let call = match proxy {
+        None => call,
+        Some(proxy) => proxy.wrap(metadata, call)?,
+    };
 
-        let nominator_staked_amount = share_price.shares_to_stake(nominator_shares);
-        total_stake = total_stake.saturating_sub(nominator_staked_amount);
-        total_shares = total_shares.saturating_sub(nominator_shares);
+    Ok(Some(BatchCall {
+        operator_id,
+        slashed_at,
+        nonce,
+        nominators,
+        executes,
+        total,
+        call,
+    }))
+}
 
-        // current staked amount + amount ready to withdraw + withdrawn storage fund
-        let total_slashed =
-            nominator_staked_amount + amount_ready_to_withdraw + storage_fund_withdrew;
-        nominators_slashed_balances.insert(nominator_id.clone(), total_slashed);
+/// Composes the `Utility::batch_all(..)` call of the transfers from the treasury to the
+/// nominators.
+fn compose_transfers_batch_call(
+    metadata: &Metadata,
+    nominator_balances: Vec<(AccountId, Balance)>,
+) -> Result<OpaqueCall, SlashError> {
+    let transfer_calls = nominator_balances
+        .into_iter()
+        .map(|(nominator_id, balance)| compose_transfer_call(metadata, nominator_id, balance))
+        .collect::<Result<Vec<_>, _>>()?;
+    compose_batch_all_call(metadata, transfer_calls)
+}
 
-        // add remaining storage fund balance that is still in the pool for each nominator
-        nominator_storage_fund_deposited_balances.push((
-            nominator_id,
-            nominator_storage.deposit.known.storage_fee_deposit,
-        ))
-    }
+fn compose_batch_all_call<C: Encode>(
+    metadata: &Metadata,
+    calls: Vec<C>,
+) -> Result<OpaqueCall, SlashError> {
+    let calls = Batch { calls };
+    let batch_call =
+        compose_call!(metadata, "Utility", "batch_all", calls).ok_or(SlashError::CallNotFound {
+            pallet: "Utility",
+            call: "batch_all",
+        })?;
+    Ok(OpaqueCall::new(batch_call))
+}
 
-    // iterate through each nominator storage fund and calculate the actual storage fund based on
-    // total storage fund balance.
-    nominator_storage_fund_deposited_balances
-        .into_iter()
-        .for_each(|(nominator_id, deposited_balance)| {
-            let storage_fund_share_price = StorageFundRedeemPrice::new(
-                operator_storage_fund_balance,
-                total_storage_fee_deposit,
-            );
-            let storage_fund_slashed = storage_fund_share_price.redeem(deposited_balance);
-            let existing_balance = nominators_slashed_balances
-                .get(&nominator_id)
-                .cloned()
-                .unwrap();
-            nominators_slashed_balances
-                .insert(nominator_id, existing_balance + storage_fund_slashed);
-        });
+/// Composes the `Sudo::sudo(Utility::batch_all(..))` call transferring the balances to the
+/// nominators.
+///
+/// The batch starts with a `System::remark` of `trace`, attributing the transfers to the run
+/// and the payout manifest. If `payout_root` is given, it is followed by a remark of the root
+/// so that the payout set is committed to on-chain.
+///
+/// With `vesting`, the batch of vested transfers is dispatched from the treasury with
+/// `Sudo::sudo_as` instead, and so is the batch of `Balances` transfers with
+/// `balances_transfer`.
+fn compose_sudo_call(
+    metadata: &Metadata,
+    nominator_balances: Vec<(AccountId, Balance)>,
+    payout_root: Option<Hash>,
+    trace: &[u8],
+    vesting: Option<&Vesting>,
+    balances_transfer: Option<&BalancesTransfer>,
+) -> Result<OpaqueCall, SlashError> {
+    let mut remarks = vec![trace.to_vec()];
+    if let Some(payout_root) = payout_root {
+        remarks.push(payout_root.as_bytes().to_vec());
+    }
+    let mut calls = vec![];
+    for remark in remarks {
+        let remark_call = compose_call!(metadata, "System", "remark", remark).ok_or(
+            SlashError::CallNotFound {
+                pallet: "System",
+                call: "remark",
+            },
+        )?;
+        calls.push(OpaqueCall::new(remark_call));
+    }
+    for (nominator_id, balance) in nominator_balances {
+        match (vesting, balances_transfer) {
+            (Some(vesting), _) => {
+                calls.extend(vesting.compose_refund_calls(metadata, nominator_id, balance)?)
+            }
+            (None, Some(balances_transfer)) => calls
+                .push(balances_transfer.compose_transfer_call(metadata, nominator_id, balance)?),
+            (None, None) => calls.push(OpaqueCall::new(compose_transfer_call(
+                metadata,
+                nominator_id,
+                balance,
+            )?)),
+        }
+    }
+    let batch_call = compose_batch_all_call(metadata, calls)?;
+    match (vesting, balances_transfer) {
+        (Some(vesting), _) => return vesting.wrap(metadata, batch_call),
+        (None, Some(balances_transfer)) => return balances_transfer.wrap(metadata, batch_call),
+        (None, None) => {}
+    }
+    let sudo_call =
+        compose_call!(metadata, "Sudo", "sudo", batch_call).ok_or(SlashError::CallNotFound {
+            pallet: "Sudo",
+            call: "sudo",
+        })?;
+    Ok(OpaqueCall::new(sudo_call))
+}
 
-    (operator_id, nominators_slashed_balances)
+/// Composes the governance call paying the balances to the nominators.
+fn compose_proposal_call(
+    metadata: &Metadata,
+    proposal: ProposalKind,
+    council_threshold: Option<u32>,
+    nominator_balances: Vec<(AccountId, Balance)>,
+) -> Result<OpaqueCall, SlashError> {
+    match proposal {
+        ProposalKind::TreasurySpend => {
+            let spend_calls = nominator_balances
+                .into_iter()
+                .map(|(nominator_id, balance)| {
+                    compose_call!(
+                        metadata,
+                        "Treasury",
+                        "spend_local",
+                        Compact(balance),
+                        MultiAddress::<AccountId, ()>::Id(nominator_id)
+                    )
+                    .ok_or(SlashError::CallNotFound {
+                        pallet: "Treasury",
+                        call: "spend_local",
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            compose_batch_all_call(metadata, spend_calls)
+        }
+        ProposalKind::Council => {
+            let threshold = council_threshold
+                .ok_or(SlashError::InvalidProposal("council threshold is required"))?;
+            let batch_call = compose_transfers_batch_call(metadata, nominator_balances)?;
+            let length_bound =
+                u32::try_from(batch_call.0.len()).map_err(|_| SlashError::Overflow)?;
+            let propose_call = compose_call!(
+                metadata,
+                "Council",
+                "propose",
+                Compact(threshold),
+                batch_call,
+                Compact(length_bound)
+            )
+            .ok_or(SlashError::CallNotFound {
+                pallet: "Council",
+                call: "propose",
+            })?;
+            Ok(OpaqueCall::new(propose_call))
+        }
+        ProposalKind::Preimage => {
+            let batch_call = compose_transfers_batch_call(metadata, nominator_balances)?;
+            let note_preimage_call =
+                compose_call!(metadata, "Preimage", "note_preimage", batch_call.0).ok_or(
+                    SlashError::CallNotFound {
+                        pallet: "Preimage",
+                        call: "note_preimage",
+                    },
+                )?;
+            Ok(OpaqueCall::new(note_preimage_call))
+        }
+    }
 }
 
-async fn do_convert_previous_epoch_deposits(
+/// Prints the hex encoded call of each batch of the payouts, split the same way as when they
+/// are submitted.
+///
+/// The batches are printed as `Sudo::sudo` calls, unless a governance proposal is given.
+#[allow(clippy::too_many_arguments)]
+fn print_batch_calls(
     api: &Api,
-    operator_id: OperatorId,
-    deposit: &mut Deposit,
-    block_hash: Hash,
-) {
-    // if it is one of the previous domain epoch, then calculate shares for the epoch and update known deposit
-    let pending_deposit = match deposit.pending.take() {
-        None => return,
-        Some(pd) => pd,
+    payouts: &PayoutManifest,
+    max_batch_size: NonZeroUsize,
+    proposal: Option<ProposalKind>,
+    council_threshold: Option<u32>,
+    trace: &[u8],
+    redirections: &Redirections,
+    vesting: Option<&Vesting>,
+    balances_transfer: Option<&BalancesTransfer>,
+    consolidate: bool,
+) -> Result<(), SlashError> {
+    let metadata = api.metadata();
+    let commitment = PayoutCommitment::new(payouts)?;
+    let operator_payouts = if consolidate {
+        payouts.consolidated_operators()?
+    } else {
+        payouts.operators.clone()
     };
-
-    let PendingDeposit {
-        effective_domain_epoch,
-        amount,
-        storage_fee_deposit,
-    } = pending_deposit;
-
-    if let Some(epoch_share_price) =
-        get_operator_epoch_share_price(api, operator_id, effective_domain_epoch, block_hash).await
-    {
-        let new_shares = epoch_share_price.stake_to_shares(amount);
-        deposit.known.shares = deposit.known.shares.checked_add(new_shares).unwrap();
-        deposit.known.storage_fee_deposit = deposit
-            .known
-            .storage_fee_deposit
-            .checked_add(storage_fee_deposit)
-            .unwrap();
+    for operator_payout in &operator_payouts {
+        let operator_id = operator_payout.operator_id;
+        let batches = split_into_batches(api, operator_payout.nominator_totals()?, max_batch_size)
+            .map_err(|err| SlashError::operator(operator_id, err))?;
+        let total_batches = batches.len();
+        for (index, batch) in batches.into_iter().enumerate() {
+            let payout_root = commitment.as_ref().and_then(|commitment| {
+                commitment.remark_for(operator_id, operator_payout.slashed_at, &batch)
+            });
+            let batch = redirections.apply(batch);
+            if proposal == Some(ProposalKind::Preimage) {
+                // the referendum refers to the noted call rather than to `note_preimage`
+                let noted_call = compose_transfers_batch_call(metadata, batch.clone())
+                    .map_err(|err| SlashError::operator(operator_id, err))?;
+                println!(
+                    "Operator[{operator_id:?}] batch {}/{total_batches} preimage hash: {:?}, length: {}",
+                    index + 1,
+                    Hash::from(noted_call.hash()),
+                    noted_call.0.len()
+                );
+            }
+            let call = match proposal {
+                None => compose_sudo_call(
+                    metadata,
+                    batch,
+                    payout_root,
+                    trace,
+                    vesting,
+                    balances_transfer,
+                ),
+                Some(proposal) => {
+                    compose_proposal_call(metadata, proposal, council_threshold, batch)
+                }
+            }
+            .map_err(|err| SlashError::operator(operator_id, err))?;
+            println!(
+                "Operator[{operator_id:?}] batch {}/{total_batches} call data: 0x{}",
+                index + 1,
+                HexDisplay::from(&call.0)
+            );
+        }
     }
+
+    Ok(())
 }
 
-async fn do_convert_previous_epoch_withdrawal(
+/// Wraps the call in `Multisig::as_multi` approved by the signatory, along with whether the
+/// approval executes the call.
+///
+/// The pending multisig operation of the call, if any, is looked up so that the approval
+/// refers to it and so that the call is not approved twice by the same signatory.
+async fn wrap_in_multisig(
     api: &Api,
     operator_id: OperatorId,
-    withdrawal: &mut Withdrawal,
-    block_hash: Hash,
-) {
-    let pending_withdrawal = match withdrawal.withdrawal_in_shares.take() {
-        None => return,
-        Some(pw) => pw,
+    call: OpaqueCall,
+    signatory: &AccountId,
+    multisig: &MultisigAccount,
+) -> Result<Option<(OpaqueCall, bool)>, SlashError> {
+    let other_signatories = multisig.other_signatories(signatory)?;
+    let call_hash = Hash::from(call.hash());
+    let pending = api
+        .get_storage_double_map::<_, _, Multisig>(
+            "Multisig",
+            "Multisigs",
+            multisig.account_id(),
+            call_hash,
+            None,
+        )
+        .await?;
+    let approvals = pending
+        .as_ref()
+        .map(|pending| pending.approvals.len())
+        .unwrap_or_default();
+    let remaining = usize::from(multisig.threshold()).saturating_sub(approvals);
+    if let Some(pending) = &pending {
+        if pending.approvals.contains(signatory) {
+            info!(
+                "Skipping batch of Operator[{operator_id:?}] with call hash {call_hash:?} since it was already approved by {signatory}, waiting for {remaining} more approvals"
+            );
+            return Ok(None);
+        }
+    }
+
+    // print the call for the other signatories to approve
+    println!(
+        "Operator[{operator_id:?}] multisig call hash: {call_hash:?}, call data: 0x{}",
+        HexDisplay::from(&call.0)
+    );
+
+    // only the final approval executes the call, so only it needs the weight of the call
+    let executes = remaining <= 1;
+    let max_weight = if executes {
+        query_call_weight(api, &call).await?
+    } else {
+        Weight::default()
     };
+    let as_multi_call = compose_call!(
+        api.metadata(),
+        "Multisig",
+        "as_multi",
+        multisig.threshold(),
+        other_signatories,
+        pending.map(|pending| pending.when),
+        call,
+        max_weight
+    )
+    .ok_or(SlashError::CallNotFound {
+        pallet: "Multisig",
+        call: "as_multi",
+    })?;
 
-    let WithdrawalInShares {
-        domain_epoch,
-        unlock_at_confirmed_domain_block_number,
-        shares,
-        storage_fee_refund,
-    } = pending_withdrawal;
+    Ok(Some((OpaqueCall::new(as_multi_call), executes)))
+}
 
-    if let Some(epoch_share_price) =
-        get_operator_epoch_share_price(api, operator_id, domain_epoch, block_hash).await
-    {
-        let withdrawal_amount = epoch_share_price.shares_to_stake(shares);
+async fn query_call_weight(api: &Api, call: &OpaqueCall) -> Result<Weight, SlashError> {
+    let length = call.0.len() as u32;
+    let dispatch_info = api
+        .runtime_api()
+        .runtime_call::<RuntimeDispatchInfo>(
+            "TransactionPaymentApi_query_call_info",
+            vec![call.0.clone(), length.encode()],
+            None,
+        )
+        .await?;
+    Ok(dispatch_info.weight)
+}
 
-        withdrawal.total_withdrawal_amount = withdrawal
-            .total_withdrawal_amount
-            .checked_add(withdrawal_amount)
-            .unwrap();
+/// Signs the batch call with the signer of the api.
+fn sign_batch_call(api: &Api, batch_call: BatchCall) -> Result<BatchExtrinsic, SlashError> {
+    let BatchCall {
+        operator_id,
+        slashed_at,
+        nonce,
+        nominators,
+        executes,
+        total,
+        call,
+    } = batch_call;
+    let signer = api.signer().ok_or(SlashError::SignerNotSet)?;
+    let extrinsic_params = api.extrinsic_params(nonce);
+    let tip = decode_tip(&extrinsic_params.signed_extra().encode())?;
+    let encoded =
+        compose_extrinsic_offline!(signer, OpaqueCall(call.0.clone()), extrinsic_params).encode();
+    Ok(BatchExtrinsic {
+        operator_id,
+        slashed_at,
+        nonce,
+        nominators,
+        executes,
+        total,
+        call,
+        tip,
+        extrinsic_hash: Hash::from(blake2_256(&encoded)),
+        encoded,
+    })
+}
 
-        let (domain_id, _) = domain_epoch.deconstruct();
+/// Signs the batch calls with the signer, or with the signer and the extra signers in turn
+/// if any, each with its own nonces.
+///
+/// Returns the batches of each signer along with its api and account.
+async fn distribute_batch_calls<'a>(
+    api: &'a Api,
+    signer_account: &'a AccountId,
+    extra_signers: &'a [(Api, AccountId)],
+    batch_calls: Vec<BatchCall>,
+) -> Result<Vec<(&'a Api, &'a AccountId, Vec<BatchExtrinsic>)>, SlashError> {
+    if extra_signers.is_empty() {
+        let batch_extrinsics = batch_calls
+            .into_iter()
+            .map(|batch_call| sign_batch_call(api, batch_call))
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(vec![(api, signer_account, batch_extrinsics)]);
+    }
 
-        let withdraw_in_balance = WithdrawalInBalance {
-            domain_id,
-            unlock_at_confirmed_domain_block_number,
-            amount_to_unlock: withdrawal_amount,
-            storage_fee_refund,
-        };
-        withdrawal.withdrawals.push_back(withdraw_in_balance);
+    let mut signer_batches = vec![(api, signer_account, vec![])];
+    signer_batches.extend(
+        extra_signers
+            .iter()
+            .map(|(extra_api, extra_signer_account)| (extra_api, extra_signer_account, vec![])),
+    );
+    let mut nonces = vec![];
+    for (signer_api, signer_account, _) in &signer_batches {
+        nonces.push(signer_api.get_account_nonce(signer_account).await?);
     }
+    // batch calls were composed with the nonces of the signer, which are reassigned
+    for (index, mut batch_call) in batch_calls.into_iter().enumerate() {
+        let signer_index = index % signer_batches.len();
+        batch_call.nonce = nonces[signer_index];
+        nonces[signer_index] += 1;
+        let (signer_api, _, batch_extrinsics) = &mut signer_batches[signer_index];
+        batch_extrinsics.push(sign_batch_call(*signer_api, batch_call)?);
+    }
+    Ok(signer_batches)
 }
 
-async fn get_operator_epoch_share_price(
+/// Signs the batch call with the key of `signer_account` held by the remote signer.
+async fn sign_batch_call_remotely(
     api: &Api,
-    operator_id: OperatorId,
-    domain_epoch: DomainEpoch,
-    block_hash: Hash,
-) -> Option<SharePrice> {
-    api.get_storage_double_map::<_, _, SharePrice>(
-        "Domains",
-        "OperatorEpochSharePrice",
-        operator_id,
-        domain_epoch,
-        Some(block_hash),
-    )
-    .await
-    .ok()
-    .flatten()
+    remote_signer: &RemoteSigner,
+    signer_account: &AccountId,
+    sudo_account: &AccountId,
+    batch_call: BatchCall,
+) -> Result<BatchExtrinsic, SlashError> {
+    let mut unsigned_batch = export_batch_call(api, signer_account, sudo_account, batch_call);
+    remote_signer.sign(&mut unsigned_batch).await?;
+    let encoded = unsigned_batch.signed_extrinsic()?;
+    let tip = unsigned_batch.tip()?;
+    Ok(BatchExtrinsic {
+        operator_id: unsigned_batch.operator_id,
+        slashed_at: unsigned_batch.slashed_at,
+        nonce: unsigned_batch.nonce,
+        nominators: unsigned_batch.nominators,
+        executes: unsigned_batch.executes,
+        total: unsigned_batch.total,
+        call: OpaqueCall(unsigned_batch.call.0),
+        tip,
+        extrinsic_hash: Hash::from(blake2_256(&encoded)),
+        encoded,
+    })
 }
 
-async fn get_operator_storage_fund_balance(
-    api: &Api,
-    operator_id: OperatorId,
-    block_hash: Hash,
-) -> Balance {
-    let runtime_api = api.runtime_api();
-    runtime_api
-        .runtime_call::<Balance>(
-            "DomainsApi_storage_fund_account_balance",
-            vec![operator_id.encode()],
-            Some(block_hash),
-        )
-        .await
-        .unwrap()
+/// Sets the tip and the era of the extrinsics signed with the api.
+///
+/// Mortal extrinsics are checked against the block their era starts at, which is at or
+/// before the finalized block.
+async fn set_extrinsic_params(
+    api: &mut Api,
+    era_period: Option<u64>,
+    tip: Balance,
+) -> Result<(), SlashError> {
+    let mut additional_params = GenericAdditionalParams::new().tip(AssetTip::<Balance>::new(tip));
+    if let Some(era_period) = era_period {
+        let finalized_number = scanner::finalized_block_number(api).await?;
+        let era = Era::mortal(era_period, finalized_number.into());
+        let birth_number = era.birth(finalized_number.into()) as Number;
+        let birth_hash = api
+            .get_block_hash(Some(birth_number))
+            .await?
+            .ok_or(SlashError::BlockNotFound(birth_number))?;
+        info!("Signing mortal extrinsics born at block #{birth_number} with {era:?}");
+        additional_params = additional_params.era(era, birth_hash);
+    }
+    api.set_additional_params(additional_params);
+    Ok(())
 }
 
-async fn get_treasury_balance(api: &Api) -> Balance {
-    let treasury_account = api
-        .get_constant::<AccountId>("Domains", "TreasuryAccount")
-        .await
-        .unwrap();
-    api.get_account_data(&treasury_account)
-        .await
-        .ok()
-        .flatten()
-        .unwrap()
-        .free
+/// Exports the batch call along with the payload to be signed by `signer_account` offline.
+fn export_batch_call(
+    api: &Api,
+    signer_account: &AccountId,
+    sudo_account: &AccountId,
+    batch_call: BatchCall,
+) -> UnsignedBatch {
+    let extrinsic_params = api.extrinsic_params(batch_call.nonce);
+    let era = extrinsic_params.signed_extra().era;
+    let signed_extra = extrinsic_params.signed_extra().encode();
+    let additional_signed = extrinsic_params.additional_signed().encode();
+    let signing_payload =
+        UnsignedBatch::signing_payload(&batch_call.call, &signed_extra, &additional_signed);
+    let runtime_version = api.runtime_version();
+    UnsignedBatch {
+        operator_id: batch_call.operator_id,
+        slashed_at: batch_call.slashed_at,
+        nominators: batch_call.nominators,
+        executes: batch_call.executes,
+        total: batch_call.total,
+        signer: signer_account.clone(),
+        sudo: Some(sudo_account.clone()),
+        nonce: batch_call.nonce,
+        era: era.encode().into(),
+        genesis_hash: api.genesis_hash(),
+        spec_version: runtime_version.spec_version,
+        transaction_version: runtime_version.transaction_version,
+        call: batch_call.call.0.into(),
+        signed_extra: signed_extra.into(),
+        additional_signed: additional_signed.into(),
+        signing_payload,
+        signature: None,
+    }
 }
 
-async fn transfer_balance_from_treasury(
+/// Estimates the weight and fee of each batch extrinsic at the best block and returns the
+/// total estimated fee.
+///
+/// Fails if any of the batches exceeds the block weight or length limits, in which case
+/// none of the batches should be submitted.
+async fn estimate_batch_extrinsics(
     api: &Api,
-    nonce: u32,
-    operator_id: OperatorId,
-    nominator_balances: BTreeMap<AccountId, Balance>,
-) {
-    debug!("Sending batch transfer for Operator[{operator_id:?}] with Nonce[{nonce}] for {:?} Nominators", nominator_balances.len());
-    let metadata = api.metadata();
-    let transfer_calls = nominator_balances
-        .into_iter()
-        .map(|(acc, balance)| {
-            compose_call!(metadata, "Domains", "transfer_treasury_funds", acc, balance).unwrap()
+    batch_extrinsics: &[BatchExtrinsic],
+) -> Result<Balance, SlashError> {
+    let block_weights = api
+        .get_constant::<BlockWeights>("System", "BlockWeights")
+        .await?;
+    let block_length = api
+        .get_constant::<BlockLength>("System", "BlockLength")
+        .await?;
+
+    let futs: Vec<_> = batch_extrinsics
+        .iter()
+        .map(|batch_extrinsic| async move {
+            let length = batch_extrinsic.encoded.len() as u32;
+            let dispatch_info = api
+                .runtime_api()
+                .runtime_call::<RuntimeDispatchInfo>(
+                    "TransactionPaymentApi_query_info",
+                    vec![batch_extrinsic.encoded.clone(), length.encode()],
+                    None,
+                )
+                .await?;
+            Ok::<_, SlashError>((batch_extrinsic, length, dispatch_info))
         })
         .collect();
 
-    let calls = Batch {
-        calls: transfer_calls,
-    };
-    let batch_call = compose_call!(metadata, "Utility", "batch_all", calls).unwrap();
-    let xt = compose_extrinsic_with_nonce!(&api, nonce, "Sudo", "sudo", batch_call).unwrap();
-    let result = api
-        .submit_and_watch_extrinsic_until(xt, XtStatus::InBlock)
-        .await;
-    match result {
-        Ok(res) => {
-            info!(
-                "Batch extrinsic for Operator[{operator_id:?}] included in block: {:?}",
-                res.block_hash
+    let mut total_fee = Balance::zero();
+    let mut exceeding = None;
+    for result in join_all(futs).await {
+        let (batch_extrinsic, length, dispatch_info) = result?;
+        let operator_id = batch_extrinsic.operator_id;
+        info!(
+            "Batch extrinsic for Operator[{operator_id:?}] with Nonce[{}]: {} Nominators, Weight: {:?}, Length: {length}, Estimated Fee: {}",
+            batch_extrinsic.nonce,
+            batch_extrinsic.nominators.len(),
+            dispatch_info.weight,
+            dispatch_info.partial_fee
+        );
+        // the partial fee does not include the tip
+        total_fee = total_fee
+            .checked_add(dispatch_info.partial_fee)
+            .and_then(|total_fee| total_fee.checked_add(batch_extrinsic.tip))
+            .ok_or(SlashError::Overflow)?;
+
+        let max_weight = block_weights.max_extrinsic(dispatch_info.class);
+        let max_length = *block_length.max.get(dispatch_info.class);
+        if dispatch_info.weight.any_gt(&max_weight) || length > max_length {
+            error!(
+                "Batch extrinsic for Operator[{operator_id:?}] with Nonce[{}] exceeds the block limits of Weight: {max_weight:?}, Length: {max_length}",
+                batch_extrinsic.nonce
             );
+            exceeding.get_or_insert(SlashError::operator(
+                operator_id,
+                SlashError::ExceedsBlockLimits {
+                    weight: dispatch_info.weight,
+                    length,
+                },
+            ));
+        }
+    }
+
+    match exceeding {
+        Some(err) => Err(err),
+        None => Ok(total_fee),
+    }
+}
+
+#[instrument(
+    skip_all,
+    fields(
+        operator_id = batch_extrinsic.operator_id,
+        nonce = batch_extrinsic.nonce,
+        extrinsic_hash = ?batch_extrinsic.extrinsic_hash,
+    )
+)]
+async fn submit_batch_extrinsic(
+    api: &Api,
+    checkpoint: &CheckpointFile,
+    batch_extrinsic: &BatchExtrinsic,
+    wait_for: WaitFor,
+) -> Result<Hash, SlashError> {
+    let BatchExtrinsic {
+        operator_id,
+        slashed_at,
+        nonce,
+        ref nominators,
+        executes,
+        extrinsic_hash,
+        ref encoded,
+        ..
+    } = *batch_extrinsic;
+    if shutdown::is_requested() {
+        return Err(SlashError::NotSubmitted { nonce });
+    }
+    debug!(
+        "Sending batch transfer for Operator[{operator_id:?}] with Nonce[{nonce}] for {:?} Nominators",
+        nominators.len()
+    );
+    checkpoint.update(
+        operator_id,
+        Submission {
+            slashed_at: Some(slashed_at),
+            status: SubmissionStatus::Submitted,
+            extrinsic_hash,
+            block_hash: None,
+            nominators: nominators.clone(),
+        },
+    )?;
+    let submitted_at = scanner::best_block_number(api).await?;
+    let xt_status = match wait_for {
+        WaitFor::InBlock => XtStatus::InBlock,
+        WaitFor::Finalized => XtStatus::Finalized,
+    };
+    let watch = api.submit_and_watch_opaque_extrinsic_until(&encoded.clone().into(), xt_status);
+    let result = match api.client().timeout() {
+        None => watch.await,
+        Some(timeout) => tokio::time::timeout(timeout, watch)
+            .await
+            .unwrap_or_else(|_| Err(substrate_api_client::Error::RpcClient(rpc::timeout_error()))),
+    };
+    let block_hash = match result {
+        Ok(res) => res
+            .block_hash
+            .ok_or(SlashError::ExtrinsicNotFound(extrinsic_hash))?,
+        Err(err) if rpc::is_dropped(&err) => {
+            checkpoint.update(
+                operator_id,
+                Submission {
+                    slashed_at: Some(slashed_at),
+                    status: SubmissionStatus::Failed,
+                    extrinsic_hash,
+                    block_hash: None,
+                    nominators: nominators.clone(),
+                },
+            )?;
+            // the extrinsic was not included, so the batch can be signed again
+            return Err(SlashError::ExtrinsicDropped(err));
         }
         Err(err) => {
-            error!(
-                "Failed to submit batch for Operator[{operator_id:?}]: {:?}",
-                err
+            // the extrinsic may have been included while the connection was down, or in a
+            // block that was retracted, so it is never signed again unless it is dropped
+            if rpc::is_watch_lost(&err) {
+                warn!(
+                    "Lost the watch of the batch extrinsic for Operator[{operator_id:?}], looking it up by hash {extrinsic_hash:?}"
+                );
+            } else {
+                warn!(
+                    "Failed to watch the batch extrinsic for Operator[{operator_id:?}]: {err:?}, looking it up by hash {extrinsic_hash:?}"
+                );
+            }
+            let block_hash = scanner::find_extrinsic(
+                api,
+                extrinsic_hash,
+                submitted_at,
+                submitted_at.saturating_add(WATCH_RESUME_BLOCKS),
             )
+            .await?;
+            // left as submitted to be checked manually since it may still be included
+            let block_hash = block_hash.ok_or(SlashError::ExtrinsicNotFound(extrinsic_hash))?;
+            if wait_for == WaitFor::Finalized {
+                scanner::wait_for_finalization(api, block_hash).await?;
+            }
+            block_hash
+        }
+    };
+
+    // an included batch may still have failed to dispatch, in which case nothing was
+    // transferred and it is to be submitted again
+    if let Err(err) = scanner::check_batch_events(api, extrinsic_hash, block_hash, executes).await {
+        if matches!(err, SlashError::BatchFailed { .. }) {
+            checkpoint.update(
+                operator_id,
+                Submission {
+                    slashed_at: Some(slashed_at),
+                    status: SubmissionStatus::Failed,
+                    extrinsic_hash,
+                    block_hash: Some(block_hash),
+                    nominators: nominators.clone(),
+                },
+            )?;
         }
+        return Err(err);
+    }
+
+    let status = if executes {
+        info!("Batch extrinsic for Operator[{operator_id:?}] included in block: {block_hash:?}");
+        SubmissionStatus::InBlock
+    } else {
+        info!("Multisig approval for Operator[{operator_id:?}] included in block: {block_hash:?}");
+        SubmissionStatus::Approved
     };
+    let submission = Submission {
+        slashed_at: Some(slashed_at),
+        status,
+        extrinsic_hash,
+        block_hash: Some(block_hash),
+        nominators: nominators.clone(),
+    };
+    checkpoint.update(operator_id, submission.clone())?;
+    // the batch is recorded as included already, so a receipt that cannot be resolved is
+    // not a failure of the batch
+    match inclusion(api, checkpoint, extrinsic_hash, block_hash).await {
+        Ok(inclusion) => {
+            let receipt = checkpoint.receipt(operator_id, &submission, &inclusion)?;
+            info!("Receipt: {receipt}");
+        }
+        Err(err) => warn!(
+            "Failed to resolve the receipt of the batch for Operator[{operator_id:?}] in block {block_hash:?}: {err}"
+        ),
+    }
+    Ok(block_hash)
+}
+
+/// Resolves the position of the extrinsic in the block it was included in.
+async fn inclusion(
+    api: &Api,
+    checkpoint: &CheckpointFile,
+    extrinsic_hash: Hash,
+    block_hash: Hash,
+) -> Result<Inclusion, SlashError> {
+    let (block_number, extrinsic_index) =
+        scanner::extrinsic_index(api, extrinsic_hash, block_hash).await?;
+    let events_digest = scanner::events_digest(api, block_hash).await?;
+    let rpc_url = api.client().url().await;
+    Ok(Inclusion {
+        block_number,
+        extrinsic_index,
+        events_digest,
+        explorer_urls: checkpoint.network().explorer_urls(
+            &rpc_url,
+            block_number,
+            block_hash,
+            extrinsic_index,
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the manifest and CSV paths of `calculate` run with the given arguments, the
+    /// given paths of the config file and `--output-dir out --run-id run`.
+    fn calculate_paths(cli: &[&str], output: OutputConfig) -> (PathBuf, Option<PathBuf>) {
+        let matches = Args::command().get_matches_from(
+            ["gemini-3h-slash", "--output-dir", "out", "--run-id", "run"]
+                .into_iter()
+                .chain(["calculate", "--operator", "1:10"])
+                .chain(cli.iter().copied()),
+        );
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        let config = RefundConfig {
+            output: output.clone(),
+            ..RefundConfig::default()
+        };
+        args.apply_config(config, &matches);
+        args.apply_artifacts(
+            &RunArtifacts::new(Path::new("out"), "run"),
+            &matches,
+            &output,
+        );
+        match args.command {
+            Command::Calculate { manifest, csv, .. } => (manifest, csv),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn paths_are_taken_from_cli_then_config_then_artifacts() {
+        let output = OutputConfig {
+            manifest: Some("config.json".into()),
+            csv: Some("config.csv".into()),
+            ..OutputConfig::default()
+        };
+        assert_eq!(
+            calculate_paths(&[], OutputConfig::default()),
+            (
+                PathBuf::from("out/run/manifest.json"),
+                Some(PathBuf::from("out/run/payouts.csv"))
+            )
+        );
+        assert_eq!(
+            calculate_paths(&[], output.clone()),
+            (
+                PathBuf::from("config.json"),
+                Some(PathBuf::from("config.csv"))
+            )
+        );
+        assert_eq!(
+            calculate_paths(&["--manifest", "cli.json", "--csv", "cli.csv"], output),
+            (PathBuf::from("cli.json"), Some(PathBuf::from("cli.csv")))
+        );
+    }
 }
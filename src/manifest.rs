@@ -0,0 +1,875 @@
+use crate::error::SlashError;
+use crate::overrides::Overrides;
+use crate::{AccountId, Balance, Hash, Number};
+use serde::{Deserialize, Serialize};
+use sp_core::hashing::blake2_256;
+use sp_domains::{DomainId, EpochIndex, OperatorId};
+use sp_runtime::traits::Zero;
+use sp_runtime::Percent;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Slashed balances to be transferred from the treasury to the nominators of the
+/// slashed operators.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayoutManifest {
+    /// Version of the tool that calculated the payouts.
+    pub tool_version: String,
+    /// Blake2-256 hash of the JSON encoded `operators`.
+    pub payload_hash: Hash,
+    /// Total balance to be transferred from the treasury.
+    pub total: Balance,
+    /// Percentage of the slashed balances that is refunded.
+    #[serde(default = "full_refund")]
+    pub refund_percent: u8,
+    pub operators: Vec<OperatorPayout>,
+    /// Payouts of the excluded accounts, which are not transferred and must be handled
+    /// separately.
+    #[serde(default)]
+    pub excluded: Vec<OperatorPayout>,
+    /// Payouts below the existential deposit, which are not transferred since they would be
+    /// burned if the account does not exist.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<OperatorPayout>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperatorPayout {
+    pub operator_id: OperatorId,
+    /// Domain the operator was staking on when it was slashed, not known for the payouts read
+    /// from a CSV without the `domain_id` column or calculated by the earlier versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain_id: Option<DomainId>,
+    /// Block number at which the operator was slashed.
+    pub slashed_at: Number,
+    /// Hash of the block whose state is used for the calculation, the block before the slash
+    /// unless calculated at another block.
+    pub block_hash: Hash,
+    /// Runtime spec version at `block_hash`.
+    pub spec_version: u32,
+    /// Slashed balance of each nominator of this operator.
+    pub nominators: BTreeMap<AccountId, NominatorPayout>,
+    /// Pending deposits and withdrawals of the nominators that could not be converted since
+    /// the share price of their epoch was not found.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub unconverted: BTreeMap<AccountId, Vec<UnconvertedPending>>,
+    /// Withdrawals of the nominators that were unlockable before the slash.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub unlockable: BTreeMap<AccountId, UnlockableWithdrawal>,
+    /// Components the payout of each nominator is derived from, not known for the payouts
+    /// read from a CSV or calculated by the earlier versions.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub components: BTreeMap<AccountId, PayoutComponents>,
+    /// Stake and storage fund of the operator left over after rounding down the payout of
+    /// each nominator, up to one unit of each per nominator.
+    #[serde(default, skip_serializing_if = "Zero::is_zero")]
+    pub dust: Balance,
+    /// Stake and storage fund of the operator left over beyond the rounding, such as the ones
+    /// of the pending deposits and withdrawals not converted or the stake of a deregistered
+    /// operator at another share price, which is never assigned to a nominator.
+    #[serde(default, skip_serializing_if = "Zero::is_zero")]
+    pub unexplained: Balance,
+}
+
+impl OperatorPayout {
+    /// Total balance refunded to all the nominators of this operator.
+    pub fn total(&self) -> Result<Balance, SlashError> {
+        self.nominators
+            .values()
+            .try_fold(Balance::zero(), |acc, nominator_payout| {
+                acc.checked_add(nominator_payout.refund()?)
+                    .ok_or(SlashError::Overflow)
+            })
+    }
+
+    /// Total slashed balance of all the nominators of this operator, regardless of the
+    /// refund percentage.
+    pub fn slashed_total(&self) -> Result<Balance, SlashError> {
+        self.nominators
+            .values()
+            .try_fold(Balance::zero(), |acc, nominator_payout| {
+                acc.checked_add(nominator_payout.total()?)
+                    .ok_or(SlashError::Overflow)
+            })
+    }
+
+    /// Balance refunded to each nominator of this operator.
+    pub fn nominator_totals(&self) -> Result<BTreeMap<AccountId, Balance>, SlashError> {
+        self.nominators
+            .iter()
+            .map(|(nominator_id, nominator_payout)| {
+                Ok((nominator_id.clone(), nominator_payout.refund()?))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PendingKind {
+    Deposit,
+    Withdrawal,
+}
+
+/// Pending deposit or withdrawal of a nominator whose epoch share price was not found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnconvertedPending {
+    pub kind: PendingKind,
+    /// Epoch the deposit or withdrawal was pending in.
+    pub epoch: EpochIndex,
+    /// Amount of the deposit, or the shares of the withdrawal.
+    pub amount: Balance,
+    /// Whether the deposit was refunded as its raw amount, or the withdrawal at the share
+    /// price of the operator, rather than left out of the payout.
+    pub refunded: bool,
+}
+
+/// Withdrawals of a nominator that were unlockable before the slash, since the domain block
+/// they unlock at was confirmed, so that the nominator could have claimed them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnlockableWithdrawal {
+    /// Amount of the withdrawals along with their storage fee refund.
+    pub amount: Balance,
+    /// Latest confirmed domain block before the slash.
+    pub confirmed_domain_block: Number,
+    /// Whether the withdrawals were refunded rather than left out of the payout.
+    pub refunded: bool,
+}
+
+/// Components the payout of a nominator is derived from, so that each of them can be derived
+/// again independently from the chain state.
+///
+/// `staked` is `shares` converted at `share_price`, along with the pending deposit refunded as
+/// its raw amount. `withdrawable` is `ready_to_withdraw`, the withdrawal in shares converted
+/// and `storage_fee_refund`, along with the pending deposit unlocked, less the unlockable
+/// withdrawals left out. `storage_fund` is `storage_fee_deposit` redeemed from
+/// `storage_fund_balance` in proportion to `total_storage_fee_deposit`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayoutComponents {
+    /// Shares of the nominator, including the pending deposit converted.
+    pub shares: Balance,
+    /// Share price of the operator as shares per stake in parts per quintillion, whichever
+    /// precision the runtime computed it in.
+    pub share_price: u64,
+    /// Withdrawals of the nominator unlocking, before the withdrawal in shares is converted.
+    pub ready_to_withdraw: Balance,
+    /// Withdrawal in shares pending in the epoch of the slash or an earlier one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub withdrawal_in_shares: Option<WithdrawalComponent>,
+    /// Storage fee refunds of the withdrawals, including the withdrawal in shares converted.
+    pub storage_fee_refund: Balance,
+    /// Deposit pending in the epoch of the slash or an earlier one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_deposit: Option<DepositComponent>,
+    /// Storage fee deposits of the nominator, including the pending deposit refunded.
+    pub storage_fee_deposit: Balance,
+    /// Balance of the storage fund of the operator.
+    pub storage_fund_balance: Balance,
+    /// Storage fee deposits of all the nominators of the operator.
+    pub total_storage_fee_deposit: Balance,
+}
+
+/// Withdrawal in shares of a nominator and the balance it was converted to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WithdrawalComponent {
+    /// Epoch the withdrawal was made in, at whose share price it is converted.
+    pub epoch: EpochIndex,
+    pub shares: Balance,
+    /// Balance the shares were converted to, at the share price of the operator if the one of
+    /// the epoch was not found, or 0 if the withdrawal was not refunded.
+    pub amount: Balance,
+}
+
+/// Deposit pending for a nominator and how it was refunded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepositComponent {
+    /// Epoch the deposit was made in, at whose share price it is converted.
+    pub epoch: EpochIndex,
+    pub amount: Balance,
+    pub storage_fee_deposit: Balance,
+    pub handling: PendingDepositHandling,
+}
+
+/// How a pending deposit was refunded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingDepositHandling {
+    /// Converted to the given shares at the share price of its epoch, part of `shares`.
+    Converted { shares: Balance },
+    /// Unlocked as is since the operator was deregistered before its epoch, part of
+    /// `withdrawable`.
+    Unlocked,
+    /// Refunded as its raw amount since the share price of its epoch was not found, part of
+    /// `staked`.
+    RawAmount,
+    /// Left out since the share price of its epoch was not found.
+    NotRefunded,
+}
+
+/// Balance slashed from a nominator split by where it was held when the operator was
+/// slashed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NominatorPayout {
+    /// Stake of the nominator, including the shares withdrawn in the current epoch.
+    pub staked: Balance,
+    /// Withdrawals that are unlocking along with their storage fee refund, and the deposits
+    /// pending unlock of a deregistered operator.
+    pub withdrawable: Balance,
+    /// Nominator's share of the operator's storage fund.
+    pub storage_fund: Balance,
+    /// Balance refunded to the nominator if only a percentage of the slashed balance is
+    /// refunded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scaled: Option<Balance>,
+    /// Refund of the nominator overridden by hand, which takes precedence over the
+    /// calculated and scaled refund.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overridden: Option<Balance>,
+    /// Dust of the operator assigned to the nominator.
+    #[serde(default, skip_serializing_if = "Zero::is_zero")]
+    pub dust: Balance,
+}
+
+impl NominatorPayout {
+    /// Total slashed balance of the nominator.
+    pub fn total(&self) -> Result<Balance, SlashError> {
+        self.staked
+            .checked_add(self.withdrawable)
+            .and_then(|total| total.checked_add(self.storage_fund))
+            .and_then(|total| total.checked_add(self.dust))
+            .ok_or(SlashError::Overflow)
+    }
+
+    /// Balance refunded to the nominator.
+    pub fn refund(&self) -> Result<Balance, SlashError> {
+        match self.overridden.or(self.scaled) {
+            Some(refund) => Ok(refund),
+            None => self.total(),
+        }
+    }
+
+    /// Balance refunded to the nominator, saturating if its total overflows, for the
+    /// summaries that only display it.
+    pub fn saturating_refund(&self) -> Balance {
+        self.refund().unwrap_or(Balance::MAX)
+    }
+
+    /// Sums the payouts of the nominator from two operators.
+    fn merge(&self, other: &NominatorPayout) -> Result<NominatorPayout, SlashError> {
+        let overridden = if self.overridden.is_some() || other.overridden.is_some() {
+            Some(self.refund()? + other.refund()?)
+        } else {
+            None
+        };
+        // bounded by the total of the manifest, so it cannot overflow
+        Ok(NominatorPayout {
+            staked: self.staked + other.staked,
+            withdrawable: self.withdrawable + other.withdrawable,
+            storage_fund: self.storage_fund + other.storage_fund,
+            scaled: self
+                .scaled
+                .zip(other.scaled)
+                .map(|(scaled, other_scaled)| scaled + other_scaled),
+            overridden,
+            dust: self.dust + other.dust,
+        })
+    }
+}
+
+/// Difference in the slashed balance of a nominator between the payout manifest and
+/// the calculated one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutMismatch {
+    pub operator_id: OperatorId,
+    pub slashed_at: Number,
+    pub nominator_id: AccountId,
+    pub expected: Option<NominatorPayout>,
+    pub calculated: Option<NominatorPayout>,
+}
+
+impl fmt::Display for PayoutMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let display = |nominator_payout: Option<NominatorPayout>| match nominator_payout {
+            None => "none".to_string(),
+            Some(nominator_payout) => format!(
+                "{} (staked: {}, withdrawable: {}, storage fund: {})",
+                nominator_payout
+                    .total()
+                    .map_or_else(|_| "overflow".to_string(), |total| total.to_string()),
+                nominator_payout.staked,
+                nominator_payout.withdrawable,
+                nominator_payout.storage_fund
+            ),
+        };
+        write!(
+            f,
+            "Operator[{:?}] slashed at #{} Nominator[{}]: manifest {}, calculated {}",
+            self.operator_id,
+            self.slashed_at,
+            self.nominator_id,
+            display(self.expected),
+            display(self.calculated)
+        )
+    }
+}
+
+impl PayoutManifest {
+    /// Creates the manifest of the payouts, failing if their total overflows.
+    pub fn new(operators: Vec<OperatorPayout>) -> Result<Self, SlashError> {
+        let total = total_payout(&operators)?;
+        Ok(PayoutManifest::with_total(operators, total))
+    }
+
+    /// Manifest without any payout.
+    pub fn empty() -> Self {
+        PayoutManifest::with_total(vec![], Balance::zero())
+    }
+
+    fn with_total(operators: Vec<OperatorPayout>, total: Balance) -> Self {
+        PayoutManifest {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            payload_hash: payload_hash(&operators),
+            total,
+            refund_percent: full_refund(),
+            operators,
+            excluded: vec![],
+            skipped: vec![],
+        }
+    }
+
+    /// Scales the refund of every nominator down to `refund_percent` of its slashed balance,
+    /// rounding down.
+    pub fn scale(self, refund_percent: u8) -> Result<Self, SlashError> {
+        let scale_operator = |mut operator_payout: OperatorPayout| {
+            for nominator_payout in operator_payout.nominators.values_mut() {
+                nominator_payout.scaled = if refund_percent < full_refund() {
+                    Some(Percent::from_percent(refund_percent).mul_floor(nominator_payout.total()?))
+                } else {
+                    None
+                };
+            }
+            Ok::<_, SlashError>(operator_payout)
+        };
+        let operators = self
+            .operators
+            .into_iter()
+            .map(scale_operator)
+            .collect::<Result<_, _>>()?;
+        let excluded = self
+            .excluded
+            .into_iter()
+            .map(scale_operator)
+            .collect::<Result<_, _>>()?;
+
+        Ok(PayoutManifest {
+            refund_percent,
+            excluded,
+            skipped: self.skipped,
+            ..PayoutManifest::new(operators)?
+        })
+    }
+
+    /// Moves the payouts of the given accounts out of the payouts to be transferred and into
+    /// `excluded`.
+    pub fn exclude(self, accounts: &BTreeSet<AccountId>) -> Result<Self, SlashError> {
+        let mut excluded = self.excluded;
+        let mut operators = vec![];
+        for mut operator_payout in self.operators {
+            let (excluded_nominators, nominators) = std::mem::take(&mut operator_payout.nominators)
+                .into_iter()
+                .partition::<BTreeMap<_, _>, _>(|(nominator_id, _)| {
+                    accounts.contains(nominator_id)
+                });
+            if !excluded_nominators.is_empty() {
+                excluded.push(OperatorPayout {
+                    nominators: excluded_nominators,
+                    ..operator_payout.clone()
+                });
+            }
+            operator_payout.nominators = nominators;
+            operators.push(operator_payout);
+        }
+
+        Ok(PayoutManifest {
+            refund_percent: self.refund_percent,
+            excluded,
+            skipped: self.skipped,
+            ..PayoutManifest::new(operators)?
+        })
+    }
+
+    /// Replaces the refunds of the nominators with their overridden refund.
+    ///
+    /// Fails if a nominator is not found among the payouts of the operator, such as when it
+    /// was excluded.
+    pub fn apply_overrides(self, overrides: &Overrides) -> Result<Self, SlashError> {
+        let mut operators = self.operators;
+        for ((operator_id, slashed_at, nominator_id), _) in overrides.iter() {
+            let mut found = false;
+            for operator_payout in operators.iter_mut().filter(|operator_payout| {
+                operator_payout.operator_id == *operator_id
+                    && slashed_at
+                        .map_or(true, |slashed_at| operator_payout.slashed_at == slashed_at)
+            }) {
+                let slashed_at = operator_payout.slashed_at;
+                if let Some(nominator_payout) = operator_payout.nominators.get_mut(nominator_id) {
+                    nominator_payout.overridden =
+                        overrides.get(*operator_id, slashed_at, nominator_id);
+                    found = true;
+                }
+            }
+            if !found {
+                return Err(SlashError::AccountNotFound(nominator_id.clone()));
+            }
+        }
+
+        Ok(PayoutManifest {
+            refund_percent: self.refund_percent,
+            excluded: self.excluded,
+            skipped: self.skipped,
+            ..PayoutManifest::new(operators)?
+        })
+    }
+
+    /// Overridden refunds of the nominators.
+    pub fn overrides(&self) -> Overrides {
+        self.operators
+            .iter()
+            .flat_map(|operator_payout| {
+                operator_payout
+                    .nominators
+                    .iter()
+                    .filter_map(|(nominator_id, nominator_payout)| {
+                        let overridden = nominator_payout.overridden?;
+                        Some((
+                            (
+                                operator_payout.operator_id,
+                                Some(operator_payout.slashed_at),
+                                nominator_id.clone(),
+                            ),
+                            overridden,
+                        ))
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns the payouts of the operators with the payouts of each nominator across all the
+    /// operators consolidated into the first operator it nominated, so that each nominator
+    /// receives a single transfer.
+    pub fn consolidated_operators(&self) -> Result<Vec<OperatorPayout>, SlashError> {
+        let mut first_operators = BTreeMap::<AccountId, usize>::new();
+        let mut operators = self.operators.clone();
+        for index in 0..operators.len() {
+            let nominators = std::mem::take(&mut operators[index].nominators);
+            for (nominator_id, nominator_payout) in nominators {
+                match first_operators.get(&nominator_id) {
+                    None => {
+                        first_operators.insert(nominator_id.clone(), index);
+                        operators[index]
+                            .nominators
+                            .insert(nominator_id, nominator_payout);
+                    }
+                    Some(&first) => {
+                        if let Some(consolidated) =
+                            operators[first].nominators.get_mut(&nominator_id)
+                        {
+                            *consolidated = consolidated.merge(&nominator_payout)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(operators)
+    }
+
+    /// Whether the pending deposits and withdrawals whose epoch share price was not found
+    /// were refunded, as with `--fallback-raw-amount`.
+    pub fn fallback_raw_amount(&self) -> bool {
+        self.operators
+            .iter()
+            .flat_map(|operator_payout| operator_payout.unconverted.values().flatten())
+            .any(|unconverted| unconverted.refunded)
+    }
+
+    /// Whether the withdrawals unlockable before the slash were left out of the payouts, as
+    /// with `--exclude-unlockable-withdrawals`.
+    pub fn exclude_unlockable_withdrawals(&self) -> bool {
+        self.operators
+            .iter()
+            .flat_map(|operator_payout| operator_payout.unlockable.values())
+            .any(|unlockable| !unlockable.refunded)
+    }
+
+    /// Assigns the dust of each operator to its nominator with the largest payout instead of
+    /// leaving it in the treasury.
+    pub fn assign_dust_to_largest_nominator(self) -> Result<Self, SlashError> {
+        let mut operators = self.operators;
+        for operator_payout in &mut operators {
+            let mut largest: Option<(&AccountId, Balance)> = None;
+            for (nominator_id, nominator_payout) in &operator_payout.nominators {
+                let total = nominator_payout.total()?;
+                if largest.map_or(true, |(_, largest_total)| total > largest_total) {
+                    largest = Some((nominator_id, total));
+                }
+            }
+            if let Some(nominator_id) = largest.map(|(nominator_id, _)| nominator_id.clone()) {
+                let dust = operator_payout.dust;
+                if let Some(largest) = operator_payout.nominators.get_mut(&nominator_id) {
+                    largest.dust = dust;
+                }
+            }
+        }
+
+        Ok(PayoutManifest {
+            refund_percent: self.refund_percent,
+            excluded: self.excluded,
+            skipped: self.skipped,
+            ..PayoutManifest::new(operators)?
+        })
+    }
+
+    /// Whether the dust of the operators was assigned to their largest nominator.
+    pub fn dust_assigned(&self) -> bool {
+        self.operators
+            .iter()
+            .flat_map(|operator_payout| operator_payout.nominators.values())
+            .any(|nominator_payout| !nominator_payout.dust.is_zero())
+    }
+
+    /// Accounts whose payouts are excluded.
+    pub fn excluded_accounts(&self) -> BTreeSet<AccountId> {
+        self.excluded
+            .iter()
+            .flat_map(|operator_payout| operator_payout.nominators.keys().cloned())
+            .collect()
+    }
+
+    /// Total balance of the excluded payouts.
+    pub fn excluded_total(&self) -> Result<Balance, SlashError> {
+        total_payout(&self.excluded)
+    }
+
+    /// Returns the payouts of the nominators refunded less than `minimum`, including the ones
+    /// refunded nothing.
+    pub fn refunds_below(&self, minimum: Balance) -> Result<Vec<OperatorPayout>, SlashError> {
+        let mut below = vec![];
+        for operator_payout in &self.operators {
+            let mut nominators = BTreeMap::new();
+            for (nominator_id, nominator_payout) in &operator_payout.nominators {
+                if nominator_payout.refund()? < minimum {
+                    nominators.insert(nominator_id.clone(), *nominator_payout);
+                }
+            }
+            if !nominators.is_empty() {
+                below.push(OperatorPayout {
+                    nominators,
+                    ..operator_payout.clone()
+                });
+            }
+        }
+        Ok(below)
+    }
+
+    /// Moves the payouts of the given nominators of each slashed operator out of the payouts
+    /// to be transferred and into `skipped`.
+    pub fn skip(
+        self,
+        payouts: &BTreeSet<(OperatorId, Number, AccountId)>,
+    ) -> Result<Self, SlashError> {
+        let mut skipped = self.skipped;
+        let mut operators = vec![];
+        for mut operator_payout in self.operators {
+            let (operator_id, slashed_at) =
+                (operator_payout.operator_id, operator_payout.slashed_at);
+            let (skipped_nominators, nominators) = std::mem::take(&mut operator_payout.nominators)
+                .into_iter()
+                .partition::<BTreeMap<_, _>, _>(|(nominator_id, _)| {
+                    payouts.contains(&(operator_id, slashed_at, nominator_id.clone()))
+                });
+            if !skipped_nominators.is_empty() {
+                skipped.push(OperatorPayout {
+                    nominators: skipped_nominators,
+                    ..operator_payout.clone()
+                });
+            }
+            operator_payout.nominators = nominators;
+            operators.push(operator_payout);
+        }
+
+        Ok(PayoutManifest {
+            refund_percent: self.refund_percent,
+            excluded: self.excluded,
+            skipped,
+            ..PayoutManifest::new(operators)?
+        })
+    }
+
+    /// Domains of the operators whose payouts are to be transferred, with `None` for the
+    /// operators whose domain is not known.
+    pub fn domain_ids(&self) -> BTreeSet<Option<DomainId>> {
+        self.operators
+            .iter()
+            .map(|operator_payout| operator_payout.domain_id)
+            .collect()
+    }
+
+    /// Nominators of each slashed operator whose payouts are skipped.
+    pub fn skipped_payouts(&self) -> BTreeSet<(OperatorId, Number, AccountId)> {
+        self.skipped
+            .iter()
+            .flat_map(|operator_payout| {
+                operator_payout.nominators.keys().map(|nominator_id| {
+                    (
+                        operator_payout.operator_id,
+                        operator_payout.slashed_at,
+                        nominator_id.clone(),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Reads the payout manifest from the given path.
+    ///
+    /// Fails if the payload hash or the total of the manifest does not match the payouts
+    /// since the manifest may have been edited during the review, or if the totals of the
+    /// payouts overflow.
+    pub fn read(path: &Path) -> Result<Self, SlashError> {
+        let manifest: PayoutManifest = serde_json::from_slice(&std::fs::read(path)?)?;
+        if manifest.payload_hash != payload_hash(&manifest.operators) {
+            return Err(SlashError::InvalidManifest(
+                "payload hash does not match the payouts",
+            ));
+        }
+        let overflow = |_| SlashError::InvalidManifest("totals of the payouts overflow");
+        for operator_payout in manifest
+            .operators
+            .iter()
+            .chain(&manifest.excluded)
+            .chain(&manifest.skipped)
+        {
+            operator_payout.slashed_total().map_err(overflow)?;
+            operator_payout.total().map_err(overflow)?;
+        }
+        total_payout(&manifest.excluded).map_err(overflow)?;
+        if manifest.total != total_payout(&manifest.operators).map_err(overflow)? {
+            return Err(SlashError::InvalidManifest(
+                "total does not match the sum of the payouts",
+            ));
+        }
+        Ok(manifest)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SlashError> {
+        Ok(std::fs::write(path, serde_json::to_vec_pretty(self)?)?)
+    }
+
+    /// Total slashed balance of each nominator across all the operators.
+    pub fn account_totals(&self) -> Result<BTreeMap<AccountId, Balance>, SlashError> {
+        let mut account_totals = BTreeMap::<AccountId, Balance>::new();
+        for operator_payout in &self.operators {
+            for (nominator_id, total) in operator_payout.nominator_totals()? {
+                // bounded by the total of the manifest, so it cannot overflow
+                *account_totals.entry(nominator_id).or_default() += total;
+            }
+        }
+        Ok(account_totals)
+    }
+
+    /// Reads the payouts from a CSV as written by `calculate --csv`, such as one whose refunds
+    /// were adjusted by hand after review.
+    ///
+    /// Only the `operator_id`, `slashed_at`, `domain_id`, `nominator` and `refund` columns are
+    /// read, and `slashed_at` may be left out if no operator was slashed more than once, and
+    /// `domain_id` if the payouts are not split by domain. Since the
+    /// breakdown of an adjusted refund is not known, it is recorded as staked.
+    pub fn read_csv(path: &Path) -> Result<Self, SlashError> {
+        let content = std::fs::read_to_string(path)?;
+        let invalid = |line: usize, reason: String| SlashError::InvalidPayoutsCsv {
+            path: path.to_path_buf(),
+            line,
+            reason,
+        };
+        let mut lines = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        let (_, header) = lines
+            .next()
+            .ok_or_else(|| invalid(1, "header is missing".to_string()))?;
+        let columns: Vec<_> = header.split(',').map(str::trim).collect();
+        let column = |name: &str| {
+            columns
+                .iter()
+                .position(|column| *column == name)
+                .ok_or_else(|| invalid(1, format!("column `{name}` is missing")))
+        };
+        let operator_column = column("operator_id")?;
+        let slashed_at_column = column("slashed_at").ok();
+        let domain_column = column("domain_id").ok();
+        let nominator_column = column("nominator")?;
+        let refund_column = column("refund")?;
+
+        let mut operators: BTreeMap<(OperatorId, Number), BTreeMap<AccountId, NominatorPayout>> =
+            BTreeMap::new();
+        let mut domain_ids = BTreeMap::new();
+        let mut total = Balance::zero();
+        for (idx, line) in lines {
+            let fields: Vec<_> = line.split(',').map(str::trim).collect();
+            let field = |index: usize| {
+                fields
+                    .get(index)
+                    .copied()
+                    .ok_or_else(|| invalid(idx + 1, format!("expected {} fields", columns.len())))
+            };
+            let operator_id = field(operator_column)?
+                .parse::<OperatorId>()
+                .map_err(|err| invalid(idx + 1, format!("invalid operator id: {err}")))?;
+            let slashed_at = match slashed_at_column {
+                Some(slashed_at_column) => field(slashed_at_column)?
+                    .parse::<Number>()
+                    .map_err(|err| invalid(idx + 1, format!("invalid block number: {err}")))?,
+                None => 0,
+            };
+            if let Some(domain_column) = domain_column {
+                let domain_id = field(domain_column)?;
+                if !domain_id.is_empty() {
+                    let domain_id = domain_id
+                        .parse::<u32>()
+                        .map_err(|err| invalid(idx + 1, format!("invalid domain id: {err}")))?;
+                    domain_ids.insert((operator_id, slashed_at), DomainId::new(domain_id));
+                }
+            }
+            let nominator_id = AccountId::from_str(field(nominator_column)?)
+                .map_err(|err| invalid(idx + 1, format!("invalid nominator: {err}")))?;
+            let refund = field(refund_column)?
+                .parse::<Balance>()
+                .map_err(|err| invalid(idx + 1, format!("invalid refund: {err}")))?;
+            total = total.checked_add(refund).ok_or(SlashError::Overflow)?;
+            let nominator_payout = NominatorPayout {
+                staked: refund,
+                ..Default::default()
+            };
+            if operators
+                .entry((operator_id, slashed_at))
+                .or_default()
+                .insert(nominator_id, nominator_payout)
+                .is_some()
+            {
+                return Err(invalid(
+                    idx + 1,
+                    "nominator is listed twice for the operator".to_string(),
+                ));
+            }
+        }
+
+        let operators = operators
+            .into_iter()
+            .map(|((operator_id, slashed_at), nominators)| OperatorPayout {
+                operator_id,
+                domain_id: domain_ids.get(&(operator_id, slashed_at)).copied(),
+                slashed_at,
+                block_hash: Hash::zero(),
+                spec_version: 0,
+                nominators,
+                unconverted: BTreeMap::new(),
+                unlockable: BTreeMap::new(),
+                components: BTreeMap::new(),
+                dust: Balance::zero(),
+                unexplained: Balance::zero(),
+            })
+            .collect();
+        PayoutManifest::new(operators)
+    }
+
+    /// Writes the slashed balance of every nominator as CSV to the given path.
+    pub fn write_csv(&self, path: &Path) -> Result<(), SlashError> {
+        let mut csv = String::from(
+            "operator_id,slashed_at,domain_id,nominator,staked,withdrawable,storage_fund,total,refund,overridden\n",
+        );
+        for operator_payout in &self.operators {
+            let domain_id = operator_payout
+                .domain_id
+                .map(|domain_id| u32::from(domain_id).to_string())
+                .unwrap_or_default();
+            for (nominator_id, nominator_payout) in &operator_payout.nominators {
+                csv.push_str(&format!(
+                    "{},{},{domain_id},{},{},{},{},{},{},{}\n",
+                    operator_payout.operator_id,
+                    operator_payout.slashed_at,
+                    nominator_id,
+                    nominator_payout.staked,
+                    nominator_payout.withdrawable,
+                    nominator_payout.storage_fund,
+                    nominator_payout.total()?,
+                    nominator_payout.refund()?,
+                    nominator_payout.overridden.is_some()
+                ));
+            }
+        }
+        Ok(std::fs::write(path, csv)?)
+    }
+
+    /// Returns every nominator whose slashed balance in this manifest differs from the
+    /// `calculated` manifest.
+    pub fn reconcile(&self, calculated: &PayoutManifest) -> Vec<PayoutMismatch> {
+        let calculated_operators = calculated
+            .operators
+            .iter()
+            .map(|operator_payout| {
+                (
+                    (operator_payout.operator_id, operator_payout.slashed_at),
+                    &operator_payout.nominators,
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+        let empty = BTreeMap::new();
+
+        let mut mismatches = vec![];
+        for operator_payout in &self.operators {
+            let calculated_nominators = calculated_operators
+                .get(&(operator_payout.operator_id, operator_payout.slashed_at))
+                .cloned()
+                .unwrap_or(&empty);
+            let nominator_ids = operator_payout
+                .nominators
+                .keys()
+                .chain(calculated_nominators.keys())
+                .collect::<BTreeSet<_>>();
+            for nominator_id in nominator_ids {
+                let expected = operator_payout.nominators.get(nominator_id).cloned();
+                let calculated = calculated_nominators.get(nominator_id).cloned();
+                if expected != calculated {
+                    mismatches.push(PayoutMismatch {
+                        operator_id: operator_payout.operator_id,
+                        slashed_at: operator_payout.slashed_at,
+                        nominator_id: nominator_id.clone(),
+                        expected,
+                        calculated,
+                    });
+                }
+            }
+        }
+
+        mismatches
+    }
+}
+
+fn total_payout(operators: &[OperatorPayout]) -> Result<Balance, SlashError> {
+    operators
+        .iter()
+        .try_fold(Balance::zero(), |acc, operator_payout| {
+            acc.checked_add(operator_payout.total()?)
+                .ok_or(SlashError::Overflow)
+        })
+}
+
+fn payload_hash(operators: &[OperatorPayout]) -> Hash {
+    Hash::from(blake2_256(&serde_json::to_vec(operators).unwrap()))
+}
+
+fn full_refund() -> u8 {
+    100
+}
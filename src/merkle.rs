@@ -0,0 +1,157 @@
+use crate::{AccountId, Balance, Hash};
+use codec::Encode;
+use serde::{Deserialize, Serialize};
+use sp_core::hashing::blake2_256;
+use std::collections::BTreeMap;
+
+/// Merkle tree committing to the total payout of each account.
+///
+/// Leaves are the Blake2-256 hashes of the SCALE encoded `(account, amount)`, sorted by
+/// account. Each parent is the Blake2-256 hash of its two children concatenated in ascending
+/// order, so that a proof can be verified without knowing the position of the leaf. A node
+/// without a sibling is promoted to the next level as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutTree {
+    payouts: Vec<(AccountId, Balance)>,
+    /// Levels of the tree from the leaves up to the root.
+    levels: Vec<Vec<Hash>>,
+}
+
+impl PayoutTree {
+    pub fn new(payouts: BTreeMap<AccountId, Balance>) -> Self {
+        let payouts: Vec<_> = payouts.into_iter().collect();
+        let mut levels = vec![payouts
+            .iter()
+            .map(|(account_id, amount)| leaf_hash(account_id, *amount))
+            .collect::<Vec<_>>()];
+        while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+            let next_level = level
+                .chunks(2)
+                .map(|nodes| match nodes {
+                    [left, right] => hash_pair(left, right),
+                    _ => nodes[0],
+                })
+                .collect();
+            levels.push(next_level);
+        }
+
+        PayoutTree { payouts, levels }
+    }
+
+    /// Root of the tree, which is the zero hash if there are no payouts.
+    pub fn root(&self) -> Hash {
+        self.levels
+            .last()
+            .and_then(|level| level.first())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the inclusion proof of the payout of the account, if any.
+    pub fn proof(&self, account_id: &AccountId) -> Option<PayoutProof> {
+        let mut index = self
+            .payouts
+            .binary_search_by(|(other, _)| other.cmp(account_id))
+            .ok()?;
+        let amount = self.payouts[index].1;
+        let mut proof = vec![];
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+
+        Some(PayoutProof {
+            account_id: account_id.clone(),
+            amount,
+            proof,
+        })
+    }
+
+    /// Returns the inclusion proofs of all the payouts.
+    pub fn proofs(&self) -> Vec<PayoutProof> {
+        self.payouts
+            .iter()
+            .filter_map(|(account_id, _)| self.proof(account_id))
+            .collect()
+    }
+}
+
+/// Proof that an account is owed `amount` in the payout set committed to by the root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayoutProof {
+    pub account_id: AccountId,
+    pub amount: Balance,
+    /// Siblings of the nodes on the path from the leaf to the root.
+    pub proof: Vec<Hash>,
+}
+
+impl PayoutProof {
+    /// Returns true if the proof leads from the payout to the given root.
+    pub fn verify(&self, root: &Hash) -> bool {
+        let computed = self
+            .proof
+            .iter()
+            .fold(leaf_hash(&self.account_id, self.amount), |node, sibling| {
+                hash_pair(&node, sibling)
+            });
+        computed == *root
+    }
+}
+
+fn leaf_hash(account_id: &AccountId, amount: Balance) -> Hash {
+    Hash::from(blake2_256(&(account_id, amount).encode()))
+}
+
+fn hash_pair(a: &Hash, b: &Hash) -> Hash {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    Hash::from(blake2_256(&[left.as_bytes(), right.as_bytes()].concat()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payouts(count: u8) -> BTreeMap<AccountId, Balance> {
+        (0..count)
+            .map(|byte| (AccountId::new([byte; 32]), Balance::from(byte) * 100 + 1))
+            .collect()
+    }
+
+    #[test]
+    fn proofs_verify_against_root() {
+        for count in 1..=9 {
+            let tree = PayoutTree::new(payouts(count));
+            let root = tree.root();
+            let proofs = tree.proofs();
+            assert_eq!(proofs.len(), usize::from(count));
+            for proof in proofs {
+                assert!(proof.verify(&root), "{count} payouts: {proof:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn tampered_proof_fails() {
+        let tree = PayoutTree::new(payouts(5));
+        let mut proof = tree.proof(&AccountId::new([3; 32])).unwrap();
+        proof.amount += 1;
+        assert!(!proof.verify(&tree.root()));
+    }
+
+    #[test]
+    fn single_payout_is_root() {
+        let tree = PayoutTree::new(payouts(1));
+        let account_id = AccountId::new([0; 32]);
+        assert_eq!(tree.root(), leaf_hash(&account_id, 1));
+        assert!(tree.proof(&account_id).unwrap().proof.is_empty());
+    }
+
+    #[test]
+    fn empty_payouts() {
+        let tree = PayoutTree::new(BTreeMap::new());
+        assert_eq!(tree.root(), Hash::zero());
+        assert!(tree.proof(&AccountId::new([0; 32])).is_none());
+    }
+}
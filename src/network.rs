@@ -0,0 +1,244 @@
+use crate::error::SlashError;
+use crate::properties::ChainProperties;
+use crate::types::versioned::RuntimeUpgrades;
+use crate::{Hash, Number};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Genesis hash of Gemini 3h, the network the refunds are for.
+pub const GEMINI_3H_GENESIS_HASH: &str =
+    "0x0c121c75f4ef450f40619e1fca9d1e8e7fbabc42c895bc4790801e85d5a91c34";
+
+/// Subspace network the refunds are run on, selecting the defaults of the settings that
+/// differ between the networks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Network {
+    #[default]
+    #[value(name = "gemini-3h")]
+    #[serde(rename = "gemini-3h")]
+    Gemini3h,
+    #[value(name = "gemini-3g")]
+    #[serde(rename = "gemini-3g")]
+    Gemini3g,
+    Taurus,
+    Mainnet,
+    /// Any other network, whose endpoints and genesis hash must be given.
+    Custom,
+}
+
+/// Settings of a network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkPreset {
+    /// RPC endpoints tried in order when none is given.
+    pub endpoints: &'static [&'static str],
+    /// Genesis hash the node must report before anything is signed or submitted, if known.
+    pub genesis_hash: Option<&'static str>,
+    /// Properties used when the chain does not declare its own.
+    pub ss58_format: u16,
+    pub token_decimals: u32,
+    pub token_symbol: &'static str,
+    /// Spec versions at which the `Domains` pallet changed, before which its storage is
+    /// decoded with the layouts of `types::versioned` and its share prices as `Perbill`.
+    pub upgrades: RuntimeUpgrades,
+    /// Subscan explorer of the network, if any.
+    pub subscan_url: Option<&'static str>,
+}
+
+impl Network {
+    pub fn preset(self) -> NetworkPreset {
+        match self {
+            Network::Gemini3h => NetworkPreset {
+                endpoints: &["wss://rpc-0.gemini-3h.subspace.network/ws"],
+                genesis_hash: Some(GEMINI_3H_GENESIS_HASH),
+                ss58_format: 2254,
+                token_decimals: 18,
+                token_symbol: "tSSC",
+                upgrades: RuntimeUpgrades {
+                    unlock_block_spec_version: 5,
+                    perquintill_share_price_spec_version: u32::MAX,
+                },
+                subscan_url: None,
+            },
+            // launched before the unlock block was recorded
+            Network::Gemini3g => NetworkPreset {
+                endpoints: &["wss://rpc-0.gemini-3g.subspace.network/ws"],
+                genesis_hash: None,
+                ss58_format: 2254,
+                token_decimals: 18,
+                token_symbol: "tSSC",
+                upgrades: RuntimeUpgrades {
+                    unlock_block_spec_version: u32::MAX,
+                    perquintill_share_price_spec_version: u32::MAX,
+                },
+                subscan_url: None,
+            },
+            // launched after the unlock block was recorded and the share prices were
+            // computed as `Perquintill`
+            Network::Taurus => NetworkPreset {
+                endpoints: &["wss://rpc-0.taurus.autonomys.xyz/ws"],
+                genesis_hash: Some(
+                    "0x295aeafca762a304d92ee1505548695091f6082d3f0aa4d092ac3cd6397a6c5e",
+                ),
+                ss58_format: 6094,
+                token_decimals: 18,
+                token_symbol: "tAI3",
+                upgrades: RuntimeUpgrades {
+                    unlock_block_spec_version: 0,
+                    perquintill_share_price_spec_version: 0,
+                },
+                subscan_url: None,
+            },
+            Network::Mainnet => NetworkPreset {
+                endpoints: &["wss://rpc.mainnet.subspace.foundation/ws"],
+                genesis_hash: Some(
+                    "0x66455a580aabff303720aa83adbe6c44502922251c03ba73686d5245da9e21bd",
+                ),
+                ss58_format: 6094,
+                token_decimals: 18,
+                token_symbol: "AI3",
+                upgrades: RuntimeUpgrades {
+                    unlock_block_spec_version: 0,
+                    perquintill_share_price_spec_version: 0,
+                },
+                subscan_url: Some("https://autonomys.subscan.io"),
+            },
+            Network::Custom => NetworkPreset {
+                endpoints: &[],
+                genesis_hash: None,
+                ss58_format: ChainProperties::default().ss58_format,
+                token_decimals: 0,
+                token_symbol: "",
+                upgrades: RuntimeUpgrades {
+                    unlock_block_spec_version: 0,
+                    perquintill_share_price_spec_version: 0,
+                },
+                subscan_url: None,
+            },
+        }
+    }
+
+    pub fn genesis_hash(self) -> Option<Hash> {
+        self.preset()
+            .genesis_hash
+            .map(|genesis_hash| Hash::from_str(genesis_hash).expect("genesis hash is valid"))
+    }
+
+    /// Returns the links to the extrinsic `<block_number>-<extrinsic_index>` in the Subscan
+    /// explorer of the network if any, and in Polkadot.js connected to `rpc_url`, which works
+    /// for any network.
+    pub fn explorer_urls(
+        self,
+        rpc_url: &str,
+        block_number: Number,
+        block_hash: Hash,
+        extrinsic_index: u32,
+    ) -> Vec<String> {
+        let mut explorer_urls = vec![];
+        if let Some(subscan_url) = self.preset().subscan_url {
+            explorer_urls.push(format!(
+                "{subscan_url}/extrinsic/{block_number}-{extrinsic_index}"
+            ));
+        }
+        explorer_urls.push(format!(
+            "https://polkadot.js.org/apps/?rpc={}#/explorer/query/{block_hash:?}",
+            encode_uri_component(rpc_url)
+        ));
+        explorer_urls
+    }
+
+    /// Returns the spec versions at which the `Domains` pallet changed on the network.
+    pub fn upgrades(self) -> RuntimeUpgrades {
+        self.preset().upgrades
+    }
+
+    /// Returns the properties of the network, used when the chain does not declare its own.
+    pub fn properties(self) -> ChainProperties {
+        let preset = self.preset();
+        ChainProperties {
+            ss58_format: preset.ss58_format,
+            token_decimals: preset.token_decimals,
+            token_symbol: preset.token_symbol.to_string(),
+        }
+    }
+}
+
+/// Percent-encodes the characters of `value` that are not unreserved in a URL.
+fn encode_uri_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Refuses to sign or submit against a node of another network than the expected one, on
+/// which the refunds would be replayed if the same sudo key works there.
+///
+/// Fails as well if the genesis hash of the network is not known, since the network cannot
+/// be checked then.
+pub fn check_genesis_hash(found: Hash, expected: Option<Hash>) -> Result<(), SlashError> {
+    let expected = expected.ok_or(SlashError::GenesisHashNotSet)?;
+    if found != expected {
+        return Err(SlashError::GenesisHashMismatch { expected, found });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    #[test]
+    fn presets_are_valid() {
+        for network in Network::value_variants() {
+            network.genesis_hash();
+        }
+        assert_eq!(
+            Network::default().genesis_hash(),
+            Hash::from_str(GEMINI_3H_GENESIS_HASH).ok()
+        );
+        assert_eq!(Network::from_str("gemini-3h", false), Ok(Network::Gemini3h));
+        assert!(Network::Custom.preset().endpoints.is_empty());
+        // the library defaults to the upgrades of the default network
+        assert_eq!(Network::default().upgrades(), RuntimeUpgrades::default());
+    }
+
+    #[test]
+    fn explorer_urls_link_to_the_extrinsic() {
+        let block_hash = Hash::repeat_byte(1);
+        let polkadot_js_url = format!(
+            "https://polkadot.js.org/apps/?rpc=wss%3A%2F%2Frpc.example%2Fws#/explorer/query/{block_hash:?}"
+        );
+        assert_eq!(
+            Network::Mainnet.explorer_urls("wss://rpc.example/ws", 12, block_hash, 3),
+            vec![
+                "https://autonomys.subscan.io/extrinsic/12-3".to_string(),
+                polkadot_js_url.clone(),
+            ]
+        );
+        assert_eq!(
+            Network::Gemini3h.explorer_urls("wss://rpc.example/ws", 12, block_hash, 3),
+            vec![polkadot_js_url]
+        );
+    }
+
+    #[test]
+    fn genesis_hash_must_match() {
+        let genesis_hash = Network::Gemini3h.genesis_hash();
+        assert!(check_genesis_hash(genesis_hash.unwrap(), genesis_hash).is_ok());
+        assert!(matches!(
+            check_genesis_hash(Hash::zero(), genesis_hash),
+            Err(SlashError::GenesisHashMismatch { .. })
+        ));
+        assert!(matches!(
+            check_genesis_hash(Hash::zero(), None),
+            Err(SlashError::GenesisHashNotSet)
+        ));
+    }
+}
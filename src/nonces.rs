@@ -0,0 +1,72 @@
+use std::collections::BTreeSet;
+
+/// Nonces of the batches being submitted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NonceTracker {
+    pending: BTreeSet<u32>,
+    /// Nonces of the batches dropped without being included, which are free to be used.
+    dropped: BTreeSet<u32>,
+}
+
+impl NonceTracker {
+    pub fn new(pending: impl IntoIterator<Item = u32>) -> Self {
+        NonceTracker {
+            pending: pending.into_iter().collect(),
+            dropped: BTreeSet::new(),
+        }
+    }
+
+    /// Records that the batch with the nonce was resolved, leaving its nonce unused if it was
+    /// dropped.
+    pub fn resolve(&mut self, nonce: u32, dropped: bool) {
+        self.pending.remove(&nonce);
+        if dropped {
+            self.dropped.insert(nonce);
+        }
+    }
+
+    /// Records that the nonce of a dropped batch was used by another extrinsic.
+    pub fn fill(&mut self, nonce: u32) {
+        self.dropped.remove(&nonce);
+    }
+
+    /// Whether the next nonce of the signer is one of a dropped batch, which blocks the
+    /// pending batches after it in the future queue of the transaction pool.
+    pub fn is_gap(&self, next_nonce: u32) -> bool {
+        self.dropped.contains(&next_nonce) && self.pending.iter().any(|nonce| *nonce > next_nonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropped_batch_before_pending_ones_is_a_gap() {
+        let mut nonces = NonceTracker::new(10..13);
+        nonces.resolve(10, true);
+        assert!(nonces.is_gap(10));
+        // other nonces are not dropped
+        assert!(!nonces.is_gap(11));
+
+        nonces.fill(10);
+        assert!(!nonces.is_gap(10));
+    }
+
+    #[test]
+    fn included_batch_is_not_a_gap() {
+        let mut nonces = NonceTracker::new(10..13);
+        nonces.resolve(10, false);
+        assert!(!nonces.is_gap(10));
+    }
+
+    #[test]
+    fn dropped_last_batch_is_not_a_gap() {
+        let mut nonces = NonceTracker::new(10..13);
+        nonces.resolve(10, false);
+        nonces.resolve(11, false);
+        nonces.resolve(12, true);
+        // no pending batch is blocked behind it
+        assert!(!nonces.is_gap(12));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{Balance, Hash};
+use serde::Serialize;
+use sp_domains::OperatorId;
+use std::fmt;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Time to wait for the webhook to accept a notification.
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Milestone of a run posted to the webhook.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Milestone {
+    CalculationComplete {
+        operators: usize,
+        nominators: usize,
+        total: Balance,
+        payload_hash: Hash,
+    },
+    BatchIncluded {
+        operator_id: OperatorId,
+        nonce: u32,
+        nominators: usize,
+        total: Balance,
+        block_hash: Hash,
+    },
+    BatchFailed {
+        operator_id: OperatorId,
+        nonce: u32,
+        nominators: usize,
+        reason: String,
+    },
+    VerificationComplete {
+        payout_mismatches: usize,
+        transfer_mismatches: usize,
+    },
+}
+
+impl fmt::Display for Milestone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Milestone::CalculationComplete {
+                operators,
+                nominators,
+                total,
+                payload_hash,
+            } => write!(
+                f,
+                "Calculation complete: {total} to refund to {nominators} nominators of {operators} operators, manifest {payload_hash:?}"
+            ),
+            Milestone::BatchIncluded {
+                operator_id,
+                nonce,
+                nominators,
+                total,
+                block_hash,
+            } => write!(
+                f,
+                "Batch for Operator[{operator_id:?}] with Nonce[{nonce}] refunding {total} to {nominators} nominators included in block {block_hash:?}"
+            ),
+            Milestone::BatchFailed {
+                operator_id,
+                nonce,
+                nominators,
+                reason,
+            } => write!(
+                f,
+                "Batch for Operator[{operator_id:?}] with Nonce[{nonce}] for {nominators} nominators failed: {reason}"
+            ),
+            Milestone::VerificationComplete {
+                payout_mismatches,
+                transfer_mismatches,
+            } => write!(
+                f,
+                "Verification complete: {payout_mismatches} payout mismatches, {transfer_mismatches} transfer mismatches"
+            ),
+        }
+    }
+}
+
+/// Body posted to the webhook.
+///
+/// The `text` field is what chat webhooks such as Slack's display, the rest is for the
+/// services that process the milestones.
+#[derive(Debug, Serialize)]
+struct Notification<'a> {
+    text: String,
+    run_id: &'a str,
+    #[serde(flatten)]
+    milestone: &'a Milestone,
+}
+
+impl<'a> Notification<'a> {
+    fn new(run_id: &'a str, milestone: &'a Milestone) -> Self {
+        Notification {
+            text: format!("[{run_id}] {milestone}"),
+            run_id,
+            milestone,
+        }
+    }
+}
+
+/// Posts the milestones of a run to a webhook, doing nothing when no webhook is set.
+pub struct Notifier {
+    url: Option<String>,
+    run_id: String,
+    client: reqwest::Client,
+}
+
+impl Notifier {
+    pub fn new(url: Option<String>, run_id: &str) -> Self {
+        Notifier {
+            url,
+            run_id: run_id.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Posts the milestone to the webhook.
+    ///
+    /// A failed notification is only logged, since it must not fail the run.
+    pub async fn notify(&self, milestone: Milestone) {
+        let Some(url) = &self.url else {
+            return;
+        };
+        let result = self
+            .client
+            .post(url)
+            .timeout(NOTIFY_TIMEOUT)
+            .json(&Notification::new(&self.run_id, &milestone))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match result {
+            Ok(_) => debug!("Notified {milestone}"),
+            Err(err) => warn!("Failed to notify {milestone}: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notification_carries_text_and_milestone_fields() {
+        let milestone = Milestone::BatchFailed {
+            operator_id: 7,
+            nonce: 3,
+            nominators: 2,
+            reason: "dropped".to_string(),
+        };
+        let notification = serde_json::to_value(Notification::new("run-1", &milestone)).unwrap();
+
+        assert_eq!(
+            notification,
+            serde_json::json!({
+                "text": "[run-1] Batch for Operator[7] with Nonce[3] for 2 nominators failed: dropped",
+                "run_id": "run-1",
+                "event": "batch_failed",
+                "operator_id": 7,
+                "nonce": 3,
+                "nominators": 2,
+                "reason": "dropped",
+            })
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use crate::calls::OpaqueCall;
+use crate::error::SlashError;
+use crate::{AccountId, Balance, Hash, Number};
+use codec::{Compact, Decode, Encode};
+use serde::{Deserialize, Serialize};
+use sp_core::hashing::blake2_256;
+use sp_core::sr25519::{Public, Signature};
+use sp_core::Bytes;
+use sp_domains::OperatorId;
+use sp_runtime::generic::Era;
+use sp_runtime::traits::Verify;
+use sp_runtime::{MultiAddress, MultiSignature};
+use std::path::{Path, PathBuf};
+
+/// Version byte of a signed extrinsic of format version 4.
+const SIGNED_EXTRINSIC_VERSION: u8 = 0b1000_0000 | 4;
+
+/// Batch call exported to be signed on an offline machine.
+///
+/// The signer signs `signing_payload` with its sr25519 key and fills in `signature`, after
+/// which the batch can be submitted with `submit-signed`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnsignedBatch {
+    pub operator_id: OperatorId,
+    /// Block the operator was slashed at.
+    #[serde(default)]
+    pub slashed_at: Number,
+    pub nominators: Vec<AccountId>,
+    /// Whether the transfers are executed once the extrinsic is included.
+    pub executes: bool,
+    /// Total balance transferred from the treasury by the batch.
+    #[serde(default)]
+    pub total: Balance,
+    pub signer: AccountId,
+    /// Account the calls are dispatched from with `Sudo`, which is the signer unless the
+    /// batch is signed for a proxy or a multisig.
+    #[serde(default)]
+    pub sudo: Option<AccountId>,
+    pub nonce: u32,
+    /// SCALE encoded era of the extrinsic.
+    pub era: Bytes,
+    pub genesis_hash: Hash,
+    pub spec_version: u32,
+    pub transaction_version: u32,
+    /// SCALE encoded call, including the pallet and call index.
+    pub call: Bytes,
+    /// SCALE encoded signed extensions included in the extrinsic.
+    pub signed_extra: Bytes,
+    /// SCALE encoded data signed along with the signed extensions without being included in
+    /// the extrinsic, starting with the spec version, the transaction version and the genesis
+    /// hash.
+    #[serde(default)]
+    pub additional_signed: Bytes,
+    /// Payload to be signed, which is hashed with Blake2-256 if longer than 256 bytes.
+    pub signing_payload: Bytes,
+    /// Sr25519 signature of `signing_payload` by `signer`.
+    #[serde(default)]
+    pub signature: Option<Bytes>,
+}
+
+impl UnsignedBatch {
+    /// Returns the payload to be signed for the call along with the signed extensions and
+    /// their additional signed data.
+    pub fn signing_payload(
+        call: &OpaqueCall,
+        signed_extra: &[u8],
+        additional_signed: &[u8],
+    ) -> Bytes {
+        let mut payload = call.0.clone();
+        payload.extend(signed_extra);
+        payload.extend(additional_signed);
+        if payload.len() > 256 {
+            payload = blake2_256(&payload).to_vec();
+        }
+        Bytes(payload)
+    }
+
+    /// Returns the account the calls are dispatched from with `Sudo`.
+    pub fn sudo_account(&self) -> &AccountId {
+        self.sudo.as_ref().unwrap_or(&self.signer)
+    }
+
+    /// Returns the tip of the batch.
+    pub fn tip(&self) -> Result<Balance, SlashError> {
+        decode_tip(&self.signed_extra)
+    }
+
+    /// Returns the path of the file of the batch with `nonce` in `dir`.
+    pub fn path(dir: &Path, nonce: u32) -> PathBuf {
+        dir.join(format!("batch-{nonce}.json"))
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SlashError> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SlashError> {
+        Ok(std::fs::write(path, serde_json::to_vec_pretty(self)?)?)
+    }
+
+    /// Reads all the batches in `dir`, sorted by nonce.
+    pub fn read_dir(dir: &Path) -> Result<Vec<Self>, SlashError> {
+        let mut batches = vec![];
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                batches.push(UnsignedBatch::read(&path)?);
+            }
+        }
+        batches.sort_by_key(|batch| batch.nonce);
+        Ok(batches)
+    }
+
+    /// Checks that `signing_payload` is the one of the call and the signed extensions
+    /// submitted with the signature, so that a payload signed for another call is not
+    /// submitted along with this one.
+    fn check_signing_payload(&self) -> Result<(), SlashError> {
+        let (era, Compact(nonce)) = <(Era, Compact<u32>)>::decode(&mut &*self.signed_extra)?;
+        if era.encode() != self.era.0 || nonce != self.nonce {
+            return Err(SlashError::InvalidSignature(
+                "signed extensions do not match the era and nonce of the batch",
+            ));
+        }
+        let (spec_version, transaction_version, genesis_hash) =
+            <(u32, u32, Hash)>::decode(&mut &*self.additional_signed)?;
+        if (spec_version, transaction_version, genesis_hash)
+            != (
+                self.spec_version,
+                self.transaction_version,
+                self.genesis_hash,
+            )
+        {
+            return Err(SlashError::InvalidSignature(
+                "additional signed data does not match the runtime of the batch",
+            ));
+        }
+        let signing_payload = Self::signing_payload(
+            &OpaqueCall(self.call.0.clone()),
+            &self.signed_extra,
+            &self.additional_signed,
+        );
+        if signing_payload != self.signing_payload {
+            return Err(SlashError::InvalidSignature(
+                "signing payload does not match the call and its signed extensions",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the encoded signed extrinsic, failing if the signature is missing or does not
+    /// match the signing payload of the call.
+    pub fn signed_extrinsic(&self) -> Result<Vec<u8>, SlashError> {
+        self.check_signing_payload()?;
+        let signature = self
+            .signature
+            .as_ref()
+            .ok_or(SlashError::InvalidSignature("signature is missing"))?;
+        let signature = <[u8; 64]>::try_from(signature.0.as_slice())
+            .map(Signature::from_raw)
+            .map_err(|_| SlashError::InvalidSignature("signature must be 64 bytes"))?;
+        let signer = Public::from_raw(self.signer.clone().into());
+        if !signature.verify(self.signing_payload.0.as_slice(), &signer) {
+            return Err(SlashError::InvalidSignature(
+                "signature does not match the signing payload",
+            ));
+        }
+
+        let mut extrinsic = vec![SIGNED_EXTRINSIC_VERSION];
+        MultiAddress::<AccountId, ()>::Id(self.signer.clone()).encode_to(&mut extrinsic);
+        MultiSignature::Sr25519(signature).encode_to(&mut extrinsic);
+        extrinsic.extend(&self.signed_extra.0);
+        extrinsic.extend(&self.call.0);
+        // extrinsics are encoded with their length as prefix
+        Ok(extrinsic.encode())
+    }
+}
+
+/// Decodes the tip from the encoded signed extensions, which start with the era, the nonce
+/// and the tip.
+pub fn decode_tip(signed_extra: &[u8]) -> Result<Balance, SlashError> {
+    let (_, _, Compact(tip)) =
+        <(Era, Compact<u32>, Compact<Balance>)>::decode(&mut &*signed_extra)?;
+    Ok(tip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::sr25519::Pair;
+    use sp_core::Pair as PairT;
+
+    fn signed_batch(signer: &Pair) -> UnsignedBatch {
+        let era = Era::mortal(64, 100);
+        let call = OpaqueCall(vec![0, 7, 4, 1, 2]);
+        let signed_extra = (
+            era,
+            Compact(3u32),
+            Compact(Balance::from(0u32)),
+            None::<u32>,
+        )
+            .encode();
+        let genesis_hash = Hash::repeat_byte(1);
+        let additional_signed = (5u32, 1u32, genesis_hash, Hash::repeat_byte(2)).encode();
+        let signing_payload =
+            UnsignedBatch::signing_payload(&call, &signed_extra, &additional_signed);
+        UnsignedBatch {
+            operator_id: 0,
+            slashed_at: 10,
+            nominators: vec![],
+            executes: true,
+            total: 0,
+            signer: AccountId::from(signer.public()),
+            sudo: None,
+            nonce: 3,
+            era: era.encode().into(),
+            genesis_hash,
+            spec_version: 5,
+            transaction_version: 1,
+            call: call.0.into(),
+            signed_extra: signed_extra.into(),
+            additional_signed: additional_signed.into(),
+            signature: Some(signer.sign(&signing_payload).as_ref().to_vec().into()),
+            signing_payload,
+        }
+    }
+
+    #[test]
+    fn signing_payload_is_checked_against_the_call() {
+        let alice = Pair::from_string("//Alice", None).unwrap();
+        let batch = signed_batch(&alice);
+        assert!(batch.signed_extrinsic().is_ok());
+
+        // payload and signature of another call
+        let mut other = batch.clone();
+        other.call = vec![0, 7, 4, 9, 9].into();
+        assert!(matches!(
+            other.signed_extrinsic(),
+            Err(SlashError::InvalidSignature(_))
+        ));
+
+        let mut other = batch.clone();
+        other.nonce = 4;
+        assert!(matches!(
+            other.signed_extrinsic(),
+            Err(SlashError::InvalidSignature(_))
+        ));
+
+        let mut other = batch;
+        other.spec_version = 6;
+        assert!(matches!(
+            other.signed_extrinsic(),
+            Err(SlashError::InvalidSignature(_))
+        ));
+    }
+
+    #[test]
+    fn tip_is_decoded_from_signed_extra() {
+        // era, nonce and tip followed by the asset of the tip
+        let signed_extra = (
+            Era::mortal(64, 100),
+            Compact(7u32),
+            Compact(Balance::from(1_000u32)),
+            None::<u32>,
+        )
+            .encode();
+        assert_eq!(decode_tip(&signed_extra).unwrap(), 1_000);
+        assert!(decode_tip(&signed_extra[..2]).is_err());
+    }
+}
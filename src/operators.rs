@@ -0,0 +1,525 @@
+use crate::backend::{ChainBackend, STORAGE_KEYS_PAGE_SIZE};
+use crate::calculator::{find_operator, get_operator_storage_fund_balance, get_spec_version};
+use crate::error::SlashError;
+use crate::types::versioned::{Layout, RuntimeUpgrades, SharePricePrecision};
+use crate::types::{Operator, OperatorStatus, SharePrice};
+use crate::{Api, Balance, Hash, Number};
+use codec::Decode;
+use futures::future::join_all;
+use sp_core::storage::StorageKey;
+use sp_domains::{DomainId, OperatorId};
+use std::collections::{BTreeMap, BTreeSet};
+use substrate_api_client::GetStorage;
+use tracing::warn;
+
+/// Operator that is slashed or pending slash, as listed to prepare the next refund run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlashedOperator {
+    pub operator_id: OperatorId,
+    pub domain_id: DomainId,
+    pub status: OperatorStatus,
+    pub total_stake: Balance,
+    pub nominator_count: u32,
+    /// First block with the slashed status, `None` if the state of the earlier blocks is
+    /// pruned from the node.
+    pub status_changed_at: Option<Number>,
+}
+
+/// Operators of the slashed-operator input that disagree with `Domains::PendingSlashes`,
+/// such as a mistyped operator id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PendingSlashMismatch {
+    /// Operators listed that are neither pending slash nor slashed at the block they are
+    /// listed as slashed at, along with that block.
+    pub not_slashed: Vec<(OperatorId, Number)>,
+    /// Operators pending slash at one of the blocks that are not listed, along with their
+    /// domain and the first of the blocks they are pending slash at.
+    pub not_listed: Vec<(DomainId, OperatorId, Number)>,
+}
+
+/// State of an operator at a block, as inspected to answer staking questions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperatorInspection {
+    pub operator_id: OperatorId,
+    pub block_number: Number,
+    pub block_hash: Hash,
+    pub spec_version: u32,
+    pub operator: Operator,
+    /// Status of the operator including `PendingSlash`, unlike the stored one.
+    pub status: OperatorStatus,
+    pub nominator_count: u32,
+    pub storage_fund_balance: Balance,
+    /// Share price of the current epoch so far, from the total shares and stake of the
+    /// operator since the price of an epoch is only stored once it ends.
+    pub share_price: SharePrice,
+}
+
+/// Returns the state of the operator at the block.
+pub async fn inspect_operator<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    at: Number,
+    upgrades: RuntimeUpgrades,
+) -> Result<OperatorInspection, SlashError> {
+    let block_hash = backend
+        .block_hash(at)
+        .await?
+        .ok_or(SlashError::BlockNotFound(at))?;
+    // storage is decoded with the layout of the runtime at the block
+    let spec_version = get_spec_version(backend, block_hash).await?;
+    let operator = find_operator(
+        backend,
+        operator_id,
+        block_hash,
+        Layout::at(spec_version, upgrades),
+    )
+    .await?
+    .ok_or(SlashError::StorageNotFound {
+        pallet: "Domains",
+        storage: "Operators",
+        at: block_hash,
+    })?;
+    let status = get_status(backend, operator_id, &operator, block_hash).await?;
+    let nominator_count = backend
+        .storage_map::<_, u32>("Domains", "NominatorCount", operator_id, block_hash)
+        .await?
+        .unwrap_or_default();
+    let storage_fund_balance =
+        get_operator_storage_fund_balance(backend, operator_id, block_hash).await?;
+    let share_price = SharePrice::new(
+        operator.current_total_shares,
+        operator.current_total_stake,
+        SharePricePrecision::at(spec_version, upgrades),
+    );
+    Ok(OperatorInspection {
+        operator_id,
+        block_number: at,
+        block_hash,
+        spec_version,
+        operator,
+        status,
+        nominator_count,
+        storage_fund_balance,
+        share_price,
+    })
+}
+
+/// Returns every operator registered at the block whose status is `Slashed` or
+/// `PendingSlash`, only keeping the operators of the given domain if any.
+pub async fn list_slashed_operators(
+    api: &Api,
+    at: Number,
+    domain_id: Option<DomainId>,
+    upgrades: RuntimeUpgrades,
+) -> Result<Vec<SlashedOperator>, SlashError> {
+    let block_hash = api
+        .block_hash(at)
+        .await?
+        .ok_or(SlashError::BlockNotFound(at))?;
+    let layout = Layout::at(get_spec_version(api, block_hash).await?, upgrades);
+    let operator_ids = get_operator_ids(api, block_hash).await?;
+
+    let statuses = join_all(operator_ids.into_iter().map(|operator_id| async move {
+        let operator = find_operator(api, operator_id, block_hash, layout)
+            .await?
+            .ok_or(SlashError::StorageNotFound {
+                pallet: "Domains",
+                storage: "Operators",
+                at: block_hash,
+            })?;
+        let status = get_status(api, operator_id, &operator, block_hash).await?;
+        Ok::<_, SlashError>((operator_id, operator, status))
+    }))
+    .await;
+
+    let mut slashed_operators = vec![];
+    for status in statuses {
+        let (operator_id, operator, status) = status?;
+        let in_domain = domain_id.map_or(true, |domain_id| operator.current_domain_id == domain_id);
+        if !is_slashed(&status) || !in_domain {
+            continue;
+        }
+        let nominator_count = api
+            .storage_map::<_, u32>("Domains", "NominatorCount", operator_id, block_hash)
+            .await?
+            .unwrap_or_default();
+        let status_changed_at = match find_status_change(api, operator_id, at).await {
+            Ok(number) => Some(number),
+            Err(err) => {
+                warn!("Block at which Operator[{operator_id:?}] was slashed not found: {err}");
+                None
+            }
+        };
+        slashed_operators.push(SlashedOperator {
+            operator_id,
+            domain_id: operator.current_domain_id,
+            status,
+            total_stake: operator.current_total_stake,
+            nominator_count,
+            status_changed_at,
+        });
+    }
+
+    Ok(slashed_operators)
+}
+
+/// Cross-references the listed operators with the operators pending slash in
+/// `Domains::PendingSlashes`.
+///
+/// Each operator is checked at the block it is listed as slashed at, since the operators
+/// leave `Domains::PendingSlashes` and then `Domains::Operators` once their slash is
+/// processed. Listed operators that are not pending slash anymore are only reported if they
+/// are not slashed either. The operators pending slash that are not listed are looked up at
+/// these blocks and at the block `at`.
+pub async fn check_pending_slashes(
+    api: &Api,
+    at: Number,
+    slashed_operators: &[(OperatorId, Number)],
+    upgrades: RuntimeUpgrades,
+) -> Result<PendingSlashMismatch, SlashError> {
+    let listed: BTreeSet<OperatorId> = slashed_operators
+        .iter()
+        .map(|(operator_id, _)| *operator_id)
+        .collect();
+    let mut slash_blocks = BTreeMap::<Number, Vec<OperatorId>>::new();
+    for &(operator_id, slashed_at) in slashed_operators {
+        slash_blocks
+            .entry(slashed_at)
+            .or_default()
+            .push(operator_id);
+    }
+    slash_blocks.entry(at).or_default();
+
+    let mut mismatch = PendingSlashMismatch::default();
+    for (number, operator_ids) in slash_blocks {
+        let block_hash = api
+            .block_hash(number)
+            .await?
+            .ok_or(SlashError::BlockNotFound(number))?;
+        let pending_slashes = get_pending_slashes(api, block_hash).await?;
+        for operator_id in operator_ids {
+            let pending = pending_slashes
+                .values()
+                .any(|operator_ids| operator_ids.contains(&operator_id));
+            if !pending && !is_slashed_at(api, operator_id, number, upgrades).await? {
+                mismatch.not_slashed.push((operator_id, number));
+            }
+        }
+        for (domain_id, operator_id) in not_listed(&pending_slashes, &listed) {
+            let reported = mismatch
+                .not_listed
+                .iter()
+                .any(|(_, reported, _)| *reported == operator_id);
+            if !reported {
+                mismatch.not_listed.push((domain_id, operator_id, number));
+            }
+        }
+    }
+    Ok(mismatch)
+}
+
+/// Returns the operators pending slash that are not listed.
+fn not_listed(
+    pending_slashes: &BTreeMap<DomainId, BTreeSet<OperatorId>>,
+    listed: &BTreeSet<OperatorId>,
+) -> Vec<(DomainId, OperatorId)> {
+    pending_slashes
+        .iter()
+        .flat_map(|(&domain_id, operator_ids)| {
+            operator_ids
+                .difference(listed)
+                .map(move |&operator_id| (domain_id, operator_id))
+        })
+        .collect()
+}
+
+/// Returns the operators pending slash of each domain at the block.
+async fn get_pending_slashes(
+    api: &Api,
+    block_hash: Hash,
+) -> Result<BTreeMap<DomainId, BTreeSet<OperatorId>>, SlashError> {
+    let domain_ids = get_map_keys::<DomainId>(api, "PendingSlashes", block_hash).await?;
+    let mut pending_slashes = BTreeMap::new();
+    for domain_id in domain_ids {
+        let operator_ids = api
+            .storage_map::<_, BTreeSet<OperatorId>>(
+                "Domains",
+                "PendingSlashes",
+                domain_id,
+                block_hash,
+            )
+            .await?
+            .unwrap_or_default();
+        pending_slashes.insert(domain_id, operator_ids);
+    }
+    Ok(pending_slashes)
+}
+
+/// Returns the ids of all the operators registered at the block.
+async fn get_operator_ids(api: &Api, block_hash: Hash) -> Result<Vec<OperatorId>, SlashError> {
+    get_map_keys(api, "Operators", block_hash).await
+}
+
+/// Returns the keys of the `Domains` storage map at the block.
+///
+/// The maps are stored with the `Identity` hasher so that their keys are decoded from the
+/// storage keys.
+async fn get_map_keys<K: Decode>(
+    api: &Api,
+    storage: &'static str,
+    block_hash: Hash,
+) -> Result<Vec<K>, SlashError> {
+    let storage_prefix = api.get_storage_map_key_prefix("Domains", storage).await?;
+    // pages start after the last key of the previous page
+    let mut storage_keys: Vec<StorageKey> = vec![];
+    loop {
+        let page = api
+            .get_storage_keys_paged(
+                Some(storage_prefix.clone()),
+                STORAGE_KEYS_PAGE_SIZE,
+                storage_keys.last().cloned(),
+                Some(block_hash),
+            )
+            .await?;
+        let last_page = page.len() < STORAGE_KEYS_PAGE_SIZE as usize;
+        storage_keys.extend(page);
+        if last_page {
+            break;
+        }
+    }
+
+    storage_keys
+        .iter()
+        .map(|storage_key| {
+            let mut key = &storage_key.0[storage_prefix.0.len()..];
+            Ok(K::decode(&mut key)?)
+        })
+        .collect()
+}
+
+/// Returns the status of the operator at the block.
+///
+/// The stored status is never `PendingSlash` since the operators pending slash are tracked
+/// in `Domains::PendingSlashes` per domain instead, so that status is derived from it the way
+/// the runtime does.
+async fn get_status<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    operator: &Operator,
+    block_hash: Hash,
+) -> Result<OperatorStatus, SlashError> {
+    if is_slashed(operator.stored_status()) {
+        return Ok(operator.stored_status().clone());
+    }
+    let pending_slashes = backend
+        .storage_map::<_, BTreeSet<OperatorId>>(
+            "Domains",
+            "PendingSlashes",
+            operator.current_domain_id,
+            block_hash,
+        )
+        .await?
+        .unwrap_or_default();
+    Ok(if pending_slashes.contains(&operator_id) {
+        OperatorStatus::PendingSlash
+    } else {
+        operator.stored_status().clone()
+    })
+}
+
+fn is_slashed(status: &OperatorStatus) -> bool {
+    matches!(
+        status,
+        OperatorStatus::Slashed | OperatorStatus::PendingSlash
+    )
+}
+
+/// Whether the operator is registered and slashed or pending slash at the block.
+async fn is_slashed_at<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    number: Number,
+    upgrades: RuntimeUpgrades,
+) -> Result<bool, SlashError> {
+    let block_hash = backend
+        .block_hash(number)
+        .await?
+        .ok_or(SlashError::BlockNotFound(number))?;
+    // storage is decoded with the layout of the runtime at the block
+    let layout = Layout::at(get_spec_version(backend, block_hash).await?, upgrades);
+    let Some(operator) = find_operator(backend, operator_id, block_hash, layout).await? else {
+        return Ok(false);
+    };
+    Ok(is_slashed(
+        &get_status(backend, operator_id, &operator, block_hash).await?,
+    ))
+}
+
+/// Returns the block at which the operator was slashed, searching the blocks up to `at`.
+///
+/// This is the first block at which `Operator.status` is `Slashed`, or the operator is
+/// pending slash, which is also the block of its `Domains::OperatorSlashed` event.
+///
+/// The operator must still be in `Domains::Operators` at `at`, since it is removed once its
+/// slash is processed, after which its slash is found from the events instead.
+pub async fn find_slash_block<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    at: Number,
+    upgrades: RuntimeUpgrades,
+) -> Result<Number, SlashError> {
+    if !is_slashed_at(backend, operator_id, at, upgrades).await? {
+        return Err(SlashError::NotSlashed(at));
+    }
+    find_status_change(backend, operator_id, at, upgrades).await
+}
+
+/// Returns the first block at which the operator, slashed or pending slash at block `at`,
+/// got that status.
+///
+/// An operator is never registered again once slashed, so the block is searched by bisecting
+/// the blocks up to `at`.
+async fn find_status_change<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    at: Number,
+    upgrades: RuntimeUpgrades,
+) -> Result<Number, SlashError> {
+    let (mut low, mut high) = (0, at);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if is_slashed_at(backend, operator_id, middle, upgrades).await? {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+    Ok(high)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+    use crate::types::RuntimeVersion;
+    use sp_runtime::traits::Zero;
+    use sp_runtime::Percent;
+
+    const OPERATOR_ID: OperatorId = 1;
+
+    /// Backend with the operator registered from block 2, pending slash from block 5 and
+    /// slashed from block 7 up to block 9.
+    fn backend() -> MemoryBackend {
+        let mut backend = MemoryBackend::default();
+        for number in 0..10 {
+            let block_hash = Hash::repeat_byte(number as u8 + 1);
+            backend.insert_block_hash(number, block_hash);
+            backend.insert_runtime_call(
+                "Core_version",
+                vec![],
+                block_hash,
+                RuntimeVersion {
+                    spec_name: "subspace".to_string(),
+                    impl_name: "subspace".to_string(),
+                    authoring_version: 0,
+                    spec_version: 7,
+                },
+            );
+            if number < 2 {
+                continue;
+            }
+            let status = if number < 7 {
+                OperatorStatus::Registered
+            } else {
+                OperatorStatus::Slashed
+            };
+            // `Operator` is inserted as a tuple of its fields since its status is private
+            backend.insert_storage_map(
+                "Domains",
+                "Operators",
+                OPERATOR_ID,
+                block_hash,
+                (
+                    [0u8; 32],
+                    DomainId::new(0),
+                    DomainId::new(0),
+                    Balance::zero(),
+                    Percent::zero(),
+                    Balance::zero(),
+                    Balance::zero(),
+                    Balance::zero(),
+                    status,
+                    Balance::zero(),
+                    Balance::zero(),
+                    Balance::zero(),
+                ),
+            );
+            if number >= 5 {
+                backend.insert_storage_map(
+                    "Domains",
+                    "PendingSlashes",
+                    DomainId::new(0),
+                    block_hash,
+                    BTreeSet::from([OPERATOR_ID]),
+                );
+            }
+        }
+        backend
+    }
+
+    #[tokio::test]
+    async fn status_change_is_found_by_bisecting_the_blocks() {
+        let backend = backend();
+        let upgrades = RuntimeUpgrades::default();
+        assert!(!is_slashed_at(&backend, OPERATOR_ID, 1, upgrades)
+            .await
+            .unwrap());
+        assert!(!is_slashed_at(&backend, OPERATOR_ID, 4, upgrades)
+            .await
+            .unwrap());
+        assert!(is_slashed_at(&backend, OPERATOR_ID, 5, upgrades)
+            .await
+            .unwrap());
+        assert_eq!(
+            find_status_change(&backend, OPERATOR_ID, 9, upgrades)
+                .await
+                .unwrap(),
+            5
+        );
+        assert_eq!(
+            find_status_change(&backend, OPERATOR_ID, 5, upgrades)
+                .await
+                .unwrap(),
+            5
+        );
+    }
+
+    #[test]
+    fn pending_slashes_not_listed_are_reported() {
+        let pending_slashes = BTreeMap::from([
+            (DomainId::new(0), BTreeSet::from([1, 2])),
+            (DomainId::new(1), BTreeSet::from([3])),
+        ]);
+        assert_eq!(
+            not_listed(&pending_slashes, &BTreeSet::from([1, 4])),
+            vec![(DomainId::new(0), 2), (DomainId::new(1), 3)]
+        );
+        assert!(not_listed(&pending_slashes, &BTreeSet::from([1, 2, 3])).is_empty());
+    }
+
+    #[tokio::test]
+    async fn slash_block_is_not_found_before_the_slash() {
+        let backend = backend();
+        let upgrades = RuntimeUpgrades::default();
+        assert_eq!(
+            find_slash_block(&backend, OPERATOR_ID, 9, upgrades)
+                .await
+                .unwrap(),
+            5
+        );
+        assert!(matches!(
+            find_slash_block(&backend, OPERATOR_ID, 4, upgrades).await,
+            Err(SlashError::NotSlashed(4))
+        ));
+    }
+}
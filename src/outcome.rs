@@ -0,0 +1,163 @@
+use crate::error::SlashError;
+use serde::Serialize;
+use std::path::Path;
+
+/// Outcome of a run, told apart by the exit code of the process so that the automation
+/// running it can branch on it without parsing the logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// Exit code 0.
+    Success,
+    /// Exit code 1, for the errors not covered by the other outcomes.
+    Failure,
+    /// Exit code 2, some of the operators failed to be calculated.
+    CalculationFailed,
+    /// Exit code 3, some of the batches failed to be submitted while the others were
+    /// included.
+    PartialSubmission,
+    /// Exit code 4, the payouts or the transfers do not match the chain state, or the chain
+    /// state does not match the indexer.
+    VerificationMismatch,
+    /// Exit code 5, the treasury cannot pay the refunds.
+    TreasuryShortfall,
+}
+
+impl Outcome {
+    pub fn of(result: &Result<(), SlashError>) -> Self {
+        match result {
+            Ok(()) => Outcome::Success,
+            Err(err) => Self::of_error(err),
+        }
+    }
+
+    fn of_error(err: &SlashError) -> Self {
+        match err {
+            SlashError::Operator { source, .. } | SlashError::Nominator { source, .. } => {
+                Self::of_error(source)
+            }
+            SlashError::OperatorsFailed { .. } => Outcome::CalculationFailed,
+            SlashError::BatchesFailed { .. } | SlashError::Interrupted { .. } => {
+                Outcome::PartialSubmission
+            }
+            SlashError::Mismatches { .. }
+            | SlashError::SlashInflowMismatches(_)
+            | SlashError::Divergences(_) => Outcome::VerificationMismatch,
+            SlashError::InsufficientTreasuryBalance { .. } => Outcome::TreasuryShortfall,
+            _ => Outcome::Failure,
+        }
+    }
+
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Success => 0,
+            Outcome::Failure => 1,
+            Outcome::CalculationFailed => 2,
+            Outcome::PartialSubmission => 3,
+            Outcome::VerificationMismatch => 4,
+            Outcome::TreasuryShortfall => 5,
+        }
+    }
+}
+
+/// Summary of a run written to `result.json` once it is over.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RunResult {
+    pub run_id: String,
+    pub command: String,
+    pub outcome: Outcome,
+    pub exit_code: i32,
+    /// Error the run failed with, if any.
+    pub error: Option<String>,
+    /// Unix time the run started at.
+    pub started_at: u64,
+    /// Unix time the run finished at.
+    pub finished_at: u64,
+}
+
+impl RunResult {
+    pub fn new(
+        run_id: &str,
+        command: &str,
+        result: &Result<(), SlashError>,
+        started_at: u64,
+        finished_at: u64,
+    ) -> Self {
+        let outcome = Outcome::of(result);
+        RunResult {
+            run_id: run_id.to_string(),
+            command: command.to_string(),
+            outcome,
+            exit_code: outcome.exit_code(),
+            error: result.as_ref().err().map(ToString::to_string),
+            started_at,
+            finished_at,
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SlashError> {
+        Ok(std::fs::write(path, serde_json::to_vec_pretty(self)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outcome_is_found_through_the_attributed_errors() {
+        assert_eq!(Outcome::of(&Ok(())).exit_code(), 0);
+        assert_eq!(
+            Outcome::of(&Err(SlashError::OperatorsFailed {
+                failed: 1,
+                total: 2
+            })),
+            Outcome::CalculationFailed
+        );
+        assert_eq!(
+            Outcome::of(&Err(SlashError::Mismatches {
+                payouts: 1,
+                transfers: 0
+            })),
+            Outcome::VerificationMismatch
+        );
+        let shortfall = SlashError::operator(
+            3,
+            SlashError::InsufficientTreasuryBalance {
+                balance: 1,
+                required: 2,
+            },
+        );
+        assert_eq!(Outcome::of(&Err(shortfall)), Outcome::TreasuryShortfall);
+        assert_eq!(
+            Outcome::of(&Err(SlashError::NoSlashedOperators)).exit_code(),
+            1
+        );
+    }
+
+    #[test]
+    fn result_carries_the_error() {
+        let result = RunResult::new(
+            "run-1",
+            "submit",
+            &Err(SlashError::BatchesFailed {
+                failed: 1,
+                total: 4,
+            }),
+            10,
+            20,
+        );
+        assert_eq!(
+            serde_json::to_value(&result).unwrap(),
+            serde_json::json!({
+                "run_id": "run-1",
+                "command": "submit",
+                "outcome": "partial_submission",
+                "exit_code": 3,
+                "error": "1 of 4 batches failed",
+                "started_at": 10,
+                "finished_at": 20,
+            })
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use crate::error::SlashError;
+use crate::{AccountId, Balance, Number};
+use serde::Deserialize;
+use sp_domains::OperatorId;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::info;
+
+#[derive(Debug, Deserialize)]
+struct OverridesFile {
+    #[serde(default, rename = "override")]
+    overrides: Vec<OverrideEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OverrideEntry {
+    operator_id: OperatorId,
+    /// Block the operator was slashed at, for the operators slashed more than once.
+    #[serde(default)]
+    slashed_at: Option<Number>,
+    account: AccountId,
+    /// Decimal amount, since TOML integers do not fit balances.
+    amount: String,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Refunds of specific nominators adjusted by hand, such as for disputed nominators, which
+/// replace their calculated refund.
+///
+/// Read from a TOML file with an `[[override]]` table per nominator of an operator:
+///
+/// ```toml
+/// [[override]]
+/// operator_id = 12
+/// account = "st..."
+/// amount = "0"
+/// reason = "disputed"
+/// ```
+///
+/// An override applies to every slash of the operator unless it is restricted to one of them
+/// with `slashed_at`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Overrides(BTreeMap<(OperatorId, Option<Number>, AccountId), Balance>);
+
+impl Overrides {
+    pub fn read(path: &Path) -> Result<Self, SlashError> {
+        let invalid = |reason: String| SlashError::InvalidOverrides {
+            path: path.to_path_buf(),
+            reason,
+        };
+        Self::parse(&std::fs::read_to_string(path)?).map_err(invalid)
+    }
+
+    fn parse(content: &str) -> Result<Self, String> {
+        let file: OverridesFile = toml::from_str(content).map_err(|err| err.to_string())?;
+        let mut overrides = BTreeMap::new();
+        for entry in file.overrides {
+            let amount = entry.amount.parse::<Balance>().map_err(|err| {
+                format!(
+                    "invalid amount `{}` of {} for Operator[{:?}]: {err}",
+                    entry.amount, entry.account, entry.operator_id
+                )
+            })?;
+            if let Some(reason) = entry.reason {
+                info!(
+                    "Overriding the refund of {} for Operator[{:?}] with {amount}: {reason}",
+                    entry.account, entry.operator_id
+                );
+            }
+            if overrides
+                .insert(
+                    (entry.operator_id, entry.slashed_at, entry.account.clone()),
+                    amount,
+                )
+                .is_some()
+            {
+                return Err(format!(
+                    "{} is overridden twice for Operator[{:?}]",
+                    entry.account, entry.operator_id
+                ));
+            }
+        }
+        Ok(Overrides(overrides))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the overridden refund of the nominator of the operator slashed at `slashed_at`,
+    /// if any, preferring the override of that slash over the one of every slash.
+    pub fn get(
+        &self,
+        operator_id: OperatorId,
+        slashed_at: Number,
+        nominator_id: &AccountId,
+    ) -> Option<Balance> {
+        self.0
+            .get(&(operator_id, Some(slashed_at), nominator_id.clone()))
+            .or_else(|| self.0.get(&(operator_id, None, nominator_id.clone())))
+            .copied()
+    }
+
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = (&(OperatorId, Option<Number>, AccountId), &Balance)> {
+        self.0.iter()
+    }
+}
+
+impl FromIterator<((OperatorId, Option<Number>, AccountId), Balance)> for Overrides {
+    fn from_iter<I: IntoIterator<Item = ((OperatorId, Option<Number>, AccountId), Balance)>>(
+        iter: I,
+    ) -> Self {
+        Overrides(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_are_parsed_per_operator() {
+        let account = AccountId::new([1; 32]);
+        let content = format!(
+            "[[override]]\noperator_id = 1\naccount = \"{account}\"\namount = \"0\"\n\n[[override]]\noperator_id = 2\naccount = \"{account}\"\namount = \"1000000000000000000000\"\nreason = \"disputed\"\n"
+        );
+        let overrides = Overrides::parse(&content).unwrap();
+        assert_eq!(overrides.get(1, 10, &account), Some(0));
+        assert_eq!(
+            overrides.get(2, 10, &account),
+            Some(1_000_000_000_000_000_000_000)
+        );
+        assert_eq!(overrides.get(3, 10, &account), None);
+
+        let per_slash = format!(
+            "{content}\n[[override]]\noperator_id = 1\nslashed_at = 20\naccount = \"{account}\"\namount = \"5\"\n"
+        );
+        let overrides = Overrides::parse(&per_slash).unwrap();
+        assert_eq!(overrides.get(1, 10, &account), Some(0));
+        assert_eq!(overrides.get(1, 20, &account), Some(5));
+
+        let duplicate = format!(
+            "{content}\n[[override]]\noperator_id = 1\naccount = \"{account}\"\namount = \"5\"\n"
+        );
+        assert!(Overrides::parse(&duplicate).is_err());
+    }
+}
@@ -0,0 +1,30 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::info;
+
+/// Progress of a long running phase, reported as log lines with counts so that a run can be
+/// told apart from a hung one.
+///
+/// Items may complete concurrently, so the count is kept atomically.
+#[derive(Debug)]
+pub struct Progress {
+    phase: &'static str,
+    total: usize,
+    done: AtomicUsize,
+}
+
+impl Progress {
+    pub fn new(phase: &'static str, total: usize) -> Self {
+        info!("{phase}: 0/{total}");
+        Progress {
+            phase,
+            total,
+            done: AtomicUsize::new(0),
+        }
+    }
+
+    /// Marks `count` more items as done and logs the progress.
+    pub fn advance(&self, count: usize) {
+        let done = self.done.fetch_add(count, Ordering::Relaxed) + count;
+        info!("{}: {done}/{}", self.phase, self.total);
+    }
+}
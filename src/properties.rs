@@ -0,0 +1,135 @@
+use crate::error::SlashError;
+use crate::{AccountId, Api, Balance};
+use serde_json::{Map, Value};
+use sp_core::crypto::{Ss58AddressFormat, Ss58Codec};
+use substrate_api_client::ac_primitives::RpcParams;
+use substrate_api_client::rpc::Request;
+
+/// Generic Substrate SS58 prefix, used when the chain does not declare its own.
+const DEFAULT_SS58_FORMAT: u16 = 42;
+
+/// Properties of the chain used to display its accounts and balances, as returned by
+/// `system_properties`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainProperties {
+    pub ss58_format: u16,
+    pub token_decimals: u32,
+    pub token_symbol: String,
+}
+
+impl Default for ChainProperties {
+    /// Displays the accounts with the generic prefix and the balances in raw units.
+    fn default() -> Self {
+        ChainProperties {
+            ss58_format: DEFAULT_SS58_FORMAT,
+            token_decimals: 0,
+            token_symbol: String::new(),
+        }
+    }
+}
+
+impl ChainProperties {
+    /// Fetches the properties of the chain, falling back to the given ones for the properties
+    /// the chain does not declare.
+    pub async fn fetch(api: &Api, fallback: ChainProperties) -> Result<Self, SlashError> {
+        let properties: Map<String, Value> = api
+            .client()
+            .request("system_properties", RpcParams::new())
+            .await
+            .map_err(substrate_api_client::Error::RpcClient)?;
+        Ok(Self::from_properties(&properties, fallback))
+    }
+
+    /// Reads the properties declared by the chain, falling back to the given ones for the
+    /// missing ones.
+    ///
+    /// The token decimals and symbol may be given as an array for chains with several tokens,
+    /// in which case the first token is the native one.
+    fn from_properties(properties: &Map<String, Value>, default: ChainProperties) -> Self {
+        let first = |key: &str| match properties.get(key) {
+            Some(Value::Array(values)) => values.first().cloned(),
+            value => value.cloned(),
+        };
+        ChainProperties {
+            ss58_format: properties
+                .get("ss58Format")
+                .and_then(Value::as_u64)
+                .and_then(|ss58_format| u16::try_from(ss58_format).ok())
+                .unwrap_or(default.ss58_format),
+            token_decimals: first("tokenDecimals")
+                .and_then(|decimals| decimals.as_u64())
+                .and_then(|decimals| u32::try_from(decimals).ok())
+                .unwrap_or(default.token_decimals),
+            token_symbol: first("tokenSymbol")
+                .and_then(|symbol| symbol.as_str().map(ToString::to_string))
+                .unwrap_or(default.token_symbol),
+        }
+    }
+
+    /// Returns the SS58 address of the account with the prefix of the chain.
+    pub fn account(&self, account: &AccountId) -> String {
+        account.to_ss58check_with_version(Ss58AddressFormat::custom(self.ss58_format))
+    }
+
+    /// Returns the balance in units of the token, such as `1.5 tSSC`.
+    pub fn balance(&self, balance: Balance) -> String {
+        let amount = match 10u128.checked_pow(self.token_decimals) {
+            Some(unit) if unit > 1 => {
+                let (whole, fraction) = (balance / unit, balance % unit);
+                if fraction == 0 {
+                    whole.to_string()
+                } else {
+                    let fraction =
+                        format!("{fraction:0width$}", width = self.token_decimals as usize);
+                    format!("{whole}.{}", fraction.trim_end_matches('0'))
+                }
+            }
+            _ => balance.to_string(),
+        };
+        if self.token_symbol.is_empty() {
+            amount
+        } else {
+            format!("{amount} {}", self.token_symbol)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn balances_are_displayed_in_token_units() {
+        let properties = json!({
+            "ss58Format": 2254,
+            "tokenDecimals": [18],
+            "tokenSymbol": ["tSSC"],
+        });
+        let properties = ChainProperties::from_properties(
+            properties.as_object().unwrap(),
+            ChainProperties::default(),
+        );
+        assert_eq!(
+            properties,
+            ChainProperties {
+                ss58_format: 2254,
+                token_decimals: 18,
+                token_symbol: "tSSC".to_string(),
+            }
+        );
+        assert_eq!(properties.balance(0), "0 tSSC");
+        assert_eq!(properties.balance(1_500_000_000_000_000_000), "1.5 tSSC");
+        assert_eq!(properties.balance(1), "0.000000000000000001 tSSC");
+        assert!(properties
+            .account(&AccountId::new([1; 32]))
+            .starts_with("st"));
+
+        let default = ChainProperties::from_properties(&Map::new(), ChainProperties::default());
+        assert_eq!(default.balance(1_500), "1500");
+        assert_eq!(
+            default.account(&AccountId::new([1; 32])),
+            AccountId::new([1; 32]).to_string()
+        );
+    }
+}
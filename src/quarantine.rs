@@ -0,0 +1,136 @@
+use crate::error::SlashError;
+use crate::types::versioned::WithdrawalV0;
+use crate::types::{Deposit, Withdrawal};
+use crate::{AccountId, Hash};
+use codec::{Decode, DecodeAll};
+use serde::{Deserialize, Serialize};
+use sp_core::Bytes;
+use sp_domains::OperatorId;
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Storage entry of a nominator that could not be decoded, kept as its raw key and value so
+/// that it can be decoded again with other type layouts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuarantinedEntry {
+    pub pallet: String,
+    pub storage: String,
+    pub operator_id: OperatorId,
+    pub block_hash: Hash,
+    pub spec_version: u32,
+    /// Part of the storage key after the prefix of the operator, encoding the nominator.
+    pub key: Bytes,
+    pub value: Bytes,
+    /// Error the entry failed to be decoded with.
+    pub error: String,
+}
+
+/// Outcome of decoding a quarantined entry again with one of the layouts of its storage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redecoded {
+    /// Name of the type the value was decoded into.
+    pub layout: &'static str,
+    /// Decoded value, or the error it failed to be decoded with.
+    pub value: Result<String, String>,
+}
+
+impl QuarantinedEntry {
+    /// Decodes the nominator of the entry.
+    pub fn nominator(&self) -> Result<AccountId, String> {
+        AccountId::decode(&mut &self.key[..]).map_err(|err| err.to_string())
+    }
+
+    /// Decodes the value of the entry with each of the layouts its storage had across the
+    /// runtime upgrades, such as to find the layout a runtime upgrade changed it to.
+    pub fn redecode(&self) -> Vec<Redecoded> {
+        let redecoded = |layout, value| Redecoded { layout, value };
+        match self.storage.as_str() {
+            "Deposits" => vec![redecoded("Deposit", debug_decode::<Deposit>(&self.value))],
+            "Withdrawals" => vec![
+                redecoded("WithdrawalV0", debug_decode::<WithdrawalV0>(&self.value)),
+                redecoded("Withdrawal", debug_decode::<Withdrawal>(&self.value)),
+            ],
+            _ => vec![],
+        }
+    }
+}
+
+fn decode_all<T: DecodeAll>(bytes: &[u8]) -> Result<T, String> {
+    T::decode_all(&mut &bytes[..]).map_err(|err| err.to_string())
+}
+
+fn debug_decode<T: DecodeAll + Debug>(bytes: &[u8]) -> Result<String, String> {
+    decode_all::<T>(bytes).map(|value| format!("{value:?}"))
+}
+
+/// File the undecodable storage entries are appended to, one JSON object per line, so that
+/// the run continues with the other operators instead of losing the raw entries.
+pub struct Quarantine {
+    file: Mutex<File>,
+}
+
+impl Quarantine {
+    /// Opens the quarantine file for appending.
+    pub fn open(path: &Path) -> Result<Self, SlashError> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Quarantine {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn add(&self, entry: &QuarantinedEntry) -> Result<(), SlashError> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.file.lock().unwrap().write_all(&line)?;
+        Ok(())
+    }
+
+    /// Reads the entries of the quarantine file.
+    pub fn read(path: &Path) -> Result<Vec<QuarantinedEntry>, SlashError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    fn parse(content: &str) -> Result<Vec<QuarantinedEntry>, SlashError> {
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+
+    #[test]
+    fn quarantined_entry_is_decoded_again_with_each_layout() {
+        let nominator_id = AccountId::new([1; 32]);
+        let entry = QuarantinedEntry {
+            pallet: "Domains".to_string(),
+            storage: "Withdrawals".to_string(),
+            operator_id: 1,
+            block_hash: Hash::repeat_byte(2),
+            spec_version: 1,
+            key: nominator_id.encode().into(),
+            value: Withdrawal::default().encode().into(),
+            error: "Not enough data to fill buffer".to_string(),
+        };
+        let line = serde_json::to_string(&entry).unwrap();
+        let entries = Quarantine::parse(&format!("{line}\n\n{line}\n")).unwrap();
+        assert_eq!(entries, vec![entry.clone(), entry.clone()]);
+
+        assert_eq!(entry.nominator(), Ok(nominator_id));
+        let redecoded = entry.redecode();
+        assert_eq!(redecoded.len(), 2);
+        assert_eq!(redecoded[1].layout, "Withdrawal");
+        assert_eq!(
+            redecoded[1].value,
+            Ok(format!("{:?}", Withdrawal::default()))
+        );
+    }
+}
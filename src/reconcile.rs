@@ -0,0 +1,359 @@
+use crate::error::SlashError;
+use crate::manifest::{OperatorPayout, PayoutManifest};
+use crate::{AccountId, Balance, Hash, Number};
+use sp_domains::OperatorId;
+use sp_runtime::traits::Zero;
+use sp_runtime::Permill;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Difference between the payout manifest and the transfers made from the treasury.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferMismatch {
+    /// Batch transferring to the nominator was not included in a block.
+    NotIncluded {
+        operator_id: OperatorId,
+        slashed_at: Number,
+        nominator_id: AccountId,
+    },
+    /// Nominator did not receive the slashed balance.
+    Missing {
+        operator_id: OperatorId,
+        slashed_at: Number,
+        nominator_id: AccountId,
+        expected: Balance,
+    },
+    /// Transfer from the treasury that does not match any payout.
+    Unexpected {
+        block_hash: Hash,
+        nominator_id: AccountId,
+        amount: Balance,
+    },
+}
+
+impl fmt::Display for TransferMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferMismatch::NotIncluded {
+                operator_id,
+                slashed_at,
+                nominator_id,
+            } => write!(
+                f,
+                "Operator[{operator_id:?}] slashed at #{slashed_at} Nominator[{nominator_id}]: batch not included in a block"
+            ),
+            TransferMismatch::Missing {
+                operator_id,
+                slashed_at,
+                nominator_id,
+                expected,
+            } => write!(
+                f,
+                "Operator[{operator_id:?}] slashed at #{slashed_at} Nominator[{nominator_id}]: {expected} not transferred"
+            ),
+            TransferMismatch::Unexpected {
+                block_hash,
+                nominator_id,
+                amount,
+            } => write!(
+                f,
+                "Block[{block_hash:?}] Nominator[{nominator_id}]: unexpected transfer of {amount}"
+            ),
+        }
+    }
+}
+
+/// Reconciles the payouts of the operators whose batches were included in the block
+/// `block_hash` with the `transfers` made from the treasury in that block.
+///
+/// Each transfer is matched against a single payout of the exact same amount to the same
+/// nominator.
+pub fn reconcile_transfers(
+    block_hash: Hash,
+    operator_payouts: &[&OperatorPayout],
+    transfers: Vec<(AccountId, Balance)>,
+) -> Result<Vec<TransferMismatch>, SlashError> {
+    let mut transfers =
+        transfers
+            .into_iter()
+            .fold(BTreeMap::<_, usize>::new(), |mut transfers, transfer| {
+                *transfers.entry(transfer).or_default() += 1;
+                transfers
+            });
+
+    let mut mismatches = vec![];
+    for operator_payout in operator_payouts {
+        for (nominator_id, nominator_payout) in &operator_payout.nominators {
+            let expected = nominator_payout.refund()?;
+            match transfers.get_mut(&(nominator_id.clone(), expected)) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => mismatches.push(TransferMismatch::Missing {
+                    operator_id: operator_payout.operator_id,
+                    slashed_at: operator_payout.slashed_at,
+                    nominator_id: nominator_id.clone(),
+                    expected,
+                }),
+            }
+        }
+    }
+
+    transfers
+        .into_iter()
+        .for_each(|((nominator_id, amount), count)| {
+            (0..count).for_each(|_| {
+                mismatches.push(TransferMismatch::Unexpected {
+                    block_hash,
+                    nominator_id: nominator_id.clone(),
+                    amount,
+                })
+            })
+        });
+
+    Ok(mismatches)
+}
+
+/// Difference between the balances slashed from the operators slashed in a block and the
+/// balance the treasury received when they were slashed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlashInflowMismatch {
+    pub slashed_at: Number,
+    pub operator_ids: Vec<OperatorId>,
+    /// Balance slashed from the operators according to the payout manifest.
+    pub expected: Balance,
+    /// Balance transferred or deposited to the treasury.
+    pub inflow: Balance,
+}
+
+impl fmt::Display for SlashInflowMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Block #{} Operators{:?}: {} slashed but the treasury received {}",
+            self.slashed_at, self.operator_ids, self.expected, self.inflow
+        )
+    }
+}
+
+/// Returns the balance slashed from the operators of the manifest to the treasury, grouped
+/// by the block they were slashed at along with the operators slashed in that block.
+///
+/// The slashed balance includes the excluded accounts and the dust of each operator, which
+/// were all moved to the treasury.
+pub fn slashed_by_block(payouts: &PayoutManifest) -> BTreeMap<Number, (Vec<OperatorId>, Balance)> {
+    let mut slashed = BTreeMap::<_, (Vec<_>, Balance)>::new();
+    for (operator_payout, includes_dust) in payouts
+        .operators
+        .iter()
+        .map(|operator_payout| (operator_payout, true))
+        .chain(
+            payouts
+                .excluded
+                .iter()
+                .map(|operator_payout| (operator_payout, false)),
+        )
+    {
+        // dust assigned to a nominator is also counted in the dust of the operator, and the
+        // unexplained leftover is moved to the treasury along with it
+        let nominators =
+            operator_payout
+                .nominators
+                .values()
+                .fold(Balance::zero(), |acc, nominator_payout| {
+                    acc.saturating_add(nominator_payout.staked)
+                        .saturating_add(nominator_payout.withdrawable)
+                        .saturating_add(nominator_payout.storage_fund)
+                });
+        let dust = if includes_dust {
+            operator_payout
+                .dust
+                .saturating_add(operator_payout.unexplained)
+        } else {
+            Balance::zero()
+        };
+        let (operator_ids, total) = slashed.entry(operator_payout.slashed_at).or_default();
+        if !operator_ids.contains(&operator_payout.operator_id) {
+            operator_ids.push(operator_payout.operator_id);
+        }
+        *total = total.saturating_add(nominators).saturating_add(dust);
+    }
+    slashed
+}
+
+/// Checks the balance slashed from the operators slashed in a block against the `inflow` of
+/// the treasury, allowing a deviation of `tolerance_bps` basis points of the slashed balance.
+pub fn check_slash_inflow(
+    slashed_at: Number,
+    operator_ids: Vec<OperatorId>,
+    expected: Balance,
+    inflow: Balance,
+    tolerance_bps: u32,
+) -> Option<SlashInflowMismatch> {
+    let tolerance = Permill::from_rational(tolerance_bps, 10_000).mul_floor(expected);
+    (expected.abs_diff(inflow) > tolerance).then_some(SlashInflowMismatch {
+        slashed_at,
+        operator_ids,
+        expected,
+        inflow,
+    })
+}
+
+/// Balance already transferred to a nominator deducted from its refund.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deduction {
+    pub operator_id: OperatorId,
+    pub slashed_at: Number,
+    pub nominator_id: AccountId,
+    pub refund: Balance,
+    pub paid: Balance,
+}
+
+impl fmt::Display for Deduction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Operator[{:?}] slashed at #{} Nominator[{}]: {} of the refund of {} already transferred from treasury",
+            self.operator_id, self.slashed_at, self.nominator_id, self.paid, self.refund
+        )
+    }
+}
+
+/// Deducts the balances already transferred to the nominators from their refunds, such as
+/// by a previous partial run or by hand.
+///
+/// The balance paid to a nominator is deducted from its refunds in the order of the payouts.
+/// Nominators whose refund is paid in full are removed and the refund of the others is
+/// overridden with the rest, leaving out the operators without any nominator left.
+pub fn deduct_paid_refunds(
+    operator_payouts: &mut Vec<OperatorPayout>,
+    mut paid: BTreeMap<AccountId, Balance>,
+) -> Result<Vec<Deduction>, SlashError> {
+    let mut deductions = vec![];
+    for operator_payout in operator_payouts.iter_mut() {
+        let (operator_id, slashed_at) = (operator_payout.operator_id, operator_payout.slashed_at);
+        let mut paid_in_full = vec![];
+        for (nominator_id, nominator_payout) in operator_payout.nominators.iter_mut() {
+            let Some(nominator_paid) = paid.get_mut(nominator_id) else {
+                continue;
+            };
+            let refund = nominator_payout.refund()?;
+            let deducted = refund.min(*nominator_paid);
+            if deducted.is_zero() {
+                continue;
+            }
+            *nominator_paid -= deducted;
+            deductions.push(Deduction {
+                operator_id,
+                slashed_at,
+                nominator_id: nominator_id.clone(),
+                refund,
+                paid: deducted,
+            });
+            nominator_payout.overridden = Some(refund - deducted);
+            if deducted == refund {
+                paid_in_full.push(nominator_id.clone());
+            }
+        }
+        for nominator_id in paid_in_full {
+            operator_payout.nominators.remove(&nominator_id);
+        }
+    }
+    operator_payouts.retain(|operator_payout| !operator_payout.nominators.is_empty());
+    Ok(deductions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::NominatorPayout;
+
+    fn operator_payout(operator_id: OperatorId, slashed_at: Number) -> OperatorPayout {
+        OperatorPayout {
+            operator_id,
+            domain_id: None,
+            slashed_at,
+            block_hash: Hash::zero(),
+            spec_version: 1,
+            nominators: BTreeMap::from([(
+                AccountId::new([1; 32]),
+                NominatorPayout {
+                    staked: 10_000,
+                    dust: 3,
+                    ..Default::default()
+                },
+            )]),
+            unconverted: BTreeMap::new(),
+            unlockable: BTreeMap::new(),
+            components: BTreeMap::new(),
+            dust: 3,
+            unexplained: 0,
+        }
+    }
+
+    #[test]
+    fn operators_slashed_in_the_same_block_are_checked_together() {
+        let payouts = PayoutManifest::new(vec![
+            operator_payout(1, 100),
+            operator_payout(2, 100),
+            operator_payout(3, 200),
+        ])
+        .unwrap();
+        let slashed = slashed_by_block(&payouts);
+        assert_eq!(slashed[&100], (vec![1, 2], 20_006));
+        assert_eq!(slashed[&200], (vec![3], 10_003));
+
+        assert!(check_slash_inflow(200, vec![3], 10_003, 10_003, 0).is_none());
+        assert!(check_slash_inflow(200, vec![3], 10_003, 10_002, 0).is_some());
+        // 1 basis point of 10_003 is 1
+        assert!(check_slash_inflow(200, vec![3], 10_003, 10_002, 1).is_none());
+    }
+
+    #[test]
+    fn paid_refunds_are_deducted_in_order() {
+        let nominator_id = AccountId::new([1; 32]);
+        let mut operator_payouts = vec![
+            operator_payout(1, 100),
+            operator_payout(2, 100),
+            operator_payout(3, 200),
+        ];
+        // the first refund is paid in full and the second in part
+        let paid = BTreeMap::from([(nominator_id.clone(), 10_003 + 4_000)]);
+
+        let deductions = deduct_paid_refunds(&mut operator_payouts, paid).unwrap();
+        assert_eq!(
+            deductions,
+            vec![
+                Deduction {
+                    operator_id: 1,
+                    slashed_at: 100,
+                    nominator_id: nominator_id.clone(),
+                    refund: 10_003,
+                    paid: 10_003,
+                },
+                Deduction {
+                    operator_id: 2,
+                    slashed_at: 100,
+                    nominator_id: nominator_id.clone(),
+                    refund: 10_003,
+                    paid: 4_000,
+                },
+            ]
+        );
+        let operator_ids: Vec<_> = operator_payouts
+            .iter()
+            .map(|operator_payout| operator_payout.operator_id)
+            .collect();
+        assert_eq!(operator_ids, vec![2, 3]);
+        assert_eq!(
+            operator_payouts[0].nominators[&nominator_id]
+                .refund()
+                .unwrap(),
+            6_003
+        );
+        assert_eq!(
+            operator_payouts[1].nominators[&nominator_id]
+                .refund()
+                .unwrap(),
+            10_003
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use crate::error::SlashError;
+use crate::{AccountId, Balance};
+use serde::Deserialize;
+use sp_core::sr25519::{Public, Signature};
+use sp_core::Bytes;
+use sp_runtime::traits::Verify;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::{info, warn};
+
+#[derive(Debug, Deserialize)]
+struct RedirectionsFile {
+    #[serde(default, rename = "redirect")]
+    redirects: Vec<RedirectEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RedirectEntry {
+    from: AccountId,
+    to: AccountId,
+    /// Sr25519 signature of the authorization message by `from`.
+    #[serde(default)]
+    signature: Option<Bytes>,
+    /// Reference to the governance decision approving the redirection, for the accounts that
+    /// cannot sign anymore.
+    #[serde(default)]
+    governance_note: Option<String>,
+}
+
+/// Message signed by a nominator to authorize the refund of its account to be paid to
+/// another account.
+pub fn authorization_message(from: &AccountId, to: &AccountId) -> String {
+    format!("slash-refund redirect {from} to {to}")
+}
+
+/// Refunds of nominators that lost access to their account, paid to another account instead.
+///
+/// Read from a TOML file with a `[[redirect]]` table per nominator, authorized by either a
+/// `signature` of the authorization message by the nominator or a `governance_note`:
+///
+/// ```toml
+/// [[redirect]]
+/// from = "st..."
+/// to = "st..."
+/// signature = "0x..."
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Redirections(BTreeMap<AccountId, AccountId>);
+
+impl Redirections {
+    /// Reads the redirections, failing if any of them is not authorized.
+    pub fn read(path: &Path) -> Result<Self, SlashError> {
+        let invalid = |reason: String| SlashError::InvalidRedirections {
+            path: path.to_path_buf(),
+            reason,
+        };
+        Self::parse(&std::fs::read_to_string(path)?).map_err(invalid)
+    }
+
+    fn parse(content: &str) -> Result<Self, String> {
+        let file: RedirectionsFile = toml::from_str(content).map_err(|err| err.to_string())?;
+        let mut redirections = BTreeMap::new();
+        for entry in file.redirects {
+            let RedirectEntry {
+                from,
+                to,
+                signature,
+                governance_note,
+            } = entry;
+            if from == to {
+                return Err(format!("{from} is redirected to itself"));
+            }
+            match (signature, governance_note) {
+                (Some(signature), _) => {
+                    if !is_authorized(&from, &to, &signature) {
+                        return Err(format!(
+                            "signature of {from} does not authorize the redirection to {to}"
+                        ));
+                    }
+                    info!("Redirecting the refunds of {from} to {to} as signed by {from}");
+                }
+                (None, Some(governance_note)) => warn!(
+                    "Redirecting the refunds of {from} to {to} as approved by governance: {governance_note}"
+                ),
+                (None, None) => {
+                    return Err(format!(
+                        "redirection of {from} has neither a signature nor a governance note"
+                    ))
+                }
+            }
+            if redirections.insert(from.clone(), to).is_some() {
+                return Err(format!("{from} is redirected twice"));
+            }
+        }
+        Ok(Redirections(redirections))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the account the refunds of the nominator are paid to.
+    pub fn destination<'a>(&'a self, nominator_id: &'a AccountId) -> &'a AccountId {
+        self.0.get(nominator_id).unwrap_or(nominator_id)
+    }
+
+    /// Returns the balances with each nominator replaced by the account its refund is paid to.
+    pub fn apply(
+        &self,
+        nominator_balances: Vec<(AccountId, Balance)>,
+    ) -> Vec<(AccountId, Balance)> {
+        nominator_balances
+            .into_iter()
+            .map(|(nominator_id, balance)| (self.destination(&nominator_id).clone(), balance))
+            .collect()
+    }
+
+    /// Returns the nominator whose refunds are paid to `destination`, if any.
+    pub fn nominator_of(&self, destination: &AccountId) -> Option<&AccountId> {
+        self.0
+            .iter()
+            .find_map(|(nominator_id, to)| (to == destination).then_some(nominator_id))
+    }
+}
+
+/// Whether the signature is the one of `from` over the authorization message, either as is
+/// or wrapped in `<Bytes>` as signed by polkadot-js.
+fn is_authorized(from: &AccountId, to: &AccountId, signature: &Bytes) -> bool {
+    let Ok(signature) = <[u8; 64]>::try_from(signature.0.as_slice()).map(Signature::from_raw)
+    else {
+        return false;
+    };
+    let signer = Public::from_raw(from.clone().into());
+    let message = authorization_message(from, to);
+    signature.verify(message.as_bytes(), &signer)
+        || signature.verify(format!("<Bytes>{message}</Bytes>").as_bytes(), &signer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::sr25519::Pair;
+    use sp_core::Pair as PairT;
+
+    fn redirect(from: &Pair, to: &AccountId, signer: &Pair) -> String {
+        let from = AccountId::from(from.public());
+        let message = format!("<Bytes>{}</Bytes>", authorization_message(&from, to));
+        let signature = signer.sign(message.as_bytes());
+        format!(
+            "[[redirect]]\nfrom = \"{from}\"\nto = \"{to}\"\nsignature = \"0x{}\"\n",
+            sp_core::hexdisplay::HexDisplay::from(&signature.as_ref())
+        )
+    }
+
+    #[test]
+    fn redirections_must_be_signed_by_the_nominator() {
+        let alice = Pair::from_string("//Alice", None).unwrap();
+        let bob = Pair::from_string("//Bob", None).unwrap();
+        let new_account = AccountId::new([9; 32]);
+
+        let redirections = Redirections::parse(&redirect(&alice, &new_account, &alice)).unwrap();
+        let alice_account = AccountId::from(alice.public());
+        assert_eq!(redirections.destination(&alice_account), &new_account);
+        assert_eq!(
+            redirections.nominator_of(&new_account),
+            Some(&alice_account)
+        );
+        let bob_account = AccountId::from(bob.public());
+        assert_eq!(redirections.destination(&bob_account), &bob_account);
+
+        assert!(Redirections::parse(&redirect(&alice, &new_account, &bob)).is_err());
+    }
+
+    #[test]
+    fn redirections_without_authorization_are_rejected() {
+        let from = AccountId::new([1; 32]);
+        let to = AccountId::new([2; 32]);
+        let unauthorized = format!("[[redirect]]\nfrom = \"{from}\"\nto = \"{to}\"\n");
+        assert!(Redirections::parse(&unauthorized).is_err());
+
+        let approved = format!("{unauthorized}governance_note = \"referendum 42\"\n");
+        assert_eq!(
+            Redirections::parse(&approved).unwrap().destination(&from),
+            &to
+        );
+    }
+}
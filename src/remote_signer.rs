@@ -0,0 +1,58 @@
+use crate::error::SlashError;
+use crate::offline::UnsignedBatch;
+use crate::AccountId;
+use sp_core::Bytes;
+use substrate_api_client::ac_primitives::RpcParams;
+use substrate_api_client::rpc::{JsonrpseeClient, Request};
+
+/// Signing service holding the signing key, such as one backed by an HSM or Vault, so that
+/// the key never reaches this tool.
+///
+/// The service is called over JSON-RPC on a WebSocket with
+/// `signer_signPayload(account, payload)`, where the account is SS58 encoded and the payload
+/// is the hex encoded signing payload of the extrinsic. It returns the hex encoded sr25519
+/// signature of the payload by the account.
+pub struct RemoteSigner {
+    url: String,
+    client: JsonrpseeClient,
+}
+
+impl RemoteSigner {
+    pub async fn connect(url: &str) -> Result<Self, SlashError> {
+        let client = JsonrpseeClient::new(url)
+            .await
+            .map_err(|err| SlashError::Connection(format!("{err:?}")))?;
+        Ok(RemoteSigner {
+            url: url.to_string(),
+            client,
+        })
+    }
+
+    /// Fills in the signature of the batch by its signer.
+    ///
+    /// The signature is verified against the signing payload when the signed extrinsic is
+    /// encoded, so a remote signer signing with another key is caught before submission.
+    pub async fn sign(&self, batch: &mut UnsignedBatch) -> Result<(), SlashError> {
+        let signature = self
+            .sign_payload(&batch.signer, &batch.signing_payload)
+            .await?;
+        batch.signature = Some(signature);
+        Ok(())
+    }
+
+    async fn sign_payload(
+        &self,
+        account: &AccountId,
+        payload: &Bytes,
+    ) -> Result<Bytes, SlashError> {
+        let mut params = RpcParams::new();
+        params.insert(account)?;
+        params.insert(payload)?;
+        self.client
+            .request("signer_signPayload", params)
+            .await
+            .map_err(|err| {
+                SlashError::Connection(format!("remote signer {} failed: {err:?}", self.url))
+            })
+    }
+}
@@ -0,0 +1,408 @@
+use crate::calculator::OperatorAudit;
+use crate::manifest::{PendingKind, UnconvertedPending, UnlockableWithdrawal};
+use crate::properties::ChainProperties;
+use crate::{AccountId, Balance};
+use sp_domains::DomainId;
+use sp_runtime::traits::Zero;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+const NOMINATOR_COLUMNS: [&str; 8] = [
+    "Nominator",
+    "Shares",
+    "Staked",
+    "Withdrawable",
+    "Storage Fee Deposit",
+    "Storage Fund",
+    "Refund",
+    "Overridden",
+];
+
+const DOMAIN_COLUMNS: [&str; 4] = ["Domain", "Operators", "Nominators", "Total Refund"];
+
+/// Human-readable report of the refund of the nominators of a slashed operator, to be
+/// published along with the refund announcement.
+///
+/// Accounts and balances are displayed with the SS58 prefix and the token units of the chain.
+pub struct OperatorReport {
+    title: String,
+    summary: Vec<(&'static str, String)>,
+    nominators: Vec<[String; 8]>,
+    notes: Vec<String>,
+}
+
+impl OperatorReport {
+    pub fn new(audit: &OperatorAudit, properties: &ChainProperties) -> Self {
+        // every nominator of the operator is calculated at the same share price and storage
+        // fund balance
+        let first = audit.nominators.values().next();
+        let total_refund = total_refund(audit);
+        let summary = vec![
+            ("Domain", format!("{:?}", audit.domain_id)),
+            (
+                "Slashed At",
+                format!("#{} (state of {:?})", audit.slashed_at, audit.block_hash),
+            ),
+            ("Spec Version", audit.spec_version.to_string()),
+            ("Total Stake", properties.balance(audit.current_total_stake)),
+            (
+                "Epoch Rewards",
+                properties.balance(audit.current_epoch_rewards),
+            ),
+            (
+                "Storage Fund Balance",
+                first
+                    .map(|breakdown| properties.balance(breakdown.storage_fund_balance))
+                    .unwrap_or_default(),
+            ),
+            (
+                "Share Price",
+                first
+                    .map(|breakdown| format!("{:?}", breakdown.share_price))
+                    .unwrap_or_default(),
+            ),
+            ("Nominators", audit.nominators.len().to_string()),
+            ("Total Refund", properties.balance(total_refund)),
+            ("Dust", properties.balance(audit.dust)),
+            ("Unexplained", properties.balance(audit.unexplained)),
+        ];
+
+        let nominators = audit
+            .nominators
+            .iter()
+            .map(|(nominator_id, breakdown)| {
+                [
+                    properties.account(nominator_id),
+                    breakdown.shares.to_string(),
+                    properties.balance(breakdown.payout.staked),
+                    properties.balance(breakdown.payout.withdrawable),
+                    properties.balance(breakdown.storage_fee_deposit),
+                    properties.balance(breakdown.payout.storage_fund),
+                    properties.balance(breakdown.payout.saturating_refund()),
+                    if breakdown.payout.overridden.is_some() {
+                        "yes"
+                    } else {
+                        "no"
+                    }
+                    .to_string(),
+                ]
+            })
+            .collect();
+
+        let notes = audit
+            .nominators
+            .iter()
+            .flat_map(|(nominator_id, breakdown)| {
+                breakdown
+                    .unconverted
+                    .iter()
+                    .map(move |unconverted| unconverted_note(properties, nominator_id, unconverted))
+                    .chain(breakdown.unlockable.iter().map(move |unlockable| {
+                        unlockable_note(properties, nominator_id, unlockable)
+                    }))
+            })
+            .collect();
+
+        OperatorReport {
+            title: format!("Refund of the nominators of Operator {}", audit.operator_id),
+            summary,
+            nominators,
+            notes,
+        }
+    }
+
+    /// Report of the refund the nominators of an operator pending slash are expected to get
+    /// once the slash is executed, calculated from the state before the operator was marked
+    /// for slash.
+    pub fn preview(audit: &OperatorAudit, properties: &ChainProperties) -> Self {
+        let mut report = OperatorReport::new(audit, properties);
+        report.title = format!(
+            "Expected refund of the nominators of Operator {}",
+            audit.operator_id
+        );
+        report.notes.insert(
+            0,
+            format!(
+                "Operator {} is pending slash since #{}, the refunds are final once the slash is executed.",
+                audit.operator_id, audit.slashed_at
+            ),
+        );
+        report
+    }
+
+    pub fn markdown(&self) -> String {
+        let mut markdown = format!("# {}\n\n", self.title);
+        markdown.push_str("| | |\n|---|---|\n");
+        for (name, value) in &self.summary {
+            let _ = writeln!(markdown, "| {name} | {value} |");
+        }
+
+        markdown.push_str("\n## Nominators\n\n");
+        let _ = writeln!(markdown, "| {} |", NOMINATOR_COLUMNS.join(" | "));
+        let _ = writeln!(markdown, "|{}", "---|".repeat(NOMINATOR_COLUMNS.len()));
+        for row in &self.nominators {
+            let _ = writeln!(markdown, "| {} |", row.join(" | "));
+        }
+
+        if !self.notes.is_empty() {
+            markdown.push_str("\n## Notes\n\n");
+            for note in &self.notes {
+                let _ = writeln!(markdown, "- {note}");
+            }
+        }
+        markdown
+    }
+
+    pub fn html(&self) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>\n",
+            self.title, self.title
+        );
+        html.push_str("<table>\n");
+        for (name, value) in &self.summary {
+            let _ = writeln!(html, "<tr><th>{name}</th><td>{value}</td></tr>");
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Nominators</h2>\n<table>\n<tr>");
+        for column in NOMINATOR_COLUMNS {
+            let _ = write!(html, "<th>{column}</th>");
+        }
+        html.push_str("</tr>\n");
+        for row in &self.nominators {
+            html.push_str("<tr>");
+            for cell in row {
+                let _ = write!(html, "<td>{cell}</td>");
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+
+        if !self.notes.is_empty() {
+            html.push_str("<h2>Notes</h2>\n<ul>\n");
+            for note in &self.notes {
+                let _ = writeln!(html, "<li>{note}</li>");
+            }
+            html.push_str("</ul>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+/// Summary of the refunds of all the reported operators with a subtotal per domain, to be
+/// published along with the reports of the operators.
+pub struct DomainSummary {
+    domains: Vec<[String; 4]>,
+    total: [String; 4],
+}
+
+impl DomainSummary {
+    pub fn new(audits: &[OperatorAudit], properties: &ChainProperties) -> Self {
+        let mut subtotals = BTreeMap::<DomainId, (usize, usize, Balance)>::new();
+        for audit in audits {
+            let (operators, nominators, refund) = subtotals.entry(audit.domain_id).or_default();
+            *operators += 1;
+            *nominators += audit.nominators.len();
+            *refund = refund.saturating_add(total_refund(audit));
+        }
+        let row = |domain: String, (operators, nominators, refund): (usize, usize, Balance)| {
+            [
+                domain,
+                operators.to_string(),
+                nominators.to_string(),
+                properties.balance(refund),
+            ]
+        };
+        let total = subtotals.values().fold(
+            (0, 0, Balance::zero()),
+            |(operators, nominators, refund), subtotal| {
+                (
+                    operators + subtotal.0,
+                    nominators + subtotal.1,
+                    refund.saturating_add(subtotal.2),
+                )
+            },
+        );
+
+        DomainSummary {
+            domains: subtotals
+                .into_iter()
+                .map(|(domain_id, subtotal)| row(format!("{domain_id:?}"), subtotal))
+                .collect(),
+            total: row("Total".to_string(), total),
+        }
+    }
+
+    pub fn markdown(&self) -> String {
+        let mut markdown = "# Refund of the nominators per domain\n\n".to_string();
+        let _ = writeln!(markdown, "| {} |", DOMAIN_COLUMNS.join(" | "));
+        let _ = writeln!(markdown, "|{}", "---|".repeat(DOMAIN_COLUMNS.len()));
+        for row in self.domains.iter().chain([&self.total]) {
+            let _ = writeln!(markdown, "| {} |", row.join(" | "));
+        }
+        markdown
+    }
+
+    pub fn html(&self) -> String {
+        let title = "Refund of the nominators per domain";
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n<table>\n<tr>"
+        );
+        for column in DOMAIN_COLUMNS {
+            let _ = write!(html, "<th>{column}</th>");
+        }
+        html.push_str("</tr>\n");
+        for row in self.domains.iter().chain([&self.total]) {
+            html.push_str("<tr>");
+            for cell in row {
+                let _ = write!(html, "<td>{cell}</td>");
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+fn total_refund(audit: &OperatorAudit) -> Balance {
+    audit
+        .nominators
+        .values()
+        .fold(Balance::zero(), |acc, breakdown| {
+            acc.saturating_add(breakdown.payout.saturating_refund())
+        })
+}
+
+fn unconverted_note(
+    properties: &ChainProperties,
+    nominator_id: &AccountId,
+    unconverted: &UnconvertedPending,
+) -> String {
+    let pending = match unconverted.kind {
+        PendingKind::Deposit => format!("deposit of {}", properties.balance(unconverted.amount)),
+        PendingKind::Withdrawal => format!("withdrawal of {} shares", unconverted.amount),
+    };
+    let outcome = if unconverted.refunded {
+        "refunded"
+    } else {
+        "not refunded"
+    };
+    format!(
+        "Share price of epoch {} not found for the pending {pending} of {}, {outcome}",
+        unconverted.epoch,
+        properties.account(nominator_id)
+    )
+}
+
+fn unlockable_note(
+    properties: &ChainProperties,
+    nominator_id: &AccountId,
+    unlockable: &UnlockableWithdrawal,
+) -> String {
+    let outcome = if unlockable.refunded {
+        "refunded"
+    } else {
+        "not refunded"
+    };
+    format!(
+        "Withdrawals of {} of {} were unlockable at confirmed domain block #{} before the slash, {outcome}",
+        properties.balance(unlockable.amount),
+        properties.account(nominator_id),
+        unlockable.confirmed_domain_block
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::NominatorBreakdown;
+    use crate::manifest::NominatorPayout;
+    use crate::types::versioned::SharePricePrecision;
+    use crate::types::SharePrice;
+    use crate::Hash;
+    use std::collections::BTreeMap;
+
+    fn audit() -> OperatorAudit {
+        let breakdown = |staked, storage_fund| NominatorBreakdown {
+            operator_id: 1,
+            slashed_at: 100,
+            block_hash: Hash::zero(),
+            shares: staked,
+            share_price: SharePrice::new(1, 1, SharePricePrecision::Perbill),
+            storage_fee_deposit: storage_fund,
+            storage_fund_balance: 30,
+            total_storage_fee_deposit: 30,
+            unconverted: vec![],
+            unlockable: None,
+            ready_to_withdraw: 0,
+            withdrawal_in_shares: None,
+            storage_fee_refund: 0,
+            pending_deposit: None,
+            payout: NominatorPayout {
+                staked,
+                storage_fund,
+                ..Default::default()
+            },
+        };
+        OperatorAudit {
+            operator_id: 1,
+            domain_id: DomainId::new(0),
+            slashed_at: 100,
+            block_hash: Hash::zero(),
+            spec_version: 1,
+            current_total_stake: 300,
+            current_epoch_rewards: 5,
+            nominators: BTreeMap::from([
+                (AccountId::new([1; 32]), breakdown(100, 10)),
+                (AccountId::new([2; 32]), breakdown(200, 20)),
+            ]),
+            dust: 5,
+            unexplained: 0,
+        }
+    }
+
+    #[test]
+    fn markdown_report_has_a_row_per_nominator() {
+        let markdown = OperatorReport::new(&audit(), &ChainProperties::default()).markdown();
+        assert!(markdown.contains("| Total Refund | 330 |"));
+        assert!(markdown.contains(&format!(
+            "| {} | 200 | 200 | 0 | 20 | 20 | 220 | no |",
+            AccountId::new([2; 32])
+        )));
+        assert!(!markdown.contains("## Notes"));
+
+        let markdown = OperatorReport::preview(&audit(), &ChainProperties::default()).markdown();
+        assert!(markdown.starts_with("# Expected refund of the nominators of Operator 1"));
+        assert!(markdown.contains("- Operator 1 is pending slash since #"));
+
+        let properties = ChainProperties {
+            ss58_format: 2254,
+            token_decimals: 1,
+            token_symbol: "tSSC".to_string(),
+        };
+        let markdown = OperatorReport::new(&audit(), &properties).markdown();
+        assert!(markdown.contains("| Total Refund | 33 tSSC |"));
+        assert!(markdown.contains(&format!(
+            "| {} | 200 | 20 tSSC | 0 tSSC | 2 tSSC | 2 tSSC | 22 tSSC | no |",
+            properties.account(&AccountId::new([2; 32]))
+        )));
+    }
+
+    #[test]
+    fn summary_has_a_row_per_domain() {
+        let other_domain = OperatorAudit {
+            operator_id: 2,
+            domain_id: DomainId::new(1),
+            ..audit()
+        };
+        let markdown = DomainSummary::new(
+            &[audit(), other_domain, audit()],
+            &ChainProperties::default(),
+        )
+        .markdown();
+        assert!(markdown.contains(&format!("| {:?} | 2 | 4 | 660 |", DomainId::new(0))));
+        assert!(markdown.contains(&format!("| {:?} | 1 | 2 | 330 |", DomainId::new(1))));
+        assert!(markdown.contains("| Total | 3 | 6 | 990 |"));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::error::SlashError;
+use crate::{AccountId, Hash};
+use serde::{Deserialize, Serialize};
+use sp_core::hashing::blake2_256;
+use sp_core::sr25519::{Pair, Public, Signature};
+use sp_core::{Bytes, Pair as PairT};
+use sp_runtime::traits::Verify;
+use std::path::{Path, PathBuf};
+
+/// Detached signature of a payout manifest by its reviewer.
+///
+/// `submit` refuses to transfer the payouts of a manifest that was not signed by the
+/// designated reviewer, so that no refund is disbursed without a second person approving it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestSignature {
+    pub reviewer: AccountId,
+    /// Blake2-256 hash of the manifest file.
+    pub manifest_hash: Hash,
+    /// Sr25519 signature of `manifest_hash` by `reviewer`.
+    pub signature: Bytes,
+}
+
+impl ManifestSignature {
+    /// Signs the contents of the manifest file with the reviewer's key.
+    pub fn new(manifest: &[u8], reviewer: &Pair) -> Self {
+        let manifest_hash = Hash::from(blake2_256(manifest));
+        ManifestSignature {
+            reviewer: AccountId::from(reviewer.public()),
+            manifest_hash,
+            signature: Bytes(reviewer.sign(manifest_hash.as_bytes()).as_ref().to_vec()),
+        }
+    }
+
+    /// Returns the path of the signature of the manifest at `manifest`, which is the path of
+    /// the manifest with `.sig` appended.
+    pub fn path(manifest: &Path) -> PathBuf {
+        let mut path = manifest.as_os_str().to_owned();
+        path.push(".sig");
+        PathBuf::from(path)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SlashError> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SlashError> {
+        Ok(std::fs::write(path, serde_json::to_vec_pretty(self)?)?)
+    }
+
+    /// Checks that the contents of the manifest file were signed by `reviewer`.
+    pub fn verify(&self, manifest: &[u8], reviewer: &AccountId) -> Result<(), SlashError> {
+        if self.reviewer != *reviewer {
+            return Err(SlashError::InvalidSignature(
+                "manifest is signed by another reviewer",
+            ));
+        }
+        if self.manifest_hash != Hash::from(blake2_256(manifest)) {
+            return Err(SlashError::InvalidSignature(
+                "manifest was changed after it was signed",
+            ));
+        }
+        let signature = <[u8; 64]>::try_from(self.signature.0.as_slice())
+            .map(Signature::from_raw)
+            .map_err(|_| SlashError::InvalidSignature("signature must be 64 bytes"))?;
+        let reviewer = Public::from_raw(reviewer.clone().into());
+        if !signature.verify(self.manifest_hash.as_bytes(), &reviewer) {
+            return Err(SlashError::InvalidSignature(
+                "signature does not match the manifest",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_is_verified_against_the_manifest_and_reviewer() {
+        let reviewer = Pair::from_string("//Alice", None).unwrap();
+        let other = Pair::from_string("//Bob", None).unwrap();
+        let manifest = br#"{"total":"100"}"#;
+        let signature = ManifestSignature::new(manifest, &reviewer);
+
+        let reviewer_account = AccountId::from(reviewer.public());
+        assert!(signature.verify(manifest, &reviewer_account).is_ok());
+        assert!(signature
+            .verify(br#"{"total":"101"}"#, &reviewer_account)
+            .is_err());
+        assert!(signature
+            .verify(manifest, &AccountId::from(other.public()))
+            .is_err());
+
+        let forged = ManifestSignature {
+            signature: ManifestSignature::new(manifest, &other).signature,
+            ..signature
+        };
+        assert!(forged.verify(manifest, &reviewer_account).is_err());
+    }
+}
@@ -0,0 +1,750 @@
+use crate::shutdown;
+use crate::Hash;
+use async_trait::async_trait;
+use futures::lock::Mutex;
+use jsonrpsee::client_transport::ws::{Url, WsTransportClientBuilder};
+use jsonrpsee::core::client::{Client, ClientBuilder, ClientT};
+use jsonrpsee::core::params::BatchRequestBuilder;
+use jsonrpsee::core::traits::ToRpcParams;
+use jsonrpsee_core::client::Error as ClientError;
+use serde::de::DeserializeOwned;
+use serde_json::value::RawValue;
+use std::fmt::Debug;
+use std::future::Future;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use substrate_api_client::ac_primitives::RpcParams;
+use substrate_api_client::api::error::UnexpectedTxStatus;
+use substrate_api_client::rpc::{Error as RpcError, JsonrpseeClient, Request, Subscribe};
+use tracing::{error, info, warn};
+
+/// Maximum number of attempts to reconnect to the node, and to replay a request whose
+/// connection dropped.
+const MAX_RECONNECT_ATTEMPTS: usize = 10;
+
+/// Delay between the attempts to reconnect to the node.
+const RECONNECT_DELAY: Duration = Duration::from_secs(3);
+
+/// Maximum number of requests packed into a single JSON-RPC batch message.
+const MAX_BATCH_REQUESTS: usize = 100;
+
+/// Connection to the node along with the number of times it was re-established.
+struct Connection {
+    url: String,
+    generation: u64,
+    client: JsonrpseeClient,
+    /// Client of the JSON-RPC batch requests, which are not supported by `JsonrpseeClient`,
+    /// connected on the first batch request.
+    batch_client: Option<Arc<Client>>,
+}
+
+/// Token bucket limiting the rate of the requests sent to the node.
+///
+/// The bucket holds up to one second worth of requests, so that short bursts are not
+/// delayed.
+struct RateLimiter {
+    max_rps: NonZeroU32,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    fn new(max_rps: NonZeroU32) -> Self {
+        RateLimiter {
+            max_rps,
+            bucket: Mutex::new(Bucket {
+                tokens: max_rps.get() as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until `requests` requests can be sent.
+    ///
+    /// More requests than the bucket holds, such as a large batch, are sent once the bucket is
+    /// full and leave it in debt, so that the requests after them wait for the debt to be
+    /// refilled.
+    async fn acquire(&self, requests: usize) {
+        let max_rps = self.max_rps.get() as f64;
+        let requests = requests as f64;
+        let needed = requests.min(max_rps);
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().await;
+                let now = Instant::now();
+                let refilled = now.duration_since(bucket.refilled_at).as_secs_f64() * max_rps;
+                bucket.tokens = (bucket.tokens + refilled).min(max_rps);
+                bucket.refilled_at = now;
+                if bucket.tokens >= needed {
+                    bucket.tokens -= requests;
+                    return;
+                }
+                Duration::from_secs_f64((needed - bucket.tokens) / max_rps)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Stops the requests once the node failed too many of them in a row, so that the run is
+/// stopped and resumed later instead of hammering a node that is going down.
+///
+/// Requests that dropped the connection or timed out count as failures, along with the
+/// failed attempts to reconnect. Any other outcome resets the count. Once tripped, the
+/// breaker stays open for the rest of the run.
+struct CircuitBreaker {
+    threshold: NonZeroUsize,
+    consecutive_failures: AtomicUsize,
+    tripped: AtomicBool,
+}
+
+impl CircuitBreaker {
+    fn new(threshold: NonZeroUsize) -> Self {
+        CircuitBreaker {
+            threshold,
+            consecutive_failures: AtomicUsize::new(0),
+            tripped: AtomicBool::new(false),
+        }
+    }
+
+    /// Records a failure and returns whether the breaker is tripped.
+    fn record_failure(&self) -> bool {
+        if self.count_failure() {
+            shutdown::request();
+        }
+        self.is_tripped()
+    }
+
+    /// Counts a failure and returns whether it is the one tripping the breaker, so that the
+    /// run is asked to stop once.
+    fn count_failure(&self) -> bool {
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::SeqCst) + 1;
+        let trips = failures >= self.threshold.get() && !self.tripped.swap(true, Ordering::SeqCst);
+        if trips {
+            error!("Node failed {failures} requests in a row, stopping the run");
+        }
+        trips
+    }
+
+    fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::SeqCst);
+    }
+
+    fn is_tripped(&self) -> bool {
+        self.tripped.load(Ordering::SeqCst)
+    }
+}
+
+/// Error of a pooled connection to a node of another chain than the first connection.
+#[derive(Debug, thiserror::Error)]
+#[error("{url} is a node of the chain with genesis hash {genesis_hash:?} instead of {expected:?}")]
+pub struct GenesisMismatch {
+    url: String,
+    genesis_hash: Option<Hash>,
+    expected: Option<Hash>,
+}
+
+/// Error of the requests not sent since the circuit breaker tripped.
+#[derive(Debug, thiserror::Error)]
+#[error("Not sent since the node failed {0} requests in a row")]
+pub struct CircuitOpen(usize);
+
+/// WebSocket client re-establishing the connection to the node when it drops.
+///
+/// Requests and subscriptions in flight when the connection drops are replayed on the new
+/// connection. Notifications of a subscription that was already established are not, so
+/// extrinsics being watched are looked up by their hash instead.
+///
+/// Requests and subscriptions that time out are replayed the same way, on a new connection
+/// since the node may be stalled.
+///
+/// Reads are spread across a pool of connections, possibly to different endpoints, since a
+/// single connection limits their throughput. Subscriptions and the requests depending on
+/// the transaction pool of the node, such as submitting an extrinsic, are always sent on the
+/// first connection so that the extrinsics of a signer reach the same node in order. So are
+/// the reads of the best or finalized block, which differ between the nodes, such as the
+/// nonce of the signer or the balance of the treasury.
+pub struct ReconnectingClient {
+    connections: Vec<Mutex<Connection>>,
+    /// Index of the connection the next read is sent on.
+    next: AtomicUsize,
+    rate_limiter: Option<RateLimiter>,
+    timeout: Option<Duration>,
+    /// Number of replays left for the whole run, unlimited if not set.
+    retry_budget: Option<AtomicUsize>,
+    circuit_breaker: Option<CircuitBreaker>,
+}
+
+impl ReconnectingClient {
+    pub async fn new(url: &str) -> Result<Self, RpcError> {
+        Ok(ReconnectingClient {
+            connections: vec![Mutex::new(Connection::new(url).await?)],
+            next: AtomicUsize::new(0),
+            rate_limiter: None,
+            timeout: None,
+            retry_budget: None,
+            circuit_breaker: None,
+        })
+    }
+
+    /// Adds a connection to `url` to the pool the reads are spread across, failing if the
+    /// node is not one of the chain of the first connection.
+    pub async fn add_connection(&mut self, url: &str) -> Result<(), RpcError> {
+        let connection = Connection::new(url).await?;
+        let expected = self.connections[0].lock().await.genesis_hash().await?;
+        let genesis_hash = connection.genesis_hash().await?;
+        if genesis_hash != expected || genesis_hash.is_none() {
+            return Err(RpcError::Client(Box::new(GenesisMismatch {
+                url: url.to_string(),
+                genesis_hash,
+                expected,
+            })));
+        }
+        self.connections.push(Mutex::new(connection));
+        Ok(())
+    }
+
+    /// Returns the URL of the node the submissions and subscriptions are sent to.
+    pub async fn url(&self) -> String {
+        self.connections[0].lock().await.url.clone()
+    }
+
+    /// Returns the number of connections in the pool.
+    pub fn connections(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Limits the requests and subscriptions, including the replayed ones, to `max_rps` per
+    /// second.
+    pub fn rate_limit(mut self, max_rps: NonZeroU32) -> Self {
+        self.rate_limiter = Some(RateLimiter::new(max_rps));
+        self
+    }
+
+    /// Fails the requests and subscriptions that do not complete within `timeout`.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Limits the replays of the requests and subscriptions to `retries` for the whole run,
+    /// on top of the attempts of each request.
+    pub fn retry_budget(mut self, retries: usize) -> Self {
+        self.retry_budget = Some(AtomicUsize::new(retries));
+        self
+    }
+
+    /// Stops sending requests once `threshold` of them failed in a row, asking the run to
+    /// stop.
+    pub fn circuit_breaker(mut self, threshold: NonZeroUsize) -> Self {
+        self.circuit_breaker = Some(CircuitBreaker::new(threshold));
+        self
+    }
+
+    /// Fails without sending the request if the circuit breaker tripped.
+    fn check_circuit(&self) -> Result<(), RpcError> {
+        match &self.circuit_breaker {
+            Some(circuit_breaker) if circuit_breaker.is_tripped() => Err(RpcError::Client(
+                Box::new(CircuitOpen(circuit_breaker.threshold.get())),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records the failure of the attempt of `method` that dropped the connection or timed
+    /// out, and returns whether it is replayed.
+    fn retries(&self, method: &str, attempt: usize) -> bool {
+        let tripped = self
+            .circuit_breaker
+            .as_ref()
+            .is_some_and(CircuitBreaker::record_failure);
+        if tripped || attempt >= MAX_RECONNECT_ATTEMPTS {
+            return false;
+        }
+        let Some(retry_budget) = &self.retry_budget else {
+            return true;
+        };
+        let taken = retry_budget
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                left.checked_sub(1)
+            })
+            .is_ok();
+        if !taken {
+            warn!("Retry budget exhausted, not replaying {method}");
+        }
+        taken
+    }
+
+    /// Resets the circuit breaker unless the request dropped the connection or timed out.
+    fn settle<T>(&self, result: Result<T, RpcError>) -> Result<T, RpcError> {
+        let failed = matches!(&result, Err(err) if is_disconnected(err) || is_timeout(err));
+        if let (Some(circuit_breaker), false) = (&self.circuit_breaker, failed) {
+            circuit_breaker.record_success();
+        }
+        result
+    }
+
+    /// Returns the index of the connection to send a request of `method` on.
+    fn select(&self, method: &str, params: &[RpcParams]) -> usize {
+        if is_pool_dependent(method) || params.iter().any(|params| reads_best_block(method, params))
+        {
+            return 0;
+        }
+        self.next.fetch_add(1, Ordering::Relaxed) % self.connections.len()
+    }
+
+    /// Returns the current connection at `index` once a request can be sent on it.
+    async fn client(&self, index: usize) -> (u64, JsonrpseeClient) {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire(1).await;
+        }
+        let connection = self.connections[index].lock().await;
+        (connection.generation, connection.client.clone())
+    }
+
+    /// Returns the current connection of the batch requests at `index` once a batch of
+    /// `requests` requests can be sent on it, connecting it if this is the first batch since
+    /// the connection was established.
+    ///
+    /// Each request of the batch counts against the rate limit, since the node serves them
+    /// one by one.
+    async fn batch_client(
+        &self,
+        index: usize,
+        requests: usize,
+    ) -> Result<(u64, Arc<Client>), RpcError> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire(requests).await;
+        }
+        let mut connection = self.connections[index].lock().await;
+        let batch_client = match &connection.batch_client {
+            Some(batch_client) => batch_client.clone(),
+            None => {
+                let batch_client = Arc::new(connect_batch_client(&connection.url).await?);
+                connection.batch_client = Some(batch_client.clone());
+                batch_client
+            }
+        };
+        Ok((connection.generation, batch_client))
+    }
+
+    /// Re-establishes the connection at `index` unless it was already re-established since
+    /// the given generation, such as by a concurrent request.
+    async fn reconnect(&self, index: usize, generation: u64) -> Result<(), RpcError> {
+        let mut connection = self.connections[index].lock().await;
+        if connection.generation != generation {
+            return Ok(());
+        }
+
+        let mut attempt = 1;
+        loop {
+            match JsonrpseeClient::new(&connection.url).await {
+                Ok(client) => {
+                    info!("Reconnected to {}", connection.url);
+                    connection.client = client;
+                    connection.batch_client = None;
+                    connection.generation += 1;
+                    return Ok(());
+                }
+                Err(err)
+                    if attempt < MAX_RECONNECT_ATTEMPTS
+                        && !self
+                            .circuit_breaker
+                            .as_ref()
+                            .is_some_and(CircuitBreaker::record_failure) =>
+                {
+                    warn!(
+                        "Failed to reconnect to {} (attempt {attempt}): {err:?}",
+                        connection.url
+                    );
+                    attempt += 1;
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Sends a request of `method` for each of the params, packed into JSON-RPC batches of
+    /// up to `MAX_BATCH_REQUESTS` requests per message, and returns the results in the
+    /// order of the params.
+    ///
+    /// Fails with the error of the first failed request of a batch.
+    pub async fn batch_request<R: DeserializeOwned + Debug>(
+        &self,
+        method: &str,
+        params: Vec<RpcParams>,
+    ) -> Result<Vec<R>, RpcError> {
+        let mut results = Vec::with_capacity(params.len());
+        for params in params.chunks(MAX_BATCH_REQUESTS) {
+            let index = self.select(method, params);
+            let mut attempt = 1;
+            loop {
+                self.check_circuit()?;
+                let (generation, client) = self.batch_client(index, params.len()).await?;
+                match with_timeout(self.timeout, send_batch(&client, method, params)).await {
+                    Err(err) if is_disconnected(&err) && self.retries(method, attempt) => {
+                        warn!("Connection dropped during a batch of {method}, reconnecting");
+                        attempt += 1;
+                        self.reconnect(index, generation).await?;
+                    }
+                    Err(err) if is_timeout(&err) && self.retries(method, attempt) => {
+                        warn!("Batch of {method} timed out, reconnecting");
+                        attempt += 1;
+                        self.reconnect(index, generation).await?;
+                    }
+                    result => {
+                        results.extend(self.settle(result)?);
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(results)
+    }
+}
+
+impl Connection {
+    async fn new(url: &str) -> Result<Self, RpcError> {
+        Ok(Connection {
+            url: url.to_string(),
+            generation: 0,
+            client: JsonrpseeClient::new(url).await?,
+            batch_client: None,
+        })
+    }
+
+    /// Returns the genesis hash of the chain of the node.
+    async fn genesis_hash(&self) -> Result<Option<Hash>, RpcError> {
+        let mut params = RpcParams::new();
+        params
+            .insert(0u32)
+            .map_err(|err| RpcError::Client(Box::new(err)))?;
+        self.client.request("chain_getBlockHash", params).await
+    }
+}
+
+/// Whether the request depends on the transaction pool of the node, which differs between
+/// the nodes and between the connections until the extrinsics are propagated.
+fn is_pool_dependent(method: &str) -> bool {
+    method.starts_with("author_") || method == "system_accountNextIndex"
+}
+
+/// Whether the request reads the state or the blocks at the best or finalized block of the
+/// node, which is requested without a block hash, or with a null one, as the last param.
+fn reads_best_block(method: &str, params: &RpcParams) -> bool {
+    if !method.starts_with("state_") && !method.starts_with("chain_") {
+        return false;
+    }
+    let params = params
+        .clone()
+        .build()
+        .and_then(|params| serde_json::from_str::<Vec<serde_json::Value>>(&params).ok())
+        .unwrap_or_default();
+    params.last().map_or(true, serde_json::Value::is_null)
+}
+
+/// Params of a request of a JSON-RPC batch.
+struct BatchParams(RpcParams);
+
+impl ToRpcParams for BatchParams {
+    fn to_rpc_params(self) -> Result<Option<Box<RawValue>>, serde_json::Error> {
+        self.0.build().map(RawValue::from_string).transpose()
+    }
+}
+
+async fn connect_batch_client(url: &str) -> Result<Client, RpcError> {
+    let url: Url = url.parse().map_err(|err| RpcError::Client(Box::new(err)))?;
+    let (sender, receiver) = WsTransportClientBuilder::default()
+        .build(url)
+        .await
+        .map_err(|err| RpcError::Client(Box::new(err)))?;
+    Ok(ClientBuilder::default().build_with_tokio(sender, receiver))
+}
+
+/// Sends the requests in a single JSON-RPC batch.
+async fn send_batch<R: DeserializeOwned + Debug>(
+    client: &Client,
+    method: &str,
+    params: &[RpcParams],
+) -> Result<Vec<R>, RpcError> {
+    let mut batch = BatchRequestBuilder::new();
+    for params in params {
+        batch
+            .insert(method, BatchParams(params.clone()))
+            .map_err(|err| RpcError::Client(Box::new(err)))?;
+    }
+    let response = client
+        .batch_request::<R>(batch)
+        .await
+        .map_err(|err| RpcError::Client(Box::new(err)))?;
+    match response.into_ok() {
+        Ok(results) => Ok(results.collect()),
+        Err(mut errors) => {
+            let err = errors.next().map(|err| err.into_owned());
+            Err(RpcError::Client(Box::new(match err {
+                Some(err) => ClientError::Call(err),
+                None => ClientError::Custom(format!("batch of {method} failed")),
+            })))
+        }
+    }
+}
+
+/// Whether the node answered the request with an error, such as for a method it does not
+/// serve, instead of the request failing to reach it.
+pub fn is_call_error(err: &RpcError) -> bool {
+    match err {
+        RpcError::Client(err) => matches!(
+            err.downcast_ref::<ClientError>(),
+            Some(ClientError::Call(_))
+        ),
+        _ => false,
+    }
+}
+
+#[async_trait(?Send)]
+impl Request for ReconnectingClient {
+    async fn request<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: RpcParams,
+    ) -> Result<R, RpcError> {
+        let index = self.select(method, std::slice::from_ref(&params));
+        let mut attempt = 1;
+        loop {
+            self.check_circuit()?;
+            let (generation, client) = self.client(index).await;
+            match with_timeout(self.timeout, client.request(method, params.clone())).await {
+                Err(err) if is_disconnected(&err) && self.retries(method, attempt) => {
+                    warn!("Connection dropped during {method}, reconnecting");
+                    attempt += 1;
+                    self.reconnect(index, generation).await?;
+                }
+                Err(err) if is_timeout(&err) && self.retries(method, attempt) => {
+                    warn!("{method} timed out, reconnecting");
+                    attempt += 1;
+                    self.reconnect(index, generation).await?;
+                }
+                result => return self.settle(result),
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Subscribe for ReconnectingClient {
+    type Subscription<Notification>
+        = <JsonrpseeClient as Subscribe>::Subscription<Notification>
+    where
+        Notification: DeserializeOwned;
+
+    async fn subscribe<Notification: DeserializeOwned>(
+        &self,
+        sub: &str,
+        params: RpcParams,
+        unsub: &str,
+    ) -> Result<Self::Subscription<Notification>, RpcError> {
+        // subscriptions are kept on the first connection along with the submissions they watch
+        let mut attempt = 1;
+        loop {
+            self.check_circuit()?;
+            let (generation, client) = self.client(0).await;
+            match with_timeout(self.timeout, client.subscribe(sub, params.clone(), unsub)).await {
+                Err(err) if is_disconnected(&err) && self.retries(sub, attempt) => {
+                    warn!("Connection dropped during {sub}, reconnecting");
+                    attempt += 1;
+                    self.reconnect(0, generation).await?;
+                }
+                Err(err) if is_timeout(&err) && self.retries(sub, attempt) => {
+                    warn!("{sub} timed out, reconnecting");
+                    attempt += 1;
+                    self.reconnect(0, generation).await?;
+                }
+                result => return self.settle(result),
+            }
+        }
+    }
+}
+
+/// Awaits the request, failing with a timeout error if it does not complete within
+/// `timeout`.
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    request: impl Future<Output = Result<T, RpcError>>,
+) -> Result<T, RpcError> {
+    match timeout {
+        None => request.await,
+        Some(timeout) => tokio::time::timeout(timeout, request)
+            .await
+            .unwrap_or_else(|_| Err(timeout_error())),
+    }
+}
+
+/// Error of a request that did not complete within the timeout.
+pub fn timeout_error() -> RpcError {
+    RpcError::Client(Box::new(ClientError::RequestTimeout))
+}
+
+/// Whether the request failed because it did not complete within the timeout.
+pub fn is_timeout(err: &RpcError) -> bool {
+    match err {
+        RpcError::Client(err) => matches!(
+            err.downcast_ref::<ClientError>(),
+            Some(ClientError::RequestTimeout)
+        ),
+        _ => false,
+    }
+}
+
+/// Whether the request failed because the connection to the node dropped.
+pub fn is_disconnected(err: &RpcError) -> bool {
+    match err {
+        RpcError::Client(err) => matches!(
+            err.downcast_ref::<ClientError>(),
+            Some(ClientError::RestartNeeded(_))
+        ),
+        _ => false,
+    }
+}
+
+/// Whether the watch of a submitted extrinsic ended because the connection to the node
+/// dropped or timed out, in which case the extrinsic may still be included.
+pub fn is_watch_lost(err: &substrate_api_client::Error) -> bool {
+    match err {
+        substrate_api_client::Error::NoStream => true,
+        substrate_api_client::Error::RpcClient(err) => is_disconnected(err) || is_timeout(err),
+        _ => false,
+    }
+}
+
+/// Whether the submitted extrinsic was dropped or rejected by the transaction pool, in which
+/// case it is never included and its batch can be signed again.
+///
+/// Any other error, such as a retracted block or a finality timeout, leaves the extrinsic
+/// possibly included, or to be included later.
+pub fn is_dropped(err: &substrate_api_client::Error) -> bool {
+    match err {
+        substrate_api_client::Error::UnexpectedTxStatus(status) => matches!(
+            status,
+            UnexpectedTxStatus::Dropped | UnexpectedTxStatus::Usurped | UnexpectedTxStatus::Invalid
+        ),
+        // rejected by the pool at submission
+        substrate_api_client::Error::RpcClient(err) => is_call_error(err),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn circuit_breaker(threshold: usize) -> CircuitBreaker {
+        CircuitBreaker::new(NonZeroUsize::new(threshold).unwrap())
+    }
+
+    fn client(retry_budget: Option<usize>) -> ReconnectingClient {
+        ReconnectingClient {
+            connections: vec![],
+            next: AtomicUsize::new(0),
+            rate_limiter: None,
+            timeout: None,
+            retry_budget: retry_budget.map(AtomicUsize::new),
+            circuit_breaker: None,
+        }
+    }
+
+    #[test]
+    fn circuit_breaker_trips_once_at_threshold() {
+        let circuit_breaker = circuit_breaker(3);
+        let trips: Vec<_> = (0..5).map(|_| circuit_breaker.count_failure()).collect();
+        assert_eq!(trips, [false, false, true, false, false]);
+        assert!(circuit_breaker.is_tripped());
+    }
+
+    #[test]
+    fn tripped_circuit_breaker_requests_shutdown() {
+        let circuit_breaker = circuit_breaker(2);
+        assert!(!circuit_breaker.record_failure());
+        assert!(circuit_breaker.record_failure());
+        assert!(shutdown::is_requested());
+    }
+
+    #[test]
+    fn success_resets_the_failures() {
+        let circuit_breaker = circuit_breaker(2);
+        assert!(!circuit_breaker.count_failure());
+        circuit_breaker.record_success();
+        assert!(!circuit_breaker.count_failure());
+        assert!(!circuit_breaker.is_tripped());
+        assert!(circuit_breaker.count_failure());
+    }
+
+    fn params(values: &[Option<&str>]) -> RpcParams {
+        let mut params = RpcParams::new();
+        for value in values {
+            params.insert(value).unwrap();
+        }
+        params
+    }
+
+    #[test]
+    fn only_dropped_statuses_allow_signing_again() {
+        let is_status_dropped =
+            |status| is_dropped(&substrate_api_client::Error::UnexpectedTxStatus(status));
+        assert!(is_status_dropped(UnexpectedTxStatus::Dropped));
+        assert!(is_status_dropped(UnexpectedTxStatus::Usurped));
+        assert!(is_status_dropped(UnexpectedTxStatus::Invalid));
+        assert!(!is_status_dropped(UnexpectedTxStatus::Future));
+        assert!(!is_status_dropped(UnexpectedTxStatus::Retracted));
+        assert!(!is_status_dropped(UnexpectedTxStatus::FinalityTimeout));
+        assert!(!is_dropped(&substrate_api_client::Error::NoStream));
+        assert!(!is_dropped(&substrate_api_client::Error::RpcClient(
+            timeout_error()
+        )));
+    }
+
+    #[test]
+    fn best_block_reads_are_detected() {
+        let key = Some("0x00");
+        let hash = Some("0x01");
+        assert!(reads_best_block("state_getStorage", &params(&[key])));
+        assert!(reads_best_block("state_getStorage", &params(&[key, None])));
+        assert!(!reads_best_block("state_getStorage", &params(&[key, hash])));
+        assert!(reads_best_block("chain_getFinalizedHead", &params(&[])));
+        assert!(!reads_best_block("chain_getBlockHash", &params(&[hash])));
+        assert!(!reads_best_block("system_chain", &params(&[])));
+    }
+
+    #[test]
+    fn retries_stop_once_budget_is_exhausted() {
+        let client = client(Some(2));
+        assert!(client.retries("state_getStorage", 1));
+        assert!(client.retries("state_getStorage", 1));
+        assert!(!client.retries("state_getStorage", 1));
+    }
+
+    #[test]
+    fn retries_stop_at_max_attempts() {
+        let client = client(None);
+        assert!(client.retries("state_getStorage", MAX_RECONNECT_ATTEMPTS - 1));
+        assert!(!client.retries("state_getStorage", MAX_RECONNECT_ATTEMPTS));
+    }
+
+    #[test]
+    fn retries_stop_once_circuit_breaker_trips() {
+        let mut client = client(None);
+        client.circuit_breaker = Some(circuit_breaker(1));
+        assert!(!client.retries("state_getStorage", 1));
+        assert!(matches!(client.check_circuit(), Err(RpcError::Client(_))));
+    }
+}
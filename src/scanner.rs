@@ -0,0 +1,374 @@
+use crate::error::SlashError;
+use crate::progress::Progress;
+use crate::{AccountId, Api, Balance, Hash, Number};
+use codec::{Decode, Encode};
+use futures::future::join_all;
+use sp_core::hashing::{blake2_256, twox_128};
+use sp_core::storage::StorageKey;
+use sp_domains::OperatorId;
+use sp_runtime::traits::Zero;
+use std::str::FromStr;
+use std::time::Duration;
+use substrate_api_client::ac_node_api::StaticEvent;
+use substrate_api_client::{GetChainInfo, GetEvents, GetStorage};
+
+/// Number of blocks scanned concurrently.
+const SCAN_CHUNK_SIZE: Number = 100;
+
+/// Interval at which the best block is polled while waiting for an extrinsic.
+const BLOCK_POLL_INTERVAL: Duration = Duration::from_secs(6);
+
+/// `Domains::OperatorSlashed` event.
+///
+/// Only the operator id is decoded since the slashed reason is not needed for the refunds.
+#[derive(Debug, Decode)]
+struct OperatorSlashed {
+    operator_id: OperatorId,
+}
+
+impl StaticEvent for OperatorSlashed {
+    const PALLET: &'static str = "Domains";
+    const EVENT: &'static str = "OperatorSlashed";
+}
+
+/// `Balances::Transfer` event.
+#[derive(Debug, Decode)]
+struct Transfer {
+    from: AccountId,
+    to: AccountId,
+    amount: Balance,
+}
+
+impl StaticEvent for Transfer {
+    const PALLET: &'static str = "Balances";
+    const EVENT: &'static str = "Transfer";
+}
+
+/// `Balances::Deposit` event.
+#[derive(Debug, Decode)]
+struct Deposit {
+    who: AccountId,
+    amount: Balance,
+}
+
+impl StaticEvent for Deposit {
+    const PALLET: &'static str = "Balances";
+    const EVENT: &'static str = "Deposit";
+}
+
+/// Block given either by its number or by its hash, such as with `--at-block`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockId {
+    Number(Number),
+    Hash(Hash),
+}
+
+impl FromStr for BlockId {
+    type Err = String;
+
+    /// Parses a `0x` prefixed block hash or a block number.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("0x") {
+            Hash::from_str(s)
+                .map(BlockId::Hash)
+                .map_err(|err| format!("invalid block hash `{s}`: {err}"))
+        } else {
+            s.parse()
+                .map(BlockId::Number)
+                .map_err(|err| format!("invalid block number `{s}`: {err}"))
+        }
+    }
+}
+
+/// Returns the number and hash of the block.
+pub async fn resolve_block(api: &Api, block: BlockId) -> Result<(Number, Hash), SlashError> {
+    match block {
+        BlockId::Number(number) => {
+            let block_hash = api
+                .get_block_hash(Some(number))
+                .await?
+                .ok_or(SlashError::BlockNotFound(number))?;
+            Ok((number, block_hash))
+        }
+        BlockId::Hash(block_hash) => {
+            let header = api
+                .get_header(Some(block_hash))
+                .await?
+                .ok_or(SlashError::BlockHashNotFound(block_hash))?;
+            Ok((header.number, block_hash))
+        }
+    }
+}
+
+/// Returns the block number of the best block.
+pub async fn best_block_number(api: &Api) -> Result<Number, SlashError> {
+    let header = api.get_header(None).await?.ok_or(SlashError::Connection(
+        "best block header not found".to_string(),
+    ))?;
+    Ok(header.number)
+}
+
+/// Returns the hash of the finalized block.
+pub async fn finalized_block_hash(api: &Api) -> Result<Hash, SlashError> {
+    api.get_finalized_head()
+        .await?
+        .ok_or(SlashError::Connection(
+            "finalized block hash not found".to_string(),
+        ))
+}
+
+/// Returns the block number of the finalized block.
+pub async fn finalized_block_number(api: &Api) -> Result<Number, SlashError> {
+    let finalized_hash = finalized_block_hash(api).await?;
+    let header = api
+        .get_header(Some(finalized_hash))
+        .await?
+        .ok_or(SlashError::Connection(
+            "finalized block header not found".to_string(),
+        ))?;
+    Ok(header.number)
+}
+
+/// Walks the blocks in `from..=to` and returns every operator slashed in this range along
+/// with the block number at which the operator was slashed.
+pub async fn scan_slashed_operators(
+    api: &Api,
+    from: Number,
+    to: Number,
+) -> Result<Vec<(OperatorId, Number)>, SlashError> {
+    Ok(scan_events::<OperatorSlashed>(api, from, to)
+        .await?
+        .into_iter()
+        .map(|(number, event)| (event.operator_id, number))
+        .collect())
+}
+
+/// Walks the blocks in `from..=to` and returns the receiver and amount of every transfer
+/// made from the `sender` account in this range.
+pub async fn scan_transfers_from(
+    api: &Api,
+    sender: &AccountId,
+    from: Number,
+    to: Number,
+) -> Result<Vec<(AccountId, Balance)>, SlashError> {
+    Ok(scan_events::<Transfer>(api, from, to)
+        .await?
+        .into_iter()
+        .filter(|(_, event)| event.from == *sender)
+        .map(|(_, event)| (event.to, event.amount))
+        .collect())
+}
+
+/// Returns the total balance transferred or deposited to the `receiver` account in the blocks
+/// `from..=to`.
+pub async fn inflow_to(
+    api: &Api,
+    receiver: &AccountId,
+    from: Number,
+    to: Number,
+) -> Result<Balance, SlashError> {
+    let transferred = scan_events::<Transfer>(api, from, to)
+        .await?
+        .into_iter()
+        .filter(|(_, event)| event.to == *receiver)
+        .map(|(_, event)| event.amount);
+    let deposited = scan_events::<Deposit>(api, from, to)
+        .await?
+        .into_iter()
+        .filter(|(_, event)| event.who == *receiver)
+        .map(|(_, event)| event.amount);
+    transferred
+        .chain(deposited)
+        .try_fold(Balance::zero(), |acc, amount| acc.checked_add(amount))
+        .ok_or(SlashError::Overflow)
+}
+
+/// Returns the receiver and amount of every transfer made from the `sender` account in the
+/// block with the given hash.
+pub async fn transfers_from_at(
+    api: &Api,
+    sender: &AccountId,
+    block_hash: Hash,
+) -> Result<Vec<(AccountId, Balance)>, SlashError> {
+    Ok(get_events_at::<Transfer>(api, block_hash)
+        .await?
+        .into_iter()
+        .filter(|event| event.from == *sender)
+        .map(|event| (event.to, event.amount))
+        .collect())
+}
+
+/// Waits for the extrinsic with the given hash to be included in the blocks `from..=to` and
+/// returns the hash of the block it was included in.
+///
+/// Returns `None` if the extrinsic is not included by block `to`.
+pub async fn find_extrinsic(
+    api: &Api,
+    extrinsic_hash: Hash,
+    from: Number,
+    to: Number,
+) -> Result<Option<Hash>, SlashError> {
+    let mut number = from;
+    while number <= to {
+        if number > best_block_number(api).await? {
+            tokio::time::sleep(BLOCK_POLL_INTERVAL).await;
+            continue;
+        }
+
+        let block_hash = api
+            .get_block_hash(Some(number))
+            .await?
+            .ok_or(SlashError::BlockNotFound(number))?;
+        let block = api
+            .get_block(Some(block_hash))
+            .await?
+            .ok_or(SlashError::BlockNotFound(number))?;
+        let included = block
+            .extrinsics
+            .iter()
+            .any(|extrinsic| Hash::from(blake2_256(&extrinsic.encode())) == extrinsic_hash);
+        if included {
+            return Ok(Some(block_hash));
+        }
+        number += 1;
+    }
+
+    Ok(None)
+}
+
+/// Returns the number of the block with the given hash and the index of the extrinsic with
+/// the given hash in it.
+pub async fn extrinsic_index(
+    api: &Api,
+    extrinsic_hash: Hash,
+    block_hash: Hash,
+) -> Result<(Number, u32), SlashError> {
+    let block = api
+        .get_block(Some(block_hash))
+        .await?
+        .ok_or(SlashError::BlockReverted(block_hash))?;
+    let index = block
+        .extrinsics
+        .iter()
+        .position(|extrinsic| Hash::from(blake2_256(&extrinsic.encode())) == extrinsic_hash)
+        .ok_or(SlashError::ExtrinsicNotFound(extrinsic_hash))?;
+    Ok((block.header.number, index as u32))
+}
+
+/// Returns the Blake2 256 hash of the encoded `System::Events` of the block.
+pub async fn events_digest(api: &Api, block_hash: Hash) -> Result<Hash, SlashError> {
+    let key = [twox_128(b"System"), twox_128(b"Events")].concat();
+    let events = api
+        .get_opaque_storage_by_key(StorageKey(key), Some(block_hash))
+        .await?
+        .unwrap_or_default();
+    Ok(Hash::from(blake2_256(&events)))
+}
+
+/// Waits for the block with the given hash to be finalized.
+///
+/// Fails if another block is finalized at its height instead.
+pub async fn wait_for_finalization(api: &Api, block_hash: Hash) -> Result<(), SlashError> {
+    let number = api
+        .get_header(Some(block_hash))
+        .await?
+        .ok_or(SlashError::BlockReverted(block_hash))?
+        .number;
+    while finalized_block_number(api).await? < number {
+        tokio::time::sleep(BLOCK_POLL_INTERVAL).await;
+    }
+
+    let finalized_hash = api
+        .get_block_hash(Some(number))
+        .await?
+        .ok_or(SlashError::BlockNotFound(number))?;
+    if finalized_hash != block_hash {
+        return Err(SlashError::BlockReverted(block_hash));
+    }
+    Ok(())
+}
+
+/// Checks the events of the batch extrinsic with the given hash in the block it was
+/// included in.
+///
+/// The extrinsic must have succeeded and, unless it only approves a multisig call, emitted
+/// `Utility::BatchCompleted`. A failed transfer reverts the whole `batch_all`, which is then
+/// only reported by the call wrapping it, such as in `Sudo::Sudid`, while the extrinsic
+/// itself succeeds.
+pub async fn check_batch_events(
+    api: &Api,
+    extrinsic_hash: Hash,
+    block_hash: Hash,
+    executes: bool,
+) -> Result<(), SlashError> {
+    let events = api
+        .fetch_events_for_extrinsic(extrinsic_hash, block_hash)
+        .await?;
+    let emitted = |pallet: &str, variant: &str| {
+        events
+            .iter()
+            .any(|event| event.pallet_name() == pallet && event.variant_name() == variant)
+    };
+    let batch_failed = |reason| SlashError::BatchFailed { block_hash, reason };
+
+    if emitted("System", "ExtrinsicFailed") {
+        return Err(batch_failed("extrinsic failed"));
+    }
+    if !emitted("System", "ExtrinsicSuccess") {
+        return Err(batch_failed("`System::ExtrinsicSuccess` not emitted"));
+    }
+    if executes {
+        if emitted("Utility", "BatchInterrupted") {
+            return Err(batch_failed("batch was interrupted"));
+        }
+        if !emitted("Utility", "BatchCompleted") {
+            return Err(batch_failed("batch was not completed"));
+        }
+    }
+    Ok(())
+}
+
+/// Returns every `E` event emitted in the blocks `from..=to` along with its block number.
+async fn scan_events<E: StaticEvent>(
+    api: &Api,
+    from: Number,
+    to: Number,
+) -> Result<Vec<(Number, E)>, SlashError> {
+    let mut events = vec![];
+    let progress = Progress::new(
+        "Scanned blocks",
+        to.saturating_add(1).saturating_sub(from) as usize,
+    );
+    for start in (from..=to).step_by(SCAN_CHUNK_SIZE as usize) {
+        let end = start.saturating_add(SCAN_CHUNK_SIZE - 1).min(to);
+        let futs: Vec<_> = (start..=end)
+            .map(|number| async move {
+                let block_hash = api
+                    .get_block_hash(Some(number))
+                    .await?
+                    .ok_or(SlashError::BlockNotFound(number))?;
+                Ok::<_, SlashError>(
+                    get_events_at::<E>(api, block_hash)
+                        .await?
+                        .into_iter()
+                        .map(|event| (number, event))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        for block_events in join_all(futs).await {
+            events.extend(block_events?);
+        }
+        progress.advance((end - start + 1) as usize);
+    }
+
+    Ok(events)
+}
+
+async fn get_events_at<E: StaticEvent>(api: &Api, block_hash: Hash) -> Result<Vec<E>, SlashError> {
+    let events = api.fetch_events_from_block(block_hash).await?;
+    events
+        .find::<E>()
+        .map(|event| event.map_err(|err| SlashError::Rpc(err.into())))
+        .collect()
+}
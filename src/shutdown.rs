@@ -0,0 +1,32 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::warn;
+
+/// Whether the run was asked to stop with Ctrl-C.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Listens for Ctrl-C in the background so that a submission stops issuing new batches
+/// instead of being torn down with batches in flight.
+///
+/// The first Ctrl-C requests the stop, which lets the batches in flight resolve and be
+/// recorded in the checkpoint. A second one exits right away.
+pub fn listen() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if REQUESTED.swap(true, Ordering::SeqCst) {
+                warn!("Exiting without waiting for the batches in flight");
+                std::process::exit(130);
+            }
+            warn!("Stopping once the batches in flight are resolved, press Ctrl-C again to exit right away");
+        }
+    });
+}
+
+/// Asks the run to stop, such as when the node keeps failing the requests.
+pub fn request() {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Whether the run was asked to stop, in which case no new batch is submitted.
+pub fn is_requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
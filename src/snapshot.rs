@@ -0,0 +1,115 @@
+use crate::backend::{ChainBackend, MemoryBackend, RawValue, RecordingBackend};
+use crate::error::SlashError;
+use crate::types::versioned::RuntimeUpgrades;
+use crate::types::DomainEpoch;
+use crate::{Balance, Calculation, Number, SlashRefundCalculator};
+use codec::{Decode, Encode};
+use futures::future::join_all;
+use sp_domains::OperatorId;
+use std::path::Path;
+
+/// Chain state of the slashed operators, so that the refunds can be calculated without
+/// access to a node.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct Snapshot {
+    /// Slashed operators along with the block number at which each operator was slashed.
+    pub operators: Vec<(OperatorId, Number)>,
+    /// Balance of the treasury when the snapshot was exported.
+    pub treasury_balance: Balance,
+    /// Existential deposit of the chain, which the refunds are checked against.
+    pub existential_deposit: Balance,
+    /// Storage items and runtime calls read for the calculation of the operators.
+    pub state: MemoryBackend,
+}
+
+impl Snapshot {
+    /// Captures the state of the slashed operators from the backend.
+    ///
+    /// Along with the state read for the calculation, the share prices of all the epochs of
+    /// each operator are captured. Operators that failed to be captured are left out of the
+    /// snapshot and returned along with their error.
+    pub async fn export<B: ChainBackend>(
+        backend: &B,
+        slashed_operators: Vec<(OperatorId, Number)>,
+        treasury_balance: Balance,
+        existential_deposit: Balance,
+        upgrades: RuntimeUpgrades,
+    ) -> (Self, Vec<SlashError>) {
+        let recorder = RecordingBackend::new(backend);
+        let futs: Vec<_> = slashed_operators
+            .iter()
+            .map(|(operator_id, slashed_at)| {
+                let recorder = &recorder;
+                async move {
+                    record_epoch_share_prices(recorder, *operator_id, *slashed_at)
+                        .await
+                        .map_err(|err| SlashError::operator(*operator_id, err))
+                }
+            })
+            .collect();
+        let mut failures = vec![];
+        let mut recorded_operators = vec![];
+        for (result, slashed_operator) in join_all(futs).await.into_iter().zip(slashed_operators) {
+            match result {
+                Ok(()) => recorded_operators.push(slashed_operator),
+                Err(err) => failures.push(err),
+            }
+        }
+
+        let Calculation {
+            payouts,
+            failures: calculation_failures,
+        } = SlashRefundCalculator::new(&recorder)
+            .runtime_upgrades(upgrades)
+            .calculate(recorded_operators)
+            .await;
+        failures.extend(calculation_failures);
+        let operators = payouts
+            .operators
+            .iter()
+            .map(|operator_payout| (operator_payout.operator_id, operator_payout.slashed_at))
+            .collect();
+
+        let snapshot = Snapshot {
+            operators,
+            treasury_balance,
+            existential_deposit,
+            state: recorder.into_state(),
+        };
+        (snapshot, failures)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SlashError> {
+        Ok(Snapshot::decode(&mut std::fs::read(path)?.as_slice())?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SlashError> {
+        Ok(std::fs::write(path, self.encode())?)
+    }
+}
+
+/// Reads all the `OperatorEpochSharePrice` entries of the operator at the block before the
+/// slash, so that they are recorded by the backend.
+///
+/// The entries are read as raw values, since their precision depends on the runtime at the
+/// block.
+async fn record_epoch_share_prices<B: ChainBackend>(
+    backend: &B,
+    operator_id: OperatorId,
+    slashed_at: Number,
+) -> Result<(), SlashError> {
+    let number = slashed_at - 1;
+    let block_hash = backend
+        .block_hash(number)
+        .await?
+        .ok_or(SlashError::BlockNotFound(number))?;
+    backend
+        .storage_double_map_entries::<_, DomainEpoch, RawValue>(
+            "Domains",
+            "OperatorEpochSharePrice",
+            operator_id,
+            block_hash,
+        )
+        .await?;
+    Ok(())
+}
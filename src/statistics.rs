@@ -0,0 +1,170 @@
+use crate::manifest::{NominatorPayout, OperatorPayout, PayoutManifest};
+use crate::Balance;
+use sp_domains::DomainId;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Distribution of the refunds of a set of nominators along with the total of each
+/// component, so that the payouts can be sanity-checked at a glance.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PayoutStatistics {
+    pub nominators: usize,
+    pub min_refund: Balance,
+    /// Median refund, rounded down between the two middle refunds of an even count.
+    pub median_refund: Balance,
+    pub max_refund: Balance,
+    pub staked: Balance,
+    pub withdrawable: Balance,
+    pub storage_fund: Balance,
+    pub total_refund: Balance,
+}
+
+impl PayoutStatistics {
+    pub fn new<'a>(payouts: impl IntoIterator<Item = &'a NominatorPayout>) -> Self {
+        let mut statistics = PayoutStatistics::default();
+        let mut refunds = vec![];
+        for payout in payouts {
+            statistics.staked = statistics.staked.saturating_add(payout.staked);
+            statistics.withdrawable = statistics.withdrawable.saturating_add(payout.withdrawable);
+            statistics.storage_fund = statistics.storage_fund.saturating_add(payout.storage_fund);
+            let refund = payout.saturating_refund();
+            statistics.total_refund = statistics.total_refund.saturating_add(refund);
+            refunds.push(refund);
+        }
+        refunds.sort_unstable();
+        statistics.nominators = refunds.len();
+        if let (Some(&min), Some(&max)) = (refunds.first(), refunds.last()) {
+            let middle = refunds.len() / 2;
+            statistics.min_refund = min;
+            statistics.max_refund = max;
+            statistics.median_refund = if refunds.len() % 2 == 0 {
+                let (lower, upper) = (refunds[middle - 1], refunds[middle]);
+                lower + (upper - lower) / 2
+            } else {
+                refunds[middle]
+            };
+        }
+        statistics
+    }
+
+    pub fn of_operator(operator_payout: &OperatorPayout) -> Self {
+        Self::new(operator_payout.nominators.values())
+    }
+
+    /// Statistics of all the payouts of the manifest, counting a nominator of several
+    /// operators once per operator.
+    pub fn of_manifest(payouts: &PayoutManifest) -> Self {
+        Self::new(
+            payouts
+                .operators
+                .iter()
+                .flat_map(|operator_payout| operator_payout.nominators.values()),
+        )
+    }
+
+    /// Statistics of the payouts of the manifest per domain, with `None` for the operators
+    /// whose domain is not known.
+    pub fn of_domains(payouts: &PayoutManifest) -> BTreeMap<Option<DomainId>, Self> {
+        payouts
+            .domain_ids()
+            .into_iter()
+            .map(|domain_id| {
+                let statistics = Self::new(
+                    payouts
+                        .operators
+                        .iter()
+                        .filter(|operator_payout| operator_payout.domain_id == domain_id)
+                        .flat_map(|operator_payout| operator_payout.nominators.values()),
+                );
+                (domain_id, statistics)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for PayoutStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} Nominators, Refund min/median/max: {}/{}/{}, Staked: {}, Withdrawable: {}, Storage Fund: {}, Total Refund: {}",
+            self.nominators,
+            self.min_refund,
+            self.median_refund,
+            self.max_refund,
+            self.staked,
+            self.withdrawable,
+            self.storage_fund,
+            self.total_refund
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AccountId, Hash};
+
+    #[test]
+    fn statistics_summarize_the_refunds() {
+        let payout = |staked, storage_fund| NominatorPayout {
+            staked,
+            storage_fund,
+            ..Default::default()
+        };
+        let payouts = [payout(40, 0), payout(10, 0), payout(25, 5), payout(100, 0)];
+        assert_eq!(
+            PayoutStatistics::new(&payouts),
+            PayoutStatistics {
+                nominators: 4,
+                min_refund: 10,
+                median_refund: 35,
+                max_refund: 100,
+                staked: 175,
+                withdrawable: 0,
+                storage_fund: 5,
+                total_refund: 180,
+            }
+        );
+        assert_eq!(PayoutStatistics::new(&payouts[..3]).median_refund, 30);
+        assert_eq!(
+            PayoutStatistics::new(std::iter::empty()),
+            PayoutStatistics::default()
+        );
+    }
+
+    #[test]
+    fn statistics_are_subtotaled_per_domain() {
+        let operator_payout = |operator_id, domain_id, staked| OperatorPayout {
+            operator_id,
+            domain_id,
+            slashed_at: 100,
+            block_hash: Hash::zero(),
+            spec_version: 1,
+            nominators: BTreeMap::from([(
+                AccountId::new([operator_id as u8; 32]),
+                NominatorPayout {
+                    staked,
+                    ..Default::default()
+                },
+            )]),
+            unconverted: BTreeMap::new(),
+            unlockable: BTreeMap::new(),
+            components: BTreeMap::new(),
+            dust: 0,
+            unexplained: 0,
+        };
+        let payouts = PayoutManifest::new(vec![
+            operator_payout(1, Some(DomainId::new(0)), 10),
+            operator_payout(2, Some(DomainId::new(1)), 20),
+            operator_payout(3, Some(DomainId::new(0)), 30),
+            operator_payout(4, None, 40),
+        ])
+        .unwrap();
+        let statistics = PayoutStatistics::of_domains(&payouts);
+        assert_eq!(statistics.len(), 3);
+        assert_eq!(statistics[&Some(DomainId::new(0))].total_refund, 40);
+        assert_eq!(statistics[&Some(DomainId::new(0))].nominators, 2);
+        assert_eq!(statistics[&Some(DomainId::new(1))].total_refund, 20);
+        assert_eq!(statistics[&None].total_refund, 40);
+    }
+}
@@ -1,76 +1,82 @@
 use crate::{AccountId, Balance, Number};
 use codec::{Decode, Encode};
 use scale_info::TypeInfo;
-use sp_domains::{DomainId, EpochIndex, OperatorId, OperatorPublicKey};
+use sp_domains::{DomainId, EpochIndex, OperatorPublicKey};
 use sp_runtime::traits::Zero;
-use sp_runtime::{Perbill, Percent};
-use std::collections::{BTreeMap, VecDeque};
+use sp_runtime::{Perbill, Percent, Perquintill};
+use std::collections::VecDeque;
+use versioned::SharePricePrecision;
+
+pub mod versioned;
 
 #[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq, Default)]
-pub(crate) struct Deposit {
-    pub(crate) known: KnownDeposit,
-    pub(crate) pending: Option<PendingDeposit>,
+pub struct Deposit {
+    pub known: KnownDeposit,
+    pub pending: Option<PendingDeposit>,
 }
 
 #[derive(TypeInfo, Debug, Encode, Decode, Copy, Clone, PartialEq, Eq, Default)]
-pub(crate) struct KnownDeposit {
-    pub(crate) shares: Balance,
-    pub(crate) storage_fee_deposit: Balance,
+pub struct KnownDeposit {
+    pub shares: Balance,
+    pub storage_fee_deposit: Balance,
 }
 
 #[derive(TypeInfo, Debug, Encode, Decode, Copy, Clone, PartialEq, Eq)]
-pub(crate) struct PendingDeposit {
-    pub(crate) effective_domain_epoch: DomainEpoch,
-    pub(crate) amount: Balance,
-    pub(crate) storage_fee_deposit: Balance,
+pub struct PendingDeposit {
+    pub effective_domain_epoch: DomainEpoch,
+    pub amount: Balance,
+    pub storage_fee_deposit: Balance,
 }
 
 #[derive(TypeInfo, Debug, Encode, Decode, Copy, Clone, PartialEq, Eq)]
 pub struct DomainEpoch(DomainId, EpochIndex);
 
 impl DomainEpoch {
-    pub(crate) fn deconstruct(&self) -> (DomainId, EpochIndex) {
+    pub fn deconstruct(&self) -> (DomainId, EpochIndex) {
         (self.0, self.1)
     }
 }
 
 #[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq, Default)]
-pub(crate) struct Withdrawal {
+pub struct Withdrawal {
     /// Total withdrawal amount requested by the nominator that are in unlocking state excluding withdrawal
     /// in shares and the storage fee
-    pub(crate) total_withdrawal_amount: Balance,
+    pub total_withdrawal_amount: Balance,
     /// Individual withdrawal amounts with their unlocking block for a given domain
-    pub(crate) withdrawals: VecDeque<WithdrawalInBalance>,
+    pub withdrawals: VecDeque<WithdrawalInBalance>,
     /// Withdrawal that was initiated by nominator and not converted to balance due to
     /// unfinished domain epoch.
-    pub(crate) withdrawal_in_shares: Option<WithdrawalInShares>,
+    pub withdrawal_in_shares: Option<WithdrawalInShares>,
 }
 
 #[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
-pub(crate) struct WithdrawalInBalance {
-    pub(crate) domain_id: DomainId,
-    pub(crate) unlock_at_confirmed_domain_block_number: Number,
-    pub(crate) amount_to_unlock: Balance,
-    pub(crate) storage_fee_refund: Balance,
+pub struct WithdrawalInBalance {
+    pub domain_id: DomainId,
+    pub unlock_at_confirmed_domain_block_number: Number,
+    pub amount_to_unlock: Balance,
+    pub storage_fee_refund: Balance,
 }
 
 #[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
-pub(crate) struct WithdrawalInShares {
-    pub(crate) domain_epoch: DomainEpoch,
-    pub(crate) unlock_at_confirmed_domain_block_number: Number,
-    pub(crate) shares: Balance,
-    pub(crate) storage_fee_refund: Balance,
+pub struct WithdrawalInShares {
+    pub domain_epoch: DomainEpoch,
+    pub unlock_at_confirmed_domain_block_number: Number,
+    pub shares: Balance,
+    pub storage_fee_refund: Balance,
 }
 
-#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
-pub(crate) struct NominatorStorage {
-    pub(crate) deposit: Deposit,
-    pub(crate) withdrawal: Option<Withdrawal>,
+/// Domain block of `Domains::LatestConfirmedDomainBlock`.
+///
+/// Only the block number is decoded since the hashes and roots following it are not needed.
+#[derive(TypeInfo, Debug, Encode, Decode, Copy, Clone, PartialEq, Eq)]
+pub struct ConfirmedDomainBlock {
+    pub block_number: Number,
 }
 
-pub(crate) struct OperatorNominators {
-    pub(crate) operator_id: OperatorId,
-    pub(crate) nominator_storage: BTreeMap<AccountId, NominatorStorage>,
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
+pub struct NominatorStorage {
+    pub deposit: Deposit,
+    pub withdrawal: Option<Withdrawal>,
 }
 
 #[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
@@ -114,34 +120,191 @@ pub struct Operator {
     pub total_storage_fee_deposit: Balance,
 }
 
-#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq, Default)]
-pub struct SharePrice(Perbill);
+impl Operator {
+    /// Status of the operator as stored, which is never `OperatorStatus::PendingSlash`.
+    pub fn stored_status(&self) -> &OperatorStatus {
+        &self.status
+    }
+}
+
+/// Leading fields of the runtime version returned by `Core_version`.
+///
+/// Remaining fields are not decoded since only the spec version is needed.
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
+pub struct RuntimeVersion {
+    pub spec_name: String,
+    pub impl_name: String,
+    pub authoring_version: u32,
+    pub spec_version: u32,
+}
+
+#[derive(TypeInfo, Debug, Encode, Decode, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Weight {
+    #[codec(compact)]
+    pub ref_time: u64,
+    #[codec(compact)]
+    pub proof_size: u64,
+}
+
+impl Weight {
+    /// Returns true if any of the components of this weight exceeds the one of `other`.
+    pub fn any_gt(&self, other: &Weight) -> bool {
+        self.ref_time > other.ref_time || self.proof_size > other.proof_size
+    }
+}
+
+#[derive(TypeInfo, Debug, Encode, Decode, Copy, Clone, PartialEq, Eq)]
+pub enum DispatchClass {
+    Normal,
+    Operational,
+    Mandatory,
+}
+
+/// Dispatch info returned by `TransactionPaymentApi_query_info`.
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
+pub struct RuntimeDispatchInfo {
+    pub weight: Weight,
+    pub class: DispatchClass,
+    pub partial_fee: Balance,
+}
+
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
+pub struct PerDispatchClass<T> {
+    pub normal: T,
+    pub operational: T,
+    pub mandatory: T,
+}
+
+impl<T> PerDispatchClass<T> {
+    pub fn get(&self, class: DispatchClass) -> &T {
+        match class {
+            DispatchClass::Normal => &self.normal,
+            DispatchClass::Operational => &self.operational,
+            DispatchClass::Mandatory => &self.mandatory,
+        }
+    }
+}
+
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
+pub struct WeightsPerClass {
+    pub base_extrinsic: Weight,
+    pub max_extrinsic: Option<Weight>,
+    pub max_total: Option<Weight>,
+    pub reserved: Option<Weight>,
+}
+
+/// `System::BlockWeights` constant.
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
+pub struct BlockWeights {
+    pub base_block: Weight,
+    pub max_block: Weight,
+    pub per_class: PerDispatchClass<WeightsPerClass>,
+}
+
+impl BlockWeights {
+    /// Maximum weight of a single extrinsic of the given dispatch class.
+    pub fn max_extrinsic(&self, class: DispatchClass) -> Weight {
+        let weights = self.per_class.get(class);
+        weights
+            .max_extrinsic
+            .or(weights.max_total)
+            .unwrap_or(self.max_block)
+    }
+}
+
+/// Block number and extrinsic index at which a multisig operation was opened.
+#[derive(TypeInfo, Debug, Encode, Decode, Copy, Clone, PartialEq, Eq)]
+pub struct Timepoint {
+    pub height: Number,
+    pub index: u32,
+}
+
+/// Pending multisig operation stored in `Multisig::Multisigs`.
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
+pub struct Multisig {
+    pub when: Timepoint,
+    pub deposit: Balance,
+    pub depositor: AccountId,
+    pub approvals: Vec<AccountId>,
+}
+
+/// Vesting schedule of `Vesting::vested_transfer`, releasing `per_period` at the end of each
+/// of the `period_count` periods of `period` blocks starting from `start`.
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
+pub struct VestingSchedule {
+    pub start: Number,
+    pub period: Number,
+    pub period_count: u32,
+    #[codec(compact)]
+    pub per_period: Balance,
+}
+
+/// `System::BlockLength` constant.
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
+pub struct BlockLength {
+    pub max: PerDispatchClass<u32>,
+}
+
+/// Share price of an operator, as shares per stake.
+///
+/// The runtime moved from `Perbill` to `Perquintill` share prices, so the price is kept in the
+/// precision of the runtime it is read from and converts with the same rounding as the pallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharePrice {
+    Perbill(Perbill),
+    Perquintill(Perquintill),
+}
 
 impl SharePrice {
-    /// Creates a new instance of share price from shares and stake.
-    pub(crate) fn new(shares: Balance, stake: Balance) -> Self {
-        SharePrice(if shares.is_zero() || stake.is_zero() {
-            Perbill::one()
-        } else {
-            Perbill::from_rational(shares, stake)
-        })
+    /// Creates a new instance of share price from shares and stake, in the given precision.
+    pub fn new(shares: Balance, stake: Balance, precision: SharePricePrecision) -> Self {
+        let is_one = shares.is_zero() || stake.is_zero();
+        match precision {
+            SharePricePrecision::Perbill => SharePrice::Perbill(if is_one {
+                Perbill::one()
+            } else {
+                Perbill::from_rational(shares, stake)
+            }),
+            SharePricePrecision::Perquintill => SharePrice::Perquintill(if is_one {
+                Perquintill::one()
+            } else {
+                Perquintill::from_rational(shares, stake)
+            }),
+        }
     }
 
-    /// Converts stake to shares based on the share price
-    pub(crate) fn stake_to_shares(&self, stake: Balance) -> Balance {
-        if self.0.is_one() {
-            stake
-        } else {
-            self.0.mul_floor(stake)
+    /// Converts stake to shares based on the share price, rounding down
+    pub fn stake_to_shares(&self, stake: Balance) -> Balance {
+        match self {
+            SharePrice::Perbill(price) if !price.is_one() => price.mul_floor(stake),
+            SharePrice::Perquintill(price) if !price.is_one() => price.mul_floor(stake),
+            _ => stake,
         }
     }
 
-    /// Converts shares to stake based on the share price
-    pub(crate) fn shares_to_stake(&self, shares: Balance) -> Balance {
-        if self.0.is_one() {
-            shares
-        } else {
-            self.0.saturating_reciprocal_mul_floor(shares)
+    /// Converts shares to stake based on the share price, rounding down
+    pub fn shares_to_stake(&self, shares: Balance) -> Balance {
+        match self {
+            SharePrice::Perbill(price) if !price.is_one() => {
+                price.saturating_reciprocal_mul_floor(shares)
+            }
+            SharePrice::Perquintill(price) if !price.is_one() => {
+                price.saturating_reciprocal_mul_floor(shares)
+            }
+            _ => shares,
+        }
+    }
+
+    /// Returns the share price as shares per stake in parts per quintillion, which holds the
+    /// prices of both precisions exactly.
+    pub fn parts(&self) -> u64 {
+        match self {
+            SharePrice::Perbill(price) => {
+                let scale =
+                    Perquintill::one().deconstruct() / u64::from(Perbill::one().deconstruct());
+                u64::from(price.deconstruct()) * scale
+            }
+            SharePrice::Perquintill(price) => price.deconstruct(),
         }
     }
 }
@@ -150,7 +313,7 @@ impl SharePrice {
 pub struct StorageFundRedeemPrice((Balance, Balance));
 
 impl StorageFundRedeemPrice {
-    pub(crate) fn new(total_balance: Balance, total_deposit: Balance) -> Self {
+    pub fn new(total_balance: Balance, total_deposit: Balance) -> Self {
         StorageFundRedeemPrice((total_balance, total_deposit))
     }
 
@@ -160,7 +323,7 @@ impl StorageFundRedeemPrice {
     /// If the inflow of the storage fund (i.e. refund of the storage fee) is larger than its
     /// outflow (i.e. payment of the storage fee), the return value will larger than `deposit`
     /// otherwise smaller.
-    pub(crate) fn redeem(&self, deposit: Balance) -> Balance {
+    pub fn redeem(&self, deposit: Balance) -> Balance {
         let (total_balance, total_deposit) = self.0;
         if total_balance == total_deposit {
             deposit
@@ -0,0 +1,317 @@
+use crate::types::{
+    DomainEpoch, Operator, OperatorDeregisteredInfo, OperatorStatus, SharePrice, Withdrawal,
+    WithdrawalInBalance, WithdrawalInShares,
+};
+use crate::Balance;
+use codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use sp_domains::{DomainId, OperatorPublicKey};
+use sp_runtime::{Perbill, Percent, Perquintill};
+use std::collections::VecDeque;
+
+/// First spec version recording the confirmed domain block at which the withdrawals in
+/// shares and the deregistered operators unlock.
+pub const UNLOCK_BLOCK_SPEC_VERSION: u32 = 5;
+
+/// Spec versions at which the types of the `Domains` pallet changed on a network, since the
+/// networks did not upgrade the pallet at the same spec version.
+///
+/// Defaults to the ones of Gemini 3h.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuntimeUpgrades {
+    /// First spec version recording the unlock block, before which the storage is decoded
+    /// with the `V0` layout.
+    pub unlock_block_spec_version: u32,
+    /// First spec version computing the share prices as `Perquintill` instead of `Perbill`,
+    /// which Gemini 3h never upgraded to.
+    pub perquintill_share_price_spec_version: u32,
+}
+
+impl Default for RuntimeUpgrades {
+    fn default() -> Self {
+        RuntimeUpgrades {
+            unlock_block_spec_version: UNLOCK_BLOCK_SPEC_VERSION,
+            perquintill_share_price_spec_version: u32::MAX,
+        }
+    }
+}
+
+/// Layout of the `Domains` pallet types.
+///
+/// The slashes span runtime upgrades, so the storage at each block is decoded with the layout
+/// of the spec version at that block and converted into the current types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Before the unlock block was recorded.
+    V0,
+    /// Layout of the types in `types`.
+    Current,
+}
+
+impl Layout {
+    /// Returns the layout of the runtime with the given spec version on a network with the
+    /// given upgrades.
+    pub fn at(spec_version: u32, upgrades: RuntimeUpgrades) -> Self {
+        if spec_version < upgrades.unlock_block_spec_version {
+            Layout::V0
+        } else {
+            Layout::Current
+        }
+    }
+}
+
+/// Precision of the share prices of the `Domains` pallet.
+///
+/// Share prices are stored and computed in the precision of the runtime, so the conversions
+/// of the shares round to a different unit before and after the upgrade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharePricePrecision {
+    Perbill,
+    Perquintill,
+}
+
+impl SharePricePrecision {
+    /// Returns the precision of the runtime with the given spec version on a network with the
+    /// given upgrades.
+    pub fn at(spec_version: u32, upgrades: RuntimeUpgrades) -> Self {
+        if spec_version < upgrades.perquintill_share_price_spec_version {
+            SharePricePrecision::Perbill
+        } else {
+            SharePricePrecision::Perquintill
+        }
+    }
+}
+
+/// `SharePrice` before it was computed as `Perquintill`.
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, Copy, PartialEq, Eq)]
+pub struct PerbillSharePrice(pub Perbill);
+
+impl From<PerbillSharePrice> for SharePrice {
+    fn from(share_price: PerbillSharePrice) -> Self {
+        SharePrice::Perbill(share_price.0)
+    }
+}
+
+/// `SharePrice` since it is computed as `Perquintill`.
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, Copy, PartialEq, Eq)]
+pub struct PerquintillSharePrice(pub Perquintill);
+
+impl From<PerquintillSharePrice> for SharePrice {
+    fn from(share_price: PerquintillSharePrice) -> Self {
+        SharePrice::Perquintill(share_price.0)
+    }
+}
+
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
+pub enum OperatorStatusV0 {
+    Registered,
+    /// De-registered at given domain epoch.
+    Deregistered(DomainEpoch),
+    Slashed,
+    PendingSlash,
+}
+
+impl From<OperatorStatusV0> for OperatorStatus {
+    fn from(status: OperatorStatusV0) -> Self {
+        match status {
+            OperatorStatusV0::Registered => OperatorStatus::Registered,
+            // unlock block is not needed for the refunds
+            OperatorStatusV0::Deregistered(domain_epoch) => {
+                OperatorStatus::Deregistered(OperatorDeregisteredInfo {
+                    domain_epoch,
+                    unlock_at_confirmed_domain_block_number: 0,
+                })
+            }
+            OperatorStatusV0::Slashed => OperatorStatus::Slashed,
+            OperatorStatusV0::PendingSlash => OperatorStatus::PendingSlash,
+        }
+    }
+}
+
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
+pub struct OperatorV0 {
+    pub signing_key: OperatorPublicKey,
+    pub current_domain_id: DomainId,
+    pub next_domain_id: DomainId,
+    pub minimum_nominator_stake: Balance,
+    pub nomination_tax: Percent,
+    pub current_total_stake: Balance,
+    pub current_epoch_rewards: Balance,
+    pub current_total_shares: Balance,
+    pub status: OperatorStatusV0,
+    pub deposits_in_epoch: Balance,
+    pub withdrawals_in_epoch: Balance,
+    pub total_storage_fee_deposit: Balance,
+}
+
+impl From<OperatorV0> for Operator {
+    fn from(operator: OperatorV0) -> Self {
+        Operator {
+            signing_key: operator.signing_key,
+            current_domain_id: operator.current_domain_id,
+            next_domain_id: operator.next_domain_id,
+            minimum_nominator_stake: operator.minimum_nominator_stake,
+            nomination_tax: operator.nomination_tax,
+            current_total_stake: operator.current_total_stake,
+            current_epoch_rewards: operator.current_epoch_rewards,
+            current_total_shares: operator.current_total_shares,
+            status: operator.status.into(),
+            deposits_in_epoch: operator.deposits_in_epoch,
+            withdrawals_in_epoch: operator.withdrawals_in_epoch,
+            total_storage_fee_deposit: operator.total_storage_fee_deposit,
+        }
+    }
+}
+
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq)]
+pub struct WithdrawalInSharesV0 {
+    pub domain_epoch: DomainEpoch,
+    pub shares: Balance,
+    pub storage_fee_refund: Balance,
+}
+
+impl From<WithdrawalInSharesV0> for WithdrawalInShares {
+    fn from(withdrawal_in_shares: WithdrawalInSharesV0) -> Self {
+        WithdrawalInShares {
+            domain_epoch: withdrawal_in_shares.domain_epoch,
+            // unlock block is not needed for the refunds
+            unlock_at_confirmed_domain_block_number: 0,
+            shares: withdrawal_in_shares.shares,
+            storage_fee_refund: withdrawal_in_shares.storage_fee_refund,
+        }
+    }
+}
+
+#[derive(TypeInfo, Debug, Encode, Decode, Clone, PartialEq, Eq, Default)]
+pub struct WithdrawalV0 {
+    pub total_withdrawal_amount: Balance,
+    pub withdrawals: VecDeque<WithdrawalInBalance>,
+    pub withdrawal_in_shares: Option<WithdrawalInSharesV0>,
+}
+
+impl From<WithdrawalV0> for Withdrawal {
+    fn from(withdrawal: WithdrawalV0) -> Self {
+        Withdrawal {
+            total_withdrawal_amount: withdrawal.total_withdrawal_amount,
+            withdrawals: withdrawal.withdrawals,
+            withdrawal_in_shares: withdrawal.withdrawal_in_shares.map(Into::into),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_domains::EpochIndex;
+
+    fn domain_epoch(epoch_index: EpochIndex) -> DomainEpoch {
+        DomainEpoch(DomainId::new(0), epoch_index)
+    }
+
+    #[test]
+    fn layout_is_selected_by_spec_version() {
+        let upgrades = RuntimeUpgrades::default();
+        assert_eq!(
+            Layout::at(UNLOCK_BLOCK_SPEC_VERSION - 1, upgrades),
+            Layout::V0
+        );
+        assert_eq!(
+            Layout::at(UNLOCK_BLOCK_SPEC_VERSION, upgrades),
+            Layout::Current
+        );
+
+        // networks launched after the upgrade only have the current layout
+        let upgrades = RuntimeUpgrades {
+            unlock_block_spec_version: 0,
+            perquintill_share_price_spec_version: 0,
+        };
+        assert_eq!(Layout::at(0, upgrades), Layout::Current);
+    }
+
+    #[test]
+    fn precision_is_selected_by_spec_version() {
+        let upgrades = RuntimeUpgrades::default();
+        assert_eq!(
+            SharePricePrecision::at(u32::MAX - 1, upgrades),
+            SharePricePrecision::Perbill
+        );
+
+        let upgrades = RuntimeUpgrades {
+            perquintill_share_price_spec_version: 8,
+            ..upgrades
+        };
+        assert_eq!(
+            SharePricePrecision::at(7, upgrades),
+            SharePricePrecision::Perbill
+        );
+        assert_eq!(
+            SharePricePrecision::at(8, upgrades),
+            SharePricePrecision::Perquintill
+        );
+    }
+
+    #[test]
+    fn withdrawal_v0_is_converted() {
+        let encoded = WithdrawalV0 {
+            total_withdrawal_amount: 10,
+            withdrawals: VecDeque::new(),
+            withdrawal_in_shares: Some(WithdrawalInSharesV0 {
+                domain_epoch: domain_epoch(3),
+                shares: 20,
+                storage_fee_refund: 2,
+            }),
+        }
+        .encode();
+
+        let withdrawal = Withdrawal::from(WithdrawalV0::decode(&mut encoded.as_slice()).unwrap());
+        assert_eq!(
+            withdrawal.withdrawal_in_shares,
+            Some(WithdrawalInShares {
+                domain_epoch: domain_epoch(3),
+                unlock_at_confirmed_domain_block_number: 0,
+                shares: 20,
+                storage_fee_refund: 2,
+            })
+        );
+        // the current layout would read past the end of the v0 encoding
+        assert!(Withdrawal::decode(&mut encoded.as_slice()).is_err());
+    }
+
+    #[test]
+    fn share_price_is_decoded_in_its_precision() {
+        let encoded = PerbillSharePrice(Perbill::from_percent(50)).encode();
+        assert_eq!(encoded.len(), 4);
+        assert_eq!(
+            SharePrice::from(PerbillSharePrice::decode(&mut encoded.as_slice()).unwrap()),
+            SharePrice::Perbill(Perbill::from_percent(50))
+        );
+        let encoded = PerquintillSharePrice(Perquintill::from_percent(50)).encode();
+        assert_eq!(encoded.len(), 8);
+        assert_eq!(
+            SharePrice::from(PerquintillSharePrice::decode(&mut encoded.as_slice()).unwrap()),
+            SharePrice::Perquintill(Perquintill::from_percent(50))
+        );
+    }
+
+    #[test]
+    fn share_price_rounds_in_its_precision() {
+        let perbill = SharePrice::new(1, 3, SharePricePrecision::Perbill);
+        let perquintill = SharePrice::new(1, 3, SharePricePrecision::Perquintill);
+        assert_eq!(perbill.parts(), 333_333_333_000_000_000);
+        assert_eq!(perquintill.parts(), 333_333_333_333_333_333);
+
+        let stake = 3 * 10u128.pow(18);
+        assert_eq!(perbill.stake_to_shares(stake), 999_999_999_000_000_000);
+        assert_eq!(perquintill.stake_to_shares(stake), 999_999_999_999_999_999);
+        let shares = 10u128.pow(18);
+        assert_eq!(perbill.shares_to_stake(shares), 3_000_000_003_000_000_003);
+        assert_eq!(
+            perquintill.shares_to_stake(shares),
+            3_000_000_000_000_000_003
+        );
+
+        let one = SharePrice::new(0, stake, SharePricePrecision::Perquintill);
+        assert_eq!(one.stake_to_shares(stake), stake);
+        assert_eq!(one.shares_to_stake(shares), shares);
+    }
+}
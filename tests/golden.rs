@@ -0,0 +1,57 @@
+//! Regression tests replaying the calculation against the chain state captured from the
+//! slash blocks of gemini-3h.
+//!
+//! Each directory in `tests/fixtures` holds the state dump `state.scale` and the payout
+//! manifest `payouts.json` written by
+//! `calculate --manifest payouts.json --record-state state.scale` for the same operators.
+//! The calculation is replayed from the state dump for the operators of the manifest and
+//! must produce the exact same payouts. `synthetic-known-deposits` is written by hand rather
+//! than captured, so that the test never passes without replaying at least one fixture.
+
+use gemini_3h_slash::backend::MemoryBackend;
+use gemini_3h_slash::manifest::PayoutManifest;
+use gemini_3h_slash::{Calculation, SlashRefundCalculator};
+use std::path::Path;
+
+#[tokio::test]
+async fn payouts_match_fixtures() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut fixture_dirs: Vec<_> = std::fs::read_dir(&fixtures)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect();
+    fixture_dirs.sort();
+    assert!(
+        !fixture_dirs.is_empty(),
+        "no fixture in {}",
+        fixtures.display()
+    );
+
+    for fixture_dir in fixture_dirs {
+        let backend = MemoryBackend::read(&fixture_dir.join("state.scale")).unwrap();
+        let expected = PayoutManifest::read(&fixture_dir.join("payouts.json")).unwrap();
+        let slashed_operators = expected
+            .operators
+            .iter()
+            .map(|operator_payout| (operator_payout.operator_id, operator_payout.slashed_at))
+            .collect();
+
+        let Calculation { payouts, failures } = SlashRefundCalculator::new(&backend)
+            .calculate(slashed_operators)
+            .await;
+        assert!(
+            failures.is_empty(),
+            "{}: {failures:?}",
+            fixture_dir.display()
+        );
+        assert_eq!(
+            payouts.operators,
+            expected.operators,
+            "{}",
+            fixture_dir.display()
+        );
+        assert_eq!(payouts.payload_hash, expected.payload_hash);
+        assert_eq!(payouts.total, expected.total);
+    }
+}